@@ -0,0 +1,77 @@
+//! Benchmarks of the work done without the database on large configs:
+//! parsing and validating, planning the SQL statements and rendering the
+//! summary.
+//!
+//! ```bash
+//! cargo bench --bench plan
+//! ```
+//!
+//! The fixtures are the configs of `grant bench-config`.
+
+use criterion::{criterion_group, criterion_main, BenchmarkId, Criterion, Throughput};
+use grant::config::Config;
+use grant::gen::gen_bench_config;
+use grant::privilege_statements;
+use grant::render::{AsciiRenderer, JsonRenderer, Renderer, Table};
+use std::str::FromStr;
+
+/// Number of users of the fixtures, with 10 roles of 100 tables
+const USERS: &[usize] = &[100, 1000];
+
+fn fixture(users: usize) -> Config {
+    gen_bench_config(users, 10, 100)
+}
+
+fn parse(c: &mut Criterion) {
+    let mut group = c.benchmark_group("parse");
+    for &users in USERS {
+        let yaml = serde_yaml::to_string(&fixture(users)).unwrap();
+
+        group.throughput(Throughput::Elements(users as u64));
+        group.bench_with_input(BenchmarkId::from_parameter(users), &yaml, |b, yaml| {
+            b.iter(|| Config::from_str(yaml).unwrap())
+        });
+    }
+    group.finish();
+}
+
+fn plan(c: &mut Criterion) {
+    let mut group = c.benchmark_group("plan");
+    for &users in USERS {
+        let config = fixture(users);
+
+        group.throughput(Throughput::Elements(users as u64));
+        group.bench_with_input(BenchmarkId::from_parameter(users), &config, |b, config| {
+            b.iter(|| privilege_statements(config))
+        });
+    }
+    group.finish();
+}
+
+fn summary(c: &mut Criterion) {
+    let mut group = c.benchmark_group("summary");
+    for &users in USERS {
+        let config = fixture(users);
+        let mut table = Table::new(&["User", "Role Name", "Detail", "Status"]);
+        for (grantee, role_name, role, _, _) in privilege_statements(&config) {
+            table.push(vec![
+                grantee.to_string(),
+                role_name.clone(),
+                format!("{:?}", role.get_tables()),
+                "dry-run".to_string(),
+            ]);
+        }
+
+        group.throughput(Throughput::Elements(table.rows.len() as u64));
+        group.bench_with_input(BenchmarkId::new("table", users), &table, |b, table| {
+            b.iter(|| AsciiRenderer::default().render(table))
+        });
+        group.bench_with_input(BenchmarkId::new("json", users), &table, |b, table| {
+            b.iter(|| JsonRenderer.render(table))
+        });
+    }
+    group.finish();
+}
+
+criterion_group!(benches, parse, plan, summary);
+criterion_main!(benches);
@@ -0,0 +1,30 @@
+//! Any YAML accepted as a config must generate SQL without panicking.
+//!
+//! ```bash
+//! cargo +nightly fuzz run config_to_sql
+//! ```
+
+#![no_main]
+
+use grant::config::Config;
+use libfuzzer_sys::fuzz_target;
+use std::str::FromStr;
+
+fuzz_target!(|data: &[u8]| {
+    let Ok(yaml) = std::str::from_utf8(data) else {
+        return;
+    };
+    let Ok(config) = Config::from_str(yaml) else {
+        return;
+    };
+
+    for user in &config.users {
+        user.to_sql_create();
+        user.to_sql_update();
+        user.to_sql_drop();
+
+        for role in &config.roles {
+            role.to_sql(&user.name);
+        }
+    }
+});
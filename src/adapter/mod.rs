@@ -0,0 +1,483 @@
+//! The SQL backends, see [DatabaseAdapter]. The users and the privileges
+//! of the config are read, rendered and executed by the adapter of the
+//! engine, `apply` converges the cluster with [converge].
+//!
+//! An adapter only grants: the users and groups of the config are created,
+//! their missing members added and privileges granted, the other ones are
+//! left as they are.
+//!
+//! A new engine is a module implementing the trait, its
+//! [ConnectionType] and its arm in [connect]: `apply` is the same for
+//! every adapter. [DbConnection](crate::DbConnection) implements it too,
+//! see [postgres], for the engines speaking the Postgres protocol.
+
+pub mod clickhouse;
+pub mod mysql;
+pub mod postgres;
+pub mod snowflake;
+
+use crate::config::{Config, ConnectionType, Grantee, Role, User};
+use crate::connection::Group;
+use crate::encrypt::{decrypt_user, Key};
+use crate::report::{MemberAction, PrivilegeStatus, Report, UserAction};
+use ansi_term::Colour::{Green, Purple, Red};
+use anyhow::{anyhow, Result};
+use log::{error, info};
+use std::collections::BTreeSet;
+
+/// A privilege on an object, as granted by the engine
+#[derive(Debug, Clone, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub struct Privilege {
+    /// The user or the group, named as in the catalog, e.g. `ROLE analysts`
+    /// on Snowflake where users and roles are apart
+    pub grantee: String,
+    /// The object named by the engine, e.g. `sales.*` on MySQL
+    pub object: String,
+    /// Upper case, e.g. `SELECT`
+    pub privilege: String,
+}
+
+/// The SQL and the catalog of a database engine
+pub trait DatabaseAdapter {
+    /// The `connection.type` of the engine
+    fn name(&self) -> &'static str;
+
+    /// Check that the config only uses what the engine supports
+    fn validate(&self, config: &Config) -> Result<()>;
+
+    /// A name of the config as in the catalog, e.g. case-folded by
+    /// Postgres
+    fn catalog_name(&self, name: &str) -> String {
+        name.to_string()
+    }
+
+    /// The users of the cluster, named as in the catalog
+    fn fetch_users(&mut self) -> Result<Vec<String>>;
+
+    /// The privileges granted to the users of the cluster
+    fn fetch_privileges(&mut self) -> Result<Vec<Privilege>>;
+
+    /// The privileges a role grants to a user or a group, named as in the
+    /// catalog
+    fn role_privileges(&self, role: &Role, grantee: Grantee) -> Vec<Privilege>;
+
+    /// The statement creating a user with its password
+    fn render_create_user(&self, user: &User) -> String;
+
+    /// The statement setting the password of an existing user
+    fn render_update_password(&self, user: &User) -> String;
+
+    /// The statements granting privileges
+    fn render_grant(&self, privileges: &[Privilege]) -> Vec<String>;
+
+    /// The groups of the cluster with their members, e.g. the roles of
+    /// ClickHouse. Empty for the engines without groups, which reject them
+    /// in [validate](Self::validate).
+    fn fetch_groups(&mut self) -> Result<Vec<Group>> {
+        Ok(vec![])
+    }
+
+    /// The statement creating a group
+    fn render_create_group(&self, group: &str) -> Result<String> {
+        Err(anyhow!(
+            "group {}: groups are not supported by {}",
+            group,
+            self.name()
+        ))
+    }
+
+    /// The statement adding a member to a group
+    fn render_add_member(&self, group: &str, member: &str) -> Result<String> {
+        Err(anyhow!(
+            "group {}: {} cannot be added, groups are not supported by {}",
+            group,
+            member,
+            self.name()
+        ))
+    }
+
+    /// Execute the statements in order, stopping at the first error
+    fn execute_batch(&mut self, statements: &[String]) -> Result<()>;
+}
+
+/// The adapter of the engine of the connection, None for `postgres` and
+/// `redshift` which have their own [DbConnection](crate::DbConnection)
+pub fn connect(config: &Config) -> Result<Option<Box<dyn DatabaseAdapter>>> {
+    match config.connection.type_ {
+        ConnectionType::Postgres | ConnectionType::Redshift => Ok(None),
+        ConnectionType::Mysql => Ok(Some(Box::new(mysql::Mysql::connect(&config.connection)?))),
+        ConnectionType::Clickhouse => Ok(Some(Box::new(clickhouse::Clickhouse::connect(
+            &config.connection,
+        )?))),
+        ConnectionType::Snowflake => Ok(Some(Box::new(snowflake::Snowflake::connect(config)?))),
+    }
+}
+
+/// What [converge] did, as the summaries of `apply`
+pub struct Converged {
+    pub users: Report<UserAction>,
+    pub groups: Option<Report<MemberAction>>,
+    pub privileges: Report<PrivilegeStatus>,
+}
+
+/// Converge the cluster of the adapter to the config: create the missing
+/// users, update the passwords of `password_policy: always`, create the
+/// missing groups and add their missing members, then grant the missing
+/// privileges of the roles of each user and group.
+///
+/// A dry run only logs the statements. The encrypted passwords are
+/// decrypted with the key for the executed statements only.
+pub fn converge(
+    adapter: &mut dyn DatabaseAdapter,
+    config: &Config,
+    key: Option<&Key>,
+    dryrun: bool,
+) -> Result<Converged> {
+    let existing = adapter.fetch_users()?;
+    let mut users = Report::new();
+
+    for user in &config.users {
+        let exists = existing.contains(&adapter.catalog_name(&user.name));
+        let (sql, action) = match (exists, user.updates_password()) {
+            (true, false) => {
+                users.push(UserAction::new(&user.name, "no action (already exists)"));
+                continue;
+            }
+            (true, true) => (adapter.render_update_password(user), "password updated"),
+            (false, _) => (adapter.render_create_user(user), "created"),
+        };
+
+        if dryrun {
+            info!("{}: {}", Purple.paint("Dry-run"), sql);
+            users.push(UserAction::new(
+                &user.name,
+                format!("would be {} (dryrun)", action),
+            ));
+            continue;
+        }
+
+        let decrypted = decrypt_user(user, key)?;
+        let statement = match exists {
+            true => adapter.render_update_password(&decrypted),
+            false => adapter.render_create_user(&decrypted),
+        };
+        adapter.execute_batch(&[statement])?;
+        info!("{}: {}", Green.paint("Success"), Purple.paint(&sql));
+        users.push(UserAction::new(&user.name, action));
+    }
+
+    let groups = match config.groups.is_empty() {
+        true => None,
+        false => Some(converge_groups(adapter, config, dryrun)?),
+    };
+
+    let mut granted = adapter
+        .fetch_privileges()?
+        .into_iter()
+        .collect::<BTreeSet<_>>();
+    let mut privileges = Report::new();
+
+    let grantees = config
+        .users
+        .iter()
+        .map(|u| (Grantee::User(&u.name), &u.roles))
+        .chain(
+            config
+                .groups
+                .iter()
+                .map(|g| (Grantee::Group(&g.name), &g.roles)),
+        );
+    for (grantee, roles) in grantees {
+        for role_name in roles {
+            let role = config.roles.iter().find(|r| r.find(role_name)).unwrap();
+            let name = adapter.catalog_name(grantee.name());
+            let wanted = adapter.role_privileges(
+                role,
+                match grantee {
+                    Grantee::Group(_) => Grantee::Group(&name),
+                    _ => Grantee::User(&name),
+                },
+            );
+            let mut objects = wanted.iter().map(|p| p.object.as_str()).collect::<Vec<_>>();
+            objects.dedup();
+            let detail = objects.join(", ");
+
+            let missing = missing(&wanted, &granted);
+            let statements = adapter.render_grant(&missing);
+            let sql = statements.join("\n");
+
+            let status = if statements.is_empty() {
+                "unchanged"
+            } else if dryrun {
+                info!("{}: {}", Purple.paint("Dry-run"), sql);
+                "dry-run"
+            } else if let Err(e) = adapter.execute_batch(&statements) {
+                error!("{}: {}", Red.paint("Error"), sql);
+                error!("  -> {}: {}", Red.paint("Error details"), e);
+                "error"
+            } else {
+                info!("{}: {}", Green.paint("Success"), Purple.paint(&sql));
+                "updated"
+            };
+            // The privileges of a role may be shared by its grantees, e.g.
+            // the ones of a role of Snowflake, they are granted once
+            if status != "error" {
+                granted.extend(missing);
+            }
+
+            privileges.push(PrivilegeStatus {
+                grantee: grantee.to_string(),
+                role: role_name.clone(),
+                detail,
+                status: status.to_string(),
+                sql,
+            });
+        }
+    }
+
+    Ok(Converged {
+        users,
+        groups,
+        privileges,
+    })
+}
+
+/// Create the missing groups of the config and add their missing members
+fn converge_groups(
+    adapter: &mut dyn DatabaseAdapter,
+    config: &Config,
+    dryrun: bool,
+) -> Result<Report<MemberAction>> {
+    let existing = adapter.fetch_groups()?;
+    let mut summary = Report::new();
+
+    for group in &config.groups {
+        let current = existing
+            .iter()
+            .find(|g| g.name == adapter.catalog_name(&group.name));
+        let mut statements = vec![];
+        if current.is_none() {
+            statements.push((String::new(), adapter.render_create_group(&group.name)?));
+        }
+        for member in &group.members {
+            match current.filter(|g| g.members.contains(&adapter.catalog_name(member))) {
+                Some(_) => summary.push(MemberAction {
+                    group: group.name.clone(),
+                    member: member.clone(),
+                    action: "no action (already a member)".to_string(),
+                }),
+                None => statements.push((
+                    member.clone(),
+                    adapter.render_add_member(&group.name, member)?,
+                )),
+            }
+        }
+
+        for (member, sql) in statements {
+            let (action, done) = match member.is_empty() {
+                true => ("create", "created"),
+                false => ("add", "added"),
+            };
+            if dryrun {
+                info!("{}: {}", Purple.paint("Dry-run"), sql);
+                summary.push(MemberAction {
+                    group: group.name.clone(),
+                    member,
+                    action: format!("would {} (dryrun) {}", action, sql),
+                });
+                continue;
+            }
+
+            adapter.execute_batch(std::slice::from_ref(&sql))?;
+            info!("{}: {}", Green.paint("Success"), Purple.paint(&sql));
+            summary.push(MemberAction {
+                group: group.name.clone(),
+                member,
+                action: format!("{} {}", done, sql),
+            });
+        }
+    }
+
+    Ok(summary)
+}
+
+/// Check the config for the checks of every adapter: the users, roles and
+/// sections which only Postgres has are rejected
+pub fn validate(config: &Config, engine: &str) -> Result<()> {
+    validate_config(config, engine)?;
+
+    for role in &config.roles {
+        let name = role.get_name();
+        if matches!(
+            role,
+            Role::Function(_) | Role::Sequence(_) | Role::Warehouse(_)
+        ) {
+            return Err(anyhow!(
+                "role {}: {} roles are not supported by {}",
+                name,
+                role.get_level(),
+                engine
+            ));
+        }
+        if role.database().is_some() {
+            return Err(anyhow!(
+                "role {}: database is not supported by {}, its databases are the schemas",
+                name,
+                engine
+            ));
+        }
+        if role.get_tables().iter().any(|t| t.starts_with('-')) {
+            return Err(anyhow!(
+                "role {}: excluding tables is not supported by {}, list the tables instead",
+                name,
+                engine
+            ));
+        }
+    }
+
+    Ok(())
+}
+
+/// The checks of [validate] but the roles: the connection options, the
+/// sections and the users which only Postgres has are rejected
+pub fn validate_config(config: &Config, engine: &str) -> Result<()> {
+    let connection = &config.connection;
+    for (name, set) in [
+        ("dialect", connection.dialect.is_some()),
+        ("sslmode", connection.sslmode.is_some()),
+        ("sslrootcert", connection.sslrootcert.is_some()),
+        ("sslcert", connection.sslcert.is_some()),
+        ("sslkey", connection.sslkey.is_some()),
+        ("statement_timeout", connection.statement_timeout.is_some()),
+    ] {
+        if set {
+            return Err(anyhow!(
+                "connection: {} is not supported by {}, set the options of the url instead",
+                name,
+                engine
+            ));
+        }
+    }
+    for (name, set) in [
+        ("managed_users", !config.managed_users.is_empty()),
+        ("redshift_roles", !config.redshift_roles.is_empty()),
+        ("audit", config.audit.is_some()),
+        ("notifications", !config.notifications.is_empty()),
+    ] {
+        if set {
+            return Err(anyhow!("{} is not supported by {}", name, engine));
+        }
+    }
+
+    for user in &config.users {
+        if user.is_public() {
+            return Err(anyhow!(
+                "user {}: PUBLIC is not supported by {}",
+                user.name,
+                engine
+            ));
+        }
+        if !user.attributes.is_empty() {
+            return Err(anyhow!(
+                "user {}: the user attributes are not supported by {}",
+                user.name,
+                engine
+            ));
+        }
+        if !user.settings.is_empty() {
+            return Err(anyhow!(
+                "user {}: settings are not supported by {}",
+                user.name,
+                engine
+            ));
+        }
+        if !user.revokes.is_empty() {
+            return Err(anyhow!(
+                "user {}: revokes are not supported by {}",
+                user.name,
+                engine
+            ));
+        }
+        if let Some(password) = user.password.as_deref() {
+            if password.len() == 35 && password.starts_with("md5") {
+                return Err(anyhow!(
+                    "user {}: md5 passwords are only supported by postgres",
+                    user.name
+                ));
+            }
+        }
+    }
+
+    Ok(())
+}
+
+/// The objects of a role as `(database, table)`, None for every table of
+/// the database: the databases of a database role, the schemas of a schema
+/// role, and the tables of a table role in each of its schemas unless
+/// qualified
+pub fn role_objects(role: &Role) -> Vec<(String, Option<String>)> {
+    let mut objects = vec![];
+    match role {
+        Role::Database(role) => objects.extend(role.databases.iter().map(|d| (d.clone(), None))),
+        Role::Schema(role) => objects.extend(role.schemas.iter().map(|s| (s.clone(), None))),
+        Role::Table(role) => {
+            for table in &role.tables {
+                let table = table.strip_prefix('+').unwrap_or(table);
+                match table.split_once('.') {
+                    Some((schema, table)) => {
+                        objects.push((schema.to_string(), Some(table.to_string())))
+                    }
+                    None => objects.extend(role.schemas.iter().map(|schema| {
+                        match table.eq_ignore_ascii_case("ALL") {
+                            true => (schema.clone(), None),
+                            false => (schema.clone(), Some(table.to_string())),
+                        }
+                    })),
+                }
+            }
+        }
+        Role::Function(_) | Role::Sequence(_) | Role::Warehouse(_) => {}
+    }
+
+    objects
+}
+
+/// The privileges wanted which are not granted, in order and once each
+pub fn missing(wanted: &[Privilege], granted: &BTreeSet<Privilege>) -> Vec<Privilege> {
+    let mut seen = BTreeSet::new();
+
+    wanted
+        .iter()
+        .filter(|p| !granted.contains(p) && seen.insert(*p))
+        .cloned()
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn privilege(object: &str, privilege: &str) -> Privilege {
+        Privilege {
+            grantee: "duyet".to_string(),
+            object: object.to_string(),
+            privilege: privilege.to_string(),
+        }
+    }
+
+    #[test]
+    fn test_missing() {
+        let granted = [privilege("sales.*", "SELECT")].into_iter().collect();
+        let wanted = [
+            privilege("sales.*", "SELECT"),
+            privilege("sales.*", "INSERT"),
+            privilege("sales.*", "INSERT"),
+            privilege("hr.*", "SELECT"),
+        ];
+
+        assert_eq!(
+            missing(&wanted, &granted),
+            vec![privilege("sales.*", "INSERT"), privilege("hr.*", "SELECT")]
+        );
+    }
+}
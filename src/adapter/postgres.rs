@@ -0,0 +1,329 @@
+//! Postgres and its dialects as a [DatabaseAdapter]: the SQL of the
+//! `to_sql` methods of the config and the catalog of [DbConnection].
+//!
+//! `apply` of a `postgres` or `redshift` connection has its own path, with
+//! its transactions, plans and revokes. The adapter is the base of the
+//! engines speaking the Postgres protocol, which only need the grants of
+//! [converge](super::converge). Only the privileges read from the catalog
+//! can be converged, `ALL` is the ones it reads:
+//!
+//! - database: `CREATE`, `TEMP`
+//! - schema: `CREATE`, `USAGE`
+//! - table: `SELECT`, `INSERT`, `UPDATE`, `DELETE`, `REFERENCES`, on
+//!   `ALL TABLES IN SCHEMA` once every table of the schema has it
+
+use super::{DatabaseAdapter, Privilege};
+use crate::config::{ident, Config, Grantee, Group as ConfigGroup, Role, User};
+use crate::connection::{DbConnection, Group};
+use anyhow::{anyhow, Result};
+use std::collections::{BTreeMap, BTreeSet};
+
+/// The privileges on databases read from the catalog
+pub const DATABASE_PRIVILEGES: &[&str] = &["CREATE", "TEMP"];
+
+/// The privileges on schemas read from the catalog
+pub const SCHEMA_PRIVILEGES: &[&str] = &["CREATE", "USAGE"];
+
+/// The privileges on tables read from the catalog
+pub const TABLE_PRIVILEGES: &[&str] = &["SELECT", "INSERT", "UPDATE", "DELETE", "REFERENCES"];
+
+impl DatabaseAdapter for DbConnection {
+    fn name(&self) -> &'static str {
+        "postgres"
+    }
+
+    fn validate(&self, config: &Config) -> Result<()> {
+        config.validate_dialect(self.dialect())?;
+
+        if let Some(user) = config.users.iter().find(|u| u.is_public()) {
+            return Err(anyhow!(
+                "user {}: PUBLIC is not supported by the postgres adapter",
+                user.name
+            ));
+        }
+        // The grants to a group need `GROUP` on Redshift, the catalog does
+        // not tell groups and users apart
+        if self.dialect().is_redshift() {
+            if let Some(group) = config.groups.iter().find(|g| !g.roles.is_empty()) {
+                return Err(anyhow!(
+                    "group {}: the roles of the groups of redshift are not supported by the postgres adapter",
+                    group.name
+                ));
+            }
+        }
+
+        for role in &config.roles {
+            let name = role.get_name();
+            let supported = match role {
+                Role::Database(_) => DATABASE_PRIVILEGES,
+                Role::Schema(_) => SCHEMA_PRIVILEGES,
+                Role::Table(_) => TABLE_PRIVILEGES,
+                Role::Function(_) | Role::Sequence(_) | Role::Warehouse(_) => {
+                    return Err(anyhow!(
+                        "role {}: {} roles are not supported by the postgres adapter",
+                        name,
+                        role.get_level()
+                    ))
+                }
+            };
+            if role.database().is_some() {
+                return Err(anyhow!(
+                    "role {}: database is not supported by the postgres adapter",
+                    name
+                ));
+            }
+            if role.get_tables().iter().any(|t| t.starts_with('-')) {
+                return Err(anyhow!(
+                    "role {}: excluding tables is not supported by the postgres adapter",
+                    name
+                ));
+            }
+            if let Some(grant) = role.get_grants().iter().find(|g| {
+                let grant = privilege(g);
+                grant != "ALL" && !supported.contains(&grant.as_str())
+            }) {
+                return Err(anyhow!(
+                    "role {}: {} is not read from the catalog by the postgres adapter",
+                    name,
+                    grant
+                ));
+            }
+        }
+
+        Ok(())
+    }
+
+    fn catalog_name(&self, name: &str) -> String {
+        ident::fold(name)
+    }
+
+    fn fetch_users(&mut self) -> Result<Vec<String>> {
+        Ok(self.get_users()?.into_iter().map(|u| u.name).collect())
+    }
+
+    fn fetch_privileges(&mut self) -> Result<Vec<Privilege>> {
+        let mut privileges = vec![];
+        let mut push = |grantee: &str, object: String, held: Vec<&str>| {
+            privileges.extend(held.into_iter().map(|privilege| Privilege {
+                grantee: grantee.to_string(),
+                object: object.clone(),
+                privilege: privilege.to_string(),
+            }))
+        };
+
+        for role in self.get_user_database_privileges()? {
+            push(&role.name, database(&role.database_name), role.privileges());
+        }
+        for role in self.get_user_schema_privileges()? {
+            push(&role.name, schema(&role.schema_name), role.privileges());
+        }
+
+        // A privilege on every table of a schema is the one of a grant on
+        // all of them
+        let tables = self.get_user_table_privileges()?;
+        let mut schemas: BTreeMap<&str, BTreeSet<&str>> = BTreeMap::new();
+        for role in &tables {
+            schemas
+                .entry(&role.schema_name)
+                .or_default()
+                .insert(&role.table_name);
+        }
+        let mut held: BTreeMap<(&str, &str, &str), usize> = BTreeMap::new();
+        for role in &tables {
+            for privilege in role.privileges() {
+                *held
+                    .entry((&role.name, &role.schema_name, privilege))
+                    .or_default() += 1;
+            }
+            push(
+                &role.name,
+                table(&role.schema_name, &role.table_name),
+                role.privileges(),
+            );
+        }
+        for ((grantee, schema_name, privilege), count) in held {
+            if schemas.get(schema_name).map(BTreeSet::len) == Some(count) {
+                push(grantee, all_tables(schema_name), vec![privilege]);
+            }
+        }
+
+        Ok(privileges)
+    }
+
+    fn role_privileges(&self, role: &Role, grantee: Grantee) -> Vec<Privilege> {
+        role_privileges(role, grantee.name())
+    }
+
+    fn render_create_user(&self, user: &User) -> String {
+        user.to_sql_create(self.dialect())
+    }
+
+    fn render_update_password(&self, user: &User) -> String {
+        user.to_sql_update()
+    }
+
+    fn render_grant(&self, privileges: &[Privilege]) -> Vec<String> {
+        render_grant(privileges)
+    }
+
+    fn fetch_groups(&mut self) -> Result<Vec<Group>> {
+        self.get_groups()
+    }
+
+    fn render_create_group(&self, group: &str) -> Result<String> {
+        Ok(config_group(group).to_sql_create(self.dialect()))
+    }
+
+    fn render_add_member(&self, group: &str, member: &str) -> Result<String> {
+        Ok(config_group(group).to_sql_add(member, self.dialect()))
+    }
+
+    fn execute_batch(&mut self, statements: &[String]) -> Result<()> {
+        DbConnection::execute_batch(self, &statements.join("\n"))
+    }
+}
+
+/// The privileges of a role granted to a user or a group, named as in the
+/// catalog
+pub fn role_privileges(role: &Role, grantee: &str) -> Vec<Privilege> {
+    let supported = match role {
+        Role::Database(_) => DATABASE_PRIVILEGES,
+        Role::Schema(_) => SCHEMA_PRIVILEGES,
+        Role::Table(_) => TABLE_PRIVILEGES,
+        Role::Function(_) | Role::Sequence(_) | Role::Warehouse(_) => &[],
+    };
+    let mut privileges = vec![];
+    for grant in role.get_grants().iter().map(|g| privilege(g)) {
+        match grant.as_str() {
+            "ALL" => privileges.extend(supported.iter().map(|p| p.to_string())),
+            _ => privileges.push(grant),
+        }
+    }
+
+    let mut result = vec![];
+    for (name, table_name) in super::role_objects(role) {
+        let name = ident::fold(&name);
+        let object = match (role, table_name) {
+            (Role::Database(_), _) => database(&name),
+            (Role::Table(_), Some(table_name)) => table(&name, &ident::fold(&table_name)),
+            (Role::Table(_), None) => all_tables(&name),
+            _ => schema(&name),
+        };
+        for privilege in &privileges {
+            result.push(Privilege {
+                grantee: grantee.to_string(),
+                object: object.clone(),
+                privilege: privilege.clone(),
+            });
+        }
+    }
+
+    result
+}
+
+/// The GRANT statements of privileges, one per grantee and object
+pub fn render_grant(privileges: &[Privilege]) -> Vec<String> {
+    let mut grants: Vec<(&str, &str, Vec<&str>)> = vec![];
+    for privilege in privileges {
+        match grants.iter_mut().find(|(grantee, object, _)| {
+            *grantee == privilege.grantee && *object == privilege.object
+        }) {
+            Some((_, _, list)) => list.push(&privilege.privilege),
+            None => grants.push((
+                &privilege.grantee,
+                &privilege.object,
+                vec![&privilege.privilege],
+            )),
+        }
+    }
+
+    grants
+        .iter()
+        .map(|(grantee, object, privileges)| {
+            format!(
+                "GRANT {} ON {} TO {};",
+                privileges.join(", "),
+                object,
+                ident::quote_folded(grantee)
+            )
+        })
+        .collect()
+}
+
+/// A privilege as read from the catalog, `TEMPORARY` is `TEMP`
+fn privilege(grant: &str) -> String {
+    match grant.to_uppercase().as_str() {
+        "TEMPORARY" => "TEMP".to_string(),
+        grant => grant.to_string(),
+    }
+}
+
+fn database(name: &str) -> String {
+    format!("DATABASE {}", ident::quote_folded(name))
+}
+
+fn schema(name: &str) -> String {
+    format!("SCHEMA {}", ident::quote_folded(name))
+}
+
+fn table(schema: &str, table: &str) -> String {
+    format!(
+        "TABLE {}.{}",
+        ident::quote_folded(schema),
+        ident::quote_folded(table)
+    )
+}
+
+fn all_tables(schema: &str) -> String {
+    format!("ALL TABLES IN SCHEMA {}", ident::quote_folded(schema))
+}
+
+/// A group of the config to render its statements
+fn config_group(name: &str) -> ConfigGroup {
+    ConfigGroup {
+        name: name.to_string(),
+        members: vec![],
+        roles: vec![],
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use indoc::indoc;
+    use std::str::FromStr;
+
+    #[test]
+    fn test_role_privileges() {
+        let config = Config::from_str(indoc! {"
+            connection:
+              type: postgres
+              url: postgres://localhost:5432/postgres
+            roles:
+              - name: role_db
+                type: database
+                grants: [ALL]
+                databases: [Analytics]
+              - name: role_read
+                type: table
+                grants: [SELECT]
+                schemas: [sales]
+                tables: [ALL, hr.\"Payroll\"]
+            users: []
+        "})
+        .unwrap();
+        let grants = |role: usize| render_grant(&role_privileges(&config.roles[role], "duyet"));
+
+        assert_eq!(
+            grants(0),
+            vec!["GRANT CREATE, TEMP ON DATABASE analytics TO duyet;"]
+        );
+        assert_eq!(
+            grants(1),
+            vec![
+                "GRANT SELECT ON ALL TABLES IN SCHEMA sales TO duyet;",
+                "GRANT SELECT ON TABLE hr.\"Payroll\" TO duyet;",
+            ]
+        );
+    }
+}
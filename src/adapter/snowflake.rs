@@ -0,0 +1,719 @@
+//! Snowflake, `connection.type: snowflake`, through its SQL REST API.
+//!
+//! The roles of the config are the roles of Snowflake: they are created
+//! with `CREATE ROLE`, their privileges granted to them and they are
+//! granted to their users with `GRANT ROLE ... TO USER`. The groups are
+//! roles too, granted to their members, with the roles of the config
+//! granted to them.
+//!
+//! The objects are `WAREHOUSE wh`, `DATABASE db`, `SCHEMA db.schema` and
+//! `TABLE db.schema.table`, the database of a schema or table role is its
+//! `database` or the one of the url. The url is the one of the account with
+//! the token of the API and the options of the statements, e.g.
+//! `https://acme-xy12345.snowflakecomputing.com/?token=${SNOWFLAKE_TOKEN}&role=SECURITYADMIN`:
+//!
+//! - `token`: an OAuth token, or a key pair JWT with
+//!   `token_type=KEYPAIR_JWT`
+//! - `role`, `warehouse`, `database`: the context of the statements
+
+use super::{DatabaseAdapter, Privilege};
+use crate::config::{Config, Grantee, Role, User};
+use crate::connection::Group;
+use anyhow::{anyhow, Context, Result};
+use reqwest::blocking::{Client, RequestBuilder};
+use reqwest::{StatusCode, Url};
+use serde::Deserialize;
+use serde_json::{json, Value};
+use std::collections::{BTreeMap, BTreeSet};
+use std::time::Duration;
+
+/// The privileges on warehouses, `ALL` is all of them
+pub const WAREHOUSE_PRIVILEGES: &[&str] = &["USAGE", "OPERATE", "MONITOR", "MODIFY"];
+
+/// The privileges of `ALL` on a database, `CREATE` is `CREATE SCHEMA`
+pub const DATABASE_ALL: &[&str] = &["USAGE", "CREATE SCHEMA"];
+
+/// The privileges of `ALL` on a schema, `CREATE` is `CREATE TABLE` and
+/// `CREATE VIEW`
+pub const SCHEMA_ALL: &[&str] = &["USAGE", "CREATE TABLE", "CREATE VIEW"];
+
+/// The privileges on tables, `ALL` is all of them
+pub const TABLE_PRIVILEGES: &[&str] = &[
+    "SELECT",
+    "INSERT",
+    "UPDATE",
+    "DELETE",
+    "TRUNCATE",
+    "REFERENCES",
+];
+
+/// How long to wait between the checks of a statement still running
+const POLL_INTERVAL: Duration = Duration::from_millis(500);
+
+/// A response of the SQL API, the rows are the first partition of the
+/// result
+#[derive(Debug, Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct Response {
+    #[serde(rename = "resultSetMetaData")]
+    metadata: Option<Metadata>,
+    #[serde(default)]
+    data: Vec<Vec<Option<String>>>,
+    statement_handle: Option<String>,
+    message: Option<String>,
+}
+
+#[derive(Debug, Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct Metadata {
+    row_type: Vec<Column>,
+    #[serde(default)]
+    partition_info: Vec<Value>,
+}
+
+#[derive(Debug, Deserialize)]
+struct Column {
+    name: String,
+}
+
+/// The SQL API of a Snowflake account
+pub struct Snowflake {
+    client: Client,
+    /// `/api/v2/statements` of the account
+    endpoint: Url,
+    token: String,
+    token_type: String,
+    /// The context of the statements: `role`, `warehouse` and `database`
+    context: BTreeMap<String, String>,
+    /// The users of the config, named as in the catalog
+    users: Vec<String>,
+    /// The roles of the config, named as in the catalog
+    roles: Vec<String>,
+    /// The groups of the config, named as in the catalog
+    groups: Vec<String>,
+    /// The roles of the account, as read by the last
+    /// [fetch_privileges](DatabaseAdapter::fetch_privileges)
+    existing_roles: BTreeSet<String>,
+}
+
+impl Snowflake {
+    /// The client of the url of the connection, the account is only
+    /// reached by the first statement
+    pub fn connect(config: &Config) -> Result<Self> {
+        let connection = &config.connection;
+        let url = Url::parse(&connection.url).context("invalid connection url")?;
+
+        let mut token = None;
+        let mut token_type = "OAUTH".to_string();
+        let mut context = BTreeMap::new();
+        for (name, value) in url.query_pairs() {
+            match name.as_ref() {
+                "token" => token = Some(value.to_string()),
+                "token_type" => token_type = value.to_uppercase(),
+                "role" | "warehouse" | "database" => {
+                    context.insert(name.to_string(), value.to_string());
+                }
+                _ => return Err(anyhow!("connection: unknown url option {}", name)),
+            }
+        }
+        let token = token.ok_or_else(|| {
+            anyhow!("connection: the url of snowflake needs its token, e.g. ?token=${{SNOWFLAKE_TOKEN}}")
+        })?;
+
+        let mut client = Client::builder();
+        if let Some(timeout) = connection.connect_timeout {
+            client = client.connect_timeout(Duration::from_secs(timeout));
+        }
+
+        Ok(Snowflake {
+            client: client.build()?,
+            endpoint: url.join("/api/v2/statements")?,
+            token,
+            token_type,
+            context,
+            users: config.users.iter().map(|u| fold(&u.name)).collect(),
+            roles: config.roles.iter().map(|r| fold(&r.get_name())).collect(),
+            groups: config.groups.iter().map(|g| fold(&g.name)).collect(),
+            existing_roles: BTreeSet::new(),
+        })
+    }
+
+    fn send(&self, request: RequestBuilder) -> Result<Response> {
+        let response = request
+            .bearer_auth(&self.token)
+            .header("X-Snowflake-Authorization-Token-Type", &self.token_type)
+            .header("Accept", "application/json")
+            .send()
+            .context("failed to reach snowflake")?;
+
+        let status = response.status();
+        let text = response.text().unwrap_or_default();
+        let body = serde_json::from_str::<Response>(&text);
+        if !status.is_success() {
+            return Err(anyhow!(
+                "{}",
+                body.ok().and_then(|b| b.message).unwrap_or_else(|| format!(
+                    "{} {}",
+                    status,
+                    text.trim()
+                ))
+            ));
+        }
+        let mut body = body.with_context(|| format!("invalid response {}", text))?;
+
+        // Accepted, the statement is still running
+        if status == StatusCode::ACCEPTED {
+            let handle = body
+                .statement_handle
+                .take()
+                .ok_or_else(|| anyhow!("no statement handle in {}", text))?;
+            std::thread::sleep(POLL_INTERVAL);
+            return self.send(self.client.get(self.statement_url(&handle)?));
+        }
+
+        Ok(body)
+    }
+
+    fn statement_url(&self, handle: &str) -> Result<Url> {
+        Ok(self.endpoint.join(&format!("statements/{}", handle))?)
+    }
+
+    /// Execute a statement, the rows of its result by lower case column
+    fn query(&self, sql: &str) -> Result<Vec<BTreeMap<String, String>>> {
+        let mut body = json!({"statement": sql.trim()});
+        for (name, value) in &self.context {
+            body[name.as_str()] = json!(value);
+        }
+        let response = self.send(
+            self.client
+                .post(self.endpoint.clone())
+                .header("Content-Type", "application/json")
+                .body(body.to_string()),
+        )?;
+
+        let Some(metadata) = response.metadata else {
+            return Ok(vec![]);
+        };
+        let mut data = response.data;
+        // The other partitions of a large result
+        if let Some(handle) = &response.statement_handle {
+            for partition in 1..metadata.partition_info.len() {
+                let mut url = self.statement_url(handle)?;
+                url.set_query(Some(&format!("partition={}", partition)));
+                data.extend(self.send(self.client.get(url))?.data);
+            }
+        }
+
+        Ok(data
+            .into_iter()
+            .map(|row| {
+                metadata
+                    .row_type
+                    .iter()
+                    .map(|c| c.name.to_lowercase())
+                    .zip(row.into_iter().map(Option::unwrap_or_default))
+                    .collect()
+            })
+            .collect())
+    }
+
+    /// The values of a column of the rows of a statement
+    fn column(&self, sql: &str, column: &str) -> Result<Vec<String>> {
+        Ok(self
+            .query(sql)?
+            .into_iter()
+            .filter_map(|mut row| row.remove(column))
+            .collect())
+    }
+}
+
+impl DatabaseAdapter for Snowflake {
+    fn name(&self) -> &'static str {
+        "snowflake"
+    }
+
+    fn validate(&self, config: &Config) -> Result<()> {
+        validate(config, self.context.get("database").map(String::as_str))
+    }
+
+    fn catalog_name(&self, name: &str) -> String {
+        fold(name)
+    }
+
+    fn fetch_users(&mut self) -> Result<Vec<String>> {
+        self.column("SHOW USERS", "name")
+            .context("failed to read the users")
+    }
+
+    fn fetch_privileges(&mut self) -> Result<Vec<Privilege>> {
+        self.existing_roles = self
+            .column("SHOW ROLES", "name")
+            .context("failed to read the roles")?
+            .into_iter()
+            .collect();
+        let users = self.fetch_users()?;
+        let mut privileges = vec![];
+
+        let roles = self.roles.iter().chain(&self.groups);
+        for role in roles.filter(|r| self.existing_roles.contains(*r)) {
+            let grants = self
+                .query(&format!("SHOW GRANTS TO ROLE {}", quote(role)))
+                .with_context(|| format!("failed to read the grants of the role {}", role))?;
+            privileges.extend(grants.into_iter().map(|row| {
+                let get = |column: &str| row.get(column).cloned().unwrap_or_default();
+                let object = match get("granted_on").as_str() {
+                    "ROLE" => format!("ROLE {}", quote(&get("name"))),
+                    granted_on => format!("{} {}", granted_on, get("name")),
+                };
+                Privilege {
+                    grantee: format!("ROLE {}", quote(role)),
+                    object,
+                    privilege: get("privilege"),
+                }
+            }));
+        }
+        for user in self.users.iter().filter(|u| users.contains(*u)) {
+            let roles = self
+                .column(&format!("SHOW GRANTS TO USER {}", quote(user)), "role")
+                .with_context(|| format!("failed to read the roles of the user {}", user))?;
+            privileges.extend(roles.into_iter().map(|role| Privilege {
+                grantee: format!("USER {}", quote(user)),
+                object: format!("ROLE {}", quote(&role)),
+                privilege: "USAGE".to_string(),
+            }));
+        }
+
+        Ok(privileges)
+    }
+
+    fn role_privileges(&self, role: &Role, grantee: Grantee) -> Vec<Privilege> {
+        role_privileges(
+            role,
+            grantee,
+            self.context.get("database").map(String::as_str),
+        )
+    }
+
+    fn render_create_user(&self, user: &User) -> String {
+        let password = match &user.password {
+            Some(password) => format!(" PASSWORD = {}", literal(password)),
+            None => String::new(),
+        };
+
+        format!("CREATE USER {}{};", quote(&fold(&user.name)), password)
+    }
+
+    fn render_update_password(&self, user: &User) -> String {
+        format!(
+            "ALTER USER {} SET PASSWORD = {};",
+            quote(&fold(&user.name)),
+            literal(user.password.as_deref().unwrap_or_default())
+        )
+    }
+
+    fn render_grant(&self, privileges: &[Privilege]) -> Vec<String> {
+        render_grant(privileges, &self.existing_roles)
+    }
+
+    fn fetch_groups(&mut self) -> Result<Vec<Group>> {
+        let existing = self
+            .column("SHOW ROLES", "name")
+            .context("failed to read the roles")?;
+
+        let mut groups = vec![];
+        for name in existing.into_iter().filter(|r| self.groups.contains(r)) {
+            let members = self
+                .query(&format!("SHOW GRANTS OF ROLE {}", quote(&name)))
+                .with_context(|| format!("failed to read the members of the role {}", name))?
+                .into_iter()
+                .filter(|row| row.get("granted_to").map(String::as_str) == Some("USER"))
+                .filter_map(|mut row| row.remove("grantee_name"))
+                .collect();
+            groups.push(Group { name, members });
+        }
+
+        Ok(groups)
+    }
+
+    fn render_create_group(&self, group: &str) -> Result<String> {
+        Ok(format!("CREATE ROLE {};", quote(&fold(group))))
+    }
+
+    fn render_add_member(&self, group: &str, member: &str) -> Result<String> {
+        Ok(format!(
+            "GRANT ROLE {} TO USER {};",
+            quote(&fold(group)),
+            quote(&fold(member))
+        ))
+    }
+
+    fn execute_batch(&mut self, statements: &[String]) -> Result<()> {
+        for statement in statements {
+            self.query(statement)?;
+        }
+
+        Ok(())
+    }
+}
+
+/// Check that the config only uses what Snowflake supports, the database
+/// is the one of the url
+pub fn validate(config: &Config, database: Option<&str>) -> Result<()> {
+    super::validate_config(config, "snowflake")?;
+
+    for group in &config.groups {
+        if config
+            .roles
+            .iter()
+            .any(|r| fold(&r.get_name()) == fold(&group.name))
+        {
+            return Err(anyhow!(
+                "group {}: a role has its name, both are roles of snowflake",
+                group.name
+            ));
+        }
+    }
+
+    for role in &config.roles {
+        let name = role.get_name();
+        let supported = match role {
+            Role::Warehouse(_) => WAREHOUSE_PRIVILEGES,
+            Role::Database(_) => &["USAGE", "CREATE"],
+            Role::Schema(_) => &["USAGE", "CREATE"],
+            Role::Table(_) => TABLE_PRIVILEGES,
+            Role::Function(_) | Role::Sequence(_) => {
+                return Err(anyhow!(
+                    "role {}: {} roles are not supported by snowflake",
+                    name,
+                    role.get_level()
+                ))
+            }
+        };
+        if let Some(grant) = role
+            .get_grants()
+            .iter()
+            .find(|g| *g != "ALL" && !supported.contains(&g.as_str()))
+        {
+            return Err(anyhow!(
+                "role {}: {} is not supported by snowflake, expected: {:?}",
+                name,
+                grant,
+                supported
+            ));
+        }
+        if matches!(role, Role::Schema(_) | Role::Table(_))
+            && role.database().or(database).is_none()
+        {
+            return Err(anyhow!(
+                "role {}: the database of the schemas is required by snowflake, set the database of the role or of the url",
+                name
+            ));
+        }
+        if let Some(database) = role
+            .get_databases()
+            .iter()
+            .find(|d| d.starts_with('-') || *d == "ALL")
+        {
+            return Err(anyhow!(
+                "role {}: {} is not supported by snowflake, list the databases instead",
+                name,
+                database
+            ));
+        }
+        if let Some(table) = role
+            .get_tables()
+            .iter()
+            .find(|t| t.starts_with('-') || t.eq_ignore_ascii_case("ALL"))
+        {
+            return Err(anyhow!(
+                "role {}: {} is not supported by snowflake, list the tables instead",
+                name,
+                table
+            ));
+        }
+    }
+
+    Ok(())
+}
+
+/// The privileges of a role: the ones on its objects granted to the role
+/// of Snowflake of the same name, and the role granted to the user or the
+/// group. The database of the schemas is the one of the role, or `database`.
+pub fn role_privileges(role: &Role, grantee: Grantee, database: Option<&str>) -> Vec<Privilege> {
+    let name = quote(&fold(&role.get_name()));
+    let database = role.database().or(database).unwrap_or_default();
+    let qualified = |parts: &[&str]| {
+        parts
+            .iter()
+            .map(|p| quote(&fold(p)))
+            .collect::<Vec<_>>()
+            .join(".")
+    };
+
+    let mut objects = vec![];
+    match role {
+        Role::Warehouse(role) => objects.extend(
+            role.warehouses
+                .iter()
+                .map(|w| format!("WAREHOUSE {}", qualified(&[w]))),
+        ),
+        Role::Database(role) => objects.extend(
+            role.databases
+                .iter()
+                .map(|d| format!("DATABASE {}", qualified(&[d]))),
+        ),
+        Role::Schema(role) => objects.extend(
+            role.schemas
+                .iter()
+                .map(|s| format!("SCHEMA {}", qualified(&[database, s]))),
+        ),
+        Role::Table(role) => {
+            for table in &role.tables {
+                let table = table.strip_prefix('+').unwrap_or(table);
+                match crate::config::ident::split_qualified(table)[..] {
+                    [schema, table] => {
+                        objects.push(format!("TABLE {}", qualified(&[database, schema, table])))
+                    }
+                    _ => objects.extend(
+                        role.schemas
+                            .iter()
+                            .map(|s| format!("TABLE {}", qualified(&[database, s, table]))),
+                    ),
+                }
+            }
+        }
+        Role::Function(_) | Role::Sequence(_) => {}
+    }
+
+    let grants = role.get_grants();
+    let mut privileges = vec![];
+    for grant in &grants {
+        match (role, grant.as_str()) {
+            (Role::Warehouse(_), "ALL") => privileges.extend_from_slice(WAREHOUSE_PRIVILEGES),
+            (Role::Database(_), "ALL") => privileges.extend_from_slice(DATABASE_ALL),
+            (Role::Database(_), "CREATE") => privileges.push("CREATE SCHEMA"),
+            (Role::Schema(_), "ALL") => privileges.extend_from_slice(SCHEMA_ALL),
+            (Role::Schema(_), "CREATE") => privileges.extend_from_slice(&SCHEMA_ALL[1..]),
+            (Role::Table(_), "ALL") => privileges.extend_from_slice(TABLE_PRIVILEGES),
+            _ => privileges.push(grant),
+        }
+    }
+
+    let mut result = vec![];
+    for object in objects {
+        for privilege in &privileges {
+            result.push(Privilege {
+                grantee: format!("ROLE {}", name),
+                object: object.clone(),
+                privilege: privilege.to_string(),
+            });
+        }
+    }
+    result.push(Privilege {
+        grantee: match grantee {
+            Grantee::Group(group) => format!("ROLE {}", quote(group)),
+            _ => format!("USER {}", quote(grantee.name())),
+        },
+        object: format!("ROLE {}", name),
+        privilege: "USAGE".to_string(),
+    });
+
+    result
+}
+
+/// The statements granting privileges, one per grantee and object: the
+/// roles of the privileges which are not in `existing` are created first,
+/// and a role is granted with `GRANT ROLE`
+pub fn render_grant(privileges: &[Privilege], existing: &BTreeSet<String>) -> Vec<String> {
+    let mut statements = vec![];
+    let mut created = BTreeSet::new();
+    for privilege in privileges {
+        if let Some(role) = privilege.grantee.strip_prefix("ROLE ") {
+            if !privilege.object.starts_with("ROLE ")
+                && !existing.contains(&unquote(role))
+                && created.insert(role)
+            {
+                statements.push(format!("CREATE ROLE IF NOT EXISTS {};", role));
+            }
+        }
+    }
+
+    let mut grants: Vec<(&str, &str, Vec<&str>)> = vec![];
+    for privilege in privileges {
+        match grants.iter_mut().find(|(grantee, object, _)| {
+            *grantee == privilege.grantee && *object == privilege.object
+        }) {
+            Some((_, _, list)) => list.push(&privilege.privilege),
+            None => grants.push((
+                &privilege.grantee,
+                &privilege.object,
+                vec![&privilege.privilege],
+            )),
+        }
+    }
+
+    statements.extend(grants.iter().map(|(grantee, object, privileges)| {
+        match object.strip_prefix("ROLE ") {
+            Some(role) => format!("GRANT ROLE {} TO {};", role, grantee),
+            None => format!(
+                "GRANT {} ON {} TO {};",
+                privileges.join(", "),
+                object,
+                grantee
+            ),
+        }
+    }));
+
+    statements
+}
+
+/// The name of the catalog: upper case, as Snowflake stores the names
+/// without quotes, unless quoted or not a plain identifier
+pub fn fold(name: &str) -> String {
+    match name.strip_prefix('"').and_then(|n| n.strip_suffix('"')) {
+        Some(quoted) if !quoted.is_empty() => quoted.replace("\"\"", "\""),
+        _ if is_plain(name) => name.to_ascii_uppercase(),
+        _ => name.to_string(),
+    }
+}
+
+/// A name of the catalog in a statement, quoted unless stored upper case,
+/// as in the names of `SHOW GRANTS`
+pub fn quote(name: &str) -> String {
+    match is_plain(name) && name.to_ascii_uppercase() == name {
+        true => name.to_string(),
+        false => format!("\"{}\"", name.replace('"', "\"\"")),
+    }
+}
+
+/// A name as quoted by [quote], unquoted
+fn unquote(name: &str) -> String {
+    match name.strip_prefix('"').and_then(|n| n.strip_suffix('"')) {
+        Some(quoted) => quoted.replace("\"\"", "\""),
+        None => name.to_string(),
+    }
+}
+
+/// A letter or an underscore, then letters, digits, underscores or dollars
+fn is_plain(name: &str) -> bool {
+    let mut chars = name.chars();
+    chars
+        .next()
+        .is_some_and(|c| c.is_ascii_alphabetic() || c == '_')
+        && chars.all(|c| c.is_ascii_alphanumeric() || c == '_' || c == '$')
+}
+
+fn literal(value: &str) -> String {
+    format!("'{}'", value.replace('\\', "\\\\").replace('\'', "\\'"))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use indoc::indoc;
+    use std::str::FromStr;
+
+    fn config(body: &str) -> Config {
+        Config::from_str(&format!(
+            "connection:\n  type: snowflake\n  url: https://acme.snowflakecomputing.com/?token=t\n{}",
+            body
+        ))
+        .unwrap()
+    }
+
+    #[test]
+    fn test_role_privileges() {
+        let config = config(indoc! {"
+            roles:
+              - name: role_compute
+                type: warehouse
+                grants: [USAGE]
+                warehouses: [compute_wh]
+              - name: role_read
+                type: table
+                grants: [SELECT]
+                database: analytics
+                schemas: [sales]
+                tables: [orders, hr.\"Payroll\"]
+              - name: role_schema
+                type: schema
+                grants: [ALL]
+                schemas: [sales]
+            users: []
+        "});
+        let grants = |role: usize, grantee: Grantee| {
+            render_grant(
+                &role_privileges(&config.roles[role], grantee, Some("warehouse_db")),
+                &BTreeSet::from(["ROLE_READ".to_string()]),
+            )
+        };
+
+        assert_eq!(
+            grants(0, Grantee::User("DUYET")),
+            vec![
+                "CREATE ROLE IF NOT EXISTS ROLE_COMPUTE;",
+                "GRANT USAGE ON WAREHOUSE COMPUTE_WH TO ROLE ROLE_COMPUTE;",
+                "GRANT ROLE ROLE_COMPUTE TO USER DUYET;",
+            ]
+        );
+        assert_eq!(
+            grants(1, Grantee::Group("ANALYSTS")),
+            vec![
+                "GRANT SELECT ON TABLE ANALYTICS.SALES.ORDERS TO ROLE ROLE_READ;",
+                "GRANT SELECT ON TABLE ANALYTICS.HR.\"Payroll\" TO ROLE ROLE_READ;",
+                "GRANT ROLE ROLE_READ TO ROLE ANALYSTS;",
+            ]
+        );
+        assert_eq!(
+            grants(2, Grantee::User("duyet@acme.com"))[1],
+            "GRANT USAGE, CREATE TABLE, CREATE VIEW ON SCHEMA WAREHOUSE_DB.SALES TO ROLE ROLE_SCHEMA;"
+        );
+        assert_eq!(
+            grants(2, Grantee::User("duyet@acme.com"))[2],
+            "GRANT ROLE ROLE_SCHEMA TO USER \"duyet@acme.com\";"
+        );
+    }
+
+    #[test]
+    fn test_validate() {
+        let role = |role: &str| format!("roles:\n  - name: r\n{}\nusers: []", role);
+        assert!(validate(
+            &config(&role(
+                "    type: schema\n    grants: [USAGE]\n    schemas: [s]"
+            )),
+            Some("analytics")
+        )
+        .is_ok());
+
+        for (invalid, error) in [
+            (
+                "    type: schema\n    grants: [USAGE]\n    schemas: [s]",
+                "the database of the schemas is required",
+            ),
+            (
+                "    type: database\n    grants: [TEMP]\n    databases: [d]",
+                "TEMP is not supported by snowflake",
+            ),
+            (
+                "    type: table\n    database: d\n    grants: [SELECT]\n    schemas: [s]\n    tables: [ALL]",
+                "ALL is not supported by snowflake",
+            ),
+        ] {
+            let e = validate(&config(&role(invalid)), None)
+                .unwrap_err()
+                .to_string();
+            assert!(e.contains(error), "{}", e);
+        }
+    }
+
+    #[test]
+    fn test_names() {
+        assert_eq!(fold("analysts"), "ANALYSTS");
+        assert_eq!(fold("\"Analysts\""), "Analysts");
+        assert_eq!(fold("duyet@acme.com"), "duyet@acme.com");
+        assert_eq!(quote("ANALYSTS"), "ANALYSTS");
+        assert_eq!(quote("Analysts"), "\"Analysts\"");
+        assert_eq!(unquote("\"Analysts\""), "Analysts");
+        assert_eq!(literal("it's"), "'it\\'s'");
+    }
+}
@@ -0,0 +1,420 @@
+//! The audit log of `apply`, for the configs with an `audit` block, see
+//! [Audit].
+//!
+//! The statements executed by `apply` are recorded with their result:
+//! `success`, `error: <message>`, or `rolled back` for a statement of a
+//! transaction rolled back afterwards. The passwords are masked. Once the
+//! apply is done, they are written in a single transaction to the audit
+//! table, created with its schema if missing:
+//!
+//! | Column        | Content                                               |
+//! |---------------|-------------------------------------------------------|
+//! | `seq`         | position of the row in the log, from 1                |
+//! | `executed_at` | when the statement was executed                       |
+//! | `executed_by` | the user of the connection                            |
+//! | `config_file` | the config file applied                               |
+//! | `git_sha`     | the commit of the config, from [GIT_SHA_VARS]         |
+//! | `statement`   | the statement                                         |
+//! | `result`      | its result                                            |
+//! | `prev_hash`   | the `hash` of the previous row, empty for the first   |
+//! | `hash`        | SHA-256 of `prev_hash` and the other columns          |
+//!
+//! The rows are chained by their hashes, see [chain_hash]: a row updated or
+//! deleted afterwards breaks the chain of the rows after it, which
+//! [verify_chain] detects.
+
+use crate::config::Audit;
+use crate::connection::DbConnection;
+use anyhow::{Context, Result};
+use jiff::Timestamp;
+use log::info;
+use sha2::{Digest, Sha256};
+
+/// The environment variables of the commit of the config, by priority:
+/// `GRANT_AUDIT_GIT_SHA` to set it explicitly, then the ones of GitHub
+/// Actions, GitLab CI, Buildkite and Jenkins
+pub const GIT_SHA_VARS: [&str; 5] = [
+    "GRANT_AUDIT_GIT_SHA",
+    "GITHUB_SHA",
+    "CI_COMMIT_SHA",
+    "BUILDKITE_COMMIT",
+    "GIT_COMMIT",
+];
+
+/// The commit of the config, see [GIT_SHA_VARS]
+pub fn git_sha() -> Option<String> {
+    GIT_SHA_VARS
+        .iter()
+        .filter_map(|var| std::env::var(var).ok())
+        .find(|sha| !sha.trim().is_empty())
+}
+
+/// The format of `executed_at` in the hash of a row, e.g.
+/// `2024-05-01T10:00:00.123456Z`: `to_char(executed_at AT TIME ZONE 'UTC',
+/// 'YYYY-MM-DD"T"HH24:MI:SS.US"Z"')` in SQL
+pub const EXECUTED_AT: &str = "%Y-%m-%dT%H:%M:%S%.6fZ";
+
+/// A statement executed by `apply`
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Entry {
+    pub executed_at: Timestamp,
+    /// The statement, its passwords masked
+    pub statement: String,
+    pub result: String,
+}
+
+/// The statements executed by an apply, in order
+#[derive(Debug, Clone, Default)]
+pub struct AuditLog {
+    pub entries: Vec<Entry>,
+    /// The entries committed already, see [AuditLog::commit]
+    committed: usize,
+}
+
+impl AuditLog {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Record a statement with the result of its execution, returned as is
+    pub fn record<T>(&mut self, sql: &str, result: Result<T>) -> Result<T> {
+        self.entries.push(Entry {
+            // The precision of the column, for the hash to match it
+            executed_at: Timestamp::from_microsecond(Timestamp::now().as_microsecond())
+                .unwrap_or_default(),
+            statement: mask_passwords(sql),
+            result: match &result {
+                Ok(_) => "success".to_string(),
+                Err(e) => format!("error: {:#}", e),
+            },
+        });
+
+        result
+    }
+
+    /// The statements recorded so far are committed, a rollback does not
+    /// undo them
+    pub fn commit(&mut self) {
+        self.committed = self.entries.len();
+    }
+
+    /// The transaction is rolled back, the statements executed since the
+    /// last commit are undone
+    pub fn rollback(&mut self) {
+        for entry in &mut self.entries[self.committed..] {
+            if entry.result == "success" {
+                entry.result = "rolled back".to_string();
+            }
+        }
+    }
+
+    /// Write the entries to the audit table with the connection, in a
+    /// transaction locking the table so that concurrent applies chain their
+    /// rows one after the other
+    pub fn write(&self, conn: &mut DbConnection, audit: &Audit, file: &str) -> Result<()> {
+        if self.entries.is_empty() {
+            return Ok(());
+        }
+
+        let table = audit.quoted_table()?;
+        let context = || format!("failed to write the audit log to {}", table);
+        create_table(conn, audit).with_context(context)?;
+
+        let executed_by = conn.get_current_user().unwrap_or_default().to_string();
+        let git_sha = git_sha().unwrap_or_default();
+        conn.begin()?;
+        let written = (|| -> Result<()> {
+            conn.execute(&format!("LOCK TABLE {} IN EXCLUSIVE MODE", table), &[])?;
+            let last = conn.query(
+                format!("SELECT seq, hash FROM {} ORDER BY seq DESC LIMIT 1", table).as_str(),
+                &[],
+            )?;
+            let (mut seq, mut prev_hash) = match last.first() {
+                Some(row) => (row.get::<_, i64>(0), row.get::<_, String>(1)),
+                None => (0, String::new()),
+            };
+
+            let insert = format!(
+                "INSERT INTO {} (seq, executed_at, executed_by, config_file, git_sha, \
+                 statement, result, prev_hash, hash) \
+                 VALUES ($1, $2::text::timestamptz, $3, $4, $5, $6, $7, $8, $9)",
+                table
+            );
+            for entry in &self.entries {
+                seq += 1;
+                let row = Row {
+                    seq,
+                    executed_at: entry.executed_at.strftime(EXECUTED_AT).to_string(),
+                    executed_by: executed_by.clone(),
+                    config_file: file.to_string(),
+                    git_sha: git_sha.clone(),
+                    statement: entry.statement.clone(),
+                    result: entry.result.clone(),
+                    prev_hash,
+                };
+                let hash = chain_hash(&row);
+                conn.execute(
+                    &insert,
+                    &[
+                        &row.seq,
+                        &row.executed_at,
+                        &row.executed_by,
+                        &row.config_file,
+                        &row.git_sha,
+                        &row.statement,
+                        &row.result,
+                        &row.prev_hash,
+                        &hash,
+                    ],
+                )?;
+                prev_hash = hash;
+            }
+
+            Ok(())
+        })();
+
+        match written {
+            Ok(()) => conn.commit().with_context(context)?,
+            Err(e) => {
+                conn.rollback()?;
+                return Err(e).with_context(context);
+            }
+        }
+        info!(
+            "Recorded {} statement(s) in the audit log {}",
+            self.entries.len(),
+            table
+        );
+
+        Ok(())
+    }
+}
+
+/// Create the audit table and its schema, unless the table exists: the user
+/// of the connection may not be allowed to create a schema
+fn create_table(conn: &mut DbConnection, audit: &Audit) -> Result<()> {
+    let (schema, table) = audit.schema_table()?;
+    let exists = !conn
+        .query(
+            "SELECT 1 FROM information_schema.tables WHERE table_schema = $1 AND table_name = $2",
+            &[&schema, &table],
+        )?
+        .is_empty();
+    if exists {
+        return Ok(());
+    }
+
+    conn.execute_batch(&create_table_sql(audit)?)
+}
+
+/// The statements creating the audit table and its schema
+pub fn create_table_sql(audit: &Audit) -> Result<String> {
+    let (schema, _) = audit.schema_table()?;
+
+    Ok(format!(
+        "CREATE SCHEMA IF NOT EXISTS {};\n\
+         CREATE TABLE IF NOT EXISTS {} (\n  \
+           seq BIGINT NOT NULL,\n  \
+           executed_at TIMESTAMPTZ NOT NULL,\n  \
+           executed_by VARCHAR(256) NOT NULL,\n  \
+           config_file VARCHAR(1024) NOT NULL,\n  \
+           git_sha VARCHAR(64) NOT NULL,\n  \
+           statement VARCHAR(65535) NOT NULL,\n  \
+           result VARCHAR(65535) NOT NULL,\n  \
+           prev_hash VARCHAR(64) NOT NULL,\n  \
+           hash VARCHAR(64) NOT NULL\n\
+         );",
+        crate::config::ident::quote_folded(&schema),
+        audit.quoted_table()?
+    ))
+}
+
+/// A row of the audit table, without its hash
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Row {
+    pub seq: i64,
+    /// In UTC with microseconds, see [EXECUTED_AT]
+    pub executed_at: String,
+    pub executed_by: String,
+    pub config_file: String,
+    pub git_sha: String,
+    pub statement: String,
+    pub result: String,
+    pub prev_hash: String,
+}
+
+/// The hash of a row: the hex SHA-256 of its columns, `prev_hash` first,
+/// each followed by a line break. A line break in a column is escaped as
+/// `\n` and a backslash as `\\`, so that the columns can not be shifted.
+pub fn chain_hash(row: &Row) -> String {
+    let escape = |s: &str| s.replace('\\', "\\\\").replace('\n', "\\n");
+    let mut hasher = Sha256::new();
+    for column in [
+        &row.prev_hash,
+        &row.seq.to_string(),
+        &row.executed_at,
+        &row.executed_by,
+        &row.config_file,
+        &row.git_sha,
+        &row.statement,
+        &row.result,
+    ] {
+        hasher.update(escape(column));
+        hasher.update("\n");
+    }
+
+    format!("{:x}", hasher.finalize())
+}
+
+/// The `seq` of the first row of the log, in order, whose `prev_hash` or
+/// hash does not match: it or a row before it was tampered with
+pub fn verify_chain(rows: &[(Row, String)]) -> Option<i64> {
+    let mut prev_hash = "";
+    for (row, hash) in rows {
+        if row.prev_hash != prev_hash || chain_hash(row) != *hash {
+            return Some(row.seq);
+        }
+        prev_hash = hash;
+    }
+
+    None
+}
+
+/// The statement with the values of its `PASSWORD` clauses replaced by
+/// `'********'`, e.g. `CREATE USER duyet WITH PASSWORD '********';`
+pub fn mask_passwords(sql: &str) -> String {
+    const KEYWORD: &str = "PASSWORD";
+    let mut masked = String::with_capacity(sql.len());
+    let mut rest = sql;
+
+    while let Some(at) = rest.to_ascii_uppercase().find(KEYWORD) {
+        let (before, after) = rest.split_at(at + KEYWORD.len());
+        masked.push_str(before);
+        let value = after.trim_start();
+        if !value.starts_with('\'') {
+            rest = after;
+            continue;
+        }
+
+        // The literal ends at a quote which is not doubled
+        let mut end = None;
+        let mut chars = value.char_indices().skip(1).peekable();
+        while let Some((i, c)) = chars.next() {
+            if c == '\'' {
+                match chars.peek() {
+                    Some((_, '\'')) => {
+                        chars.next();
+                    }
+                    _ => {
+                        end = Some(i + 1);
+                        break;
+                    }
+                }
+            }
+        }
+        masked.push_str(&after[..after.len() - value.len()]);
+        masked.push_str("'********'");
+        rest = match end {
+            Some(end) => &value[end..],
+            None => "",
+        };
+    }
+    masked.push_str(rest);
+
+    masked
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_mask_passwords() {
+        assert_eq!(
+            mask_passwords("CREATE USER duyet WITH PASSWORD 'it''s secret';"),
+            "CREATE USER duyet WITH PASSWORD '********';"
+        );
+        assert_eq!(
+            mask_passwords("ALTER USER a password 'x' VALID UNTIL 'infinity';\nALTER USER b PASSWORD 'y';"),
+            "ALTER USER a password '********' VALID UNTIL 'infinity';\nALTER USER b PASSWORD '********';"
+        );
+        assert_eq!(
+            mask_passwords("ALTER USER duyet PASSWORD DISABLE;"),
+            "ALTER USER duyet PASSWORD DISABLE;"
+        );
+        assert_eq!(
+            mask_passwords("GRANT SELECT ON TABLE passwords TO duyet;"),
+            "GRANT SELECT ON TABLE passwords TO duyet;"
+        );
+        // Not terminated
+        assert_eq!(
+            mask_passwords("CREATE USER duyet PASSWORD 'oops"),
+            "CREATE USER duyet PASSWORD '********'"
+        );
+    }
+
+    #[test]
+    fn test_record() {
+        let mut log = AuditLog::new();
+        assert!(log.record("CREATE USER a;", Ok(())).is_ok());
+        log.commit();
+        assert!(log.record("GRANT x;", Ok(())).is_ok());
+        assert!(log
+            .record::<()>("GRANT y;", Err(anyhow::anyhow!("denied")))
+            .is_err());
+        log.rollback();
+
+        assert_eq!(
+            log.entries
+                .iter()
+                .map(|e| e.result.as_str())
+                .collect::<Vec<_>>(),
+            ["success", "rolled back", "error: denied"]
+        );
+    }
+
+    #[test]
+    fn test_executed_at() {
+        assert_eq!(
+            Timestamp::from_second(0)
+                .unwrap()
+                .strftime(EXECUTED_AT)
+                .to_string(),
+            "1970-01-01T00:00:00.000000Z"
+        );
+    }
+
+    #[test]
+    fn test_chain() {
+        let row = |seq: i64, prev_hash: &str| Row {
+            seq,
+            executed_at: "2024-05-01T10:00:00.123456Z".to_string(),
+            executed_by: "grant_bot".to_string(),
+            config_file: "prod.yaml".to_string(),
+            git_sha: "abc123".to_string(),
+            statement: "GRANT SELECT ON ALL TABLES IN SCHEMA public TO duyet;".to_string(),
+            result: "success".to_string(),
+            prev_hash: prev_hash.to_string(),
+        };
+        let first = row(1, "");
+        let first_hash = chain_hash(&first);
+        assert_eq!(first_hash.len(), 64);
+        let second = row(2, &first_hash);
+        let second_hash = chain_hash(&second);
+        assert_ne!(first_hash, second_hash);
+
+        let mut rows = vec![(first, first_hash), (second, second_hash)];
+        assert_eq!(verify_chain(&rows), None);
+
+        // Shifting a line break between two columns changes the hash
+        let mut shifted = rows[0].0.clone();
+        shifted.executed_by = "grant_bot\nprod.yaml".to_string();
+        shifted.config_file = String::new();
+        assert_ne!(chain_hash(&shifted), rows[0].1);
+
+        rows[1].0.result = "error: denied".to_string();
+        assert_eq!(verify_chain(&rows), Some(2));
+        rows.remove(0);
+        assert_eq!(verify_chain(&rows), Some(2));
+    }
+}
@@ -0,0 +1,313 @@
+//! `grant bootstrap`: onboard a new cluster by creating the maintenance user
+//! of grant, the user of the `connection` of its configs, with an admin
+//! connection:
+//!
+//! ```bash
+//! grant bootstrap --url postgres://admin@cluster:5432/dev --maintenance-user grant_bot
+//! ```
+//!
+//! The maintenance user is not a superuser, it only gets what `apply`
+//! needs: creating, altering and dropping users (`CREATEROLE` on Postgres,
+//! the `CREATE USER`, `ALTER USER` and `DROP USER` system permissions of a
+//! role on Redshift), and every privilege with the grant option on the
+//! database of the url and on its schemas, their tables, their sequences
+//! (not on Redshift, which has none), and `EXECUTE` on their functions and
+//! procedures, to grant them again. The objects created later are not
+//! covered, run it again or grant them with the grant option.
+
+use crate::config::{ident, Dialect};
+use crate::connection::DbConnection;
+use crate::gen::random_password;
+use ansi_term::Colour::{Green, Purple};
+use anyhow::{anyhow, Context, Result};
+use log::info;
+use std::str::FromStr;
+
+/// The length of a generated password, letters and digits only so that it
+/// can be written in a url
+const PASSWORD_LENGTH: u8 = 32;
+
+/// Options of `grant bootstrap`
+#[derive(Debug, Clone, Default)]
+pub struct BootstrapOptions {
+    /// The maintenance user to create
+    pub user: String,
+    /// Its password, generated if not set and the user does not exist
+    pub password: Option<String>,
+    /// The schemas it can grant on, all of the database if empty
+    pub schemas: Vec<String>,
+    /// Only print the statements
+    pub dryrun: bool,
+}
+
+/// The role of the system permissions of the maintenance user on Redshift,
+/// which only grants them to roles
+pub fn redshift_role(user: &str) -> String {
+    format!("{}_maintenance", user)
+}
+
+/// Create the maintenance user, or update its privileges if it exists, with
+/// the admin connection `url`. The statements are logged without the
+/// password, a generated one is printed once on stdout.
+pub fn bootstrap(url: &str, opts: &BootstrapOptions) -> Result<()> {
+    let mut conn = DbConnection::from_str(url).context("failed to connect with --url")?;
+    let dialect = conn.dialect();
+    let database = conn
+        .get_current_database()
+        .ok_or_else(|| anyhow!("--url has no database"))?
+        .to_string();
+
+    let name = ident::fold(&opts.user);
+    let exists = conn.get_users()?.iter().any(|u| u.name == name);
+    let schemas = match opts.schemas.is_empty() {
+        true => schemas(&mut conn)?,
+        false => opts.schemas.iter().map(|s| ident::fold(s)).collect(),
+    };
+    let role_exists = match dialect.is_redshift() {
+        true => !conn
+            .query(
+                "SELECT 1 FROM svv_roles WHERE role_name = $1",
+                &[&redshift_role(&name)],
+            )?
+            .is_empty(),
+        false => false,
+    };
+
+    let generated = match (&opts.password, exists) {
+        (None, false) => Some(random_password(PASSWORD_LENGTH, true)),
+        _ => None,
+    };
+    let password = opts.password.as_deref().or(generated.as_deref());
+    let statements = bootstrap_sql(&Bootstrap {
+        user: &name,
+        password,
+        exists,
+        role_exists,
+        database: &database,
+        schemas: &schemas,
+        dialect,
+    });
+    let masked = |sql: &str| match password {
+        Some(password) => sql.replace(&quote_literal(password), "'********'"),
+        None => sql.to_string(),
+    };
+
+    if opts.dryrun {
+        for sql in &statements {
+            info!("{}: {}", Purple.paint("Dry-run"), masked(sql));
+        }
+        info!("Run without --dryrun to bootstrap {}", name);
+        return Ok(());
+    }
+
+    conn.begin()?;
+    for sql in &statements {
+        conn.execute(sql, &[])
+            .with_context(|| format!("failed to execute {}", masked(sql)))?;
+        info!("{}: {}", Green.paint("Success"), Purple.paint(masked(sql)));
+    }
+    conn.commit()?;
+
+    info!(
+        "Bootstrapped {}: it can manage the users and grant on {} schema(s) of {}",
+        name,
+        schemas.len(),
+        database
+    );
+    if let Some(password) = generated {
+        println!("Generated password of {}: {}", name, password);
+    }
+
+    Ok(())
+}
+
+/// The schemas of the current database, without the ones of the system,
+/// named as in the catalog
+fn schemas(conn: &mut DbConnection) -> Result<Vec<String>> {
+    let rows = conn.query(
+        "SELECT nspname::text FROM pg_namespace \
+         WHERE nspname NOT LIKE 'pg\\_%' AND nspname <> 'information_schema' \
+         ORDER BY nspname",
+        &[],
+    )?;
+
+    Ok(rows.iter().map(|row| row.get(0)).collect())
+}
+
+/// What the statements of [bootstrap_sql] depend on
+#[derive(Debug, Clone)]
+pub struct Bootstrap<'a> {
+    /// The maintenance user, named as in the catalog
+    pub user: &'a str,
+    pub password: Option<&'a str>,
+    /// Whether the user already exists
+    pub exists: bool,
+    /// Whether the [redshift_role] already exists
+    pub role_exists: bool,
+    pub database: &'a str,
+    pub schemas: &'a [String],
+    pub dialect: Dialect,
+}
+
+/// The statements creating the maintenance user, or altering an existing
+/// one, then granting it the privileges to manage users and grants
+pub fn bootstrap_sql(b: &Bootstrap) -> Vec<String> {
+    let user = ident::quote_folded(b.user);
+    let password = b
+        .password
+        .map(|p| format!(" PASSWORD {}", quote_literal(p)))
+        .unwrap_or_default();
+    let mut statements = vec![];
+
+    if b.dialect.is_redshift() {
+        let role = ident::quote_folded(&redshift_role(b.user));
+        match b.exists {
+            true if !password.is_empty() => {
+                statements.push(format!("ALTER USER {}{};", user, password))
+            }
+            true => {}
+            false => statements.push(format!("CREATE USER {}{};", user, password)),
+        }
+        if !b.role_exists {
+            statements.push(format!("CREATE ROLE {};", role));
+        }
+        statements.push(format!(
+            "GRANT CREATE USER, ALTER USER, DROP USER TO ROLE {};",
+            role
+        ));
+        statements.push(format!("GRANT ROLE {} TO {};", role, user));
+    } else {
+        match b.exists {
+            true => statements.push(format!("ALTER USER {} WITH CREATEROLE{};", user, password)),
+            false => statements.push(format!("CREATE USER {} WITH CREATEROLE{};", user, password)),
+        }
+    }
+
+    statements.push(format!(
+        "GRANT ALL ON DATABASE {} TO {} WITH GRANT OPTION;",
+        ident::quote_folded(b.database),
+        user
+    ));
+    for schema in b.schemas {
+        let schema = ident::quote_folded(schema);
+        statements.push(format!(
+            "GRANT ALL ON SCHEMA {} TO {} WITH GRANT OPTION;",
+            schema, user
+        ));
+        statements.push(format!(
+            "GRANT ALL ON ALL TABLES IN SCHEMA {} TO {} WITH GRANT OPTION;",
+            schema, user
+        ));
+        if !b.dialect.is_redshift() {
+            statements.push(format!(
+                "GRANT ALL ON ALL SEQUENCES IN SCHEMA {} TO {} WITH GRANT OPTION;",
+                schema, user
+            ));
+        }
+        for routines in ["FUNCTIONS", "PROCEDURES"] {
+            statements.push(format!(
+                "GRANT EXECUTE ON ALL {} IN SCHEMA {} TO {} WITH GRANT OPTION;",
+                routines, schema, user
+            ));
+        }
+    }
+
+    statements
+}
+
+/// A SQL string literal
+fn quote_literal(s: &str) -> String {
+    format!("'{}'", s.replace('\'', "''"))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn bootstrap(dialect: Dialect, exists: bool) -> Bootstrap<'static> {
+        Bootstrap {
+            user: "grant_bot",
+            password: Some("it's"),
+            exists,
+            role_exists: false,
+            database: "dev",
+            schemas: &[],
+            dialect,
+        }
+    }
+
+    #[test]
+    fn test_bootstrap_sql_postgres() {
+        let schemas = ["public".to_string(), "Sales".to_string()];
+        assert_eq!(
+            bootstrap_sql(&Bootstrap {
+                schemas: &schemas,
+                ..bootstrap(Dialect::Postgres, false)
+            }),
+            vec![
+                "CREATE USER grant_bot WITH CREATEROLE PASSWORD 'it''s';",
+                "GRANT ALL ON DATABASE dev TO grant_bot WITH GRANT OPTION;",
+                "GRANT ALL ON SCHEMA public TO grant_bot WITH GRANT OPTION;",
+                "GRANT ALL ON ALL TABLES IN SCHEMA public TO grant_bot WITH GRANT OPTION;",
+                "GRANT ALL ON ALL SEQUENCES IN SCHEMA public TO grant_bot WITH GRANT OPTION;",
+                "GRANT EXECUTE ON ALL FUNCTIONS IN SCHEMA public TO grant_bot WITH GRANT OPTION;",
+                "GRANT EXECUTE ON ALL PROCEDURES IN SCHEMA public TO grant_bot WITH GRANT OPTION;",
+                "GRANT ALL ON SCHEMA \"Sales\" TO grant_bot WITH GRANT OPTION;",
+                "GRANT ALL ON ALL TABLES IN SCHEMA \"Sales\" TO grant_bot WITH GRANT OPTION;",
+                "GRANT ALL ON ALL SEQUENCES IN SCHEMA \"Sales\" TO grant_bot WITH GRANT OPTION;",
+                "GRANT EXECUTE ON ALL FUNCTIONS IN SCHEMA \"Sales\" TO grant_bot WITH GRANT OPTION;",
+                "GRANT EXECUTE ON ALL PROCEDURES IN SCHEMA \"Sales\" TO grant_bot WITH GRANT OPTION;",
+            ]
+        );
+
+        // The password of an existing user is kept
+        assert_eq!(
+            bootstrap_sql(&Bootstrap {
+                password: None,
+                ..bootstrap(Dialect::Postgres, true)
+            })[0],
+            "ALTER USER grant_bot WITH CREATEROLE;"
+        );
+    }
+
+    #[test]
+    fn test_bootstrap_sql_redshift() {
+        assert_eq!(
+            bootstrap_sql(&bootstrap(Dialect::Redshift, false)),
+            vec![
+                "CREATE USER grant_bot PASSWORD 'it''s';",
+                "CREATE ROLE grant_bot_maintenance;",
+                "GRANT CREATE USER, ALTER USER, DROP USER TO ROLE grant_bot_maintenance;",
+                "GRANT ROLE grant_bot_maintenance TO grant_bot;",
+                "GRANT ALL ON DATABASE dev TO grant_bot WITH GRANT OPTION;",
+            ]
+        );
+
+        assert_eq!(
+            bootstrap_sql(&Bootstrap {
+                password: None,
+                role_exists: true,
+                ..bootstrap(Dialect::Redshift, true)
+            })[..2],
+            [
+                "GRANT CREATE USER, ALTER USER, DROP USER TO ROLE grant_bot_maintenance;",
+                "GRANT ROLE grant_bot_maintenance TO grant_bot;",
+            ]
+        );
+
+        // Redshift has no sequences
+        let schemas = ["public".to_string()];
+        assert_eq!(
+            bootstrap_sql(&Bootstrap {
+                schemas: &schemas,
+                ..bootstrap(Dialect::Redshift, true)
+            })[5..],
+            [
+                "GRANT ALL ON SCHEMA public TO grant_bot WITH GRANT OPTION;",
+                "GRANT ALL ON ALL TABLES IN SCHEMA public TO grant_bot WITH GRANT OPTION;",
+                "GRANT EXECUTE ON ALL FUNCTIONS IN SCHEMA public TO grant_bot WITH GRANT OPTION;",
+                "GRANT EXECUTE ON ALL PROCEDURES IN SCHEMA public TO grant_bot WITH GRANT OPTION;",
+            ]
+        );
+    }
+}
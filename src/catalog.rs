@@ -0,0 +1,802 @@
+//! Catalog queries used to read the current state of a cluster.
+//!
+//! Each [Query] has one SQL text per [Dialect], and a mapping function which
+//! turns a result row into the privilege model of [crate::connection]. The
+//! mapping works on any [CatalogRow], so it can be tested with fixture rows
+//! without a database.
+
+use crate::config::{Dialect, SyslogAccess, UserAttributes};
+use crate::connection::{
+    Interner, Object, ObjectOwner, User, UserDatabaseRole, UserSchemaRole, UserTableRole,
+};
+use std::collections::BTreeMap;
+use std::fmt;
+use tokio_postgres::error::SqlState;
+use tokio_postgres::row::Row;
+
+/// Name of a catalog query
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum Query {
+    Users,
+    Groups,
+    DatabasePrivileges,
+    SchemaPrivileges,
+    TablePrivileges,
+    Owners,
+}
+
+impl fmt::Display for Query {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Query::Users => write!(f, "users"),
+            Query::Groups => write!(f, "groups"),
+            Query::DatabasePrivileges => write!(f, "database privileges"),
+            Query::SchemaPrivileges => write!(f, "schema privileges"),
+            Query::TablePrivileges => write!(f, "table privileges"),
+            Query::Owners => write!(f, "owners"),
+        }
+    }
+}
+
+/// Error of a catalog query, telling apart missing permissions from other failures
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum CatalogError {
+    /// The connected user is not allowed to read a catalog object
+    PermissionDenied { query: Query, message: String },
+    /// Any other database error
+    Failed { query: Query, message: String },
+}
+
+impl CatalogError {
+    /// Classify an error from the server by its SQLSTATE code
+    pub fn new(query: Query, code: Option<&SqlState>, message: String) -> Self {
+        if code == Some(&SqlState::INSUFFICIENT_PRIVILEGE) {
+            CatalogError::PermissionDenied { query, message }
+        } else {
+            CatalogError::Failed { query, message }
+        }
+    }
+
+    pub fn query(&self) -> Query {
+        match self {
+            CatalogError::PermissionDenied { query, .. } => *query,
+            CatalogError::Failed { query, .. } => *query,
+        }
+    }
+}
+
+impl From<(Query, tokio_postgres::Error)> for CatalogError {
+    fn from((query, e): (Query, tokio_postgres::Error)) -> Self {
+        let message = match e.as_db_error() {
+            Some(db_error) => db_error.message().to_string(),
+            None => e.to_string(),
+        };
+
+        CatalogError::new(query, e.code(), message)
+    }
+}
+
+impl fmt::Display for CatalogError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            CatalogError::PermissionDenied { query, message } => {
+                write!(f, "{} unavailable: permission denied ({})", query, message)
+            }
+            CatalogError::Failed { query, message } => {
+                write!(f, "{} unavailable: {}", query, message)
+            }
+        }
+    }
+}
+
+impl std::error::Error for CatalogError {}
+
+/// Get the SQL of the query for the given dialect
+pub fn sql(dialect: Dialect, query: Query) -> String {
+    match query {
+        Query::Users => users_query(dialect),
+        Query::Groups => groups_query(dialect),
+        Query::DatabasePrivileges => database_privileges_query(dialect),
+        Query::SchemaPrivileges => schema_privileges_query(dialect),
+        Query::TablePrivileges => table_privileges_query(dialect),
+        Query::Owners => owners_query(dialect),
+    }
+}
+
+/// Access to the columns of a result row by index
+pub trait CatalogRow {
+    fn get_str(&self, idx: usize) -> Option<&str>;
+    fn get_bool(&self, idx: usize) -> Option<bool>;
+    fn get_i32(&self, idx: usize) -> Option<i32>;
+}
+
+impl CatalogRow for Row {
+    fn get_str(&self, idx: usize) -> Option<&str> {
+        self.try_get::<_, Option<&str>>(idx).ok().flatten()
+    }
+
+    fn get_bool(&self, idx: usize) -> Option<bool> {
+        self.try_get::<_, Option<bool>>(idx).ok().flatten()
+    }
+
+    fn get_i32(&self, idx: usize) -> Option<i32> {
+        self.try_get::<_, Option<i32>>(idx).ok().flatten()
+    }
+}
+
+/// Map a row of [Query::Users].
+/// Users without name are skipped, missing attributes default to false / empty.
+/// The Redshift attributes are only in the rows of Redshift, a session
+/// timeout of 0 is none and an `UNLIMITED` connection limit is -1. The
+/// settings are the text of the `useconfig` array, see [parse_settings].
+pub fn map_user(row: &impl CatalogRow) -> Option<User> {
+    let name = row.get_str(0)?;
+    let attributes = UserAttributes {
+        createdb: row.get_bool(1),
+        superuser: row.get_bool(2),
+        connection_limit: row.get_str(7).and_then(|limit| match limit {
+            "UNLIMITED" => Some(-1),
+            limit => limit.parse().ok(),
+        }),
+        valid_until: row.get_str(6).map(|v| v.to_string()),
+        syslog_access: row.get_str(4).and_then(SyslogAccess::parse),
+        session_timeout: row
+            .get_i32(5)
+            .and_then(|timeout| u32::try_from(timeout).ok())
+            .filter(|timeout| *timeout > 0),
+    };
+
+    let settings = row.get_str(8).map(parse_settings).unwrap_or_default();
+
+    match (row.get_bool(1), row.get_bool(2), row.get_str(3)) {
+        (Some(user_createdb), Some(user_super), Some(password)) => Some(User {
+            name: name.to_string(),
+            user_createdb,
+            user_super,
+            password: password.to_string(),
+            attributes,
+            settings,
+        }),
+        _ => Some(User {
+            name: name.to_string(),
+            user_createdb: false,
+            user_super: false,
+            password: String::from(""),
+            attributes,
+            settings,
+        }),
+    }
+}
+
+/// The settings of the text of a `useconfig` array, e.g.
+/// `{search_path=analytics,"statement_timeout=30s"}`, by name
+pub fn parse_settings(useconfig: &str) -> BTreeMap<String, String> {
+    let mut items = vec![];
+    let mut item = String::new();
+    let mut chars = useconfig
+        .trim()
+        .trim_start_matches('{')
+        .trim_end_matches('}')
+        .chars();
+    let mut quoted = false;
+    while let Some(c) = chars.next() {
+        match c {
+            '"' => quoted = !quoted,
+            '\\' if quoted => item.extend(chars.next()),
+            ',' if !quoted => items.push(std::mem::take(&mut item)),
+            c => item.push(c),
+        }
+    }
+    items.push(item);
+
+    items
+        .iter()
+        .filter_map(|item| item.split_once('='))
+        .map(|(name, value)| (name.to_string(), value.to_string()))
+        .collect()
+}
+
+/// Map a row of [Query::Groups] to the group name and one of its members,
+/// None for a group without member
+pub fn map_group_member(row: &impl CatalogRow) -> Option<(String, Option<String>)> {
+    Some((
+        row.get_str(0)?.to_string(),
+        row.get_str(1).map(|m| m.to_string()),
+    ))
+}
+
+/// Map a row of [Query::DatabasePrivileges]
+pub fn map_database_privilege(
+    row: &impl CatalogRow,
+    interner: &mut Interner,
+) -> Option<UserDatabaseRole> {
+    Some(UserDatabaseRole {
+        name: interner.intern(row.get_str(0)?),
+        database_name: interner.intern(row.get_str(1)?),
+        has_create: row.get_bool(2)?,
+        has_temp: row.get_bool(3)?,
+    })
+}
+
+/// Map a row of [Query::SchemaPrivileges]
+pub fn map_schema_privilege(
+    row: &impl CatalogRow,
+    interner: &mut Interner,
+) -> Option<UserSchemaRole> {
+    Some(UserSchemaRole {
+        name: interner.intern(row.get_str(0)?),
+        schema_name: interner.intern(row.get_str(1)?),
+        has_create: row.get_bool(2)?,
+        has_usage: row.get_bool(3)?,
+    })
+}
+
+/// Map a row of [Query::TablePrivileges]
+pub fn map_table_privilege(
+    row: &impl CatalogRow,
+    interner: &mut Interner,
+) -> Option<UserTableRole> {
+    Some(UserTableRole {
+        name: interner.intern(row.get_str(0)?),
+        schema_name: interner.intern(row.get_str(1)?),
+        table_name: interner.intern(row.get_str(2)?),
+        has_select: row.get_bool(3)?,
+        has_insert: row.get_bool(4)?,
+        has_update: row.get_bool(5)?,
+        has_delete: row.get_bool(6)?,
+        has_references: row.get_bool(7)?,
+    })
+}
+
+/// Map a row of [Query::Owners], rows of an unknown object type are skipped
+pub fn map_owner(row: &impl CatalogRow) -> Option<ObjectOwner> {
+    let name = row.get_str(1)?.to_string();
+    let object = match row.get_str(0)? {
+        "database" => Object::Database(name),
+        "schema" => Object::Schema(name),
+        "table" => Object::Table(name, row.get_str(2)?.to_string()),
+        _ => return None,
+    };
+
+    Some(ObjectOwner {
+        object,
+        owner: row.get_str(3)?.to_string(),
+    })
+}
+
+/// Render a list of names as a SQL condition `AND <column> NOT IN ('a', 'b')`,
+/// empty if there is nothing to exclude.
+fn not_in(column: &str, names: &[&str]) -> String {
+    if names.is_empty() {
+        return "".to_string();
+    }
+
+    let names = names
+        .iter()
+        .map(|n| format!("'{}'", n))
+        .collect::<Vec<_>>()
+        .join(", ");
+
+    format!("AND {} NOT IN ({})", column, names)
+}
+
+/// Query listing the users and their attributes, without the internal users
+/// of the dialect. On Redshift, the connection limit and the Redshift
+/// attributes are the ones of `svl_user_info`.
+fn users_query(dialect: Dialect) -> String {
+    if dialect.is_redshift() {
+        return format!(
+            "
+                SELECT usename, usecreatedb, usesuper, passwd, i.syslogaccess, i.sessiontimeout,
+                  valuntil::text, i.useconnlimit::text, useconfig::text
+                FROM pg_user
+                  LEFT JOIN (
+                    SELECT usesysid AS id, syslogaccess, sessiontimeout, useconnlimit
+                    FROM svl_user_info
+                  ) i ON i.id = usesysid
+                WHERE 1 = 1 {}
+            ",
+            not_in("usename", dialect.system_users())
+        );
+    }
+
+    format!(
+        "
+            SELECT usename, usecreatedb, usesuper, passwd, NULL::text, NULL::int,
+              valuntil::text, rolconnlimit::text, useconfig::text
+            FROM pg_user
+              JOIN pg_roles ON pg_roles.oid = usesysid
+            WHERE 1 = 1 {}
+        ",
+        not_in("usename", dialect.system_users())
+    )
+}
+
+/// Query listing the groups with one row per member. On Postgres the groups
+/// are the roles which can't login, without the predefined `pg_*` roles.
+fn groups_query(dialect: Dialect) -> String {
+    format!(
+        "
+            SELECT g.groname AS group_name, u.usename AS member
+            FROM pg_group g
+              LEFT JOIN pg_user u ON u.usesysid = ANY(g.grolist) {}
+            WHERE g.groname NOT LIKE 'pg_%'
+            ORDER BY g.groname, u.usename;
+        ",
+        not_in("u.usename", dialect.system_users()),
+    )
+}
+
+/// Query listing `create` and `temp` privileges of each user on each database
+fn database_privileges_query(dialect: Dialect) -> String {
+    format!(
+        r#"
+            WITH db AS (
+                SELECT d.datname AS database_name
+                FROM pg_database d
+                WHERE 1 = 1 {}
+            ),
+            users AS (
+                SELECT usename as user_name FROM pg_user WHERE 1 = 1 {}
+            )
+            SELECT
+                u.user_name,
+                db.database_name,
+                pg_catalog.has_database_privilege(u.user_name, database_name, 'CREATE') AS "create",
+                pg_catalog.has_database_privilege(u.user_name, database_name, 'TEMP') AS "temp"
+            FROM db CROSS JOIN users u;
+        "#,
+        not_in("d.datname", dialect.system_databases()),
+        not_in("usename", dialect.system_users()),
+    )
+}
+
+/// Query listing `create` and `usage` privileges of each user on each schema
+fn schema_privileges_query(dialect: Dialect) -> String {
+    format!(
+        "
+            SELECT
+              u.usename AS name,
+              s.schemaname AS schema_name,
+              has_schema_privilege(u.usename, s.schemaname, 'create') AS has_create,
+              has_schema_privilege(u.usename, s.schemaname, 'usage') AS has_usage
+            FROM
+              pg_user u
+              CROSS JOIN (SELECT DISTINCT schemaname FROM pg_tables) s
+            WHERE
+              1 = 1
+              AND s.schemaname NOT LIKE 'pg_%'
+              {}
+              {};
+        ",
+        not_in("s.schemaname", dialect.system_schemas()),
+        not_in("u.usename", dialect.system_users()),
+    )
+}
+
+/// Query listing the privileges of each user on each table
+fn table_privileges_query(dialect: Dialect) -> String {
+    format!(
+        "
+            SELECT
+              u.usename AS name,
+              t.schemaname AS schema_name,
+              t.tablename AS table_name,
+              has_table_privilege(u.usename, quote_ident(t.schemaname) || '.' || quote_ident(t.tablename), 'select') AS has_select,
+              has_table_privilege(u.usename, quote_ident(t.schemaname) || '.' || quote_ident(t.tablename), 'insert') AS has_insert,
+              has_table_privilege(u.usename, quote_ident(t.schemaname) || '.' || quote_ident(t.tablename), 'update') AS has_update,
+              has_table_privilege(u.usename, quote_ident(t.schemaname) || '.' || quote_ident(t.tablename), 'delete') AS has_delete,
+              has_table_privilege(u.usename, quote_ident(t.schemaname) || '.' || quote_ident(t.tablename), 'references') AS has_references
+            FROM
+              pg_user u
+              CROSS JOIN (SELECT DISTINCT schemaname, tablename FROM pg_tables) t
+              WHERE 1 = 1
+                AND t.schemaname NOT LIKE 'pg_%'
+                {}
+                {};
+        ",
+        not_in("t.schemaname", dialect.system_schemas()),
+        not_in("u.usename", dialect.system_users()),
+    )
+}
+
+/// Query listing the schemas of the current database with their tables,
+/// views and foreign tables, a schema without any with a NULL name, see
+/// [crate::objects]
+pub fn relations_query(dialect: Dialect) -> String {
+    format!(
+        "
+            SELECT n.nspname::text, c.relname::text
+            FROM pg_namespace n
+              LEFT JOIN pg_class c ON c.relnamespace = n.oid
+                AND c.relkind IN ('r', 'v', 'm', 'p', 'f')
+            WHERE n.nspname NOT LIKE 'pg_%'
+              {}
+            ORDER BY 1, 2;
+        ",
+        not_in("n.nspname", dialect.system_schemas()),
+    )
+}
+
+/// Query listing the schemas of the current database whose name matches
+/// the POSIX regular expression `$1`, see [crate::patterns]
+pub fn schemas_matching_query(dialect: Dialect) -> String {
+    format!(
+        "
+            SELECT n.nspname::text
+            FROM pg_namespace n
+            WHERE n.nspname ~ $1
+              AND n.nspname NOT LIKE 'pg_%'
+              {}
+            ORDER BY 1;
+        ",
+        not_in("n.nspname", dialect.system_schemas()),
+    )
+}
+
+/// Query listing the databases of the cluster but the templates, see
+/// [crate::patterns]
+pub fn databases_query(dialect: Dialect) -> String {
+    format!(
+        "
+            SELECT d.datname::text
+            FROM pg_database d
+            WHERE NOT d.datistemplate {}
+            ORDER BY 1;
+        ",
+        not_in("d.datname", dialect.system_databases()),
+    )
+}
+
+/// Query listing the native roles of Redshift, one row per role, per
+/// member and per system privilege, without the `sys:` roles
+pub fn redshift_roles_query() -> String {
+    "
+        SELECT r.role_name::varchar, NULL::varchar AS member, NULL::varchar AS privilege
+        FROM svv_roles r
+        WHERE r.role_name NOT LIKE 'sys:%'
+        UNION ALL
+        SELECT g.role_name::varchar, g.user_name::varchar, NULL::varchar
+        FROM svv_user_grants g
+        UNION ALL
+        SELECT p.identity_name::varchar, NULL::varchar, p.system_privilege::varchar
+        FROM svv_system_privileges p
+        WHERE p.identity_type = 'role'
+        ORDER BY 1, 2, 3;
+    "
+    .to_string()
+}
+
+/// Map a row of [redshift_roles_query] to the role name and one of its
+/// members or system privileges
+pub fn map_redshift_role(
+    row: &impl CatalogRow,
+) -> Option<(String, Option<String>, Option<String>)> {
+    Some((
+        row.get_str(0)?.to_string(),
+        row.get_str(1).map(|m| m.to_string()),
+        row.get_str(2).map(|p| p.to_string()),
+    ))
+}
+
+/// Query listing the tables of the current database whose schema matches
+/// the POSIX regular expression `$1` and whose name matches `$2`, see
+/// [crate::patterns]
+pub fn tables_matching_query(dialect: Dialect) -> String {
+    format!(
+        "
+            SELECT t.schemaname::text, t.tablename::text
+            FROM pg_tables t
+            WHERE t.schemaname ~ $1
+              AND t.tablename ~ $2
+              AND t.schemaname NOT LIKE 'pg_%'
+              {}
+            ORDER BY 1, 2;
+        ",
+        not_in("t.schemaname", dialect.system_schemas()),
+    )
+}
+
+/// Query listing the owner of each database, and of each schema and table
+/// of the current database
+fn owners_query(dialect: Dialect) -> String {
+    format!(
+        "
+            SELECT 'database' AS kind, d.datname::text AS name, NULL AS table_name,
+              pg_get_userbyid(d.datdba)::text AS owner
+            FROM pg_database d
+            WHERE 1 = 1 {}
+            UNION ALL
+            SELECT 'schema', n.nspname::text, NULL, pg_get_userbyid(n.nspowner)::text
+            FROM pg_namespace n
+            WHERE n.nspname NOT LIKE 'pg_%' {}
+            UNION ALL
+            SELECT 'table', t.schemaname::text, t.tablename::text, t.tableowner::text
+            FROM pg_tables t
+            WHERE t.schemaname NOT LIKE 'pg_%' {};
+        ",
+        not_in("d.datname", dialect.system_databases()),
+        not_in("n.nspname", dialect.system_schemas()),
+        not_in("t.schemaname", dialect.system_schemas()),
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// A column value of a fixture row
+    enum Value {
+        Str(&'static str),
+        Bool(bool),
+        Int(i32),
+        Null,
+    }
+
+    struct FixtureRow(Vec<Value>);
+
+    impl CatalogRow for FixtureRow {
+        fn get_str(&self, idx: usize) -> Option<&str> {
+            match self.0.get(idx) {
+                Some(Value::Str(s)) => Some(s),
+                _ => None,
+            }
+        }
+
+        fn get_bool(&self, idx: usize) -> Option<bool> {
+            match self.0.get(idx) {
+                Some(Value::Bool(b)) => Some(*b),
+                _ => None,
+            }
+        }
+
+        fn get_i32(&self, idx: usize) -> Option<i32> {
+            match self.0.get(idx) {
+                Some(Value::Int(i)) => Some(*i),
+                _ => None,
+            }
+        }
+    }
+
+    #[test]
+    fn test_sql_per_dialect() {
+        assert!(!sql(Dialect::Postgres, Query::Users).contains("NOT IN"));
+        assert!(sql(Dialect::Redshift, Query::Users).contains("AND usename NOT IN ('rdsdb')"));
+        assert!(sql(Dialect::AuroraPostgres, Query::DatabasePrivileges)
+            .contains("AND d.datname NOT IN ('rdsadmin')"));
+        assert!(sql(Dialect::Greenplum, Query::SchemaPrivileges)
+            .contains("AND s.schemaname NOT IN ('information_schema', 'gp_toolkit')"));
+        assert!(sql(Dialect::Redshift, Query::Groups).contains("AND u.usename NOT IN ('rdsdb')"));
+        assert!(sql(Dialect::Postgres, Query::TablePrivileges)
+            .contains("AND t.schemaname NOT IN ('information_schema')"));
+    }
+
+    #[test]
+    fn test_catalog_error() {
+        let e = CatalogError::new(
+            Query::SchemaPrivileges,
+            Some(&SqlState::INSUFFICIENT_PRIVILEGE),
+            "permission denied for view pg_user".to_string(),
+        );
+        assert_eq!(e.query(), Query::SchemaPrivileges);
+        assert_eq!(
+            e.to_string(),
+            "schema privileges unavailable: permission denied (permission denied for view pg_user)"
+        );
+
+        let e = CatalogError::new(
+            Query::TablePrivileges,
+            Some(&SqlState::UNDEFINED_TABLE),
+            "relation does not exist".to_string(),
+        );
+        assert!(matches!(e, CatalogError::Failed { .. }));
+        assert_eq!(
+            e.to_string(),
+            "table privileges unavailable: relation does not exist"
+        );
+    }
+
+    #[test]
+    fn test_map_user() {
+        let row = FixtureRow(vec![
+            Value::Str("duyet"),
+            Value::Bool(true),
+            Value::Bool(false),
+            Value::Str("********"),
+        ]);
+        let user = map_user(&row).unwrap();
+        assert_eq!(user.name, "duyet");
+        assert!(user.user_createdb);
+        assert!(!user.user_super);
+        assert_eq!(user.password, "********");
+
+        // missing attributes
+        let row = FixtureRow(vec![
+            Value::Str("duyet"),
+            Value::Null,
+            Value::Null,
+            Value::Null,
+        ]);
+        let user = map_user(&row).unwrap();
+        assert!(!user.user_createdb);
+        assert_eq!(user.password, "");
+
+        assert!(user.attributes.is_empty());
+
+        // missing name
+        let row = FixtureRow(vec![Value::Null, Value::Null, Value::Null, Value::Null]);
+        assert!(map_user(&row).is_none());
+
+        // Redshift attributes, a session timeout of 0 is none
+        let row = FixtureRow(vec![
+            Value::Str("duyet"),
+            Value::Bool(false),
+            Value::Bool(false),
+            Value::Str("********"),
+            Value::Str("UNRESTRICTED"),
+            Value::Int(3600),
+            Value::Str("2027-01-01 00:00:00+00"),
+            Value::Str("UNLIMITED"),
+        ]);
+        let user = map_user(&row).unwrap();
+        assert_eq!(user.attributes.createdb, Some(false));
+        assert_eq!(user.attributes.connection_limit, Some(-1));
+        assert_eq!(
+            user.attributes.valid_until.as_deref(),
+            Some("2027-01-01 00:00:00+00")
+        );
+        assert_eq!(
+            user.attributes.syslog_access,
+            Some(SyslogAccess::Unrestricted)
+        );
+        assert_eq!(user.attributes.session_timeout, Some(3600));
+        let row = FixtureRow(vec![
+            Value::Str("duyet"),
+            Value::Bool(false),
+            Value::Bool(false),
+            Value::Str("********"),
+            Value::Str("RESTRICTED"),
+            Value::Int(0),
+        ]);
+        assert_eq!(map_user(&row).unwrap().attributes.session_timeout, None);
+    }
+
+    #[test]
+    fn test_parse_settings() {
+        let settings = parse_settings(r#"{"search_path=\"$user\", public",work_mem=64MB}"#);
+        assert_eq!(settings["search_path"], r#""$user", public"#);
+        assert_eq!(settings["work_mem"], "64MB");
+
+        let settings = parse_settings("{statement_timeout=30s,work_mem=64MB}");
+        assert_eq!(
+            settings.into_iter().collect::<Vec<_>>(),
+            vec![
+                ("statement_timeout".to_string(), "30s".to_string()),
+                ("work_mem".to_string(), "64MB".to_string()),
+            ]
+        );
+        assert!(parse_settings("{}").is_empty());
+    }
+
+    #[test]
+    fn test_map_group_member() {
+        let row = FixtureRow(vec![Value::Str("analysts"), Value::Str("duyet")]);
+        assert_eq!(
+            map_group_member(&row),
+            Some(("analysts".to_string(), Some("duyet".to_string())))
+        );
+
+        // group without member
+        let row = FixtureRow(vec![Value::Str("analysts"), Value::Null]);
+        assert_eq!(map_group_member(&row), Some(("analysts".to_string(), None)));
+    }
+
+    #[test]
+    fn test_map_redshift_role() {
+        let row = FixtureRow(vec![
+            Value::Str("data_admin"),
+            Value::Str("duyet"),
+            Value::Null,
+        ]);
+        assert_eq!(
+            map_redshift_role(&row),
+            Some(("data_admin".to_string(), Some("duyet".to_string()), None))
+        );
+
+        let row = FixtureRow(vec![
+            Value::Str("data_admin"),
+            Value::Null,
+            Value::Str("CREATE USER"),
+        ]);
+        assert_eq!(
+            map_redshift_role(&row),
+            Some((
+                "data_admin".to_string(),
+                None,
+                Some("CREATE USER".to_string())
+            ))
+        );
+
+        let row = FixtureRow(vec![Value::Null, Value::Null, Value::Null]);
+        assert!(map_redshift_role(&row).is_none());
+    }
+
+    #[test]
+    fn test_map_privileges() {
+        let mut interner = Interner::default();
+
+        let row = FixtureRow(vec![
+            Value::Str("duyet"),
+            Value::Str("postgres"),
+            Value::Bool(true),
+            Value::Bool(false),
+        ]);
+        let privilege = map_database_privilege(&row, &mut interner).unwrap();
+        assert_eq!(privilege.perm_to_string(true), "postgres(C)");
+
+        let row = FixtureRow(vec![
+            Value::Str("duyet"),
+            Value::Str("public"),
+            Value::Bool(false),
+            Value::Bool(true),
+        ]);
+        let privilege = map_schema_privilege(&row, &mut interner).unwrap();
+        assert_eq!(privilege.perm_to_string(true), "public(U)");
+
+        let row = FixtureRow(vec![
+            Value::Str("duyet"),
+            Value::Str("public"),
+            Value::Str("orders"),
+            Value::Bool(true),
+            Value::Bool(true),
+            Value::Bool(false),
+            Value::Bool(false),
+            Value::Bool(false),
+        ]);
+        let privilege = map_table_privilege(&row, &mut interner).unwrap();
+        assert_eq!(privilege.perm_to_string(true), "public.orders(SI)");
+
+        // "duyet" and "public" are shared between rows
+        assert_eq!(interner.len(), 4);
+
+        // rows with null columns are skipped
+        let row = FixtureRow(vec![Value::Str("duyet"), Value::Null]);
+        assert!(map_schema_privilege(&row, &mut interner).is_none());
+    }
+
+    #[test]
+    fn test_map_owner() {
+        let row = FixtureRow(vec![
+            Value::Str("table"),
+            Value::Str("public"),
+            Value::Str("orders"),
+            Value::Str("duyet"),
+        ]);
+        assert_eq!(
+            map_owner(&row),
+            Some(ObjectOwner {
+                object: Object::Table("public".to_string(), "orders".to_string()),
+                owner: "duyet".to_string(),
+            })
+        );
+
+        let row = FixtureRow(vec![
+            Value::Str("schema"),
+            Value::Str("public"),
+            Value::Null,
+            Value::Str("postgres"),
+        ]);
+        assert_eq!(
+            map_owner(&row).unwrap().object,
+            Object::Schema("public".to_string())
+        );
+
+        let row = FixtureRow(vec![
+            Value::Str("view"),
+            Value::Str("public"),
+            Value::Null,
+            Value::Str("postgres"),
+        ]);
+        assert!(map_owner(&row).is_none());
+    }
+}
@@ -0,0 +1,226 @@
+use crate::config::{ident, Config};
+use crate::connection::DbConnection;
+use crate::state::ClusterState;
+use anyhow::{anyhow, Result};
+use log::{info, warn};
+use std::collections::BTreeSet;
+
+/// Copy the live grants of the users on the schema `from` to the schema `to`,
+/// e.g. during a blue/green swap of `analytics_v1` to `analytics_v2`.
+///
+/// The GRANT statements are printed, and executed only with `apply`.
+/// Tables of `from` which do not exist in `to` are skipped with a warning.
+pub fn clone_grants(config: &Config, from: &str, to: &str, apply: bool) -> Result<()> {
+    let mut conn = DbConnection::new(config)?;
+    let state = conn.get_cluster_state();
+
+    if !state.has_schema(&ident::fold(from)) {
+        return Err(anyhow!("schema {} not found or has no tables", from));
+    }
+
+    let statements = clone_sql(&ident::fold(from), &ident::fold(to), &state);
+    if statements.is_empty() {
+        info!("No grants to clone from schema {} to {}", from, to);
+        return Ok(());
+    }
+
+    for sql in &statements {
+        if apply {
+            conn.execute(sql, &[])?;
+            info!("{}", sql);
+        } else {
+            println!("{}", sql);
+        }
+    }
+
+    if apply {
+        info!(
+            "Cloned {} grant(s) from schema {} to {}",
+            statements.len(),
+            from,
+            to
+        );
+    } else {
+        info!(
+            "{} grant(s) to clone from schema {} to {}, run with --apply to execute them",
+            statements.len(),
+            from,
+            to
+        );
+    }
+
+    Ok(())
+}
+
+/// SQL granting on `to` the privileges the users have on `from`, both names
+/// as in the catalog. Privileges already in place on `to` and the superusers
+/// are skipped.
+fn clone_sql(from: &str, to: &str, state: &ClusterState) -> Vec<String> {
+    let mut statements = vec![];
+    let mut missing_tables = BTreeSet::new();
+
+    // Superusers have every privilege, there is nothing to clone for them
+    for user in state.users().iter().filter(|u| !u.user_super) {
+        let granted =
+            |schema: &str, privilege: &str| state.has_schema_priv(&user.name, schema, privilege);
+        for p in state
+            .schema_privileges_of(&user.name)
+            .iter()
+            .filter(|p| &*p.schema_name == from)
+        {
+            let privileges = [
+                ("CREATE", p.has_create && !granted(to, "CREATE")),
+                ("USAGE", p.has_usage && !granted(to, "USAGE")),
+            ];
+            if let Some(privileges) = missing(&privileges) {
+                statements.push(format!(
+                    "GRANT {} ON SCHEMA {} TO {};",
+                    privileges,
+                    ident::quote_folded(to),
+                    ident::quote_folded(&user.name)
+                ));
+            }
+        }
+
+        for p in state
+            .table_privileges_of(&user.name)
+            .iter()
+            .filter(|p| &*p.schema_name == from)
+        {
+            if !state.has_table(to, &p.table_name) {
+                missing_tables.insert(p.table_name.clone());
+                continue;
+            }
+
+            let granted =
+                |privilege: &str| state.has_table_priv(&user.name, to, &p.table_name, privilege);
+            let privileges = [
+                ("SELECT", p.has_select && !granted("SELECT")),
+                ("INSERT", p.has_insert && !granted("INSERT")),
+                ("UPDATE", p.has_update && !granted("UPDATE")),
+                ("DELETE", p.has_delete && !granted("DELETE")),
+                ("REFERENCES", p.has_references && !granted("REFERENCES")),
+            ];
+            if let Some(privileges) = missing(&privileges) {
+                statements.push(format!(
+                    "GRANT {} ON TABLE {}.{} TO {};",
+                    privileges,
+                    ident::quote_folded(to),
+                    ident::quote_folded(&p.table_name),
+                    ident::quote_folded(&user.name)
+                ));
+            }
+        }
+    }
+
+    for table in missing_tables {
+        warn!(
+            "Skipping {}.{}: the table does not exist in {}",
+            from, table, to
+        );
+    }
+
+    statements
+}
+
+/// The privileges to grant, e.g. `SELECT, INSERT`, None if there is none
+fn missing(privileges: &[(&str, bool)]) -> Option<String> {
+    let privileges = privileges
+        .iter()
+        .filter(|(_, missing)| *missing)
+        .map(|(privilege, _)| *privilege)
+        .collect::<Vec<_>>();
+
+    if privileges.is_empty() {
+        None
+    } else {
+        Some(privileges.join(", "))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::connection::{User, UserSchemaRole, UserTableRole};
+
+    fn user(name: &str, user_super: bool) -> User {
+        User {
+            name: name.to_string(),
+            user_createdb: false,
+            user_super,
+            password: "********".to_string(),
+            attributes: Default::default(),
+            settings: Default::default(),
+        }
+    }
+
+    fn schema(name: &str, schema_name: &str, has_create: bool, has_usage: bool) -> UserSchemaRole {
+        UserSchemaRole {
+            name: name.into(),
+            schema_name: schema_name.into(),
+            has_create,
+            has_usage,
+        }
+    }
+
+    fn table(name: &str, schema_name: &str, table_name: &str, has_select: bool) -> UserTableRole {
+        UserTableRole {
+            name: name.into(),
+            schema_name: schema_name.into(),
+            table_name: table_name.into(),
+            has_select,
+            has_insert: false,
+            has_update: false,
+            has_delete: false,
+            has_references: false,
+        }
+    }
+
+    #[test]
+    fn test_clone_sql() {
+        let schemas = vec![
+            schema("duyet", "v1", true, true),
+            schema("duyet", "v2", false, true),
+            schema("postgres", "v1", true, true),
+        ];
+        let tables = vec![
+            table("duyet", "v1", "events", true),
+            table("duyet", "v1", "legacy", true),
+            table("duyet", "v2", "events", false),
+            table("postgres", "v1", "events", true),
+            table("postgres", "v2", "events", true),
+        ];
+
+        let state = ClusterState::new(
+            vec![user("duyet", false), user("postgres", true)],
+            vec![],
+            vec![],
+            schemas,
+            tables,
+        );
+
+        assert_eq!(
+            clone_sql("v1", "v2", &state),
+            vec![
+                "GRANT CREATE ON SCHEMA v2 TO duyet;",
+                "GRANT SELECT ON TABLE v2.events TO duyet;",
+            ]
+        );
+    }
+
+    #[test]
+    fn test_clone_sql_already_granted() {
+        let schemas = vec![
+            schema("duyet", "v1", false, true),
+            schema("duyet", "v2", false, true),
+        ];
+        let tables = vec![
+            table("duyet", "v1", "events", true),
+            table("duyet", "v2", "events", true),
+        ];
+
+        let state = ClusterState::new(vec![user("duyet", false)], vec![], vec![], schemas, tables);
+
+        assert!(clone_sql("v1", "v2", &state).is_empty());
+    }
+}
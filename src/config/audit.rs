@@ -0,0 +1,94 @@
+use super::ident;
+use anyhow::{anyhow, Result};
+use serde::{Deserialize, Serialize};
+
+/// Audit log of `apply`, opt-in: every statement it executes is recorded in
+/// a table of the cluster, see [crate::audit].
+///
+/// For example:
+///
+/// ```yaml
+/// audit:
+///   # optional, `schema.table`, created with its schema if missing
+///   table: grant_audit.changes
+/// ```
+///
+/// `audit: {}` records in the default table.
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq, Eq)]
+pub struct Audit {
+    #[serde(default = "default_table")]
+    pub table: String,
+}
+
+impl Default for Audit {
+    fn default() -> Self {
+        Audit {
+            table: default_table(),
+        }
+    }
+}
+
+fn default_table() -> String {
+    "grant_audit.changes".to_string()
+}
+
+impl Audit {
+    /// The schema and the table, named as in the catalog
+    pub fn schema_table(&self) -> Result<(String, String)> {
+        match self.table.split_once('.') {
+            Some((schema, table))
+                if !schema.trim().is_empty()
+                    && !table.trim().is_empty()
+                    && !table.contains('.') =>
+            {
+                Ok((ident::fold(schema.trim()), ident::fold(table.trim())))
+            }
+            _ => Err(anyhow!(
+                "audit: table {} must be schema.table, e.g. grant_audit.changes",
+                self.table
+            )),
+        }
+    }
+
+    /// The table in a statement, e.g. `grant_audit.changes`
+    pub fn quoted_table(&self) -> Result<String> {
+        let (schema, table) = self.schema_table()?;
+
+        Ok(format!(
+            "{}.{}",
+            ident::quote_folded(&schema),
+            ident::quote_folded(&table)
+        ))
+    }
+
+    pub fn validate(&self) -> Result<()> {
+        self.schema_table().map(|_| ())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_schema_table() {
+        let audit = Audit::default();
+        assert_eq!(
+            audit.schema_table().unwrap(),
+            ("grant_audit".to_string(), "changes".to_string())
+        );
+        assert_eq!(audit.quoted_table().unwrap(), "grant_audit.changes");
+
+        let audit = Audit {
+            table: "Compliance.Grant_Log".to_string(),
+        };
+        assert_eq!(audit.quoted_table().unwrap(), "compliance.grant_log");
+
+        for table in ["changes", ".changes", "a.b.c", "grant_audit."] {
+            let audit = Audit {
+                table: table.to_string(),
+            };
+            assert!(audit.validate().is_err(), "{}", table);
+        }
+    }
+}
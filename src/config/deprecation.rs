@@ -0,0 +1,115 @@
+//! Renamed config fields.
+//!
+//! A renamed field is still read under its old name, with a serde alias, until
+//! the version in which its support is removed. Meanwhile loading a config
+//! using it warns with the file, the field and that version:
+//!
+//! ```text
+//! config.yaml: users[0].update_password is deprecated, use password_policy
+//! (true is always, false is on_create), it will be removed in 0.1.0
+//! ```
+
+use serde_yaml::Value;
+use std::fmt;
+
+/// A field renamed in the items of a top level list of the config
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Rename {
+    /// The top level list, e.g. `users`
+    pub section: &'static str,
+    pub old: &'static str,
+    pub new: &'static str,
+    /// How to translate the old values, if they changed
+    pub note: Option<&'static str>,
+    /// Version in which the old name is no longer read
+    pub removed_in: &'static str,
+}
+
+/// The renamed fields, still read under their old name
+pub const RENAMES: &[Rename] = &[Rename {
+    section: "users",
+    old: "update_password",
+    new: "password_policy",
+    note: Some("true is always, false is on_create"),
+    removed_in: "0.1.0",
+}];
+
+/// Use of a deprecated field in a config
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Deprecation {
+    /// Config file, or `GRANT_CONFIG`
+    pub source: String,
+    /// Path of the field, e.g. `users[0].update_password`
+    pub field: String,
+    pub rename: &'static Rename,
+}
+
+impl fmt::Display for Deprecation {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(
+            f,
+            "{}: {} is deprecated, use {}",
+            self.source, self.field, self.rename.new
+        )?;
+        if let Some(note) = self.rename.note {
+            write!(f, " ({})", note)?;
+        }
+
+        write!(f, ", it will be removed in {}", self.rename.removed_in)
+    }
+}
+
+/// The deprecated fields used by the config
+pub fn deprecations(source: &str, config: &Value) -> Vec<Deprecation> {
+    let mut deprecations = vec![];
+
+    for rename in RENAMES {
+        let items = match config.get(rename.section).and_then(Value::as_sequence) {
+            Some(items) => items,
+            None => continue,
+        };
+
+        for (i, item) in items.iter().enumerate() {
+            if item.get(rename.old).is_some() {
+                deprecations.push(Deprecation {
+                    source: source.to_string(),
+                    field: format!("{}[{}].{}", rename.section, i, rename.old),
+                    rename,
+                });
+            }
+        }
+    }
+
+    deprecations
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use indoc::indoc;
+
+    #[test]
+    fn test_deprecations() {
+        let config: Value = serde_yaml::from_str(indoc! {"
+            users:
+              - name: duyet
+                password_policy: always
+                roles: []
+              - name: duyet2
+                update_password: true
+                roles: []
+        "})
+        .unwrap();
+
+        let found = deprecations("config.yaml", &config);
+        assert_eq!(found.len(), 1);
+        assert_eq!(found[0].field, "users[1].update_password");
+        assert_eq!(
+            found[0].to_string(),
+            "config.yaml: users[1].update_password is deprecated, use password_policy \
+             (true is always, false is on_create), it will be removed in 0.1.0"
+        );
+
+        assert!(deprecations("config.yaml", &Value::Null).is_empty());
+    }
+}
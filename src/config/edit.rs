@@ -0,0 +1,599 @@
+use super::Config;
+use anyhow::{anyhow, Context, Result};
+use serde::Serialize;
+use std::path::Path;
+use std::str::FromStr;
+use std::{fmt, fs};
+
+/// Line based editor for a configuration file.
+///
+/// A round trip through `serde_yaml` would drop the comments and rewrite the
+/// whole file. The editor only touches the lines of the list item it adds,
+/// removes or replaces, so comments, ordering and the formatting of
+/// everything else are preserved.
+///
+/// It understands the block style of the generated and example configs:
+///
+/// ```yaml
+/// users:
+///   # comments are kept
+///   - name: duyet
+///     roles:
+///       - role_database_level
+/// ```
+///
+/// Items are identified by their `name` key.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ConfigEditor {
+    lines: Vec<String>,
+}
+
+/// Top level key and its block, `end` is after the last indented line
+#[derive(Debug)]
+struct Section {
+    key_line: usize,
+    end: usize,
+}
+
+/// List item in a section, lines `start..end`
+#[derive(Debug)]
+struct Item {
+    start: usize,
+    end: usize,
+    name: Option<String>,
+}
+
+impl std::str::FromStr for ConfigEditor {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> Result<Self> {
+        Ok(Self {
+            lines: s.lines().map(|l| l.to_string()).collect(),
+        })
+    }
+}
+
+impl fmt::Display for ConfigEditor {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        for line in &self.lines {
+            writeln!(f, "{}", line)?;
+        }
+
+        Ok(())
+    }
+}
+
+impl ConfigEditor {
+    /// Read the config file for editing
+    pub fn open(file: &Path) -> Result<Self> {
+        let content = fs::read_to_string(file)
+            .with_context(|| format!("failed to read config file {}", file.display()))?;
+
+        Self::from_str(&content)
+    }
+
+    /// Write the config file, it is validated first so that a bad edit
+    /// never leaves an invalid file behind
+    pub fn save(&self, file: &Path) -> Result<()> {
+        let content = self.to_string();
+        Config::from_str(&content).context("the edited config is not valid")?;
+
+        fs::write(file, content)
+            .with_context(|| format!("failed to write config file {}", file.display()))?;
+
+        Ok(())
+    }
+
+    /// Names of the items in the `key` section, e.g. `users`
+    pub fn names(&self, key: &str) -> Vec<String> {
+        match self.section(key) {
+            Some(section) => self
+                .items(&section)
+                .into_iter()
+                .filter_map(|item| item.name)
+                .collect(),
+            None => vec![],
+        }
+    }
+
+    /// Source lines of the item `name` in the `key` section, as in the file
+    pub fn get(&self, key: &str, name: &str) -> Option<String> {
+        let section = self.section(key)?;
+        let item = self.find(&section, name)?;
+
+        Some(self.lines[item.start..item.end].join("\n"))
+    }
+
+    /// Append `value` to the end of the `key` section, the section is
+    /// created at the end of the file if missing.
+    pub fn add<T: Serialize>(&mut self, key: &str, value: &T) -> Result<()> {
+        let section = match self.section(key) {
+            Some(section) => section,
+            None => {
+                self.lines.push(format!("{}:", key));
+                self.section(key).unwrap()
+            }
+        };
+
+        let items = self.items(&section);
+        let indent = match items.first() {
+            Some(item) => indent_of(&self.lines[item.start]),
+            None => 2,
+        };
+
+        // `users: []` becomes `users:` followed by the new item
+        if items.is_empty() {
+            self.lines[section.key_line] = format!("{}:", key);
+        }
+
+        let rendered = render_item(value, indent)?;
+        let at = section.end.max(section.key_line + 1);
+        self.lines.splice(at..at, rendered);
+
+        Ok(())
+    }
+
+    /// Remove the item `name` from the `key` section
+    pub fn remove(&mut self, key: &str, name: &str) -> Result<()> {
+        let section = self
+            .section(key)
+            .ok_or_else(|| anyhow!("no {} section", key))?;
+        let item = self
+            .find(&section, name)
+            .ok_or_else(|| anyhow!("{} not found in {}", name, key))?;
+
+        self.lines.drain(item.start..item.end);
+
+        // Keep the section valid when the last item is removed
+        let section = self.section(key).unwrap();
+        if self.items(&section).is_empty() {
+            self.lines[section.key_line] = format!("{}: []", key);
+        }
+
+        Ok(())
+    }
+
+    /// Replace the item `name` in the `key` section with `value`, in place
+    pub fn replace<T: Serialize>(&mut self, key: &str, name: &str, value: &T) -> Result<()> {
+        let section = self
+            .section(key)
+            .ok_or_else(|| anyhow!("no {} section", key))?;
+        let item = self
+            .find(&section, name)
+            .ok_or_else(|| anyhow!("{} not found in {}", name, key))?;
+
+        let rendered = render_item(value, indent_of(&self.lines[item.start]))?;
+        self.lines.splice(item.start..item.end, rendered);
+
+        Ok(())
+    }
+
+    /// Rewrite each entry of the list `field` of the item `name` in the `key`
+    /// section with `f`, every other line is left untouched. Both block and
+    /// flow (`[a, b]`) lists are supported. Returns true if anything changed.
+    pub fn map_list<F>(&mut self, key: &str, name: &str, field: &str, f: F) -> Result<bool>
+    where
+        F: Fn(&str) -> String,
+    {
+        let section = self
+            .section(key)
+            .ok_or_else(|| anyhow!("no {} section", key))?;
+        let item = self
+            .find(&section, name)
+            .ok_or_else(|| anyhow!("{} not found in {}", name, key))?;
+        let key_indent = self.key_indent(item.start);
+        let prefix = format!("{}:", field);
+
+        let field_line = match self.field_line(&item, field) {
+            Some(i) => i,
+            None => return Ok(false),
+        };
+
+        let mut changed = false;
+        let (head, inline) = self.lines[field_line]
+            .split_at(self.lines[field_line].find(&prefix).unwrap() + prefix.len());
+        let inline = inline.trim();
+
+        if inline.starts_with('[') {
+            let (list, comment) =
+                inline.split_at(inline.rfind(']').map_or(inline.len(), |i| i + 1));
+            let values: Vec<String> = serde_yaml::from_str(list)?;
+            let mapped = values.iter().map(|v| f(v)).collect::<Vec<_>>();
+            if mapped != values {
+                self.lines[field_line] = format!("{} [{}]{}", head, mapped.join(", "), comment);
+                changed = true;
+            }
+            return Ok(changed);
+        }
+
+        for i in field_line + 1..item.end {
+            let line = &self.lines[i];
+            if is_blank(line) || line.trim_start().starts_with('#') {
+                continue;
+            }
+            if indent_of(line) < key_indent
+                || (indent_of(line) == key_indent && !is_item_start(line))
+            {
+                break;
+            }
+            if !is_item_start(line) {
+                continue;
+            }
+
+            let entry = line.trim_start()[1..].trim_start();
+            let (value, comment) = match entry.find(" #") {
+                Some(at) => entry.split_at(at),
+                None => (entry, ""),
+            };
+            let value = parse_scalar(value);
+            let mapped = f(&value);
+            if mapped != value {
+                self.lines[i] = format!("{}- {}{}", " ".repeat(indent_of(line)), mapped, comment);
+                changed = true;
+            }
+        }
+
+        Ok(changed)
+    }
+
+    /// Rewrite the scalar `field` of the item `name` in the `key` section.
+    /// `f` gets the value without quotes and returns the new value as raw
+    /// YAML, or None to leave it. The trailing comment is kept. Returns true
+    /// if anything changed.
+    pub fn map_scalar<F>(&mut self, key: &str, name: &str, field: &str, f: F) -> Result<bool>
+    where
+        F: FnOnce(&str) -> Option<String>,
+    {
+        let section = self
+            .section(key)
+            .ok_or_else(|| anyhow!("no {} section", key))?;
+        let item = self
+            .find(&section, name)
+            .ok_or_else(|| anyhow!("{} not found in {}", name, key))?;
+        let field_line = match self.field_line(&item, field) {
+            Some(i) => i,
+            None => return Ok(false),
+        };
+
+        let line = &self.lines[field_line];
+        let (head, value) =
+            line.split_at(line.find(&format!("{}:", field)).unwrap() + field.len() + 1);
+        let comment = value.find(" #").map_or("", |at| &value[at..]);
+
+        match f(&parse_scalar(value)) {
+            Some(mapped) => {
+                self.lines[field_line] = format!("{} {}{}", head, mapped, comment);
+                Ok(true)
+            }
+            None => Ok(false),
+        }
+    }
+
+    /// The line of `field` in the item, either on the `- ` line or at the
+    /// key indentation
+    fn field_line(&self, item: &Item, field: &str) -> Option<usize> {
+        let key_indent = self.key_indent(item.start);
+        let prefix = format!("{}:", field);
+
+        (item.start..item.end).find(|&i| {
+            let line = &self.lines[i];
+            let content = if i == item.start {
+                line.trim_start().trim_start_matches('-').trim_start()
+            } else if indent_of(line) == key_indent {
+                line.trim_start()
+            } else {
+                return false;
+            };
+            content.starts_with(&prefix)
+        })
+    }
+
+    fn section(&self, key: &str) -> Option<Section> {
+        let key_line = self.lines.iter().position(|line| {
+            line.strip_prefix(key)
+                .and_then(|rest| rest.strip_prefix(':'))
+                .map(|rest| rest.is_empty() || rest.starts_with(' '))
+                .unwrap_or(false)
+        })?;
+
+        // The block ends at the next top level key, top level comments
+        // before it belong to that key
+        let mut end = key_line + 1;
+        for (i, line) in self.lines.iter().enumerate().skip(key_line + 1) {
+            if is_blank(line) || line.starts_with('#') {
+                continue;
+            }
+            if indent_of(line) == 0 && !line.starts_with('-') {
+                break;
+            }
+            end = i + 1;
+        }
+
+        Some(Section { key_line, end })
+    }
+
+    fn items(&self, section: &Section) -> Vec<Item> {
+        let lines = &self.lines[section.key_line + 1..section.end];
+        let indent = match lines.iter().find(|l| is_item_start(l)) {
+            Some(line) => indent_of(line),
+            None => return vec![],
+        };
+
+        let starts = lines
+            .iter()
+            .enumerate()
+            .filter(|(_, l)| is_item_start(l) && indent_of(l) == indent)
+            .map(|(i, _)| section.key_line + 1 + i)
+            .collect::<Vec<_>>();
+
+        starts
+            .iter()
+            .enumerate()
+            .map(|(n, &start)| {
+                let mut end = starts.get(n + 1).copied().unwrap_or(section.end);
+
+                // Blank lines and comments before the next item belong to it
+                while end > start + 1 {
+                    let line = &self.lines[end - 1];
+                    if is_blank(line) || line.trim_start().starts_with('#') {
+                        end -= 1;
+                    } else {
+                        break;
+                    }
+                }
+
+                Item {
+                    start,
+                    end,
+                    name: self.item_name(start, end),
+                }
+            })
+            .collect()
+    }
+
+    fn find(&self, section: &Section, name: &str) -> Option<Item> {
+        self.items(section)
+            .into_iter()
+            .find(|item| item.name.as_deref() == Some(name))
+    }
+
+    /// Value of the `name:` key of the item, either on the `- ` line or on
+    /// a following line at the item key indentation.
+    fn item_name(&self, start: usize, end: usize) -> Option<String> {
+        let first = self.lines[start].trim_start().trim_start_matches('-');
+        let key_indent = self.key_indent(start);
+
+        std::iter::once(first.trim_start())
+            .chain(
+                self.lines[start + 1..end]
+                    .iter()
+                    .filter(|l| indent_of(l) == key_indent)
+                    .map(|l| l.trim_start()),
+            )
+            .find_map(|l| l.strip_prefix("name:"))
+            .map(parse_scalar)
+    }
+
+    /// Indentation of the keys of the item starting at line `start`
+    fn key_indent(&self, start: usize) -> usize {
+        let line = &self.lines[start];
+        let after_dash = line.trim_start().trim_start_matches('-');
+
+        indent_of(line) + 1 + (after_dash.len() - after_dash.trim_start().len())
+    }
+}
+
+/// Render `value` as a list item at the given indentation.
+///
+/// `serde_yaml` writes sequences at the indentation of their key, they are
+/// indented by two more spaces to match the style of the config files.
+fn render_item<T: Serialize>(value: &T, indent: usize) -> Result<Vec<String>> {
+    let yaml = serde_yaml::to_string(value)?;
+    let pad = " ".repeat(indent);
+
+    let mut seq_indent = None;
+    Ok(yaml
+        .lines()
+        .enumerate()
+        .map(|(i, line)| {
+            let prefix = if i == 0 { "- " } else { "  " };
+
+            let line_indent = indent_of(line);
+            let line = match seq_indent {
+                Some(n) if n == line_indent && is_item_start(line) => format!("  {}", line),
+                _ => {
+                    seq_indent = line.ends_with(':').then_some(line_indent);
+                    line.to_string()
+                }
+            };
+
+            format!("{}{}{}", pad, prefix, line)
+        })
+        .collect())
+}
+
+/// Scalar value without its trailing comment and quotes
+fn parse_scalar(value: &str) -> String {
+    let value = match value.find(" #") {
+        Some(i) => &value[..i],
+        None => value,
+    }
+    .trim();
+
+    serde_yaml::from_str::<String>(value).unwrap_or_else(|_| value.to_string())
+}
+
+fn indent_of(line: &str) -> usize {
+    line.len() - line.trim_start().len()
+}
+
+fn is_blank(line: &str) -> bool {
+    line.trim().is_empty()
+}
+
+fn is_item_start(line: &str) -> bool {
+    let line = line.trim_start();
+    line == "-" || line.starts_with("- ")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::config::User;
+    use indoc::indoc;
+
+    const CONFIG: &str = indoc! {r#"
+        connection:
+          type: postgres
+          url: postgres://localhost:5432/postgres
+
+        # the roles
+        roles:
+          - name: role_a
+            type: database
+            grants:
+              - CREATE
+            databases:
+              - postgres
+
+        users:
+          # first user
+          - name: duyet # the owner
+            roles:
+              - role_a
+
+          - roles:
+              - role_a
+            name: "duyet2"
+    "#};
+
+    fn user(name: &str) -> User {
+        User {
+            name: name.to_string(),
+            password: None,
+            password_policy: None,
+            attributes: Default::default(),
+            settings: Default::default(),
+            roles: vec!["role_a".to_string()],
+            revokes: vec![],
+        }
+    }
+
+    #[test]
+    fn test_names() {
+        let editor = ConfigEditor::from_str(CONFIG).unwrap();
+
+        assert_eq!(editor.names("users"), vec!["duyet", "duyet2"]);
+        assert_eq!(editor.names("roles"), vec!["role_a"]);
+        assert!(editor.names("other").is_empty());
+    }
+
+    #[test]
+    fn test_add_keeps_comments() {
+        let mut editor = ConfigEditor::from_str(CONFIG).unwrap();
+        editor.add("users", &user("alice")).unwrap();
+
+        let text = editor.to_string();
+        assert!(text.starts_with(CONFIG));
+        assert!(text.ends_with("  - name: alice\n    roles:\n      - role_a\n"));
+
+        let config = crate::config::Config::from_str(&text).unwrap();
+        assert_eq!(config.users.len(), 3);
+        assert_eq!(config.users[2], user("alice"));
+    }
+
+    #[test]
+    fn test_add_to_empty_section() {
+        let mut editor = ConfigEditor::from_str("roles: []\nusers: []\n").unwrap();
+        editor.add("users", &user("alice")).unwrap();
+
+        assert_eq!(
+            editor.to_string(),
+            "roles: []\nusers:\n  - name: alice\n    roles:\n      - role_a\n"
+        );
+    }
+
+    #[test]
+    fn test_remove() {
+        let mut editor = ConfigEditor::from_str(CONFIG).unwrap();
+        editor.remove("users", "duyet").unwrap();
+
+        let text = editor.to_string();
+        assert!(text.contains("# the roles"));
+        assert!(!text.contains("# the owner"));
+        assert_eq!(editor.names("users"), vec!["duyet2"]);
+
+        editor.remove("users", "duyet2").unwrap();
+        assert!(editor.to_string().contains("users: []\n"));
+        let config = crate::config::Config::from_str(&editor.to_string()).unwrap();
+        assert!(config.users.is_empty());
+
+        assert!(editor.remove("users", "nobody").is_err());
+    }
+
+    #[test]
+    fn test_map_list() {
+        let mut editor = ConfigEditor::from_str(CONFIG).unwrap();
+        let rename = |v: &str| {
+            if v == "postgres" {
+                "analytics".to_string()
+            } else {
+                v.to_string()
+            }
+        };
+
+        assert!(editor
+            .map_list("roles", "role_a", "databases", rename)
+            .unwrap());
+        assert!(!editor
+            .map_list("roles", "role_a", "grants", rename)
+            .unwrap());
+        assert_eq!(
+            editor.to_string(),
+            CONFIG.replace("      - postgres", "      - analytics")
+        );
+
+        let mut editor =
+            ConfigEditor::from_str("roles:\n  - name: a\n    schemas: [public, b] # c\n").unwrap();
+        let upper = |v: &str| v.to_uppercase();
+        assert!(editor.map_list("roles", "a", "schemas", upper).unwrap());
+        assert_eq!(
+            editor.to_string(),
+            "roles:\n  - name: a\n    schemas: [PUBLIC, B] # c\n"
+        );
+    }
+
+    #[test]
+    fn test_replace() {
+        let mut editor = ConfigEditor::from_str(CONFIG).unwrap();
+        editor.replace("users", "duyet2", &user("duyet3")).unwrap();
+
+        assert_eq!(editor.names("users"), vec!["duyet", "duyet3"]);
+        assert!(editor
+            .get("users", "duyet3")
+            .unwrap()
+            .contains("- name: duyet3"));
+    }
+
+    #[test]
+    fn test_map_scalar() {
+        let mut editor = ConfigEditor::from_str(CONFIG).unwrap();
+
+        assert!(editor
+            .map_scalar("users", "duyet", "name", |v| Some(format!("{}_a", v)))
+            .unwrap());
+        assert!(editor
+            .map_scalar("users", "duyet2", "name", |v| Some(format!("{}_b", v)))
+            .unwrap());
+        assert!(!editor
+            .map_scalar("users", "duyet_a", "password", |_| Some("x".to_string()))
+            .unwrap());
+        assert!(!editor
+            .map_scalar("users", "duyet_a", "name", |_| None)
+            .unwrap());
+
+        let content = editor.to_string();
+        assert!(content.contains("  - name: duyet_a # the owner\n"));
+        assert!(content.contains("    name: duyet2_b\n"));
+    }
+}
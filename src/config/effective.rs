@@ -0,0 +1,275 @@
+//! The privileges a user ends up with once a config is applied, resolved
+//! from the roles of the user, the roles of its groups and the roles of
+//! `PUBLIC`, e.g. to preview what a user of the config can access.
+//!
+//! ```rust
+//! use grant::config::Config;
+//! use std::str::FromStr;
+//!
+//! let config = Config::from_str(
+//!     r#"
+//!       connection:
+//!         type: postgres
+//!         url: "postgres://postgres@localhost:5432/postgres"
+//!       roles:
+//!         - name: role_read
+//!           type: table
+//!           grants: [SELECT]
+//!           schemas: [public]
+//!           tables: [ALL, -secrets]
+//!       users:
+//!         - name: duyet
+//!           roles: [role_read]
+//!     "#,
+//! )
+//! .unwrap();
+//!
+//! let effective = config.effective_privileges("duyet");
+//! assert_eq!(effective.privileges.len(), 2);
+//! assert!(effective.privileges[1].revoked);
+//! ```
+
+use super::{ident, Config, Role};
+use serde::Serialize;
+use std::fmt;
+
+/// Where a privilege of a user comes from
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum Source {
+    /// A role of the user
+    User,
+    /// A role of a group the user is a member of
+    Group(String),
+    /// A role of `PUBLIC`, every role of the cluster has it
+    Public,
+    /// A role revoked from the user, see [User::revokes](super::User::revokes)
+    Revoked,
+}
+
+/// The privileges granted, or revoked, on an object by a role
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct EffectivePrivilege {
+    /// The object as in the statement, e.g. `TABLE public.orders` or
+    /// `ALL TABLES IN SCHEMA public`
+    pub object: String,
+    /// The database of the object, None for the one of the connection
+    pub database: Option<String>,
+    /// The grants of the role, `ALL` for every privilege
+    pub privileges: Vec<String>,
+    /// A `-` entry of the role, revoked after the grants of the role, or an
+    /// object of a revoked role
+    pub revoked: bool,
+    pub role: String,
+    pub source: Source,
+}
+
+impl fmt::Display for EffectivePrivilege {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(
+            f,
+            "{} {} on {}",
+            if self.revoked { "revoke" } else { "grant" },
+            self.privileges.join(", "),
+            self.object
+        )?;
+        if let Some(database) = &self.database {
+            write!(f, " in {}", database)?;
+        }
+        match &self.source {
+            Source::User => write!(f, " (role {})", self.role),
+            Source::Group(group) => write!(f, " (role {} of group {})", self.role, group),
+            Source::Public => write!(f, " (role {} of PUBLIC)", self.role),
+            Source::Revoked => write!(f, " (revoked role {})", self.role),
+        }
+    }
+}
+
+/// The privileges of a user, see [Config::effective_privileges]
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct EffectivePrivileges {
+    /// The user, named as in the catalog
+    pub user: String,
+    /// The groups of the config the user is a member of
+    pub groups: Vec<String>,
+    /// In the order they are applied: the roles of the user, its revoked
+    /// roles, then the roles of its groups, then of `PUBLIC`
+    pub privileges: Vec<EffectivePrivilege>,
+}
+
+impl EffectivePrivileges {
+    /// The privileges granted and not revoked by the same source on the
+    /// same object, the revoked roles revoking the ones of the user. A revoke
+    /// on a single table does not remove a grant on all the tables of its
+    /// schema, the tables are only known to the cluster.
+    pub fn granted(&self) -> Vec<&EffectivePrivilege> {
+        self.privileges
+            .iter()
+            .filter(|p| !p.revoked)
+            .filter(|p| {
+                !self.privileges.iter().any(|r| {
+                    r.revoked
+                        && r.object == p.object
+                        && r.database == p.database
+                        && (r.source == p.source
+                            || (r.source == Source::Revoked && p.source == Source::User))
+                })
+            })
+            .collect()
+    }
+}
+
+impl Config {
+    /// Resolve the privileges of a user of the cluster: the roles of the
+    /// user in the config, of the groups of the config it is a member of,
+    /// and of `PUBLIC`. A user which is not in the config only has the
+    /// privileges of its groups and of `PUBLIC`.
+    pub fn effective_privileges(&self, user: &str) -> EffectivePrivileges {
+        let name = ident::fold(user);
+        let groups = self
+            .groups
+            .iter()
+            .filter(|g| g.folded_members().contains(&name))
+            .collect::<Vec<_>>();
+
+        let mut sources: Vec<(&String, Source)> = vec![];
+        for u in self.users.iter().filter(|u| ident::fold(&u.name) == name) {
+            sources.extend(u.roles.iter().map(|r| (r, Source::User)));
+            sources.extend(u.revokes.iter().map(|r| (r, Source::Revoked)));
+        }
+        for group in &groups {
+            sources.extend(
+                group
+                    .roles
+                    .iter()
+                    .map(|r| (r, Source::Group(group.name.clone()))),
+            );
+        }
+        for public in self.users.iter().filter(|u| u.is_public()) {
+            sources.extend(public.roles.iter().map(|r| (r, Source::Public)));
+        }
+
+        let mut privileges = vec![];
+        for (role_name, source) in sources {
+            // As applied, see `privilege_statements`
+            let Some(role) = self.roles.iter().find(|r| r.find(role_name)) else {
+                continue;
+            };
+            privileges.extend(role.objects().into_iter().map(|(revoked, object)| {
+                EffectivePrivilege {
+                    object,
+                    database: role.database().map(str::to_string),
+                    privileges: grants(role),
+                    revoked: revoked || source == Source::Revoked,
+                    role: role.get_name(),
+                    source: source.clone(),
+                }
+            }));
+        }
+
+        EffectivePrivileges {
+            user: name,
+            groups: groups.iter().map(|g| g.name.clone()).collect(),
+            privileges,
+        }
+    }
+}
+
+/// The grants of a role, a database or schema role without grants grants
+/// every privilege
+fn grants(role: &Role) -> Vec<String> {
+    match role.get_grants() {
+        grants if grants.is_empty() => vec!["ALL".to_string()],
+        grants => grants,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use indoc::indoc;
+    use std::str::FromStr;
+
+    fn config() -> Config {
+        Config::from_str(indoc! {"
+            connection:
+              type: postgres
+              url: postgres://localhost:5432/postgres
+            roles:
+              - name: role_read
+                type: table
+                grants: [SELECT]
+                schemas: [public]
+                tables: [ALL, -secrets]
+              - name: role_marts
+                type: schema
+                database: analytics
+                grants: [USAGE]
+                schemas: [marts]
+              - name: role_connect
+                type: database
+                grants: [TEMP]
+                databases: [postgres]
+            users:
+              - name: Duyet
+                roles: [role_read]
+              - name: PUBLIC
+                roles: [role_connect]
+            groups:
+              - name: analysts
+                members: [duyet, bi]
+                roles: [role_marts]
+        "})
+        .unwrap()
+    }
+
+    #[test]
+    fn test_effective_privileges() {
+        let effective = config().effective_privileges("duyet");
+
+        assert_eq!(effective.user, "duyet");
+        assert_eq!(effective.groups, vec!["analysts"]);
+        assert_eq!(
+            effective
+                .privileges
+                .iter()
+                .map(|p| p.to_string())
+                .collect::<Vec<_>>(),
+            vec![
+                "grant SELECT on ALL TABLES IN SCHEMA public (role role_read)",
+                "revoke SELECT on TABLE public.secrets (role role_read)",
+                "grant USAGE on SCHEMA marts in analytics (role role_marts of group analysts)",
+                "grant TEMP on DATABASE postgres (role role_connect of PUBLIC)",
+            ]
+        );
+        assert_eq!(effective.granted().len(), 3);
+    }
+
+    #[test]
+    fn test_effective_privileges_not_in_config() {
+        let effective = config().effective_privileges("bi");
+        assert_eq!(effective.privileges.len(), 2);
+        assert_eq!(
+            effective.privileges[0].source,
+            Source::Group("analysts".to_string())
+        );
+
+        let effective = config().effective_privileges("nobody");
+        assert!(effective.groups.is_empty());
+        assert_eq!(effective.privileges[0].source, Source::Public);
+    }
+
+    #[test]
+    fn test_effective_privileges_revoked() {
+        let mut config = config();
+        config.users[0].roles.clear();
+        config.users[0].revokes = vec!["role_read".to_string()];
+        let effective = config.effective_privileges("duyet");
+
+        assert_eq!(
+            effective.privileges[0].to_string(),
+            "revoke SELECT on ALL TABLES IN SCHEMA public (revoked role role_read)"
+        );
+        assert!(effective.granted().iter().all(|p| p.role != "role_read"));
+    }
+}
@@ -0,0 +1,157 @@
+use super::ident;
+use super::Dialect;
+use anyhow::{anyhow, Result};
+use serde::{Deserialize, Serialize};
+
+/// A database role which users are members of, e.g. `GRANT analysts TO duyet`
+/// in Postgres. On Redshift, which has no roles, it is a user group.
+///
+/// For example:
+///
+/// ```yaml
+/// groups:
+///   - name: analysts
+///     members:
+///       - duyet
+///       - duyet2
+///     roles:
+///       - role_table_level
+/// ```
+///
+/// The membership converges to the config: the members not listed are
+/// removed from the group. The `roles` are granted to the group, e.g.
+/// `GRANT SELECT ON ... TO GROUP analysts`, the members get them through it.
+#[derive(Debug, Serialize, Deserialize, Clone, PartialEq, Eq)]
+pub struct Group {
+    pub name: String,
+    #[serde(default)]
+    pub members: Vec<String>,
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub roles: Vec<String>,
+}
+
+impl Group {
+    pub fn to_sql_create(&self, dialect: Dialect) -> String {
+        if dialect.is_redshift() {
+            format!("CREATE GROUP {};", ident::quote(&self.name))
+        } else {
+            format!("CREATE ROLE {};", ident::quote(&self.name))
+        }
+    }
+
+    /// Add a member, named as in the catalog
+    pub fn to_sql_add(&self, member: &str, dialect: Dialect) -> String {
+        if dialect.is_redshift() {
+            format!(
+                "ALTER GROUP {} ADD USER {};",
+                ident::quote(&self.name),
+                ident::quote_folded(member)
+            )
+        } else {
+            format!(
+                "GRANT {} TO {};",
+                ident::quote(&self.name),
+                ident::quote_folded(member)
+            )
+        }
+    }
+
+    /// Remove a member, named as in the catalog
+    pub fn to_sql_remove(&self, member: &str, dialect: Dialect) -> String {
+        if dialect.is_redshift() {
+            format!(
+                "ALTER GROUP {} DROP USER {};",
+                ident::quote(&self.name),
+                ident::quote_folded(member)
+            )
+        } else {
+            format!(
+                "REVOKE {} FROM {};",
+                ident::quote(&self.name),
+                ident::quote_folded(member)
+            )
+        }
+    }
+
+    /// The members named as in the catalog
+    pub fn folded_members(&self) -> Vec<String> {
+        self.members.iter().map(|m| ident::fold(m)).collect()
+    }
+
+    pub fn validate(&self) -> Result<()> {
+        if self.name.is_empty() {
+            return Err(anyhow!("group name is empty"));
+        }
+
+        let members = self.folded_members();
+        for (i, member) in members.iter().enumerate() {
+            if member.is_empty() {
+                return Err(anyhow!("group {}: member name is empty", self.name));
+            }
+            if members[..i].contains(member) {
+                return Err(anyhow!("group {}: duplicated member {}", self.name, member));
+            }
+        }
+
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn group() -> Group {
+        Group {
+            name: "Analysts".to_string(),
+            members: vec!["duyet".to_string(), "ident team".to_string()],
+            roles: vec![],
+        }
+    }
+
+    #[test]
+    fn test_group_to_sql() {
+        let group = group();
+
+        assert_eq!(
+            group.to_sql_create(Dialect::Postgres),
+            "CREATE ROLE Analysts;"
+        );
+        assert_eq!(
+            group.to_sql_add("ident team", Dialect::Postgres),
+            r#"GRANT Analysts TO "ident team";"#
+        );
+        assert_eq!(
+            group.to_sql_remove("duyet", Dialect::Postgres),
+            "REVOKE Analysts FROM duyet;"
+        );
+
+        assert_eq!(
+            group.to_sql_create(Dialect::Redshift),
+            "CREATE GROUP Analysts;"
+        );
+        assert_eq!(
+            group.to_sql_add("duyet", Dialect::Redshift),
+            "ALTER GROUP Analysts ADD USER duyet;"
+        );
+        assert_eq!(
+            group.to_sql_remove("duyet", Dialect::RedshiftServerless),
+            "ALTER GROUP Analysts DROP USER duyet;"
+        );
+    }
+
+    #[test]
+    fn test_group_validate() {
+        assert!(group().validate().is_ok());
+
+        let mut group = group();
+        group.members.push("Duyet".to_string());
+        assert_eq!(
+            group.validate().unwrap_err().to_string(),
+            "group Analysts: duplicated member duyet"
+        );
+
+        group.name = "".to_string();
+        assert!(group.validate().is_err());
+    }
+}
@@ -0,0 +1,291 @@
+//! SQL identifiers of the config: user, database, schema and table names.
+//!
+//! Names are written in the config the way they are written in SQL:
+//!
+//! - a plain name such as `Duyet` or `données` is case-folded by the server,
+//!   it is the same user as `duyet` in the catalog;
+//! - a name which is not a plain identifier, e.g. `data team` or `user`
+//!   (a reserved keyword), is quoted and kept as written;
+//! - a name in double quotes, e.g. `'"Duyet"'`, keeps its case.
+
+/// Reserved keywords of PostgreSQL, they must be quoted to be used as names.
+/// Sorted for the binary search.
+const RESERVED_KEYWORDS: &[&str] = &[
+    "all",
+    "analyse",
+    "analyze",
+    "and",
+    "any",
+    "array",
+    "as",
+    "asc",
+    "asymmetric",
+    "authorization",
+    "binary",
+    "both",
+    "case",
+    "cast",
+    "check",
+    "collate",
+    "collation",
+    "column",
+    "concurrently",
+    "constraint",
+    "create",
+    "cross",
+    "current_catalog",
+    "current_date",
+    "current_role",
+    "current_schema",
+    "current_time",
+    "current_timestamp",
+    "current_user",
+    "default",
+    "deferrable",
+    "desc",
+    "distinct",
+    "do",
+    "else",
+    "end",
+    "except",
+    "false",
+    "fetch",
+    "for",
+    "foreign",
+    "freeze",
+    "from",
+    "full",
+    "grant",
+    "group",
+    "having",
+    "ilike",
+    "in",
+    "initially",
+    "inner",
+    "intersect",
+    "into",
+    "is",
+    "isnull",
+    "join",
+    "lateral",
+    "leading",
+    "left",
+    "like",
+    "limit",
+    "localtime",
+    "localtimestamp",
+    "natural",
+    "not",
+    "notnull",
+    "null",
+    "offset",
+    "on",
+    "only",
+    "or",
+    "order",
+    "outer",
+    "overlaps",
+    "placing",
+    "primary",
+    "references",
+    "returning",
+    "right",
+    "select",
+    "session_user",
+    "similar",
+    "some",
+    "symmetric",
+    "system_user",
+    "table",
+    "tablesample",
+    "then",
+    "to",
+    "trailing",
+    "true",
+    "union",
+    "unique",
+    "user",
+    "using",
+    "variadic",
+    "verbose",
+    "when",
+    "where",
+    "window",
+    "with",
+];
+
+/// Render a config name for a SQL statement, quoted when needed
+pub fn quote(name: &str) -> String {
+    if is_quoted(name) {
+        name.to_string()
+    } else if !is_plain(name) {
+        quote_exact(name)
+    } else if is_reserved(name) {
+        quote_exact(&name.to_ascii_lowercase())
+    } else {
+        name.to_string()
+    }
+}
+
+/// Render a list of config names, e.g. `db1, "Sales DB"`
+pub fn quote_list(names: &[String]) -> String {
+    names
+        .iter()
+        .map(|name| quote(name))
+        .collect::<Vec<_>>()
+        .join(", ")
+}
+
+/// Render a possibly schema qualified config name, e.g. `Sales."Q1 Orders"`
+pub fn quote_qualified(name: &str) -> String {
+    split_qualified(name)
+        .iter()
+        .map(|part| quote(part))
+        .collect::<Vec<_>>()
+        .join(".")
+}
+
+/// The name as stored in the catalog, to compare a config name with the
+/// names read from the cluster
+pub fn fold(name: &str) -> String {
+    if is_quoted(name) {
+        name[1..name.len() - 1].replace("\"\"", "\"")
+    } else if is_plain(name) {
+        // Only ASCII letters are folded, as the server does for UTF-8
+        name.to_ascii_lowercase()
+    } else {
+        name.to_string()
+    }
+}
+
+/// Render a name read from the catalog for a SQL statement
+pub fn quote_folded(name: &str) -> String {
+    if is_plain(name) && !is_reserved(name) && fold(name) == name {
+        name.to_string()
+    } else {
+        quote_exact(name)
+    }
+}
+
+/// Split `schema.table` on the dots which are not inside double quotes
+pub fn split_qualified(name: &str) -> Vec<&str> {
+    let mut parts = vec![];
+    let mut in_quotes = false;
+    let mut start = 0;
+
+    for (i, c) in name.char_indices() {
+        match c {
+            '"' => in_quotes = !in_quotes,
+            '.' if !in_quotes => {
+                parts.push(&name[start..i]);
+                start = i + 1;
+            }
+            _ => {}
+        }
+    }
+    parts.push(&name[start..]);
+
+    parts
+}
+
+fn quote_exact(name: &str) -> String {
+    format!("\"{}\"", name.replace('"', "\"\""))
+}
+
+/// A non-empty name in double quotes, with the inner quotes doubled.
+/// Anything else, e.g. `"""`, is a name which needs quotes itself.
+fn is_quoted(name: &str) -> bool {
+    match name
+        .strip_prefix('"')
+        .and_then(|name| name.strip_suffix('"'))
+    {
+        Some(inner) => !inner.is_empty() && !inner.replace("\"\"", "").contains('"'),
+        None => false,
+    }
+}
+
+/// A name which can be written without quotes: a letter or `_` followed by
+/// letters, digits, `_` or `$`. Non-ASCII letters are allowed.
+fn is_plain(name: &str) -> bool {
+    let mut chars = name.chars();
+
+    match chars.next() {
+        Some(c) if c.is_alphabetic() || c == '_' => {
+            chars.all(|c| c.is_alphanumeric() || c == '_' || c == '$')
+        }
+        _ => false,
+    }
+}
+
+fn is_reserved(name: &str) -> bool {
+    RESERVED_KEYWORDS
+        .binary_search(&name.to_ascii_lowercase().as_str())
+        .is_ok()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_reserved_keywords_sorted() {
+        assert!(RESERVED_KEYWORDS.windows(2).all(|w| w[0] < w[1]));
+    }
+
+    #[test]
+    fn test_quote() {
+        assert_eq!(quote("duyet"), "duyet");
+        assert_eq!(quote("Duyet"), "Duyet");
+        assert_eq!(quote("données"), "données");
+        assert_eq!(quote("\"Duyet\""), "\"Duyet\"");
+        assert_eq!(quote("data team"), "\"data team\"");
+        assert_eq!(quote("analytics-v1"), "\"analytics-v1\"");
+        assert_eq!(quote("1st"), "\"1st\"");
+        assert_eq!(quote("say \"hi\""), "\"say \"\"hi\"\"\"");
+        assert_eq!(quote("User"), "\"user\"");
+        assert_eq!(quote("\"\"\""), "\"\"\"\"\"\"\"\"");
+        assert_eq!(quote("\"a\"b\""), "\"\"\"a\"\"b\"\"\"");
+    }
+
+    #[test]
+    fn test_quote_qualified() {
+        assert_eq!(quote_qualified("public.events"), "public.events");
+        assert_eq!(quote_qualified("Sales.Q1 Orders"), "Sales.\"Q1 Orders\"");
+        assert_eq!(quote_qualified("\"a.b\".c"), "\"a.b\".c");
+    }
+
+    #[test]
+    fn test_fold() {
+        assert_eq!(fold("Duyet"), "duyet");
+        assert_eq!(fold("DONNÉES"), "donnÉes");
+        assert_eq!(fold("\"Duyet\""), "Duyet");
+        assert_eq!(fold("\"say \"\"hi\"\"\""), "say \"hi\"");
+        assert_eq!(fold("Data Team"), "Data Team");
+        assert_eq!(fold("User"), "user");
+    }
+
+    #[test]
+    fn test_quote_folded() {
+        assert_eq!(quote_folded("duyet"), "duyet");
+        assert_eq!(quote_folded("Duyet"), "\"Duyet\"");
+        assert_eq!(quote_folded("data team"), "\"data team\"");
+        assert_eq!(quote_folded("user"), "\"user\"");
+        assert_eq!(quote_folded("données"), "données");
+    }
+
+    /// The rendered names refer to the same object as the config name
+    #[test]
+    fn test_round_trip() {
+        for name in [
+            "Duyet",
+            "\"Duyet\"",
+            "data team",
+            "User",
+            "données",
+            "\"\"\"",
+        ] {
+            assert_eq!(fold(&quote(name)), fold(name));
+            assert_eq!(fold(&quote_folded(&fold(name))), fold(name));
+        }
+    }
+}
@@ -1,12 +1,40 @@
+pub mod audit;
 pub mod config_base;
 pub mod connection;
+pub mod connections;
+pub mod deprecation;
+pub mod edit;
+pub mod effective;
+pub mod group;
+pub mod ident;
+pub mod notifications;
+pub mod overlay;
+pub mod redshift_role;
 pub mod role;
 mod role_database;
+pub mod role_extends;
+mod role_function;
 mod role_schema;
+mod role_sequence;
 mod role_table;
+pub mod role_template;
+mod role_warehouse;
+pub mod scope;
 pub mod user;
+pub mod user_attributes;
+pub mod user_mapping;
+pub mod user_settings;
+pub mod vars;
 
-pub use config_base::Config;
-pub use connection::{Connection, ConnectionType};
-pub use role::{Role, RoleLevelType};
-pub use user::User;
+pub use audit::Audit;
+pub use config_base::{Config, LoadOptions};
+pub use connection::{Connection, ConnectionType, Dialect, SslMode};
+pub use group::Group;
+pub use notifications::Notifications;
+pub use redshift_role::RedshiftRole;
+pub use role::{Grantee, Role, RoleLevelType};
+pub use scope::Scope;
+pub use user::{PasswordPolicy, User};
+pub use user_attributes::{SyslogAccess, UserAttributes};
+pub use user_mapping::UserMapping;
+pub use user_settings::UserSettings;
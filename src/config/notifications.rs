@@ -0,0 +1,51 @@
+use anyhow::{anyhow, Result};
+use serde::{Deserialize, Serialize};
+
+/// Notifications of `apply`, sent once it is done, see [crate::slack].
+///
+/// For example:
+///
+/// ```yaml
+/// notifications:
+///   slack:
+///     # an incoming webhook, the environment variables are expanded when
+///     # sending so that it is not printed with the config
+///     webhook_url: ${SLACK_WEBHOOK_URL}
+///     # optional, the channel of the webhook if not set
+///     channel: "#data-access"
+///     # optional, only notify the applies which changed something or failed
+///     only_changes: true
+/// ```
+///
+/// Dry runs are not notified.
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq, Eq, Default)]
+pub struct Notifications {
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub slack: Option<Slack>,
+}
+
+/// A Slack incoming webhook
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq, Eq)]
+pub struct Slack {
+    pub webhook_url: String,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub channel: Option<String>,
+    #[serde(default, skip_serializing_if = "std::ops::Not::not")]
+    pub only_changes: bool,
+}
+
+impl Notifications {
+    pub fn is_empty(&self) -> bool {
+        self.slack.is_none()
+    }
+
+    pub fn validate(&self) -> Result<()> {
+        if let Some(slack) = &self.slack {
+            if slack.webhook_url.trim().is_empty() {
+                return Err(anyhow!("notifications.slack: webhook_url is empty"));
+            }
+        }
+
+        Ok(())
+    }
+}
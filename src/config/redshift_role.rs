@@ -0,0 +1,236 @@
+use super::ident;
+use anyhow::{anyhow, Result};
+use serde::{Deserialize, Serialize};
+
+/// The system privileges of Redshift which can be granted to a role
+pub const SYSTEM_PRIVILEGES: &[&str] = &[
+    "CREATE USER",
+    "DROP USER",
+    "ALTER USER",
+    "CREATE SCHEMA",
+    "DROP SCHEMA",
+    "ALTER DEFAULT PRIVILEGES",
+    "ACCESS CATALOG",
+    "ACCESS SYSTEM TABLE",
+    "CREATE TABLE",
+    "DROP TABLE",
+    "ALTER TABLE",
+    "CREATE OR REPLACE FUNCTION",
+    "CREATE OR REPLACE EXTERNAL FUNCTION",
+    "DROP FUNCTION",
+    "CREATE OR REPLACE PROCEDURE",
+    "DROP PROCEDURE",
+    "CREATE OR REPLACE VIEW",
+    "DROP VIEW",
+    "CREATE MODEL",
+    "DROP MODEL",
+    "CREATE DATASHARE",
+    "ALTER DATASHARE",
+    "DROP DATASHARE",
+    "CREATE LIBRARY",
+    "DROP LIBRARY",
+    "CREATE ROLE",
+    "DROP ROLE",
+    "TRUNCATE TABLE",
+    "VACUUM",
+    "ANALYZE",
+    "CANCEL",
+    "IGNORE RLS",
+    "EXPLAIN RLS",
+    "EXPLAIN MASKING",
+];
+
+/// A native role of Redshift (RBAC), with its system privileges and the
+/// users it is granted to, e.g. `GRANT ROLE data_admin TO duyet`. Unlike the
+/// `roles` of the config, which are bundles of object privileges granted to
+/// each user, it is a role of the cluster.
+///
+/// For example:
+///
+/// ```yaml
+/// redshift_roles:
+///   - name: data_admin
+///     privileges:
+///       - CREATE USER
+///       - ALTER USER
+///     members:
+///       - duyet
+/// ```
+///
+/// The system privileges and the membership converge to the config: the
+/// privileges and members not listed are revoked from the role.
+#[derive(Debug, Serialize, Deserialize, Clone, PartialEq, Eq)]
+pub struct RedshiftRole {
+    pub name: String,
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub privileges: Vec<String>,
+    #[serde(default)]
+    pub members: Vec<String>,
+}
+
+impl RedshiftRole {
+    pub fn to_sql_create(&self) -> String {
+        format!("CREATE ROLE {};", ident::quote(&self.name))
+    }
+
+    pub fn to_sql_grant_privilege(&self, privilege: &str) -> String {
+        format!("GRANT {} TO ROLE {};", privilege, ident::quote(&self.name))
+    }
+
+    pub fn to_sql_revoke_privilege(&self, privilege: &str) -> String {
+        format!(
+            "REVOKE {} FROM ROLE {};",
+            privilege,
+            ident::quote(&self.name)
+        )
+    }
+
+    /// Grant the role to a member, named as in the catalog
+    pub fn to_sql_add(&self, member: &str) -> String {
+        format!(
+            "GRANT ROLE {} TO {};",
+            ident::quote(&self.name),
+            ident::quote_folded(member)
+        )
+    }
+
+    /// Revoke the role from a member, named as in the catalog
+    pub fn to_sql_remove(&self, member: &str) -> String {
+        format!(
+            "REVOKE ROLE {} FROM {};",
+            ident::quote(&self.name),
+            ident::quote_folded(member)
+        )
+    }
+
+    /// The system privileges in upper case, with single spaces, as in
+    /// `svv_system_privileges`
+    pub fn normalized_privileges(&self) -> Vec<String> {
+        self.privileges.iter().map(|p| normalize(p)).collect()
+    }
+
+    /// The members named as in the catalog
+    pub fn folded_members(&self) -> Vec<String> {
+        self.members.iter().map(|m| ident::fold(m)).collect()
+    }
+
+    pub fn validate(&self) -> Result<()> {
+        if self.name.is_empty() {
+            return Err(anyhow!("redshift role name is empty"));
+        }
+
+        let privileges = self.normalized_privileges();
+        for (i, privilege) in privileges.iter().enumerate() {
+            if !SYSTEM_PRIVILEGES.contains(&privilege.as_str()) {
+                return Err(anyhow!(
+                    "redshift role {}: unknown system privilege {}, expected one of: {}",
+                    self.name,
+                    self.privileges[i],
+                    SYSTEM_PRIVILEGES.join(", ")
+                ));
+            }
+            if privileges[..i].contains(privilege) {
+                return Err(anyhow!(
+                    "redshift role {}: duplicated privilege {}",
+                    self.name,
+                    privilege
+                ));
+            }
+        }
+
+        let members = self.folded_members();
+        for (i, member) in members.iter().enumerate() {
+            if member.is_empty() {
+                return Err(anyhow!("redshift role {}: member name is empty", self.name));
+            }
+            if members[..i].contains(member) {
+                return Err(anyhow!(
+                    "redshift role {}: duplicated member {}",
+                    self.name,
+                    member
+                ));
+            }
+        }
+
+        Ok(())
+    }
+}
+
+/// A system privilege in upper case, with single spaces
+pub fn normalize(privilege: &str) -> String {
+    privilege
+        .split_whitespace()
+        .collect::<Vec<_>>()
+        .join(" ")
+        .to_uppercase()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn role() -> RedshiftRole {
+        RedshiftRole {
+            name: "data_admin".to_string(),
+            privileges: vec!["create user".to_string(), "ALTER  USER".to_string()],
+            members: vec!["duyet".to_string(), "ident team".to_string()],
+        }
+    }
+
+    #[test]
+    fn test_redshift_role_to_sql() {
+        let role = role();
+
+        assert_eq!(role.to_sql_create(), "CREATE ROLE data_admin;");
+        assert_eq!(
+            role.to_sql_grant_privilege("CREATE USER"),
+            "GRANT CREATE USER TO ROLE data_admin;"
+        );
+        assert_eq!(
+            role.to_sql_revoke_privilege("DROP USER"),
+            "REVOKE DROP USER FROM ROLE data_admin;"
+        );
+        assert_eq!(
+            role.to_sql_add("ident team"),
+            r#"GRANT ROLE data_admin TO "ident team";"#
+        );
+        assert_eq!(
+            role.to_sql_remove("duyet"),
+            "REVOKE ROLE data_admin FROM duyet;"
+        );
+        assert_eq!(
+            role.normalized_privileges(),
+            vec!["CREATE USER", "ALTER USER"]
+        );
+    }
+
+    #[test]
+    fn test_redshift_role_validate() {
+        assert!(role().validate().is_ok());
+
+        let mut invalid = role();
+        invalid.privileges.push("Create User".to_string());
+        assert_eq!(
+            invalid.validate().unwrap_err().to_string(),
+            "redshift role data_admin: duplicated privilege CREATE USER"
+        );
+
+        let mut invalid = role();
+        invalid.privileges.push("SELECT".to_string());
+        assert!(invalid
+            .validate()
+            .unwrap_err()
+            .to_string()
+            .starts_with("redshift role data_admin: unknown system privilege SELECT"));
+
+        let mut invalid = role();
+        invalid.members.push("Duyet".to_string());
+        assert_eq!(
+            invalid.validate().unwrap_err().to_string(),
+            "redshift role data_admin: duplicated member duyet"
+        );
+
+        invalid.name = "".to_string();
+        assert!(invalid.validate().is_err());
+    }
+}
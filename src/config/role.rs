@@ -1,10 +1,71 @@
-use anyhow::Result;
+use super::ident;
+use anyhow::{anyhow, Result};
 use serde::{Deserialize, Serialize};
+use serde_yaml::{Mapping, Value};
 use std::fmt;
 
 pub use super::role_database::RoleDatabaseLevel;
+pub use super::role_function::RoleFunctionLevel;
 pub use super::role_schema::RoleSchemaLevel;
+pub use super::role_sequence::RoleSequenceLevel;
 pub use super::role_table::RoleTableLevel;
+pub use super::role_warehouse::RoleWarehouseLevel;
+
+/// The privileges of `revoked` which are also in `granted`, `ALL` (or no
+/// grant) being every privilege
+fn shared_privileges(revoked: &[String], granted: &[String]) -> Vec<String> {
+    let all = |grants: &[String]| grants.is_empty() || grants.iter().any(|g| g == "ALL");
+    match (all(revoked), all(granted)) {
+        (true, true) => vec!["ALL".to_string()],
+        (true, false) => granted.to_vec(),
+        (false, true) => revoked.to_vec(),
+        (false, false) => revoked
+            .iter()
+            .filter(|p| granted.iter().any(|g| g.eq_ignore_ascii_case(p)))
+            .cloned()
+            .collect(),
+    }
+}
+
+/// The object of both `revoked` and `granted`, e.g. `TABLE public.orders`
+/// of `ALL TABLES IN SCHEMA public`, but the `excluded` objects of the
+/// granted role
+fn overlap(revoked: &str, granted: &str, excluded: &[&str]) -> Option<String> {
+    if revoked == granted {
+        return Some(revoked.to_string());
+    }
+
+    match (in_schemas(revoked), in_schemas(granted)) {
+        (Some((kind, schemas)), Some((other, others))) if kind == other => schemas
+            .iter()
+            .find(|s| others.contains(s))
+            .map(|s| format!("ALL {}S IN SCHEMA {}", kind, s)),
+        (Some((kind, schemas)), None) => {
+            in_schema(granted, kind, &schemas).then(|| granted.to_string())
+        }
+        (None, Some((kind, schemas))) => (in_schema(revoked, kind, &schemas)
+            && !excluded.contains(&revoked))
+        .then(|| revoked.to_string()),
+        _ => None,
+    }
+}
+
+/// The kind and the schemas of `ALL TABLES IN SCHEMA public, sales`, e.g.
+/// `TABLE` and the quoted schemas
+fn in_schemas(object: &str) -> Option<(&str, Vec<&str>)> {
+    let (kinds, schemas) = object.strip_prefix("ALL ")?.split_once(" IN SCHEMA ")?;
+
+    Some((kinds.strip_suffix('S')?, schemas.split(", ").collect()))
+}
+
+/// Whether the object, e.g. `TABLE public.orders`, is of the kind and in one
+/// of the schemas
+fn in_schema(object: &str, kind: &str, schemas: &[&str]) -> bool {
+    object
+        .strip_prefix(kind)
+        .and_then(|name| name.strip_prefix(' '))
+        .is_some_and(|name| schemas.contains(&ident::split_qualified(name)[0]))
+}
 
 /// Level type for role.
 #[derive(Debug, Serialize, Deserialize, Clone, PartialEq)]
@@ -13,6 +74,9 @@ pub enum RoleLevelType {
     Database,
     Schema,
     Table,
+    Function,
+    Sequence,
+    Warehouse,
 }
 
 impl fmt::Display for RoleLevelType {
@@ -21,10 +85,76 @@ impl fmt::Display for RoleLevelType {
             RoleLevelType::Database => write!(f, "database"),
             RoleLevelType::Schema => write!(f, "schema"),
             RoleLevelType::Table => write!(f, "table"),
+            RoleLevelType::Function => write!(f, "function"),
+            RoleLevelType::Sequence => write!(f, "sequence"),
+            RoleLevelType::Warehouse => write!(f, "warehouse"),
+        }
+    }
+}
+
+impl std::str::FromStr for RoleLevelType {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> Result<Self> {
+        match s {
+            "database" => Ok(RoleLevelType::Database),
+            "schema" => Ok(RoleLevelType::Schema),
+            "table" => Ok(RoleLevelType::Table),
+            "function" => Ok(RoleLevelType::Function),
+            "sequence" => Ok(RoleLevelType::Sequence),
+            "warehouse" => Ok(RoleLevelType::Warehouse),
+            _ => Err(anyhow::anyhow!(
+                "invalid role type {}, expected: database, schema, table, function, sequence or warehouse",
+                s
+            )),
         }
     }
 }
 
+/// Who a role is granted to: a user, a group of users (`GROUP name`) or
+/// every role of the cluster (`PUBLIC`), see [User::is_public]
+///
+/// [User::is_public]: super::User::is_public
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Grantee<'a> {
+    User(&'a str),
+    Group(&'a str),
+    Public,
+}
+
+impl<'a> Grantee<'a> {
+    pub fn name(&self) -> &'a str {
+        match self {
+            Grantee::User(name) | Grantee::Group(name) => name,
+            Grantee::Public => "PUBLIC",
+        }
+    }
+
+    /// The grantee of a GRANT or REVOKE statement
+    pub fn to_sql(&self) -> String {
+        match self {
+            Grantee::User(name) => ident::quote(name),
+            Grantee::Group(name) => format!("GROUP {}", ident::quote(name)),
+            Grantee::Public => "PUBLIC".to_string(),
+        }
+    }
+}
+
+impl fmt::Display for Grantee<'_> {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Grantee::User(name) => write!(f, "{}", name),
+            Grantee::Group(name) => write!(f, "group {}", name),
+            Grantee::Public => write!(f, "PUBLIC"),
+        }
+    }
+}
+
+/// Privileges which do not let a role change anything, the only ones a
+/// role can grant to PUBLIC without `allow_public_write`
+pub const READ_PRIVILEGES: &[&str] =
+    &["SELECT", "USAGE", "CONNECT", "TEMP", "TEMPORARY", "EXECUTE"];
+
 /// Configuration for a role.
 #[derive(Debug, Serialize, Deserialize, Clone, PartialEq, Eq)]
 #[serde(tag = "type")]
@@ -35,6 +165,12 @@ pub enum Role {
     Schema(RoleSchemaLevel),
     #[serde(rename = "table")]
     Table(RoleTableLevel),
+    #[serde(rename = "function")]
+    Function(RoleFunctionLevel),
+    #[serde(rename = "sequence")]
+    Sequence(RoleSequenceLevel),
+    #[serde(rename = "warehouse")]
+    Warehouse(RoleWarehouseLevel),
 }
 
 pub trait RoleValidate {
@@ -43,18 +179,67 @@ pub trait RoleValidate {
 
 impl Role {
     pub fn to_sql(&self, user: &str) -> String {
+        self.to_sql_for(Grantee::User(user))
+    }
+
+    /// The role SQL for a user or a group
+    pub fn to_sql_for(&self, grantee: Grantee) -> String {
         match self {
-            Role::Database(role) => role.to_sql(user),
-            Role::Schema(role) => role.to_sql(user),
-            Role::Table(role) => role.to_sql(user),
+            Role::Database(role) => role.to_sql_for(grantee),
+            Role::Schema(role) => role.to_sql_for(grantee),
+            Role::Table(role) => role.to_sql_for(grantee),
+            Role::Function(role) => role.to_sql_for(grantee),
+            Role::Sequence(role) => role.to_sql_for(grantee),
+            Role::Warehouse(role) => role.to_sql_for(grantee),
+        }
+    }
+
+    /// The SQL revoking every privilege of the role from a grantee, granted
+    /// or revoked by the role, e.g. for the `revokes` of a user:
+    /// `REVOKE SELECT ON ALL TABLES IN SCHEMA public FROM duyet;`
+    pub fn to_revoke_sql_for(&self, grantee: Grantee) -> String {
+        let grants = self.get_grants().join(", ");
+        let mut objects: Vec<String> = vec![];
+        for (_, object) in self.objects() {
+            if !objects.contains(&object) {
+                objects.push(object);
+            }
         }
+
+        objects
+            .iter()
+            .map(|object| format!("REVOKE {} ON {} FROM {};", grants, object, grantee.to_sql()))
+            .collect::<Vec<_>>()
+            .join(" ")
+    }
+
+    /// The role as YAML with the name first, as in the example configs
+    pub fn to_yaml(&self) -> Result<Value> {
+        let name = Value::from("name");
+        let mut mapping = Mapping::new();
+
+        if let Value::Mapping(fields) = serde_yaml::to_value(self)? {
+            if let Some(value) = fields.get(&name) {
+                mapping.insert(name.clone(), value.clone());
+            }
+            mapping.extend(fields.into_iter().filter(|(k, _)| *k != name));
+        }
+
+        Ok(Value::Mapping(mapping))
     }
 
     pub fn validate(&self) -> Result<()> {
+        if self.database().is_some_and(|d| d.trim().is_empty()) {
+            return Err(anyhow!("role.database is empty"));
+        }
+
         match self {
             Role::Database(role) => role.validate(),
             Role::Schema(role) => role.validate(),
             Role::Table(role) => role.validate(),
+            Role::Function(role) => role.validate(),
+            Role::Sequence(role) => role.validate(),
+            Role::Warehouse(role) => role.validate(),
         }
     }
 
@@ -63,6 +248,9 @@ impl Role {
             Role::Database(role) => role.name.clone(),
             Role::Schema(role) => role.name.clone(),
             Role::Table(role) => role.name.clone(),
+            Role::Function(role) => role.name.clone(),
+            Role::Sequence(role) => role.name.clone(),
+            Role::Warehouse(role) => role.name.clone(),
         }
     }
 
@@ -74,6 +262,9 @@ impl Role {
             Role::Database(role) => role.name == name,
             Role::Schema(role) => role.name == name,
             Role::Table(role) => role.name == name,
+            Role::Function(role) => role.name == name,
+            Role::Sequence(role) => role.name == name,
+            Role::Warehouse(role) => role.name == name,
         }
     }
 
@@ -82,6 +273,9 @@ impl Role {
             Role::Database(_role) => RoleLevelType::Database,
             Role::Schema(_role) => RoleLevelType::Schema,
             Role::Table(_role) => RoleLevelType::Table,
+            Role::Function(_role) => RoleLevelType::Function,
+            Role::Sequence(_role) => RoleLevelType::Sequence,
+            Role::Warehouse(_role) => RoleLevelType::Warehouse,
         }
     }
 
@@ -90,6 +284,107 @@ impl Role {
             Role::Database(role) => role.grants.clone(),
             Role::Schema(role) => role.grants.clone(),
             Role::Table(role) => role.grants.clone(),
+            Role::Function(role) => role.grants.clone(),
+            Role::Sequence(role) => role.grants.clone(),
+            Role::Warehouse(role) => role.grants.clone(),
+        }
+    }
+
+    /// Schemas referenced by the role, including the ones of `schema.table`,
+    /// `schema.function` and `schema.sequence`
+    pub fn referenced_schemas(&self) -> Vec<String> {
+        let mut schemas = self.get_schemas();
+
+        match self {
+            Role::Table(role) => {
+                for table in &role.tables {
+                    let table = table.trim_start_matches(['+', '-']);
+                    if let [schema, _] = ident::split_qualified(table)[..] {
+                        schemas.push(schema.to_string());
+                    }
+                }
+            }
+            Role::Sequence(role) => {
+                for sequence in &role.sequences {
+                    let sequence = sequence.trim_start_matches(['+', '-']);
+                    if let [schema, _] = ident::split_qualified(sequence)[..] {
+                        schemas.push(schema.to_string());
+                    }
+                }
+            }
+            Role::Function(role) => schemas.extend(role.qualified_schemas()),
+            _ => {}
+        }
+
+        schemas
+    }
+
+    /// Number of privileges the role revokes from each user, only table,
+    /// function and sequence roles revoke (`-table`, `-function`, `-sequence`)
+    pub fn revoke_count(&self) -> usize {
+        match self {
+            Role::Database(_) => 0,
+            Role::Schema(_) => 0,
+            Role::Table(role) => role.revoke_count(),
+            Role::Function(role) => role.revoke_count(),
+            Role::Sequence(role) => role.revoke_count(),
+            Role::Warehouse(_) => 0,
+        }
+    }
+
+    /// The privileges and the object which the role, revoked from a user,
+    /// revokes while the `granted` role of the user grants them: the user
+    /// would be granted then revoked them at every apply. None if the roles
+    /// do not overlap.
+    pub fn revoke_overlap(&self, granted: &Role) -> Option<(Vec<String>, String)> {
+        if std::mem::discriminant(self) != std::mem::discriminant(granted)
+            || self.database() != granted.database()
+        {
+            return None;
+        }
+
+        let privileges = shared_privileges(&self.get_grants(), &granted.get_grants());
+        if privileges.is_empty() {
+            return None;
+        }
+
+        let objects = granted.objects();
+        let excluded = objects
+            .iter()
+            .filter(|(revoke, _)| *revoke)
+            .map(|(_, object)| object.as_str())
+            .collect::<Vec<_>>();
+        self.objects().iter().find_map(|(_, revoked)| {
+            objects
+                .iter()
+                .filter(|(revoke, _)| !revoke)
+                .find_map(|(_, object)| overlap(revoked, object, &excluded))
+                .map(|object| (privileges.clone(), object))
+        })
+    }
+
+    /// Whether the role only revokes privileges, e.g. a table role of `-ALL`
+    pub fn only_revokes(&self) -> bool {
+        match self {
+            Role::Database(_) => false,
+            Role::Schema(_) => false,
+            Role::Table(role) => role.tables.iter().all(|t| t.starts_with('-')),
+            Role::Function(role) => role.objects().iter().all(|(revoke, _)| *revoke),
+            Role::Sequence(role) => role.objects().iter().all(|(revoke, _)| *revoke),
+            Role::Warehouse(_) => false,
+        }
+    }
+
+    /// The objects of the GRANT and REVOKE statements of the role, in the
+    /// order they are executed, each with whether it is revoked
+    pub fn objects(&self) -> Vec<(bool, String)> {
+        match self {
+            Role::Database(role) => role.objects(),
+            Role::Schema(role) => role.objects(),
+            Role::Table(role) => role.objects(),
+            Role::Function(role) => role.objects(),
+            Role::Sequence(role) => role.objects(),
+            Role::Warehouse(role) => role.objects(),
         }
     }
 
@@ -98,6 +393,22 @@ impl Role {
             Role::Database(role) => role.databases.clone(),
             Role::Schema(_) => vec![],
             Role::Table(_) => vec![],
+            Role::Function(_) => vec![],
+            Role::Sequence(_) => vec![],
+            Role::Warehouse(_) => vec![],
+        }
+    }
+
+    /// Database of the schemas of the role, None for the database of the
+    /// connection. The databases of a database role are its `databases`.
+    pub fn database(&self) -> Option<&str> {
+        match self {
+            Role::Database(_) => None,
+            Role::Schema(role) => role.database.as_deref(),
+            Role::Table(role) => role.database.as_deref(),
+            Role::Function(role) => role.database.as_deref(),
+            Role::Sequence(role) => role.database.as_deref(),
+            Role::Warehouse(_) => None,
         }
     }
 
@@ -106,6 +417,21 @@ impl Role {
             Role::Database(_) => vec![],
             Role::Schema(role) => role.schemas.clone(),
             Role::Table(role) => role.schemas.clone(),
+            Role::Function(role) => role.schemas.clone(),
+            Role::Sequence(role) => role.schemas.clone(),
+            Role::Warehouse(_) => vec![],
+        }
+    }
+
+    /// The schemas of a schema, table, function or sequence role
+    pub fn schemas_mut(&mut self) -> Option<&mut Vec<String>> {
+        match self {
+            Role::Database(_) => None,
+            Role::Schema(role) => Some(&mut role.schemas),
+            Role::Table(role) => Some(&mut role.schemas),
+            Role::Function(role) => Some(&mut role.schemas),
+            Role::Sequence(role) => Some(&mut role.schemas),
+            Role::Warehouse(_) => None,
         }
     }
 
@@ -114,6 +440,9 @@ impl Role {
             Role::Database(_) => vec![],
             Role::Schema(_) => vec![],
             Role::Table(role) => role.tables.clone(),
+            Role::Function(_) => vec![],
+            Role::Sequence(_) => vec![],
+            Role::Warehouse(_) => vec![],
         }
     }
 }
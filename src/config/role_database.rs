@@ -1,4 +1,5 @@
-use super::role::RoleValidate;
+use super::ident;
+use super::role::{Grantee, RoleValidate};
 use anyhow::{anyhow, Result};
 use serde::{Deserialize, Serialize};
 use std::collections::HashSet;
@@ -17,6 +18,17 @@ use std::collections::HashSet;
 ///     - db1
 ///     - db2
 /// ```
+///
+/// `ALL` is every database of the cluster but the templates, listed when the
+/// config is applied, without the ones excluded with `-`, see
+/// [crate::patterns]:
+///
+/// ```yaml
+/// - name: role_temp
+///   type: database
+///   grants: [TEMP]
+///   databases: [ALL, -scratch]
+/// ```
 #[derive(Debug, Serialize, Deserialize, Clone, PartialEq, Eq)]
 pub struct RoleDatabaseLevel {
     pub name: String,
@@ -25,6 +37,11 @@ pub struct RoleDatabaseLevel {
 }
 
 impl RoleDatabaseLevel {
+    /// Generate the role SQL for a user, see [Self::to_sql_for]
+    pub fn to_sql(&self, user: &str) -> String {
+        self.to_sql_for(Grantee::User(user))
+    }
+
     /// Generate role database to SQL.
     ///
     /// ```sql
@@ -32,7 +49,7 @@ impl RoleDatabaseLevel {
     /// ON DATABASE db_name [, ...]
     /// TO { username [ WITH GRANT OPTION ] | GROUP group_name | PUBLIC } [, ...]
     /// ```
-    pub fn to_sql(&self, user: &str) -> String {
+    pub fn to_sql_for(&self, grantee: Grantee) -> String {
         // grant all if no grants specified or contains "ALL"
         let grants = if self.grants.is_empty() || self.grants.contains(&"ALL".to_string()) {
             "ALL PRIVILEGES".to_string()
@@ -44,12 +61,21 @@ impl RoleDatabaseLevel {
         let sql = format!(
             "GRANT {} ON DATABASE {} TO {};",
             grants,
-            self.databases.join(", "),
-            user
+            ident::quote_list(&self.databases),
+            grantee.to_sql()
         );
 
         sql
     }
+
+    /// The objects of the GRANT statement of the role, each with whether it
+    /// is revoked, e.g. `(false, "DATABASE postgres")`
+    pub fn objects(&self) -> Vec<(bool, String)> {
+        self.databases
+            .iter()
+            .map(|d| (false, format!("DATABASE {}", ident::quote(d))))
+            .collect()
+    }
 }
 
 impl RoleValidate for RoleDatabaseLevel {
@@ -62,8 +88,18 @@ impl RoleValidate for RoleDatabaseLevel {
             return Err(anyhow!("role databases is empty"));
         }
 
-        // Check valid grants: CREATE, TEMP, TEMPORARY, ALL
-        let valid_grants = vec!["CREATE", "TEMP", "TEMPORARY", "ALL"];
+        if !self.databases.iter().any(|d| d == "ALL") {
+            if let Some(database) = self.databases.iter().find(|d| d.starts_with('-')) {
+                return Err(anyhow!(
+                    "role databases invalid: {}, only the databases of ALL can be excluded",
+                    database
+                ));
+            }
+        }
+
+        // Check valid grants: CREATE, TEMP, TEMPORARY, ALL, and USAGE of
+        // Snowflake
+        let valid_grants = vec!["CREATE", "TEMP", "TEMPORARY", "USAGE", "ALL"];
         let mut grants = HashSet::new();
         for grant in &self.grants {
             if !valid_grants.contains(&&grant[..]) {
@@ -99,7 +135,23 @@ mod tests {
         assert!(role.validate().is_ok());
         assert_eq!(
             role.to_sql("user"),
-            "GRANT CREATE, TEMP ON DATABASE db1, db2 TO user;"
+            "GRANT CREATE, TEMP ON DATABASE db1, db2 TO \"user\";"
+        );
+
+        let role = RoleDatabaseLevel {
+            name: "role_temp".to_string(),
+            grants: vec!["TEMP".to_string()],
+            databases: vec!["ALL".to_string(), "-scratch".to_string()],
+        };
+        assert!(role.validate().is_ok());
+
+        let role = RoleDatabaseLevel {
+            databases: vec!["db1".to_string(), "-scratch".to_string()],
+            ..role
+        };
+        assert_eq!(
+            role.validate().unwrap_err().to_string(),
+            "role databases invalid: -scratch, only the databases of ALL can be excluded"
         );
     }
 }
@@ -0,0 +1,250 @@
+//! Role composition: a role with `extends` has the objects and privileges
+//! of its parent roles, merged with its own when the config is loaded.
+//!
+//! ```yaml
+//! roles:
+//!   - name: base_readonly
+//!     type: table
+//!     grants: [SELECT]
+//!     schemas: [marts, staging, finance]
+//!     tables: [ALL]
+//!   - name: role_etl
+//!     extends: [base_readonly]
+//!     grants: [INSERT, UPDATE]
+//!   - name: role_usage
+//!     type: schema
+//!     extends: [base_readonly]
+//!     grants: [USAGE]
+//! ```
+//!
+//! `role_etl` is a table role with `SELECT`, `INSERT` and `UPDATE` on the
+//! tables of the three schemas, `role_usage` a schema role with `USAGE` on
+//! them.
+//!
+//! The lists of the parents are merged in order, then the list of the role,
+//! without the duplicates: a role cannot remove what it extends. A role
+//! without a `type` or a `database` has the one of its first parent. A
+//! parent of another type only gives the lists of objects the role has,
+//! e.g. the `schemas` of a table role to a schema role, not its `grants`.
+//! A parent can extend other roles, and stays a role of the config.
+//!
+//! The roles are composed after the role templates are stamped, so that a
+//! role can extend a stamped one, see [super::role_template].
+
+use anyhow::{anyhow, Result};
+use serde_yaml::Value;
+use std::collections::BTreeMap;
+
+/// The key of the parents of a role
+const EXTENDS: &str = "extends";
+
+/// The lists of objects of each type of role, merged from the parents of
+/// the same type or not
+fn object_fields(role_type: &str) -> &'static [&'static str] {
+    match role_type {
+        "database" => &["databases"],
+        "schema" => &["schemas"],
+        "table" => &["schemas", "tables"],
+        "function" => &["schemas", "functions", "procedures"],
+        "sequence" => &["schemas", "sequences"],
+        "warehouse" => &["warehouses"],
+        _ => &[],
+    }
+}
+
+/// Replace the roles with `extends` by their composed roles, in place.
+/// Whether the config has such roles.
+pub fn expand(config: &mut Value) -> Result<bool> {
+    let roles = match config.get_mut("roles") {
+        Some(Value::Sequence(roles)) if roles.iter().any(|r| r.get(EXTENDS).is_some()) => roles,
+        _ => return Ok(false),
+    };
+
+    let by_name = roles
+        .iter()
+        .filter_map(|role| Some((role.get("name")?.as_str()?.to_string(), role.clone())))
+        .collect::<BTreeMap<_, _>>();
+    for role in roles.iter_mut() {
+        if role.get(EXTENDS).is_some() {
+            *role = compose(role, &by_name, &mut vec![])?;
+        }
+    }
+
+    Ok(true)
+}
+
+/// The role merged with its parents, `path` has the roles being composed to
+/// detect the cycles
+fn compose(role: &Value, roles: &BTreeMap<String, Value>, path: &mut Vec<String>) -> Result<Value> {
+    let Value::Mapping(fields) = role else {
+        return Ok(role.clone());
+    };
+    let name = fields
+        .get("name")
+        .and_then(Value::as_str)
+        .unwrap_or_default()
+        .to_string();
+    if path.contains(&name) {
+        path.push(name.clone());
+        return Err(anyhow!(
+            "role {}: extends itself through {}",
+            path[0],
+            path.join(" -> ")
+        ));
+    }
+
+    let parents = match fields.get(EXTENDS) {
+        None => return Ok(role.clone()),
+        Some(Value::Sequence(parents)) => parents
+            .iter()
+            .map(|p| p.as_str().map(str::to_string))
+            .collect::<Option<Vec<_>>>()
+            .ok_or_else(|| anyhow!("role {}: extends must be a list of role names", name))?,
+        Some(_) => {
+            return Err(anyhow!(
+                "role {}: extends must be a list of role names",
+                name
+            ))
+        }
+    };
+
+    path.push(name.clone());
+    let parents = parents
+        .iter()
+        .map(|parent| match roles.get(parent) {
+            Some(parent) => compose(parent, roles, path),
+            None => Err(anyhow!("role {}: extends unknown role {}", name, parent)),
+        })
+        .collect::<Result<Vec<_>>>()?;
+    path.pop();
+
+    let mut composed = fields.clone();
+    composed.remove(EXTENDS);
+    for key in ["type", "database"] {
+        if !composed.contains_key(key) {
+            if let Some(value) = parents.first().and_then(|p| p.get(key)) {
+                composed.insert(key.into(), value.clone());
+            }
+        }
+    }
+    let role_type = composed
+        .get("type")
+        .and_then(Value::as_str)
+        .unwrap_or_default()
+        .to_string();
+
+    for field in object_fields(&role_type).iter().chain(&["grants"]) {
+        let mut items = vec![];
+        for parent in &parents {
+            let same_type = parent.get("type").and_then(Value::as_str) == Some(&role_type);
+            if *field == "grants" && !same_type {
+                continue;
+            }
+            if let Some(Value::Sequence(parent_items)) = parent.get(*field) {
+                items.extend(parent_items.iter().cloned());
+            }
+        }
+        match composed.get(*field) {
+            Some(Value::Sequence(own)) => items.extend(own.iter().cloned()),
+            // invalid, as the role would be without extends
+            Some(_) => continue,
+            None if items.is_empty() => continue,
+            None => {}
+        }
+
+        let mut merged: Vec<Value> = vec![];
+        for item in items {
+            if !merged.contains(&item) {
+                merged.push(item);
+            }
+        }
+        composed.insert((*field).into(), Value::Sequence(merged));
+    }
+
+    Ok(Value::Mapping(composed))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use indoc::indoc;
+
+    fn expanded(yaml: &str) -> Result<Value> {
+        let mut config: Value = serde_yaml::from_str(yaml).unwrap();
+        expand(&mut config)?;
+
+        Ok(config)
+    }
+
+    #[test]
+    fn test_expand() {
+        let config = expanded(indoc! {"
+            roles:
+              - name: base_readonly
+                type: table
+                grants: [SELECT]
+                schemas: [marts, staging]
+                tables: [ALL]
+              - name: base_finance
+                type: table
+                database: finance
+                grants: [SELECT]
+                schemas: [finance]
+                tables: [ALL]
+              - name: role_etl
+                extends: [base_readonly, base_finance]
+                grants: [INSERT, SELECT]
+                schemas: [raw]
+              - name: role_usage
+                type: schema
+                extends: [role_etl]
+                grants: [USAGE]
+        "})
+        .unwrap();
+
+        assert_eq!(
+            config["roles"],
+            serde_yaml::from_str::<Value>(indoc! {"
+                - name: base_readonly
+                  type: table
+                  grants: [SELECT]
+                  schemas: [marts, staging]
+                  tables: [ALL]
+                - name: base_finance
+                  type: table
+                  database: finance
+                  grants: [SELECT]
+                  schemas: [finance]
+                  tables: [ALL]
+                - name: role_etl
+                  grants: [SELECT, INSERT]
+                  schemas: [marts, staging, finance, raw]
+                  type: table
+                  tables: [ALL]
+                - name: role_usage
+                  type: schema
+                  grants: [USAGE]
+                  schemas: [marts, staging, finance, raw]
+            "})
+            .unwrap()
+        );
+    }
+
+    #[test]
+    fn test_expand_errors() {
+        let err = |yaml: &str| expanded(yaml).unwrap_err().to_string();
+
+        assert_eq!(
+            err("roles: [{name: a, type: schema, extends: [b]}]"),
+            "role a: extends unknown role b"
+        );
+        assert_eq!(
+            err("roles: [{name: a, extends: [b]}, {name: b, extends: [a]}]"),
+            "role a: extends itself through a -> b -> a"
+        );
+        assert_eq!(
+            err("roles: [{name: a, extends: b}, {name: b, type: schema}]"),
+            "role a: extends must be a list of role names"
+        );
+    }
+}
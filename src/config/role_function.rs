@@ -0,0 +1,394 @@
+use super::ident;
+use super::role::{Grantee, RoleValidate};
+use anyhow::{anyhow, Result};
+use serde::{Deserialize, Serialize};
+
+/// Role Function Level, `EXECUTE` on functions and procedures.
+///
+/// For example:
+///
+/// ```yaml
+/// - name: role_etl
+///   type: function
+///   grants:
+///     - EXECUTE
+///   schemas:
+///     - etl
+///   functions:
+///     - ALL
+///     - -internal_cleanup
+///   procedures:
+///     - load_orders(date)
+/// ```
+///
+/// The entries are signed as the tables of a [table role], `ALL` being every
+/// function (or procedure) of the schemas. A name without a schema is in
+/// every schema of the role, and without an argument list it must not be
+/// overloaded, e.g. `load_orders(date, text)` otherwise.
+///
+/// [table role]: super::role::RoleTableLevel
+#[derive(Debug, Serialize, Deserialize, Clone, PartialEq, Eq)]
+pub struct RoleFunctionLevel {
+    pub name: String,
+    pub grants: Vec<String>,
+    pub schemas: Vec<String>,
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub functions: Vec<String>,
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub procedures: Vec<String>,
+    /// Database of the schemas, the one of the connection if not set, see
+    /// [Role::database](super::Role::database)
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub database: Option<String>,
+}
+
+/// Kind of the routines of an entry list, the keyword of the statements
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Kind {
+    Function,
+    Procedure,
+}
+
+impl Kind {
+    fn keyword(&self) -> &'static str {
+        match self {
+            Kind::Function => "FUNCTION",
+            Kind::Procedure => "PROCEDURE",
+        }
+    }
+
+    fn all(&self) -> &'static str {
+        match self {
+            Kind::Function => "ALL FUNCTIONS",
+            Kind::Procedure => "ALL PROCEDURES",
+        }
+    }
+}
+
+impl RoleFunctionLevel {
+    /// Generate the role SQL for a user, see [Self::to_sql_for]
+    pub fn to_sql(&self, user: &str) -> String {
+        self.to_sql_for(Grantee::User(user))
+    }
+
+    /// Generate role function to sql, the functions then the procedures.
+    ///
+    /// ```sql
+    /// {GRANT | REVOKE} { EXECUTE | ALL [ PRIVILEGES ] }
+    /// ON { { FUNCTION | PROCEDURE } name [ ( [ argtype [, ...] ] ) ] [, ...]
+    ///    | ALL { FUNCTIONS | PROCEDURES } IN SCHEMA schema_name [, ...] }
+    /// TO { username | GROUP group_name | PUBLIC } [, ...]
+    /// ```
+    pub fn to_sql_for(&self, grantee: Grantee) -> String {
+        let mut sqls = self.sqls_for(Kind::Function, &self.functions, grantee);
+        sqls.extend(self.sqls_for(Kind::Procedure, &self.procedures, grantee));
+
+        sqls.join(" ")
+    }
+
+    fn sqls_for(&self, kind: Kind, entries: &[String], grantee: Grantee) -> Vec<String> {
+        let grants = self.grants_sql();
+
+        self.statements(kind, entries)
+            .into_iter()
+            .map(|(revoke, objects)| {
+                if revoke {
+                    format!(
+                        "REVOKE {} ON {} FROM {};",
+                        grants,
+                        objects.join(", "),
+                        grantee.to_sql()
+                    )
+                } else {
+                    format!(
+                        "GRANT {} ON {} TO {};",
+                        grants,
+                        objects.join(", "),
+                        grantee.to_sql()
+                    )
+                }
+            })
+            .collect()
+    }
+
+    /// The objects of the GRANT and REVOKE statements of the role, each with
+    /// whether it is revoked, e.g. `(false, "FUNCTION etl.load(date)")`
+    pub fn objects(&self) -> Vec<(bool, String)> {
+        [
+            (Kind::Function, &self.functions),
+            (Kind::Procedure, &self.procedures),
+        ]
+        .into_iter()
+        .flat_map(|(kind, entries)| self.statements(kind, entries))
+        .flat_map(|(revoke, objects)| objects.into_iter().map(move |o| (revoke, o)))
+        .collect()
+    }
+
+    /// The objects of each statement for the entries: `ALL` or the `+`
+    /// entries are granted, then the `-` entries are revoked
+    fn statements(&self, kind: Kind, entries: &[String]) -> Vec<(bool, Vec<String>)> {
+        let entries = entries.iter().map(|e| signed(e)).collect::<Vec<_>>();
+        let mut statements = vec![];
+
+        // As for the tables, `ALL` ignores the other granted entries
+        match entries.iter().find(|(_, name)| *name == "ALL") {
+            Some(&(revoke, _)) => statements.push((
+                revoke,
+                vec![format!(
+                    "{} IN SCHEMA {}",
+                    kind.all(),
+                    ident::quote_list(&self.schemas)
+                )],
+            )),
+            None => statements.push((
+                false,
+                entries
+                    .iter()
+                    .filter(|(revoke, _)| !revoke)
+                    .flat_map(|(_, name)| self.qualified_names(kind, name))
+                    .collect(),
+            )),
+        }
+        statements.push((
+            true,
+            entries
+                .iter()
+                .filter(|(revoke, name)| *revoke && *name != "ALL")
+                .flat_map(|(_, name)| self.qualified_names(kind, name))
+                .collect(),
+        ));
+
+        statements.retain(|(_, objects)| !objects.is_empty());
+        statements
+    }
+
+    fn grants_sql(&self) -> String {
+        if self.grants.contains(&"ALL".to_string()) {
+            "ALL PRIVILEGES".to_string()
+        } else {
+            self.grants.join(", ")
+        }
+    }
+
+    /// Number of privileges the role revokes from each user: on each `-`
+    /// entry, or on all the functions (or procedures) of each schema for
+    /// `-ALL`
+    pub fn revoke_count(&self) -> usize {
+        [&self.functions, &self.procedures]
+            .iter()
+            .flat_map(|entries| entries.iter().map(|e| signed(e)))
+            .filter(|(revoke, _)| *revoke)
+            .map(|(_, name)| {
+                if name == "ALL" {
+                    self.schemas.len()
+                } else {
+                    self.qualified_names(Kind::Function, name).len()
+                }
+            })
+            .sum()
+    }
+
+    /// The routine of an entry qualified by its schema, or by each schema of
+    /// the role if it has none, e.g. `FUNCTION etl.load(date)`
+    fn qualified_names(&self, kind: Kind, entry: &str) -> Vec<String> {
+        let (name, args) = split_signature(entry);
+        let names = if ident::split_qualified(name).len() > 1 {
+            vec![ident::quote_qualified(name)]
+        } else {
+            self.schemas
+                .iter()
+                .map(|s| format!("{}.{}", ident::quote(s), ident::quote(name)))
+                .collect()
+        };
+
+        names
+            .into_iter()
+            .map(|name| format!("{} {}{}", kind.keyword(), name, args))
+            .collect()
+    }
+
+    /// The schemas of the `schema.name` entries
+    pub fn qualified_schemas(&self) -> Vec<String> {
+        self.functions
+            .iter()
+            .chain(&self.procedures)
+            .filter_map(|e| {
+                let (name, _) = split_signature(signed(e).1);
+                match ident::split_qualified(name)[..] {
+                    [schema, _] => Some(schema.to_string()),
+                    _ => None,
+                }
+            })
+            .collect()
+    }
+}
+
+/// An entry split into its sign and name, only the first character is the
+/// sign
+fn signed(entry: &str) -> (bool, &str) {
+    match entry.strip_prefix('-') {
+        Some(name) => (true, name),
+        None => (false, entry.strip_prefix('+').unwrap_or(entry)),
+    }
+}
+
+/// Split `name(argtypes)` on the first parenthesis which is not inside double
+/// quotes, the argument list is kept as written
+fn split_signature(entry: &str) -> (&str, &str) {
+    let mut in_quotes = false;
+    for (i, c) in entry.char_indices() {
+        match c {
+            '"' => in_quotes = !in_quotes,
+            '(' if !in_quotes => return (&entry[..i], &entry[i..]),
+            _ => {}
+        }
+    }
+
+    (entry, "")
+}
+
+impl RoleValidate for RoleFunctionLevel {
+    fn validate(&self) -> Result<()> {
+        if self.name.is_empty() {
+            return Err(anyhow!("role.name is empty"));
+        }
+
+        if self.schemas.is_empty() {
+            return Err(anyhow!("role.schemas is empty"));
+        }
+
+        if self.schemas.contains(&"ALL".to_string()) {
+            return Err(anyhow!("role.schemas is not supported yet: ALL"));
+        }
+
+        if self.functions.is_empty() && self.procedures.is_empty() {
+            return Err(anyhow!("role.functions and role.procedures are empty"));
+        }
+
+        for (field, entries) in [
+            ("functions", &self.functions),
+            ("procedures", &self.procedures),
+        ] {
+            for entry in entries {
+                let (name, args) = split_signature(signed(entry).1);
+                if name.is_empty() || !(args.is_empty() || args.ends_with(')')) {
+                    return Err(anyhow!("role.{} invalid: {}", field, entry));
+                }
+            }
+        }
+
+        if self.grants.is_empty() {
+            return Err(anyhow!("role.grants is empty"));
+        }
+
+        let valid_grants = vec!["EXECUTE", "ALL"];
+        for grant in &self.grants {
+            if !valid_grants.contains(&&grant[..]) {
+                return Err(anyhow!(
+                    "role.grants invalid: {}, expected: {:?}",
+                    grant,
+                    valid_grants
+                ));
+            }
+        }
+
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn role(functions: &[&str], procedures: &[&str]) -> RoleFunctionLevel {
+        RoleFunctionLevel {
+            name: "role_etl".to_string(),
+            grants: vec!["EXECUTE".to_string()],
+            schemas: vec!["etl".to_string(), "Sales Data".to_string()],
+            functions: functions.iter().map(|f| f.to_string()).collect(),
+            procedures: procedures.iter().map(|p| p.to_string()).collect(),
+            database: None,
+        }
+    }
+
+    #[test]
+    fn test_role_function_level() {
+        assert_eq!(
+            role(&["ALL", "+ignored", "-cleanup()"], &[]).to_sql("duyet"),
+            "GRANT EXECUTE ON ALL FUNCTIONS IN SCHEMA etl, \"Sales Data\" TO duyet; \
+             REVOKE EXECUTE ON FUNCTION etl.cleanup(), FUNCTION \"Sales Data\".cleanup() FROM duyet;"
+        );
+        assert_eq!(
+            role(&["etl.total(integer, text)", "-ALL"], &["public.Load"]).to_sql("duyet"),
+            "REVOKE EXECUTE ON ALL FUNCTIONS IN SCHEMA etl, \"Sales Data\" FROM duyet; \
+             GRANT EXECUTE ON PROCEDURE public.Load TO duyet;"
+        );
+        assert_eq!(
+            role(&[], &["ALL"]).to_sql_for(Grantee::Group("etl")),
+            "GRANT EXECUTE ON ALL PROCEDURES IN SCHEMA etl, \"Sales Data\" TO GROUP etl;"
+        );
+
+        let role = RoleFunctionLevel {
+            grants: vec!["ALL".to_string()],
+            schemas: vec!["etl".to_string()],
+            ..role(&["\"f(x)\"(date)", "-\"Total\""], &[])
+        };
+        assert_eq!(
+            role.to_sql("duyet"),
+            "GRANT ALL PRIVILEGES ON FUNCTION etl.\"f(x)\"(date) TO duyet; \
+             REVOKE ALL PRIVILEGES ON FUNCTION etl.\"Total\" FROM duyet;"
+        );
+    }
+
+    #[test]
+    fn test_objects() {
+        assert_eq!(
+            role(&["ALL", "-etl.cleanup"], &["load(date)"]).objects(),
+            vec![
+                (
+                    false,
+                    "ALL FUNCTIONS IN SCHEMA etl, \"Sales Data\"".to_string()
+                ),
+                (true, "FUNCTION etl.cleanup".to_string()),
+                (false, "PROCEDURE etl.load(date)".to_string()),
+                (false, "PROCEDURE \"Sales Data\".load(date)".to_string()),
+            ]
+        );
+    }
+
+    #[test]
+    fn test_revoke_count() {
+        assert_eq!(
+            role(&["ALL", "-a", "-etl.b(int)"], &["-ALL"]).revoke_count(),
+            5
+        );
+        assert_eq!(role(&["ALL"], &["p"]).revoke_count(), 0);
+    }
+
+    #[test]
+    fn test_qualified_schemas() {
+        assert_eq!(
+            role(&["-marts.f(int)", "g"], &["\"Raw\".p"]).qualified_schemas(),
+            vec!["marts", "\"Raw\""]
+        );
+    }
+
+    #[test]
+    fn test_role_function_level_validate() {
+        assert!(role(&["ALL"], &[]).validate().is_ok());
+        assert!(role(&[], &[]).validate().is_err());
+        assert_eq!(
+            role(&["f(int"], &[]).validate().unwrap_err().to_string(),
+            "role.functions invalid: f(int"
+        );
+
+        let role = RoleFunctionLevel {
+            grants: vec!["SELECT".to_string()],
+            ..role(&["ALL"], &[])
+        };
+        assert_eq!(
+            role.validate().unwrap_err().to_string(),
+            "role.grants invalid: SELECT, expected: [\"EXECUTE\", \"ALL\"]"
+        );
+    }
+}
@@ -1,4 +1,5 @@
-use super::role::RoleValidate;
+use super::ident;
+use super::role::{Grantee, RoleValidate};
 use anyhow::{anyhow, Result};
 use serde::{Deserialize, Serialize};
 use std::collections::HashSet;
@@ -24,9 +25,18 @@ pub struct RoleSchemaLevel {
     pub name: String,
     pub grants: Vec<String>,
     pub schemas: Vec<String>,
+    /// Database of the schemas, the one of the connection if not set, see
+    /// [Role::database](super::Role::database)
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub database: Option<String>,
 }
 
 impl RoleSchemaLevel {
+    /// Generate the role SQL for a user, see [Self::to_sql_for]
+    pub fn to_sql(&self, user: &str) -> String {
+        self.to_sql_for(Grantee::User(user))
+    }
+
     /// Generate role schema to sql.
     ///
     /// ```sql
@@ -34,7 +44,7 @@ impl RoleSchemaLevel {
     /// ON SCHEMA schema_name [, ...]
     /// TO { username [ WITH GRANT OPTION ] | GROUP group_name | PUBLIC } [, ...]
     /// ```
-    pub fn to_sql(&self, user: &str) -> String {
+    pub fn to_sql_for(&self, grantee: Grantee) -> String {
         // grant all privileges if no grants are specified or if grants contains "ALL"
         let grants = if self.grants.is_empty() || self.grants.contains(&"ALL".to_string()) {
             "ALL PRIVILEGES".to_string()
@@ -46,12 +56,21 @@ impl RoleSchemaLevel {
         let sql = format!(
             "GRANT {} ON SCHEMA {} TO {};",
             grants,
-            self.schemas.join(", "),
-            user
+            ident::quote_list(&self.schemas),
+            grantee.to_sql()
         );
 
         sql
     }
+
+    /// The objects of the GRANT statement of the role, each with whether it
+    /// is revoked, e.g. `(false, "SCHEMA public")`
+    pub fn objects(&self) -> Vec<(bool, String)> {
+        self.schemas
+            .iter()
+            .map(|s| (false, format!("SCHEMA {}", ident::quote(s))))
+            .collect()
+    }
 }
 
 impl RoleValidate for RoleSchemaLevel {
@@ -97,6 +116,7 @@ mod tests {
             name: "role_schema_level".to_string(),
             grants: vec!["CREATE".to_string(), "TEMP".to_string()],
             schemas: vec!["schema1".to_string(), "schema2".to_string()],
+            database: None,
         };
 
         role_schema_level.validate().ok();
@@ -104,7 +124,7 @@ mod tests {
         let sql = role_schema_level.to_sql("user");
         assert_eq!(
             sql,
-            "GRANT CREATE, TEMP ON SCHEMA schema1, schema2 TO user;"
+            "GRANT CREATE, TEMP ON SCHEMA schema1, schema2 TO \"user\";"
         );
     }
 }
@@ -0,0 +1,276 @@
+use super::ident;
+use super::role::{Grantee, RoleValidate};
+use anyhow::{anyhow, Result};
+use serde::{Deserialize, Serialize};
+
+/// Role Sequence Level, e.g. `USAGE` on the sequences of the `serial`
+/// columns of the tables an app inserts into.
+///
+/// For example:
+///
+/// ```yaml
+/// - name: role_app_sequences
+///   type: sequence
+///   grants:
+///     - USAGE
+///   schemas:
+///     - public
+///   sequences:
+///     - ALL
+///     - -internal_seq
+/// ```
+///
+/// The entries are signed as the tables of a [table role], `ALL` being every
+/// sequence of the schemas. A sequence without a schema is in every schema
+/// of the role.
+///
+/// [table role]: super::role::RoleTableLevel
+#[derive(Debug, Serialize, Deserialize, Clone, PartialEq, Eq)]
+pub struct RoleSequenceLevel {
+    pub name: String,
+    pub grants: Vec<String>,
+    pub schemas: Vec<String>,
+    pub sequences: Vec<String>,
+    /// Database of the schemas, the one of the connection if not set, see
+    /// [Role::database](super::Role::database)
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub database: Option<String>,
+}
+
+impl RoleSequenceLevel {
+    /// Generate the role SQL for a user, see [Self::to_sql_for]
+    pub fn to_sql(&self, user: &str) -> String {
+        self.to_sql_for(Grantee::User(user))
+    }
+
+    /// Generate role sequence to sql.
+    ///
+    /// ```sql
+    /// {GRANT | REVOKE} { { USAGE | SELECT | UPDATE } [, ...] | ALL [ PRIVILEGES ] }
+    /// ON { SEQUENCE sequence_name [, ...] | ALL SEQUENCES IN SCHEMA schema_name [, ...] }
+    /// TO { username | GROUP group_name | PUBLIC } [, ...]
+    /// ```
+    pub fn to_sql_for(&self, grantee: Grantee) -> String {
+        let grants = if self.grants.contains(&"ALL".to_string()) {
+            "ALL PRIVILEGES".to_string()
+        } else {
+            self.grants.join(", ")
+        };
+
+        self.statements()
+            .into_iter()
+            .map(|(revoke, objects)| {
+                if revoke {
+                    format!(
+                        "REVOKE {} ON {} FROM {};",
+                        grants,
+                        objects.join(", "),
+                        grantee.to_sql()
+                    )
+                } else {
+                    format!(
+                        "GRANT {} ON {} TO {};",
+                        grants,
+                        objects.join(", "),
+                        grantee.to_sql()
+                    )
+                }
+            })
+            .collect::<Vec<_>>()
+            .join(" ")
+    }
+
+    /// The objects of the GRANT and REVOKE statements of the role, each with
+    /// whether it is revoked, e.g. `(false, "SEQUENCE public.orders_id_seq")`
+    pub fn objects(&self) -> Vec<(bool, String)> {
+        self.statements()
+            .into_iter()
+            .flat_map(|(revoke, objects)| objects.into_iter().map(move |o| (revoke, o)))
+            .collect()
+    }
+
+    /// The objects of each statement: `ALL` or the `+` sequences are
+    /// granted, then the `-` sequences are revoked
+    fn statements(&self) -> Vec<(bool, Vec<String>)> {
+        let entries = self.sequences.iter().map(|s| signed(s)).collect::<Vec<_>>();
+        let mut statements = vec![];
+
+        // As for the tables, `ALL` ignores the other granted sequences
+        match entries.iter().find(|(_, name)| *name == "ALL") {
+            Some(&(revoke, _)) => statements.push((
+                revoke,
+                vec![format!(
+                    "ALL SEQUENCES IN SCHEMA {}",
+                    ident::quote_list(&self.schemas)
+                )],
+            )),
+            None => statements.push((
+                false,
+                entries
+                    .iter()
+                    .filter(|(revoke, _)| !revoke)
+                    .flat_map(|(_, name)| self.qualified_names(name))
+                    .collect(),
+            )),
+        }
+        statements.push((
+            true,
+            entries
+                .iter()
+                .filter(|(revoke, name)| *revoke && *name != "ALL")
+                .flat_map(|(_, name)| self.qualified_names(name))
+                .collect(),
+        ));
+
+        statements.retain(|(_, objects)| !objects.is_empty());
+        statements
+    }
+
+    /// Number of privileges the role revokes from each user: every grant on
+    /// each `-sequence`, or on all sequences of each schema for `-ALL`
+    pub fn revoke_count(&self) -> usize {
+        let grants = if self.grants.contains(&"ALL".to_string()) {
+            1
+        } else {
+            self.grants.len()
+        };
+
+        let objects: usize = self
+            .sequences
+            .iter()
+            .map(|s| signed(s))
+            .filter(|(revoke, _)| *revoke)
+            .map(|(_, name)| {
+                if name == "ALL" {
+                    self.schemas.len()
+                } else {
+                    self.qualified_names(name).len()
+                }
+            })
+            .sum();
+
+        grants * objects
+    }
+
+    /// The sequence qualified by its schema, or by each schema of the role
+    /// if it has none
+    fn qualified_names(&self, sequence: &str) -> Vec<String> {
+        if ident::split_qualified(sequence).len() > 1 {
+            vec![format!("SEQUENCE {}", ident::quote_qualified(sequence))]
+        } else {
+            self.schemas
+                .iter()
+                .map(|s| format!("SEQUENCE {}.{}", ident::quote(s), ident::quote(sequence)))
+                .collect()
+        }
+    }
+}
+
+/// An entry split into its sign and name, only the first character is the
+/// sign
+fn signed(entry: &str) -> (bool, &str) {
+    match entry.strip_prefix('-') {
+        Some(name) => (true, name),
+        None => (false, entry.strip_prefix('+').unwrap_or(entry)),
+    }
+}
+
+impl RoleValidate for RoleSequenceLevel {
+    fn validate(&self) -> Result<()> {
+        if self.name.is_empty() {
+            return Err(anyhow!("role.name is empty"));
+        }
+
+        if self.schemas.is_empty() {
+            return Err(anyhow!("role.schemas is empty"));
+        }
+
+        if self.schemas.contains(&"ALL".to_string()) {
+            return Err(anyhow!("role.schemas is not supported yet: ALL"));
+        }
+
+        if self.sequences.is_empty() {
+            return Err(anyhow!("role.sequences is empty"));
+        }
+
+        if self.grants.is_empty() {
+            return Err(anyhow!("role.grants is empty"));
+        }
+
+        let valid_grants = vec!["USAGE", "SELECT", "UPDATE", "ALL"];
+        for grant in &self.grants {
+            if !valid_grants.contains(&&grant[..]) {
+                return Err(anyhow!(
+                    "role.grants invalid: {}, expected: {:?}",
+                    grant,
+                    valid_grants
+                ));
+            }
+        }
+
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn role(grants: &[&str], sequences: &[&str]) -> RoleSequenceLevel {
+        RoleSequenceLevel {
+            name: "role_app_sequences".to_string(),
+            grants: grants.iter().map(|g| g.to_string()).collect(),
+            schemas: vec!["public".to_string(), "Sales Data".to_string()],
+            sequences: sequences.iter().map(|s| s.to_string()).collect(),
+            database: None,
+        }
+    }
+
+    #[test]
+    fn test_role_sequence_level() {
+        assert_eq!(
+            role(&["USAGE"], &["ALL", "+ignored", "-internal_seq"]).to_sql("app"),
+            "GRANT USAGE ON ALL SEQUENCES IN SCHEMA public, \"Sales Data\" TO app; \
+             REVOKE USAGE ON SEQUENCE public.internal_seq, SEQUENCE \"Sales Data\".internal_seq FROM app;"
+        );
+        assert_eq!(
+            role(&["USAGE", "SELECT"], &["public.orders_id_seq"]).to_sql("app"),
+            "GRANT USAGE, SELECT ON SEQUENCE public.orders_id_seq TO app;"
+        );
+        assert_eq!(
+            role(&["ALL"], &["-ALL"]).to_sql_for(Grantee::Group("apps")),
+            "REVOKE ALL PRIVILEGES ON ALL SEQUENCES IN SCHEMA public, \"Sales Data\" FROM GROUP apps;"
+        );
+    }
+
+    #[test]
+    fn test_objects() {
+        assert_eq!(
+            role(&["USAGE"], &["a", "-public.b"]).objects(),
+            vec![
+                (false, "SEQUENCE public.a".to_string()),
+                (false, "SEQUENCE \"Sales Data\".a".to_string()),
+                (true, "SEQUENCE public.b".to_string()),
+            ]
+        );
+    }
+
+    #[test]
+    fn test_revoke_count() {
+        assert_eq!(role(&["USAGE", "SELECT"], &["ALL", "-a"]).revoke_count(), 4);
+        assert_eq!(role(&["ALL"], &["-ALL", "-public.a"]).revoke_count(), 3);
+    }
+
+    #[test]
+    fn test_role_sequence_level_validate() {
+        assert!(role(&["USAGE"], &["ALL"]).validate().is_ok());
+        assert!(role(&["USAGE"], &[]).validate().is_err());
+        assert_eq!(
+            role(&["INSERT"], &["ALL"])
+                .validate()
+                .unwrap_err()
+                .to_string(),
+            "role.grants invalid: INSERT, expected: [\"USAGE\", \"SELECT\", \"UPDATE\", \"ALL\"]"
+        );
+    }
+}
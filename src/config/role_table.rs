@@ -1,4 +1,5 @@
-use super::role::RoleValidate;
+use super::ident;
+use super::role::{Grantee, RoleValidate};
 use anyhow::{anyhow, Result};
 use serde::{Deserialize, Serialize};
 use std::collections::HashSet;
@@ -33,6 +34,10 @@ pub struct RoleTableLevel {
     pub grants: Vec<String>,
     pub schemas: Vec<String>,
     pub tables: Vec<String>,
+    /// Database of the schemas, the one of the connection if not set, see
+    /// [Role::database](super::Role::database)
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub database: Option<String>,
 }
 
 #[derive(Debug, Serialize, Deserialize, Clone, PartialEq)]
@@ -48,13 +53,19 @@ impl Table {
             Some('-') => "-".to_string(),
             _ => "+".to_string(),
         };
-        let name = name.trim_start_matches(&sign).to_string();
+        // Only the first character is the sign, `--x` revokes on `-x`
+        let name = name.strip_prefix(&sign).unwrap_or(name).to_string();
 
         Self { name, sign }
     }
 }
 
 impl RoleTableLevel {
+    /// Generate the role SQL for a user, see [Self::to_sql_for]
+    pub fn to_sql(&self, user: &str) -> String {
+        self.to_sql_for(Grantee::User(user))
+    }
+
     /// Generate role table to sql.
     ///
     /// ```sql
@@ -62,7 +73,7 @@ impl RoleTableLevel {
     /// ON { [ TABLE ] table_name [, ...] | ALL TABLES IN SCHEMA schema_name [, ...] }
     /// TO { username [ WITH GRANT OPTION ] | GROUP group_name | PUBLIC } [, ...]
     /// ```
-    pub fn to_sql(&self, user: &str) -> String {
+    pub fn to_sql_for(&self, grantee: Grantee) -> String {
         let mut sqls = vec![];
         let mut tables = self
             .tables
@@ -83,14 +94,14 @@ impl RoleTableLevel {
                 "+" => format!(
                     "GRANT {} ON ALL TABLES IN SCHEMA {} TO {};",
                     grants,
-                    self.schemas.join(", "),
-                    user
+                    ident::quote_list(&self.schemas),
+                    grantee.to_sql()
                 ),
                 "-" => format!(
                     "REVOKE {} ON ALL TABLES IN SCHEMA {} FROM {};",
                     grants,
-                    self.schemas.join(", "),
-                    user
+                    ident::quote_list(&self.schemas),
+                    grantee.to_sql()
                 ),
                 _ => "".to_string(),
             };
@@ -109,20 +120,16 @@ impl RoleTableLevel {
         if !grant_tables.is_empty() {
             let _with_schema = grant_tables
                 .iter()
-                .flat_map(|t| {
-                    if t.name.contains('.') {
-                        vec![t.name.clone()]
-                    } else {
-                        self.schemas
-                            .iter()
-                            .map(|s| format!("{}.{}", s, &t.name))
-                            .collect::<Vec<_>>()
-                    }
-                })
+                .flat_map(|t| self.qualified_names(&t.name))
                 .collect::<Vec<String>>()
                 .join(", ");
 
-            let sql = format!("GRANT {} ON {} TO {};", grants, _with_schema, user);
+            let sql = format!(
+                "GRANT {} ON {} TO {};",
+                grants,
+                _with_schema,
+                grantee.to_sql()
+            );
             sqls.push(sql);
 
             // remove all tables start with `+`
@@ -138,25 +145,117 @@ impl RoleTableLevel {
         if !revoke_tables.is_empty() {
             let _with_schema = revoke_tables
                 .iter()
-                .flat_map(|t| {
-                    if t.name.contains('.') {
-                        vec![t.name.clone()]
-                    } else {
-                        self.schemas
-                            .iter()
-                            .map(|s| format!("{}.{}", s, &t.name))
-                            .collect::<Vec<_>>()
-                    }
-                })
+                .flat_map(|t| self.qualified_names(&t.name))
                 .collect::<Vec<String>>()
                 .join(", ");
 
-            let sql = format!("REVOKE {} ON {} FROM {};", grants, _with_schema, user);
+            let sql = format!(
+                "REVOKE {} ON {} FROM {};",
+                grants,
+                _with_schema,
+                grantee.to_sql()
+            );
             sqls.push(sql);
         }
 
         sqls.join(" ")
     }
+
+    /// The objects of the GRANT and REVOKE statements of the role, each with
+    /// whether it is revoked, e.g. `(false, "ALL TABLES IN SCHEMA public")`
+    /// then `(true, "TABLE public.secrets")`. As in [Self::to_sql_for], `ALL`
+    /// ignores the `+` tables.
+    pub fn objects(&self) -> Vec<(bool, String)> {
+        let tables = self
+            .tables
+            .iter()
+            .map(|t| Table::new(t))
+            .collect::<Vec<_>>();
+        let all = tables.iter().find(|t| t.name == "ALL");
+
+        let mut objects = vec![];
+        match all {
+            Some(all) => objects.extend(self.schemas.iter().map(|s| {
+                (
+                    all.sign == "-",
+                    format!("ALL TABLES IN SCHEMA {}", ident::quote(s)),
+                )
+            })),
+            None => objects.extend(
+                tables
+                    .iter()
+                    .filter(|t| t.sign == "+")
+                    .flat_map(|t| self.qualified_names(&t.name))
+                    .map(|t| (false, format!("TABLE {}", t))),
+            ),
+        }
+        objects.extend(
+            tables
+                .iter()
+                .filter(|t| t.sign == "-" && t.name != "ALL")
+                .flat_map(|t| self.qualified_names(&t.name))
+                .map(|t| (true, format!("TABLE {}", t))),
+        );
+
+        objects
+    }
+
+    /// Number of privileges the role revokes from each user: every grant on
+    /// each `-table`, or on all tables of each schema for `-ALL`
+    pub fn revoke_count(&self) -> usize {
+        let grants = if self.grants.contains(&"ALL".to_string()) {
+            1
+        } else {
+            self.grants.len()
+        };
+
+        let objects: usize = self
+            .tables
+            .iter()
+            .map(|t| Table::new(t))
+            .filter(|t| t.sign == "-")
+            .map(|t| {
+                if t.name == "ALL" {
+                    self.schemas.len()
+                } else {
+                    self.qualified_names(&t.name).len()
+                }
+            })
+            .sum();
+
+        grants * objects
+    }
+
+    /// The schema and name of the tables of the role, as in the catalog,
+    /// each unqualified table in each schema of the role, `ALL` aside
+    pub fn table_names(&self) -> Vec<(String, String)> {
+        self.tables
+            .iter()
+            .map(|t| Table::new(t))
+            .filter(|t| t.name != "ALL")
+            .flat_map(|t| match ident::split_qualified(&t.name)[..] {
+                [schema, table] => vec![(ident::fold(schema), ident::fold(table))],
+                _ => self
+                    .schemas
+                    .iter()
+                    .map(|s| (ident::fold(s), ident::fold(&t.name)))
+                    .collect(),
+            })
+            .collect()
+    }
+
+    /// The table qualified by its schema, or by each schema of the role
+    /// if it has none
+    fn qualified_names(&self, table: &str) -> Vec<String> {
+        if ident::split_qualified(table).len() > 1 {
+            vec![ident::quote_qualified(table)]
+        } else {
+            self.schemas
+                .iter()
+                .map(|s| format!("{}.{}", ident::quote(s), ident::quote(table)))
+                .collect()
+        }
+    }
 }
 
 impl RoleValidate for RoleTableLevel {
@@ -178,11 +277,25 @@ impl RoleValidate for RoleTableLevel {
             return Err(anyhow!("role.tables is empty"));
         }
 
+        // The patterns are expanded at apply time, see `crate::patterns`
+        if let Some(table) = self
+            .tables
+            .iter()
+            .find(|t| !t.starts_with('-') && crate::patterns::is_pattern(t))
+        {
+            return Err(anyhow!(
+                "role.tables invalid: {}, only the excluded tables can be patterns, e.g. -{}",
+                table,
+                table.trim_start_matches('+')
+            ));
+        }
+
         if self.grants.is_empty() {
             return Err(anyhow!("role.grants is empty"));
         }
 
-        // Check valid grants: SELECT, INSERT, UPDATE, DELETE, DROP, REFERENCES, ALL
+        // Check valid grants, the ones supported by the server dialect
+        // are checked again when connected (see `Config::validate_dialect`)
         let valid_grants = vec![
             "SELECT",
             "INSERT",
@@ -190,6 +303,9 @@ impl RoleValidate for RoleTableLevel {
             "DELETE",
             "DROP",
             "REFERENCES",
+            "ALTER",
+            "TRUNCATE",
+            "TRIGGER",
             "ALL",
         ];
         let mut grants = HashSet::new();
@@ -219,6 +335,7 @@ mod tests {
             grants: vec!["SELECT".to_string()],
             schemas: vec!["public".to_string()],
             tables: vec!["test".to_string()],
+            database: None,
         };
         assert_eq!(role.to_sql("test"), "GRANT SELECT ON public.test TO test;");
 
@@ -227,6 +344,7 @@ mod tests {
             grants: vec!["SELECT".to_string(), "INSERT".to_string()],
             schemas: vec!["public".to_string()],
             tables: vec!["test".to_string()],
+            database: None,
         };
         assert_eq!(
             role.to_sql("test"),
@@ -238,6 +356,7 @@ mod tests {
             grants: vec!["SELECT".to_string(), "INSERT".to_string()],
             schemas: vec!["public".to_string(), "test".to_string()],
             tables: vec!["test".to_string()],
+            database: None,
         };
         assert_eq!(
             role.to_sql("test"),
@@ -249,6 +368,7 @@ mod tests {
             grants: vec!["ALL".to_string()],
             schemas: vec!["public".to_string()],
             tables: vec!["test".to_string()],
+            database: None,
         };
         assert_eq!(
             role.to_sql("test"),
@@ -260,6 +380,7 @@ mod tests {
             grants: vec!["SELECT".to_string(), "INSERT".to_string()],
             schemas: vec!["public".to_string()],
             tables: vec!["ALL".to_string()],
+            database: None,
         };
         assert_eq!(
             role.to_sql("test"),
@@ -271,6 +392,7 @@ mod tests {
             grants: vec!["ALL".to_string()],
             schemas: vec!["public".to_string(), "test".to_string()],
             tables: vec!["ALL".to_string()],
+            database: None,
         };
         assert_eq!(
             role.to_sql("test"),
@@ -282,6 +404,7 @@ mod tests {
             grants: vec!["SELECT".to_string(), "INSERT".to_string()],
             schemas: vec!["public".to_string(), "test".to_string()],
             tables: vec!["ALL".to_string()],
+            database: None,
         };
         assert_eq!(
             role.to_sql("test"),
@@ -293,6 +416,7 @@ mod tests {
             grants: vec!["SELECT".to_string(), "INSERT".to_string()],
             schemas: vec!["public".to_string(), "test".to_string()],
             tables: vec!["test".to_string(), "test.test2".to_string()],
+            database: None,
         };
         assert_eq!(
             role.to_sql("test"),
@@ -304,6 +428,7 @@ mod tests {
             grants: vec!["SELECT".to_string(), "INSERT".to_string()],
             schemas: vec!["public".to_string(), "test".to_string()],
             tables: vec!["test".to_string(), "-test.test2".to_string()],
+            database: None,
         };
         assert_eq!(
             role.to_sql("test"),
@@ -315,6 +440,7 @@ mod tests {
             grants: vec!["SELECT".to_string(), "INSERT".to_string()],
             schemas: vec!["public".to_string(), "test".to_string()],
             tables: vec!["test".to_string(), "-test2".to_string()],
+            database: None,
         };
         assert_eq!(
             role.to_sql("test"),
@@ -326,10 +452,130 @@ mod tests {
             grants: vec!["SELECT".to_string(), "INSERT".to_string()],
             schemas: vec!["public".to_string(), "test".to_string()],
             tables: vec!["ALL".to_string(), "-test.test2".to_string()],
+            database: None,
         };
         assert_eq!(
             role.to_sql("test"),
             "GRANT SELECT, INSERT ON ALL TABLES IN SCHEMA public, test TO test; REVOKE SELECT, INSERT ON test.test2 FROM test;"
         );
     }
+
+    #[test]
+    fn test_revoke_count() {
+        let role = RoleTableLevel {
+            name: "test".to_string(),
+            grants: vec!["SELECT".to_string(), "INSERT".to_string()],
+            schemas: vec!["public".to_string(), "test".to_string()],
+            tables: vec![
+                "ALL".to_string(),
+                "-secret".to_string(),
+                "-test.t".to_string(),
+            ],
+            database: None,
+        };
+        assert_eq!(role.revoke_count(), 6);
+
+        let role = RoleTableLevel {
+            tables: vec!["-ALL".to_string()],
+            grants: vec!["ALL".to_string()],
+            ..role
+        };
+        assert_eq!(role.revoke_count(), 2);
+    }
+
+    #[test]
+    fn test_role_table_level_quoted_names() {
+        let role = RoleTableLevel {
+            name: "test".to_string(),
+            grants: vec!["SELECT".to_string()],
+            schemas: vec!["Sales Data".to_string()],
+            tables: vec![
+                "Q1 Orders".to_string(),
+                "+\"Mixed\".Events".to_string(),
+                "-order".to_string(),
+            ],
+            database: None,
+        };
+        assert_eq!(
+            role.to_sql("data team"),
+            "GRANT SELECT ON \"Sales Data\".\"Q1 Orders\", \"Mixed\".Events TO \"data team\"; REVOKE SELECT ON \"Sales Data\".\"order\" FROM \"data team\";"
+        );
+    }
+
+    #[test]
+    fn test_role_table_level_signed_names() {
+        let role = RoleTableLevel {
+            name: "test".to_string(),
+            grants: vec!["SELECT".to_string()],
+            schemas: vec!["public".to_string()],
+            tables: vec!["--".to_string(), "+-x".to_string()],
+            database: None,
+        };
+        assert_eq!(
+            role.to_sql("duyet"),
+            "GRANT SELECT ON public.\"-x\" TO duyet; REVOKE SELECT ON public.\"-\" FROM duyet;"
+        );
+    }
+
+    #[test]
+    fn test_role_table_level_table_names() {
+        let role = RoleTableLevel {
+            name: "test".to_string(),
+            grants: vec!["SELECT".to_string()],
+            schemas: vec!["public".to_string(), "\"Sales\"".to_string()],
+            tables: vec![
+                "ALL".to_string(),
+                "-secrets".to_string(),
+                "+marts.Orders".to_string(),
+            ],
+            database: None,
+        };
+
+        assert_eq!(
+            role.table_names(),
+            vec![
+                ("public".to_string(), "secrets".to_string()),
+                ("Sales".to_string(), "secrets".to_string()),
+                ("marts".to_string(), "orders".to_string()),
+            ]
+        );
+    }
+
+    #[test]
+    fn test_role_table_level_patterns() {
+        let role = |tables: &[&str]| RoleTableLevel {
+            name: "test".to_string(),
+            grants: vec!["SELECT".to_string()],
+            schemas: vec!["public".to_string()],
+            tables: tables.iter().map(|t| t.to_string()).collect(),
+            database: None,
+        };
+
+        assert!(role(&["ALL", "-tmp_*", "-staging.*_raw"])
+            .validate()
+            .is_ok());
+        assert_eq!(
+            role(&["+tmp_*"]).validate().unwrap_err().to_string(),
+            "role.tables invalid: +tmp_*, only the excluded tables can be patterns, e.g. -tmp_*"
+        );
+    }
+
+    #[test]
+    fn test_role_table_level_to_group() {
+        let role = RoleTableLevel {
+            name: "test".to_string(),
+            grants: vec!["SELECT".to_string()],
+            schemas: vec!["public".to_string()],
+            tables: vec!["ALL".to_string(), "-secrets".to_string()],
+            database: None,
+        };
+        assert_eq!(
+            role.to_sql_for(Grantee::Group("Analytics RO")),
+            "GRANT SELECT ON ALL TABLES IN SCHEMA public TO GROUP \"Analytics RO\"; REVOKE SELECT ON public.secrets FROM GROUP \"Analytics RO\";"
+        );
+        assert_eq!(
+            Grantee::Group("analytics_ro").to_string(),
+            "group analytics_ro"
+        );
+    }
 }
@@ -0,0 +1,198 @@
+//! Parameterized roles, stamped once per schema when the config is loaded.
+//!
+//! A role with a `for_each` is a template: it is replaced by a role for each
+//! of the `schemas_from`, with `{schema}` replaced by the schema in every
+//! field of the role:
+//!
+//! ```yaml
+//! roles:
+//!   - name: read_{schema}
+//!     type: table
+//!     for_each:
+//!       schemas_from: [marts, staging]
+//!     grants: [SELECT]
+//!     schemas: ["{schema}"]
+//!     tables: [ALL]
+//! ```
+//!
+//! is the roles `read_marts` and `read_staging`, which the users list as any
+//! other role.
+
+use anyhow::{anyhow, Result};
+use serde_yaml::{Mapping, Value};
+
+/// The placeholder of the schema in the fields of a template
+const PLACEHOLDER: &str = "{schema}";
+
+/// Replace the role templates of the config with their roles, in place.
+/// Whether the config has templates.
+pub fn expand(config: &mut Value) -> Result<bool> {
+    let roles = match config.get_mut("roles") {
+        Some(Value::Sequence(roles)) if roles.iter().any(|r| r.get("for_each").is_some()) => roles,
+        _ => return Ok(false),
+    };
+
+    let mut expanded = vec![];
+    for role in roles.drain(..) {
+        match role.get("for_each") {
+            Some(_) => expanded.extend(stamp(role)?),
+            None => expanded.push(role),
+        }
+    }
+    *roles = expanded;
+
+    Ok(true)
+}
+
+/// The roles of a template, one per schema
+fn stamp(mut role: Value) -> Result<Vec<Value>> {
+    let name = role
+        .get("name")
+        .and_then(Value::as_str)
+        .unwrap_or_default()
+        .to_string();
+    let for_each = match &mut role {
+        Value::Mapping(fields) => fields.remove("for_each"),
+        _ => None,
+    };
+
+    let schemas = match for_each {
+        Some(Value::Mapping(for_each)) => schemas_from(&name, &for_each)?,
+        _ => return Err(anyhow!("role {}: for_each must be a mapping", name)),
+    };
+    if !name.contains(PLACEHOLDER) {
+        return Err(anyhow!(
+            "role {}: the name of a for_each role needs {}, each role must have its own name",
+            name,
+            PLACEHOLDER
+        ));
+    }
+
+    Ok(schemas
+        .iter()
+        .map(|schema| replace(&role, schema))
+        .collect())
+}
+
+/// The schemas of `for_each`, the only key it has for now
+fn schemas_from(name: &str, for_each: &Mapping) -> Result<Vec<String>> {
+    if let Some(key) = for_each.keys().find(|k| k.as_str() != Some("schemas_from")) {
+        return Err(anyhow!(
+            "role {}: unknown for_each key {:?}, expected: schemas_from",
+            name,
+            key.as_str().unwrap_or_default()
+        ));
+    }
+
+    let schemas = match for_each.get("schemas_from") {
+        Some(Value::Sequence(schemas)) => schemas,
+        _ => {
+            return Err(anyhow!(
+                "role {}: for_each.schemas_from must be a list of schemas",
+                name
+            ))
+        }
+    };
+    let schemas = schemas
+        .iter()
+        .map(|s| s.as_str().map(|s| s.to_string()))
+        .collect::<Option<Vec<_>>>()
+        .ok_or_else(|| anyhow!("role {}: for_each.schemas_from must be strings", name))?;
+    if schemas.is_empty() {
+        return Err(anyhow!("role {}: for_each.schemas_from is empty", name));
+    }
+
+    Ok(schemas)
+}
+
+/// The value with the placeholder replaced by the schema in every string
+fn replace(value: &Value, schema: &str) -> Value {
+    match value {
+        Value::String(s) => Value::String(s.replace(PLACEHOLDER, schema)),
+        Value::Sequence(values) => {
+            Value::Sequence(values.iter().map(|v| replace(v, schema)).collect())
+        }
+        Value::Mapping(fields) => Value::Mapping(
+            fields
+                .iter()
+                .map(|(k, v)| (k.clone(), replace(v, schema)))
+                .collect(),
+        ),
+        value => value.clone(),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use indoc::indoc;
+
+    fn expanded(yaml: &str) -> Result<Value> {
+        let mut config: Value = serde_yaml::from_str(yaml).unwrap();
+        expand(&mut config)?;
+
+        Ok(config)
+    }
+
+    #[test]
+    fn test_expand() {
+        let config = expanded(indoc! {r#"
+            roles:
+              - name: read_{schema}
+                type: table
+                for_each:
+                  schemas_from: [marts, staging]
+                grants: [SELECT]
+                schemas: ["{schema}"]
+                tables: [ALL, "-{schema}.secrets"]
+              - name: role_database
+                type: database
+                grants: [CREATE]
+                databases: [db]
+        "#})
+        .unwrap();
+
+        assert_eq!(
+            config["roles"],
+            serde_yaml::from_str::<Value>(indoc! {r#"
+                - name: read_marts
+                  type: table
+                  grants: [SELECT]
+                  schemas: [marts]
+                  tables: [ALL, -marts.secrets]
+                - name: read_staging
+                  type: table
+                  grants: [SELECT]
+                  schemas: [staging]
+                  tables: [ALL, -staging.secrets]
+                - name: role_database
+                  type: database
+                  grants: [CREATE]
+                  databases: [db]
+            "#})
+            .unwrap()
+        );
+    }
+
+    #[test]
+    fn test_expand_errors() {
+        let err = |yaml: &str| expanded(yaml).unwrap_err().to_string();
+
+        assert_eq!(
+            err("roles: [{name: read, for_each: {schemas_from: [a]}}]"),
+            "role read: the name of a for_each role needs {schema}, each role must have its own name"
+        );
+        assert_eq!(
+            err("roles: [{name: 'read_{schema}', for_each: {databases_from: [a]}}]"),
+            "role read_{schema}: unknown for_each key \"databases_from\", expected: schemas_from"
+        );
+        assert_eq!(
+            err("roles: [{name: 'read_{schema}', for_each: {schemas_from: []}}]"),
+            "role read_{schema}: for_each.schemas_from is empty"
+        );
+        assert_eq!(
+            err("roles: [{name: 'read_{schema}', for_each: [a]}]"),
+            "role read_{schema}: for_each must be a mapping"
+        );
+    }
+}
@@ -0,0 +1,118 @@
+use super::ident;
+use super::role::{Grantee, RoleValidate};
+use anyhow::{anyhow, Result};
+use serde::{Deserialize, Serialize};
+
+/// Role Warehouse Level, only supported by Snowflake.
+///
+/// For example:
+///
+/// ```yaml
+/// - name: role_warehouse_level
+///   type: warehouse
+///   grants:
+///     - USAGE
+///     - OPERATE
+///   warehouses:
+///     - compute_wh
+/// ```
+#[derive(Debug, Serialize, Deserialize, Clone, PartialEq, Eq)]
+pub struct RoleWarehouseLevel {
+    pub name: String,
+    pub grants: Vec<String>,
+    pub warehouses: Vec<String>,
+}
+
+impl RoleWarehouseLevel {
+    /// Generate the role SQL for a user, see [Self::to_sql_for]
+    pub fn to_sql(&self, user: &str) -> String {
+        self.to_sql_for(Grantee::User(user))
+    }
+
+    /// Generate role warehouse to SQL.
+    ///
+    /// ```sql
+    /// GRANT { { USAGE | OPERATE | MONITOR | MODIFY } [,...] | ALL [ PRIVILEGES ] }
+    /// ON WAREHOUSE warehouse_name
+    /// TO ROLE role_name
+    /// ```
+    pub fn to_sql_for(&self, grantee: Grantee) -> String {
+        let grants = if self.grants.is_empty() || self.grants.contains(&"ALL".to_string()) {
+            "ALL PRIVILEGES".to_string()
+        } else {
+            self.grants.join(", ")
+        };
+
+        format!(
+            "GRANT {} ON WAREHOUSE {} TO {};",
+            grants,
+            ident::quote_list(&self.warehouses),
+            grantee.to_sql()
+        )
+    }
+
+    /// The objects of the GRANT statement of the role, each with whether it
+    /// is revoked, e.g. `(false, "WAREHOUSE compute_wh")`
+    pub fn objects(&self) -> Vec<(bool, String)> {
+        self.warehouses
+            .iter()
+            .map(|w| (false, format!("WAREHOUSE {}", ident::quote(w))))
+            .collect()
+    }
+}
+
+impl RoleValidate for RoleWarehouseLevel {
+    fn validate(&self) -> Result<()> {
+        if self.name.is_empty() {
+            return Err(anyhow!("role name is empty"));
+        }
+
+        if self.warehouses.is_empty() {
+            return Err(anyhow!("role warehouses is empty"));
+        }
+
+        // Check valid grants: USAGE, OPERATE, MONITOR, MODIFY, ALL
+        let valid_grants = ["USAGE", "OPERATE", "MONITOR", "MODIFY", "ALL"];
+        for grant in &self.grants {
+            if !valid_grants.contains(&&grant[..]) {
+                return Err(anyhow!(
+                    "invalid grant: {}, expected: {:?}",
+                    grant,
+                    valid_grants
+                ));
+            }
+        }
+
+        if self.grants.is_empty() {
+            return Err(anyhow!("role grants is empty"));
+        }
+
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_role_warehouse_level() {
+        let role = RoleWarehouseLevel {
+            name: "role_warehouse_level".to_string(),
+            grants: vec!["USAGE".to_string(), "OPERATE".to_string()],
+            warehouses: vec!["compute_wh".to_string()],
+        };
+
+        assert!(role.validate().is_ok());
+        assert_eq!(
+            role.objects(),
+            vec![(false, "WAREHOUSE compute_wh".to_string())]
+        );
+
+        let role = RoleWarehouseLevel {
+            grants: vec!["CREATE".to_string()],
+            ..role
+        };
+        assert!(role.validate().is_err());
+    }
+}
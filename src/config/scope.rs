@@ -0,0 +1,117 @@
+use super::ident;
+use super::{Role, User};
+use crate::patterns;
+use anyhow::{anyhow, Result};
+use serde::{Deserialize, Serialize};
+
+/// Scope of a config file: the schemas and users it manages, so that grant
+/// can coexist with other tooling managing other schemas of the same cluster.
+///
+/// For example:
+///
+/// ```yaml
+/// scope:
+///   schemas:
+///     - analytics
+///     - marts
+///   users:
+///     - duyet
+/// ```
+///
+/// Everything outside of the scope is ignored by `apply` and `inspect`.
+/// A list which is not set does not restrict anything.
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq, Eq, Default)]
+pub struct Scope {
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub schemas: Option<Vec<String>>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub users: Option<Vec<String>>,
+}
+
+impl Scope {
+    pub fn is_empty(&self) -> bool {
+        self.schemas.is_none() && self.users.is_none()
+    }
+
+    /// Whether the schema, named as in the catalog, is managed
+    pub fn has_schema(&self, schema: &str) -> bool {
+        contains(&self.schemas, schema)
+    }
+
+    /// Whether the user, named as in the catalog, is managed
+    pub fn has_user(&self, user: &str) -> bool {
+        contains(&self.users, user)
+    }
+
+    /// The roles and users of the config must be in the scope
+    pub fn validate(&self, roles: &[Role], users: &[User]) -> Result<()> {
+        for user in users.iter().filter(|u| !u.is_public()) {
+            if !self.has_user(&ident::fold(&user.name)) {
+                return Err(anyhow!("user {} is outside of the scope", user.name));
+            }
+        }
+
+        for role in roles {
+            // the schemas matched by a pattern are filtered by the scope
+            // when it is expanded, see crate::patterns
+            for schema in role.referenced_schemas() {
+                if !patterns::is_pattern(&schema) && !self.has_schema(&ident::fold(&schema)) {
+                    return Err(anyhow!(
+                        "role {}: schema {} is outside of the scope",
+                        role.get_name(),
+                        schema
+                    ));
+                }
+            }
+        }
+
+        Ok(())
+    }
+}
+
+fn contains(names: &Option<Vec<String>>, name: &str) -> bool {
+    match names {
+        Some(names) => names.iter().any(|n| ident::fold(n) == name),
+        None => true,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::config::role::RoleTableLevel;
+
+    fn scope() -> Scope {
+        Scope {
+            schemas: Some(vec!["Analytics".to_string()]),
+            users: None,
+        }
+    }
+
+    #[test]
+    fn test_scope_has() {
+        let scope = scope();
+
+        assert!(scope.has_schema("analytics"));
+        assert!(!scope.has_schema("public"));
+        assert!(scope.has_user("anyone"));
+        assert!(Scope::default().has_schema("public"));
+    }
+
+    #[test]
+    fn test_scope_validate() {
+        let role = Role::Table(RoleTableLevel {
+            name: "role_table".to_string(),
+            grants: vec!["SELECT".to_string()],
+            schemas: vec!["analytics".to_string()],
+            tables: vec!["ALL".to_string(), "-public.secret".to_string()],
+            database: None,
+        });
+
+        let err = scope().validate(&[role], &[]).unwrap_err();
+        assert_eq!(
+            err.to_string(),
+            "role role_table: schema public is outside of the scope"
+        );
+    }
+}
@@ -1,24 +1,107 @@
+use super::{ident, Dialect, UserAttributes, UserSettings};
 use anyhow::{anyhow, Result};
+use serde::de::{self, Deserializer};
 use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+
+/// When the password of the config is set on the user
+#[derive(Debug, Serialize, Clone, Copy, PartialEq, Eq)]
+#[serde(rename_all = "snake_case")]
+pub enum PasswordPolicy {
+    /// Only when the user is created, the default
+    OnCreate,
+    /// At every apply, e.g. to rotate it
+    Always,
+}
+
+/// Also reads the booleans of the deprecated `update_password`
+impl<'de> Deserialize<'de> for PasswordPolicy {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        match serde_yaml::Value::deserialize(deserializer)? {
+            serde_yaml::Value::Bool(true) => Ok(PasswordPolicy::Always),
+            serde_yaml::Value::Bool(false) => Ok(PasswordPolicy::OnCreate),
+            serde_yaml::Value::String(s) if s == "always" => Ok(PasswordPolicy::Always),
+            serde_yaml::Value::String(s) if s == "on_create" => Ok(PasswordPolicy::OnCreate),
+            value => Err(de::Error::custom(format!(
+                "invalid password_policy {:?}, expected always or on_create",
+                value
+            ))),
+        }
+    }
+}
 
 #[derive(Debug, Serialize, Deserialize, Clone, PartialEq, Eq)]
 pub struct User {
     pub name: String,
     // password is optional
+    #[serde(default, skip_serializing_if = "Option::is_none")]
     pub password: Option<String>,
-    // Need to update password at anytime? by default is false
-    pub update_password: Option<bool>,
+    // Need to update password at anytime? by default only on create.
+    // Renamed from `update_password`, see `deprecation`
+    #[serde(
+        default,
+        alias = "update_password",
+        skip_serializing_if = "Option::is_none"
+    )]
+    pub password_policy: Option<PasswordPolicy>,
+    #[serde(flatten)]
+    pub attributes: UserAttributes,
+    /// The session defaults of the user, see [UserSettings]
+    #[serde(default, skip_serializing_if = "UserSettings::is_empty")]
+    pub settings: UserSettings,
     pub roles: Vec<String>,
+    /// The roles whose privileges are revoked from the user, e.g. the ones
+    /// a former team granted it, see [Role::to_revoke_sql_for]
+    ///
+    /// [Role::to_revoke_sql_for]: super::Role::to_revoke_sql_for
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub revokes: Vec<String>,
 }
 
 impl User {
-    pub fn to_sql_create(&self) -> String {
+    /// The `CREATE USER` with the password and the attributes in the dialect
+    pub fn to_sql_create(&self, dialect: Dialect) -> String {
         let password = match &self.password {
             Some(p) => format!(" WITH PASSWORD '{}'", p),
             None => "".to_string(),
         };
 
-        format!("CREATE USER {}{};", self.name, password)
+        let attributes = match self.attributes.to_sql(dialect) {
+            options if options.is_empty() => options,
+            options => format!(" {}", options),
+        };
+
+        format!(
+            "CREATE USER {}{}{};",
+            ident::quote(&self.name),
+            password,
+            attributes
+        )
+    }
+
+    /// The `ALTER USER` of the attributes which differ from the `current`
+    /// ones of the cluster, None if there is none
+    pub fn to_sql_alter(&self, current: &UserAttributes, dialect: Dialect) -> Option<String> {
+        let changed = self.attributes.diff(current);
+        if changed.is_empty() {
+            return None;
+        }
+
+        Some(format!(
+            "ALTER USER {} {};",
+            ident::quote(&self.name),
+            changed.to_sql(dialect)
+        ))
+    }
+
+    /// The `ALTER USER ... SET` and `RESET` of the settings which differ
+    /// from the `current` ones of the cluster
+    pub fn to_sql_settings(&self, current: &BTreeMap<String, String>) -> Vec<String> {
+        self.settings
+            .diff(current)
+            .iter()
+            .map(|change| change.to_sql(&self.name))
+            .collect()
     }
 
     pub fn to_sql_update(&self) -> String {
@@ -27,11 +110,22 @@ impl User {
             None => "".to_string(),
         };
 
-        format!("ALTER USER {}{};", self.name, password)
+        format!("ALTER USER {}{};", ident::quote(&self.name), password)
     }
 
     pub fn to_sql_drop(&self) -> String {
-        format!("DROP USER IF EXISTS {};", self.name)
+        format!("DROP USER IF EXISTS {};", ident::quote(&self.name))
+    }
+
+    /// Whether the password is set at every apply, not only on create
+    pub fn updates_password(&self) -> bool {
+        self.password_policy == Some(PasswordPolicy::Always)
+    }
+
+    /// Whether the user is `PUBLIC`, every role of the cluster: its roles are
+    /// granted `TO PUBLIC`, it is neither created nor dropped
+    pub fn is_public(&self) -> bool {
+        ident::fold(&self.name) == "public"
     }
 
     pub fn validate(&self) -> Result<()> {
@@ -39,6 +133,20 @@ impl User {
             return Err(anyhow!("user name is empty"));
         }
 
+        if self.is_public() && (self.password.is_some() || self.password_policy.is_some()) {
+            return Err(anyhow!("user {}: PUBLIC has no password", self.name));
+        }
+        if self.is_public() && !self.settings.is_empty() {
+            return Err(anyhow!("user {}: PUBLIC has no settings", self.name));
+        }
+
+        self.attributes
+            .validate()
+            .map_err(|e| anyhow!("user {}: {}", self.name, e))?;
+        self.settings
+            .validate()
+            .map_err(|e| anyhow!("user {}: {}", self.name, e))?;
+
         Ok(())
     }
 
@@ -63,16 +171,48 @@ impl User {
 mod tests {
     use super::*;
 
+    #[test]
+    fn test_user_password_policy() {
+        let user: User = serde_yaml::from_str("name: duyet\nroles: []").unwrap();
+        assert_eq!(user.password_policy, None);
+        assert!(!user.updates_password());
+
+        let user: User =
+            serde_yaml::from_str("name: duyet\npassword_policy: always\nroles: []").unwrap();
+        assert!(user.updates_password());
+
+        // The deprecated name and its booleans
+        let user: User =
+            serde_yaml::from_str("name: duyet\nupdate_password: true\nroles: []").unwrap();
+        assert_eq!(user.password_policy, Some(PasswordPolicy::Always));
+        let user: User =
+            serde_yaml::from_str("name: duyet\nupdate_password: false\nroles: []").unwrap();
+        assert_eq!(user.password_policy, Some(PasswordPolicy::OnCreate));
+        assert_eq!(
+            serde_yaml::to_string(&user).unwrap(),
+            "name: duyet\npassword_policy: on_create\nroles: []\n"
+        );
+
+        let err = serde_yaml::from_str::<User>("name: duyet\npassword_policy: never\nroles: []");
+        assert!(err
+            .unwrap_err()
+            .to_string()
+            .contains("expected always or on_create"));
+    }
+
     #[test]
     fn test_user_to_sql_create() {
         let user = User {
             name: "test".to_string(),
             password: Some("test".to_string()),
-            update_password: Some(true),
+            password_policy: Some(PasswordPolicy::Always),
+            attributes: Default::default(),
+            settings: Default::default(),
             roles: vec!["test".to_string()],
+            revokes: vec![],
         };
 
-        let sql = user.to_sql_create();
+        let sql = user.to_sql_create(Dialect::Postgres);
         assert_eq!(sql, "CREATE USER test WITH PASSWORD 'test';");
     }
 
@@ -81,21 +221,58 @@ mod tests {
         let user = User {
             name: "test".to_string(),
             password: Some("test".to_string()),
-            update_password: Some(true),
+            password_policy: Some(PasswordPolicy::Always),
+            attributes: Default::default(),
+            settings: Default::default(),
             roles: vec!["test".to_string()],
+            revokes: vec![],
         };
 
         let sql = user.to_sql_update();
         assert_eq!(sql, "ALTER USER test WITH PASSWORD 'test';");
     }
 
+    #[test]
+    fn test_user_attributes() {
+        let user: User = serde_yaml::from_str(
+            "name: analyst\npassword: test\nsyslog_access: unrestricted\nsession_timeout: 3600\nroles: []",
+        )
+        .unwrap();
+        assert_eq!(
+            user.to_sql_create(Dialect::Postgres),
+            "CREATE USER analyst WITH PASSWORD 'test' SYSLOG ACCESS UNRESTRICTED SESSION TIMEOUT 3600;"
+        );
+
+        let current = UserAttributes {
+            session_timeout: Some(3600),
+            ..Default::default()
+        };
+        assert_eq!(
+            user.to_sql_alter(&current, Dialect::Redshift).unwrap(),
+            "ALTER USER analyst SYSLOG ACCESS UNRESTRICTED;"
+        );
+        assert_eq!(user.to_sql_alter(&user.attributes, Dialect::Redshift), None);
+
+        let err = serde_yaml::from_str::<User>("name: analyst\nsession_timeout: 10\nroles: []")
+            .unwrap()
+            .validate()
+            .unwrap_err();
+        assert_eq!(
+            err.to_string(),
+            "user analyst: session_timeout must be between 60 and 1728000 seconds"
+        );
+    }
+
     #[test]
     fn test_user_to_sql_drop() {
         let user = User {
             name: "test".to_string(),
             password: Some("test".to_string()),
-            update_password: Some(true),
+            password_policy: Some(PasswordPolicy::Always),
+            attributes: Default::default(),
+            settings: Default::default(),
             roles: vec!["test".to_string()],
+            revokes: vec![],
         };
 
         let sql = user.to_sql_drop();
@@ -107,8 +284,11 @@ mod tests {
         let user = User {
             name: "test".to_string(),
             password: Some("test".to_string()),
-            update_password: Some(true),
+            password_policy: Some(PasswordPolicy::Always),
+            attributes: Default::default(),
+            settings: Default::default(),
             roles: vec!["test".to_string()],
+            revokes: vec![],
         };
 
         assert!(user.validate().is_ok());
@@ -119,8 +299,11 @@ mod tests {
         let user = User {
             name: "".to_string(),
             password: Some("test".to_string()),
-            update_password: Some(true),
+            password_policy: Some(PasswordPolicy::Always),
+            attributes: Default::default(),
+            settings: Default::default(),
             roles: vec!["test".to_string()],
+            revokes: vec![],
         };
 
         assert!(user.validate().is_err());
@@ -131,8 +314,11 @@ mod tests {
         let user = User {
             name: "test".to_string(),
             password: None,
-            update_password: Some(true),
+            password_policy: Some(PasswordPolicy::Always),
+            attributes: Default::default(),
+            settings: Default::default(),
             roles: vec!["test".to_string()],
+            revokes: vec![],
         };
 
         assert!(user.validate().is_ok());
@@ -143,8 +329,11 @@ mod tests {
         let user = User {
             name: "test".to_string(),
             password: Some("test".to_string()),
-            update_password: Some(true),
+            password_policy: Some(PasswordPolicy::Always),
+            attributes: Default::default(),
+            settings: Default::default(),
             roles: vec![],
+            revokes: vec![],
         };
 
         assert!(user.validate().is_ok());
@@ -155,8 +344,11 @@ mod tests {
         let user = User {
             name: "test".to_string(),
             password: Some("test".to_string()),
-            update_password: Some(true),
+            password_policy: Some(PasswordPolicy::Always),
+            attributes: Default::default(),
+            settings: Default::default(),
             roles: vec!["test".to_string()],
+            revokes: vec![],
         };
 
         assert_eq!(user.get_name(), "test");
@@ -167,8 +359,11 @@ mod tests {
         let user = User {
             name: "test".to_string(),
             password: Some("test".to_string()),
-            update_password: Some(true),
+            password_policy: Some(PasswordPolicy::Always),
+            attributes: Default::default(),
+            settings: Default::default(),
             roles: vec!["test".to_string()],
+            revokes: vec![],
         };
 
         assert_eq!(user.get_password(), "test");
@@ -179,8 +374,11 @@ mod tests {
         let user = User {
             name: "test".to_string(),
             password: Some("test".to_string()),
-            update_password: Some(true),
+            password_policy: Some(PasswordPolicy::Always),
+            attributes: Default::default(),
+            settings: Default::default(),
             roles: vec!["test".to_string()],
+            revokes: vec![],
         };
 
         assert_eq!(user.get_roles(), vec!["test".to_string()]);
@@ -0,0 +1,337 @@
+use super::Dialect;
+use anyhow::{anyhow, Result};
+use jiff::Timestamp;
+use serde::{Deserialize, Serialize};
+use std::fmt;
+
+/// Whether a Redshift user sees the rows of the other users in the system
+/// tables and views
+#[derive(Debug, Serialize, Deserialize, Clone, Copy, PartialEq, Eq)]
+#[serde(rename_all = "snake_case")]
+pub enum SyslogAccess {
+    /// Only its own rows, the default
+    Restricted,
+    Unrestricted,
+}
+
+impl fmt::Display for SyslogAccess {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            SyslogAccess::Restricted => write!(f, "RESTRICTED"),
+            SyslogAccess::Unrestricted => write!(f, "UNRESTRICTED"),
+        }
+    }
+}
+
+impl SyslogAccess {
+    /// The value of `svl_user_info.syslogaccess`
+    pub fn parse(value: &str) -> Option<Self> {
+        match value.trim() {
+            "RESTRICTED" => Some(SyslogAccess::Restricted),
+            "UNRESTRICTED" => Some(SyslogAccess::Unrestricted),
+            _ => None,
+        }
+    }
+}
+
+/// Session timeouts of Redshift, in seconds
+const SESSION_TIMEOUT: (u32, u32) = (60, 1_728_000);
+
+/// The attributes of a user other than its password, set when the user is
+/// created and altered when they differ from the cluster. An attribute which
+/// is not in the config is left as it is.
+///
+/// For example:
+///
+/// ```yaml
+/// users:
+///   - name: analyst
+///     createdb: false
+///     superuser: false
+///     connection_limit: 10
+///     valid_until: "2027-01-01T00:00:00Z"
+///     # Redshift only
+///     syslog_access: unrestricted
+///     session_timeout: 3600
+///     roles: []
+/// ```
+#[derive(Debug, Serialize, Deserialize, Clone, Default, PartialEq, Eq)]
+pub struct UserAttributes {
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub createdb: Option<bool>,
+    /// `CREATEUSER` on Redshift
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub superuser: Option<bool>,
+    /// Maximum number of concurrent connections, -1 for no limit
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub connection_limit: Option<i32>,
+    /// Expiry of the password, `infinity` for none
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub valid_until: Option<String>,
+    /// Redshift only, see [SyslogAccess]
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub syslog_access: Option<SyslogAccess>,
+    /// Redshift only, seconds of inactivity before a session is closed
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub session_timeout: Option<u32>,
+}
+
+impl UserAttributes {
+    /// The options of `CREATE USER` and `ALTER USER` in the dialect, e.g.
+    /// `NOCREATEDB CONNECTION LIMIT 10`
+    pub fn to_sql(&self, dialect: Dialect) -> String {
+        let mut options = vec![];
+        if let Some(createdb) = self.createdb {
+            options.push(if createdb { "CREATEDB" } else { "NOCREATEDB" }.to_string());
+        }
+        if let Some(superuser) = self.superuser {
+            let option = match (dialect.is_redshift(), superuser) {
+                (true, true) => "CREATEUSER",
+                (true, false) => "NOCREATEUSER",
+                (false, true) => "SUPERUSER",
+                (false, false) => "NOSUPERUSER",
+            };
+            options.push(option.to_string());
+        }
+        if let Some(limit) = self.connection_limit {
+            match limit {
+                -1 if dialect.is_redshift() => {
+                    options.push("CONNECTION LIMIT UNLIMITED".to_string())
+                }
+                limit => options.push(format!("CONNECTION LIMIT {}", limit)),
+            }
+        }
+        if let Some(valid_until) = &self.valid_until {
+            options.push(format!("VALID UNTIL '{}'", valid_until));
+        }
+        if let Some(access) = self.syslog_access {
+            options.push(format!("SYSLOG ACCESS {}", access));
+        }
+        if let Some(timeout) = self.session_timeout {
+            options.push(format!("SESSION TIMEOUT {}", timeout));
+        }
+
+        options.join(" ")
+    }
+
+    /// The attributes of the config which differ from the `current` ones of
+    /// the cluster, the others are None
+    pub fn diff(&self, current: &UserAttributes) -> UserAttributes {
+        UserAttributes {
+            createdb: self.createdb.filter(|v| current.createdb != Some(*v)),
+            superuser: self.superuser.filter(|v| current.superuser != Some(*v)),
+            connection_limit: self
+                .connection_limit
+                .filter(|v| current.connection_limit != Some(*v)),
+            valid_until: self
+                .valid_until
+                .clone()
+                .filter(|v| !same_expiry(v, current.valid_until.as_deref())),
+            syslog_access: self
+                .syslog_access
+                .filter(|v| current.syslog_access != Some(*v)),
+            session_timeout: self
+                .session_timeout
+                .filter(|v| current.session_timeout != Some(*v)),
+        }
+    }
+
+    pub fn is_empty(&self) -> bool {
+        *self == UserAttributes::default()
+    }
+
+    /// The names of the Redshift attributes which are set
+    pub fn redshift_only(&self) -> Vec<&'static str> {
+        let mut names = vec![];
+        if self.syslog_access.is_some() {
+            names.push("syslog_access");
+        }
+        if self.session_timeout.is_some() {
+            names.push("session_timeout");
+        }
+
+        names
+    }
+
+    pub fn validate(&self) -> Result<()> {
+        if let Some(limit) = self.connection_limit {
+            if limit < -1 {
+                return Err(anyhow!("connection_limit must be -1 (no limit) or more"));
+            }
+        }
+
+        if let Some(valid_until) = &self.valid_until {
+            if valid_until != "infinity" && valid_until.parse::<Timestamp>().is_err() {
+                return Err(anyhow!(
+                    "valid_until {:?} must be infinity or a timestamp with an offset, e.g. 2027-01-01T00:00:00Z",
+                    valid_until
+                ));
+            }
+        }
+
+        if let Some(timeout) = self.session_timeout {
+            let (min, max) = SESSION_TIMEOUT;
+            if !(min..=max).contains(&timeout) {
+                return Err(anyhow!(
+                    "session_timeout must be between {} and {} seconds",
+                    min,
+                    max
+                ));
+            }
+        }
+
+        Ok(())
+    }
+}
+
+/// The attributes as in the config, e.g. `createdb: false, connection_limit: 10`
+impl fmt::Display for UserAttributes {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let mut fields = vec![];
+        if let Some(createdb) = self.createdb {
+            fields.push(format!("createdb: {}", createdb));
+        }
+        if let Some(superuser) = self.superuser {
+            fields.push(format!("superuser: {}", superuser));
+        }
+        if let Some(limit) = self.connection_limit {
+            fields.push(format!("connection_limit: {}", limit));
+        }
+        if let Some(valid_until) = &self.valid_until {
+            fields.push(format!("valid_until: {}", valid_until));
+        }
+        if let Some(access) = self.syslog_access {
+            fields.push(format!(
+                "syslog_access: {}",
+                access.to_string().to_lowercase()
+            ));
+        }
+        if let Some(timeout) = self.session_timeout {
+            fields.push(format!("session_timeout: {}", timeout));
+        }
+
+        write!(f, "{}", fields.join(", "))
+    }
+}
+
+/// Whether the `valid_until` of the config is the one of the catalog, e.g.
+/// `2027-01-01T00:00:00Z` and `2027-01-01 00:00:00+00`. No expiry is NULL or
+/// `infinity`.
+fn same_expiry(config: &str, current: Option<&str>) -> bool {
+    let current = current.unwrap_or("infinity");
+    if config == "infinity" || current == "infinity" {
+        return config == current;
+    }
+
+    match (config.parse::<Timestamp>(), parse_catalog(current)) {
+        (Ok(config), Some(current)) => config == current,
+        _ => false,
+    }
+}
+
+/// A `timestamptz` as printed by the server, e.g. `2027-01-01 00:00:00+00`
+fn parse_catalog(value: &str) -> Option<Timestamp> {
+    if let Ok(timestamp) = value.parse() {
+        return Some(timestamp);
+    }
+
+    // The offsets are printed without minutes when they have none
+    let (time, offset) = value.rsplit_once(['+', '-'])?;
+    let sign = &value[time.len()..time.len() + 1];
+    format!("{}{}{}:00", time, sign, offset).parse().ok()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn attributes(yaml: &str) -> UserAttributes {
+        serde_yaml::from_str(yaml).unwrap()
+    }
+
+    #[test]
+    fn test_to_sql() {
+        let redshift = attributes("syslog_access: unrestricted\nsession_timeout: 3600");
+        assert_eq!(
+            redshift.to_sql(Dialect::Redshift),
+            "SYSLOG ACCESS UNRESTRICTED SESSION TIMEOUT 3600"
+        );
+        assert_eq!(UserAttributes::default().to_sql(Dialect::Postgres), "");
+
+        let attributes = attributes(
+            "createdb: false\nsuperuser: true\nconnection_limit: -1\nvalid_until: infinity",
+        );
+        assert_eq!(
+            attributes.to_sql(Dialect::Postgres),
+            "NOCREATEDB SUPERUSER CONNECTION LIMIT -1 VALID UNTIL 'infinity'"
+        );
+        assert_eq!(
+            attributes.to_sql(Dialect::Redshift),
+            "NOCREATEDB CREATEUSER CONNECTION LIMIT UNLIMITED VALID UNTIL 'infinity'"
+        );
+        assert_eq!(
+            attributes.to_string(),
+            "createdb: false, superuser: true, connection_limit: -1, valid_until: infinity"
+        );
+    }
+
+    #[test]
+    fn test_diff() {
+        let config = attributes(
+            "createdb: true\nconnection_limit: 10\nsyslog_access: unrestricted\nsession_timeout: 3600",
+        );
+        let current = UserAttributes {
+            createdb: Some(false),
+            superuser: Some(false),
+            connection_limit: Some(10),
+            valid_until: None,
+            syslog_access: Some(SyslogAccess::Unrestricted),
+            session_timeout: None,
+        };
+
+        assert_eq!(
+            config.diff(&current),
+            attributes("createdb: true\nsession_timeout: 3600")
+        );
+        assert!(config.diff(&config).is_empty());
+        assert!(UserAttributes::default().diff(&current).is_empty());
+    }
+
+    #[test]
+    fn test_same_expiry() {
+        assert!(same_expiry(
+            "2027-01-01T00:00:00Z",
+            Some("2027-01-01 00:00:00+00")
+        ));
+        assert!(same_expiry(
+            "2027-01-01T07:00:00+07:00",
+            Some("2027-01-01 00:00:00+00")
+        ));
+        assert!(same_expiry(
+            "2027-01-01T00:00:00Z",
+            Some("2026-12-31 19:00:00-05")
+        ));
+        assert!(!same_expiry(
+            "2027-01-01T00:00:00Z",
+            Some("2028-01-01 00:00:00+00")
+        ));
+        assert!(same_expiry("infinity", None));
+        assert!(!same_expiry("2027-01-01T00:00:00Z", None));
+    }
+
+    #[test]
+    fn test_validate() {
+        assert_eq!(
+            attributes("session_timeout: 30")
+                .validate()
+                .unwrap_err()
+                .to_string(),
+            "session_timeout must be between 60 and 1728000 seconds"
+        );
+        assert!(attributes("connection_limit: -2").validate().is_err());
+        assert!(attributes("valid_until: 2027-01-01").validate().is_err());
+        assert!(attributes("valid_until: 2027-01-01T00:00:00Z")
+            .validate()
+            .is_ok());
+    }
+}
@@ -0,0 +1,306 @@
+use anyhow::{anyhow, Result};
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+
+/// Mapping of the directory identities to database user names, for the users
+/// imported with `grant users import`.
+///
+/// For example:
+///
+/// ```yaml
+/// user_mapping:
+///   username_template: "{email_local}_{team}"
+///   lowercase: true
+///   max_length: 63
+/// ```
+///
+/// The placeholders are the columns of the directory export, and
+/// `email_local` and `email_domain`, the parts of its `email` column. The
+/// rendered name is lowercased (unless `lowercase: false`), the characters
+/// other than ASCII letters, digits and `_` are replaced with `_`, and it is
+/// truncated to `max_length` bytes, 63 by default as in Postgres.
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq, Eq)]
+pub struct UserMapping {
+    pub username_template: String,
+    #[serde(default = "default_lowercase")]
+    pub lowercase: bool,
+    #[serde(default = "default_max_length")]
+    pub max_length: usize,
+}
+
+/// Longest user name of Redshift, Postgres truncates at 63 bytes
+const MAX_LENGTH: usize = 127;
+
+fn default_lowercase() -> bool {
+    true
+}
+
+fn default_max_length() -> usize {
+    63
+}
+
+/// An identity of the directory: its fields by column name
+pub type Identity = BTreeMap<String, String>;
+
+impl UserMapping {
+    /// The user name of the identity, see [UserMapping]
+    pub fn username(&self, identity: &Identity) -> Result<String> {
+        let mut fields = identity.clone();
+        if let Some((local, domain)) = identity.get("email").and_then(|e| e.split_once('@')) {
+            fields.insert("email_local".to_string(), local.to_string());
+            fields.insert("email_domain".to_string(), domain.to_string());
+        }
+
+        let mut rendered = String::new();
+        for (literal, placeholder) in self.parts()? {
+            rendered += literal;
+            if let Some(placeholder) = placeholder {
+                match fields.get(placeholder) {
+                    Some(value) => rendered += value,
+                    None => {
+                        return Err(anyhow!(
+                            "user_mapping: no {} field for {{{}}}",
+                            placeholder,
+                            placeholder
+                        ))
+                    }
+                }
+            }
+        }
+
+        let name = self.normalize(&rendered);
+        if name.is_empty() {
+            return Err(anyhow!(
+                "user_mapping: {:?} renders to an empty user name",
+                rendered
+            ));
+        }
+
+        Ok(name)
+    }
+
+    /// Apply the rules: case, invalid characters, leading digit and length
+    fn normalize(&self, rendered: &str) -> String {
+        let mut name = String::new();
+        for c in rendered.chars() {
+            let c = match c {
+                c if c.is_ascii_alphanumeric() => c,
+                _ => '_',
+            };
+            // `a  b` is `a_b`, not `a__b`
+            if !(c == '_' && name.ends_with('_')) {
+                name.push(c);
+            }
+        }
+        if self.lowercase {
+            name = name.to_ascii_lowercase();
+        }
+
+        let mut name = name.trim_matches('_').to_string();
+        if name.starts_with(|c: char| c.is_ascii_digit()) {
+            name.insert(0, '_');
+        }
+        name.truncate(self.max_length);
+
+        name.trim_end_matches('_').to_string()
+    }
+
+    /// The template split into its literals, each followed by a placeholder
+    /// but the last one
+    fn parts(&self) -> Result<Vec<(&str, Option<&str>)>> {
+        let mut parts = vec![];
+        let mut rest = self.username_template.as_str();
+
+        while let Some(start) = rest.find('{') {
+            let end = rest[start..]
+                .find('}')
+                .map(|end| start + end)
+                .ok_or_else(|| {
+                    anyhow!("user_mapping: unclosed {{ in {}", self.username_template)
+                })?;
+            let placeholder = &rest[start + 1..end];
+            if placeholder.is_empty() || placeholder.contains('{') {
+                return Err(anyhow!(
+                    "user_mapping: invalid placeholder in {}",
+                    self.username_template
+                ));
+            }
+
+            parts.push((&rest[..start], Some(placeholder)));
+            rest = &rest[end + 1..];
+        }
+        parts.push((rest, None));
+
+        Ok(parts)
+    }
+
+    pub fn validate(&self) -> Result<()> {
+        if self.username_template.trim().is_empty() {
+            return Err(anyhow!("user_mapping: username_template is empty"));
+        }
+        if self
+            .parts()?
+            .iter()
+            .all(|(_, placeholder)| placeholder.is_none())
+        {
+            return Err(anyhow!(
+                "user_mapping: username_template has no placeholder, every user would have the same name"
+            ));
+        }
+        if self.max_length == 0 || self.max_length > MAX_LENGTH {
+            return Err(anyhow!(
+                "user_mapping: max_length must be between 1 and {}",
+                MAX_LENGTH
+            ));
+        }
+
+        Ok(())
+    }
+
+    /// The user names of the identities, in order. Two identities with the
+    /// same user name are an error rather than a single database user.
+    pub fn usernames(&self, identities: &[Identity]) -> Result<Vec<String>> {
+        let mut names: Vec<String> = vec![];
+
+        for (i, identity) in identities.iter().enumerate() {
+            let name = self
+                .username(identity)
+                .map_err(|e| anyhow!("{} ({})", e, describe(identity)))?;
+            if let Some(j) = names.iter().position(|n| *n == name) {
+                return Err(anyhow!(
+                    "user_mapping: {} and {} both map to user {}, \
+                     add a field to username_template to tell them apart",
+                    describe(&identities[j]),
+                    describe(&identities[i]),
+                    name
+                ));
+            }
+            names.push(name);
+        }
+
+        Ok(names)
+    }
+}
+
+/// The identity in errors: its email, or its fields
+fn describe(identity: &Identity) -> String {
+    match identity.get("email") {
+        Some(email) => email.clone(),
+        None => identity
+            .iter()
+            .map(|(k, v)| format!("{}={}", k, v))
+            .collect::<Vec<_>>()
+            .join(" "),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn mapping(template: &str) -> UserMapping {
+        UserMapping {
+            username_template: template.to_string(),
+            lowercase: default_lowercase(),
+            max_length: default_max_length(),
+        }
+    }
+
+    fn identity(email: &str, team: &str) -> Identity {
+        [("email", email), ("team", team)]
+            .iter()
+            .map(|(k, v)| (k.to_string(), v.to_string()))
+            .collect()
+    }
+
+    #[test]
+    fn test_username() {
+        let template = mapping("{email_local}_{team}");
+
+        assert_eq!(
+            template
+                .username(&identity("Jane.Doe@corp.com", "Data Eng"))
+                .unwrap(),
+            "jane_doe_data_eng"
+        );
+        assert_eq!(
+            template
+                .username(&identity("42@corp.com", "-ops-"))
+                .unwrap(),
+            "_42_ops"
+        );
+        assert_eq!(
+            template
+                .username(&identity("@corp.com", ""))
+                .unwrap_err()
+                .to_string(),
+            "user_mapping: \"_\" renders to an empty user name"
+        );
+        assert_eq!(
+            mapping("{login}")
+                .username(&identity("jane@corp.com", "data"))
+                .unwrap_err()
+                .to_string(),
+            "user_mapping: no login field for {login}"
+        );
+
+        let mapping = UserMapping {
+            lowercase: false,
+            max_length: 8,
+            ..template
+        };
+        assert_eq!(
+            mapping
+                .username(&identity("Jane@corp.com", "data"))
+                .unwrap(),
+            "Jane_dat"
+        );
+        assert_eq!(
+            mapping
+                .username(&identity("Jane.Do@corp.com", "x"))
+                .unwrap(),
+            "Jane_Do"
+        );
+    }
+
+    #[test]
+    fn test_usernames_collision() {
+        let mapping = mapping("{email_local}");
+        let identities = vec![
+            identity("jane@corp.com", "data"),
+            identity("john@corp.com", "data"),
+            identity("Jane@other.com", "ops"),
+        ];
+
+        assert_eq!(
+            mapping.usernames(&identities).unwrap_err().to_string(),
+            "user_mapping: jane@corp.com and Jane@other.com both map to user jane, \
+             add a field to username_template to tell them apart"
+        );
+        assert_eq!(
+            mapping.usernames(&identities[..2]).unwrap(),
+            vec!["jane", "john"]
+        );
+    }
+
+    #[test]
+    fn test_validate() {
+        assert!(mapping("{email_local}_{team}").validate().is_ok());
+        assert!(mapping("").validate().is_err());
+        assert!(mapping("svc_app").validate().is_err());
+        assert_eq!(
+            mapping("{email").validate().unwrap_err().to_string(),
+            "user_mapping: unclosed { in {email"
+        );
+        assert!(mapping("{}").validate().is_err());
+
+        let mapping = UserMapping {
+            max_length: 128,
+            ..mapping("{email}")
+        };
+        assert_eq!(
+            mapping.validate().unwrap_err().to_string(),
+            "user_mapping: max_length must be between 1 and 127"
+        );
+    }
+}
@@ -0,0 +1,241 @@
+use super::ident;
+use anyhow::{anyhow, Result};
+use serde::{Deserialize, Serialize};
+use serde_yaml::Value;
+use std::collections::BTreeMap;
+use std::fmt;
+
+/// The session defaults of a user, set with `ALTER USER ... SET` and applied
+/// to its new sessions. A setting is set when it differs from the cluster, a
+/// `null` resets it, a setting which is not in the config is left as it is.
+///
+/// For example:
+///
+/// ```yaml
+/// users:
+///   - name: analyst
+///     settings:
+///       search_path: [analytics, public]
+///       statement_timeout: 30s
+///       work_mem: null
+///     roles: []
+/// ```
+///
+/// A list is set as a list of values, e.g. `SET search_path = 'analytics',
+/// 'public'`. The `search_path` can also be a comma separated string.
+#[derive(Debug, Serialize, Deserialize, Clone, Default, PartialEq, Eq)]
+#[serde(transparent)]
+pub struct UserSettings(pub BTreeMap<String, Value>);
+
+/// A setting of a user which differs from the cluster: its new values, None
+/// to reset it
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct SettingChange {
+    pub name: String,
+    pub values: Option<Vec<String>>,
+}
+
+impl SettingChange {
+    /// The `ALTER USER` of the setting, the user named as in the config
+    pub fn to_sql(&self, user: &str) -> String {
+        match &self.values {
+            Some(values) => format!(
+                "ALTER USER {} SET {} = {};",
+                ident::quote(user),
+                self.name,
+                values
+                    .iter()
+                    .map(|v| format!("'{}'", v.replace('\'', "''")))
+                    .collect::<Vec<_>>()
+                    .join(", ")
+            ),
+            None => format!("ALTER USER {} RESET {};", ident::quote(user), self.name),
+        }
+    }
+}
+
+/// The setting as in the config, e.g. `search_path: analytics, public`
+impl fmt::Display for SettingChange {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match &self.values {
+            Some(values) => write!(f, "{}: {}", self.name, values.join(", ")),
+            None => write!(f, "{}: null", self.name),
+        }
+    }
+}
+
+impl UserSettings {
+    pub fn is_empty(&self) -> bool {
+        self.0.is_empty()
+    }
+
+    /// The settings of the config which differ from the `current` ones of
+    /// the cluster, by name in lower case as in `pg_user.useconfig`
+    pub fn diff(&self, current: &BTreeMap<String, String>) -> Vec<SettingChange> {
+        let mut changes = vec![];
+        for (name, value) in &self.0 {
+            let name = name.to_lowercase();
+            let values = values(&name, value);
+            let changed = match (&values, current.get(&name)) {
+                (Some(values), Some(current)) => !same(values, current),
+                (Some(_), None) => true,
+                (None, current) => current.is_some(),
+            };
+            if changed {
+                changes.push(SettingChange { name, values });
+            }
+        }
+
+        changes
+    }
+
+    pub fn validate(&self) -> Result<()> {
+        for (name, value) in &self.0 {
+            let valid_name = name.starts_with(|c: char| c.is_ascii_alphabetic() || c == '_')
+                && name
+                    .chars()
+                    .all(|c| c.is_ascii_alphanumeric() || c == '_' || c == '.');
+            if !valid_name {
+                return Err(anyhow!("settings: invalid setting name {:?}", name));
+            }
+
+            let valid_value = match value {
+                Value::Sequence(items) => !items.is_empty() && items.iter().all(is_scalar),
+                value => value.is_null() || is_scalar(value),
+            };
+            if !valid_value {
+                return Err(anyhow!(
+                    "settings: {} must be a string, a number, a boolean, a list of them or null",
+                    name
+                ));
+            }
+        }
+
+        let mut names = self.0.keys().map(|n| n.to_lowercase()).collect::<Vec<_>>();
+        names.sort();
+        if let Some(name) = names.windows(2).find(|w| w[0] == w[1]) {
+            return Err(anyhow!("settings: duplicated setting {}", name[0]));
+        }
+
+        Ok(())
+    }
+}
+
+/// The values of a setting, None for null
+fn values(name: &str, value: &Value) -> Option<Vec<String>> {
+    match value {
+        Value::Null => None,
+        Value::Sequence(items) => Some(items.iter().map(scalar).collect()),
+        Value::String(s) if name == "search_path" => {
+            Some(s.split(',').map(|item| item.trim().to_string()).collect())
+        }
+        value => Some(vec![scalar(value)]),
+    }
+}
+
+/// Whether the values are the current value of the cluster, where a list is
+/// stored comma separated with its quoted items, e.g. `"$user", public`
+fn same(values: &[String], current: &str) -> bool {
+    let items = current
+        .split(',')
+        .map(|item| item.trim().trim_matches('"'))
+        .collect::<Vec<_>>();
+
+    items == values || [current] == values
+}
+
+fn is_scalar(value: &Value) -> bool {
+    matches!(value, Value::String(_) | Value::Number(_) | Value::Bool(_))
+}
+
+/// A string, a number or a boolean as written in YAML
+fn scalar(value: &Value) -> String {
+    match value {
+        Value::String(s) => s.clone(),
+        Value::Number(n) => n.to_string(),
+        Value::Bool(b) => b.to_string(),
+        _ => String::new(),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use indoc::indoc;
+
+    fn settings(yaml: &str) -> UserSettings {
+        serde_yaml::from_str(yaml).unwrap()
+    }
+
+    #[test]
+    fn test_settings_diff() {
+        let settings = settings(indoc! {"
+            search_path: analytics, public
+            statement_timeout: 30s
+            Work_Mem: null
+            enable_seqscan: false
+            extra_float_digits: 2
+        "});
+        let current = BTreeMap::from([
+            ("search_path".to_string(), "analytics, public".to_string()),
+            ("statement_timeout".to_string(), "60s".to_string()),
+            ("work_mem".to_string(), "64MB".to_string()),
+            ("extra_float_digits".to_string(), "2".to_string()),
+        ]);
+
+        let changes = settings.diff(&current);
+        assert_eq!(
+            changes
+                .iter()
+                .map(|c| c.to_sql("Analyst"))
+                .collect::<Vec<_>>(),
+            vec![
+                "ALTER USER Analyst RESET work_mem;",
+                "ALTER USER Analyst SET enable_seqscan = 'false';",
+                "ALTER USER Analyst SET statement_timeout = '30s';",
+            ]
+        );
+        assert_eq!(changes[0].to_string(), "work_mem: null");
+
+        // Every setting of a new user, but the reset ones
+        let changes = settings.diff(&BTreeMap::new());
+        assert_eq!(changes.len(), 4);
+        assert_eq!(
+            changes[2].to_sql("analyst"),
+            "ALTER USER analyst SET search_path = 'analytics', 'public';"
+        );
+    }
+
+    #[test]
+    fn test_settings_same_list() {
+        let settings = settings("search_path: [$user, \"it's\"]");
+        let current =
+            BTreeMap::from([("search_path".to_string(), "\"$user\", \"it's\"".to_string())]);
+        assert!(settings.diff(&current).is_empty());
+        assert_eq!(
+            settings.diff(&BTreeMap::new())[0].to_sql("analyst"),
+            "ALTER USER analyst SET search_path = '$user', 'it''s';"
+        );
+    }
+
+    #[test]
+    fn test_settings_validate() {
+        assert!(settings("search_path: [analytics]\nwork_mem: null")
+            .validate()
+            .is_ok());
+
+        let err = |yaml: &str| settings(yaml).validate().unwrap_err().to_string();
+        assert_eq!(
+            err("search_path; drop: x"),
+            "settings: invalid setting name \"search_path; drop\""
+        );
+        assert_eq!(
+            err("search_path: {a: b}"),
+            "settings: search_path must be a string, a number, a boolean, a list of them or null"
+        );
+        assert_eq!(
+            err("work_mem: 1MB\nWORK_MEM: 2MB"),
+            "settings: duplicated setting work_mem"
+        );
+    }
+}
@@ -0,0 +1,317 @@
+//! Variables of a config, interpolated when it is loaded.
+//!
+//! The `vars` of the config are the default values of the variables, a
+//! `--var name=value` overrides one. `{{ name }}` is replaced by the value of
+//! the variable in every string of the config:
+//!
+//! ```yaml
+//! vars:
+//!   tenant: acme
+//!   tenant_schemas: [acme_raw, acme_marts]
+//!
+//! roles:
+//!   - name: "{{ tenant }}_read"
+//!     type: table
+//!     grants: [SELECT]
+//!     schemas: [common, "{{ tenant_schemas }}"]
+//!     tables: [ALL]
+//!
+//! users:
+//!   - name: "{{ tenant }}_bot"
+//!     roles: ["{{ tenant }}_read"]
+//! ```
+//!
+//! ```bash
+//! grant apply --file tenant.yaml --var tenant=globex --var tenant_schemas=globex_raw,globex_marts
+//! ```
+//!
+//! A variable can be a list: an item of a list which is only a list
+//! variable is replaced by its items, e.g. the schemas above. The override
+//! of a list variable is comma separated.
+//!
+//! The variables are interpolated before the role templates are stamped,
+//! so that `for_each.schemas_from` can be a list variable, see
+//! [super::role_template]. A config without `vars` nor overrides is not
+//! interpolated, its `{{` are kept as they are.
+
+use anyhow::{anyhow, Result};
+use serde_yaml::{Mapping, Value};
+
+/// Parse `--var name=value`
+pub fn parse_override(var: &str) -> Result<(String, String)> {
+    match var.split_once('=') {
+        Some((name, value)) if !name.trim().is_empty() => {
+            Ok((name.trim().to_string(), value.to_string()))
+        }
+        _ => Err(anyhow!("invalid variable {}, expected name=value", var)),
+    }
+}
+
+/// Replace the variables of the config with their values, overridden by
+/// the `--var` ones, in place, and remove its `vars`. Whether the config has
+/// variables.
+pub fn expand(config: &mut Value, overrides: &[(String, String)]) -> Result<bool> {
+    let Value::Mapping(fields) = config else {
+        return Ok(false);
+    };
+    if !fields.contains_key("vars") && overrides.is_empty() {
+        return Ok(false);
+    }
+
+    let defined = fields.remove("vars");
+    let has_vars = defined.is_some();
+    let mut vars = match defined {
+        Some(Value::Mapping(vars)) => vars,
+        Some(Value::Null) | None => Mapping::new(),
+        Some(_) => return Err(anyhow!("vars must be a map of variables")),
+    };
+    for (name, value) in &vars {
+        let valid = match value {
+            Value::Sequence(items) => items.iter().all(is_scalar),
+            value => is_scalar(value),
+        };
+        if name.as_str().is_none() || !valid {
+            return Err(anyhow!(
+                "vars: {} must be a string, a number, a boolean or a list of them",
+                name.as_str().unwrap_or("the variables")
+            ));
+        }
+    }
+    for (name, value) in overrides {
+        let value = match vars.get(name.as_str()) {
+            Some(Value::Sequence(_)) => Value::Sequence(
+                value
+                    .split(',')
+                    .map(|item| Value::String(item.trim().to_string()))
+                    .collect(),
+            ),
+            _ => Value::String(value.clone()),
+        };
+        vars.insert(name.as_str().into(), value);
+    }
+
+    let mut changed = false;
+    for (key, value) in fields.iter_mut() {
+        let path = key.as_str().unwrap_or_default().to_string();
+        changed |= interpolate(value, &vars, &path)?;
+    }
+
+    Ok(changed || has_vars)
+}
+
+/// Interpolate the variables in every string of a value. Whether a string
+/// had one.
+fn interpolate(value: &mut Value, vars: &Mapping, path: &str) -> Result<bool> {
+    match value {
+        Value::String(s) if s.contains("{{") => {
+            *value = match list(s, vars) {
+                Some(items) => Value::Sequence(items.clone()),
+                None => Value::String(replace(s, vars, path)?),
+            };
+            Ok(true)
+        }
+        Value::Sequence(values) => {
+            let mut changed = false;
+            let mut interpolated = vec![];
+            for (i, mut value) in values.drain(..).enumerate() {
+                match value.as_str().and_then(|s| list(s, vars)) {
+                    Some(items) => {
+                        interpolated.extend(items.iter().cloned());
+                        changed = true;
+                    }
+                    None => {
+                        changed |= interpolate(&mut value, vars, &format!("{}[{}]", path, i))?;
+                        interpolated.push(value);
+                    }
+                }
+            }
+            *values = interpolated;
+            Ok(changed)
+        }
+        Value::Mapping(fields) => {
+            let mut changed = false;
+            for (key, value) in fields.iter_mut() {
+                let path = format!("{}.{}", path, key.as_str().unwrap_or_default());
+                changed |= interpolate(value, vars, &path)?;
+            }
+            Ok(changed)
+        }
+        _ => Ok(false),
+    }
+}
+
+/// The items of the list variable of a string which is only that variable
+fn list<'a>(s: &str, vars: &'a Mapping) -> Option<&'a Vec<Value>> {
+    let name = s.trim().strip_prefix("{{")?.strip_suffix("}}")?.trim();
+    match vars.get(name) {
+        Some(Value::Sequence(items)) => Some(items),
+        _ => None,
+    }
+}
+
+/// The string with every `{{ name }}` replaced by the value of the variable
+fn replace(s: &str, vars: &Mapping, path: &str) -> Result<String> {
+    let mut replaced = String::new();
+    let mut rest = s;
+    while let Some(start) = rest.find("{{") {
+        let end = rest[start..]
+            .find("}}")
+            .map(|end| start + end)
+            .ok_or_else(|| anyhow!("{}: unclosed {{{{ in {}", path, s))?;
+        let name = rest[start + 2..end].trim();
+        let value = match vars.get(name) {
+            Some(Value::String(value)) => value.clone(),
+            Some(Value::Sequence(_)) => {
+                return Err(anyhow!(
+                    "{}: {} is a list, it can only be a whole item of a list",
+                    path,
+                    name
+                ))
+            }
+            Some(value) => scalar(value),
+            None => {
+                return Err(anyhow!(
+                    "{}: unknown variable {}, set it in vars or with --var {}=...",
+                    path,
+                    name,
+                    name
+                ))
+            }
+        };
+        replaced.push_str(&rest[..start]);
+        replaced.push_str(&value);
+        rest = &rest[end + 2..];
+    }
+    replaced.push_str(rest);
+
+    Ok(replaced)
+}
+
+fn is_scalar(value: &Value) -> bool {
+    matches!(value, Value::String(_) | Value::Number(_) | Value::Bool(_))
+}
+
+/// A number or a boolean as written in YAML
+fn scalar(value: &Value) -> String {
+    match value {
+        Value::Number(n) => n.to_string(),
+        Value::Bool(b) => b.to_string(),
+        _ => String::new(),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use indoc::indoc;
+
+    fn expanded(yaml: &str, overrides: &[(&str, &str)]) -> Result<Value> {
+        let overrides = overrides
+            .iter()
+            .map(|(name, value)| (name.to_string(), value.to_string()))
+            .collect::<Vec<_>>();
+        let mut config: Value = serde_yaml::from_str(yaml).unwrap();
+        expand(&mut config, &overrides)?;
+
+        Ok(config)
+    }
+
+    #[test]
+    fn test_expand() {
+        let config = indoc! {r#"
+            vars:
+              tenant: acme
+              port: 5439
+              tenant_schemas: [acme_raw, acme_marts]
+            connection:
+              url: "postgres://localhost:{{port}}/{{ tenant }}"
+            roles:
+              - name: "{{ tenant }}_read"
+                schemas: [common, "{{ tenant_schemas }}"]
+            users:
+              - name: "{{ tenant }}_bot"
+                roles: ["{{ tenant }}_read"]
+        "#};
+
+        assert_eq!(
+            expanded(config, &[]).unwrap(),
+            serde_yaml::from_str::<Value>(indoc! {"
+                connection:
+                  url: postgres://localhost:5439/acme
+                roles:
+                  - name: acme_read
+                    schemas: [common, acme_raw, acme_marts]
+                users:
+                  - name: acme_bot
+                    roles: [acme_read]
+            "})
+            .unwrap()
+        );
+
+        let config = expanded(
+            config,
+            &[
+                ("tenant", "globex"),
+                ("tenant_schemas", "globex_raw, globex_marts"),
+            ],
+        )
+        .unwrap();
+        assert_eq!(config["users"][0]["name"], Value::from("globex_bot"));
+        assert_eq!(
+            config["roles"][0]["schemas"],
+            serde_yaml::from_str::<Value>("[common, globex_raw, globex_marts]").unwrap()
+        );
+    }
+
+    #[test]
+    fn test_expand_without_vars() {
+        let mut config: Value = serde_yaml::from_str("users: [{name: duyet}]").unwrap();
+        assert!(!expand(&mut config, &[]).unwrap());
+
+        // Neither vars nor overrides, the strings are kept as they are
+        let yaml = "users: [{name: duyet, password: 'pa{{ss'}]";
+        let mut config: Value = serde_yaml::from_str(yaml).unwrap();
+        assert!(!expand(&mut config, &[]).unwrap());
+        assert_eq!(config, serde_yaml::from_str::<Value>(yaml).unwrap());
+
+        let config = expanded("users: [{name: '{{ user }}'}]", &[("user", "duyet")]).unwrap();
+        assert_eq!(config["users"][0]["name"], Value::from("duyet"));
+    }
+
+    #[test]
+    fn test_expand_errors() {
+        let err = |yaml: &str| expanded(yaml, &[]).unwrap_err().to_string();
+
+        assert_eq!(
+            err("vars: {a: 1}\nusers: [{name: '{{ tenant }}_bot'}]"),
+            "users[0].name: unknown variable tenant, set it in vars or with --var tenant=..."
+        );
+        assert_eq!(
+            err("vars: {a: [x]}\nusers: [{name: '{{ a }}_bot'}]"),
+            "users[0].name: a is a list, it can only be a whole item of a list"
+        );
+        assert_eq!(
+            err("vars: {a: x}\nusers: [{name: '{{ a'}]"),
+            "users[0].name: unclosed {{ in {{ a"
+        );
+        assert_eq!(
+            err("vars: {a: {b: c}}"),
+            "vars: a must be a string, a number, a boolean or a list of them"
+        );
+        assert_eq!(err("vars: [a]"), "vars must be a map of variables");
+    }
+
+    #[test]
+    fn test_parse_override() {
+        assert_eq!(
+            parse_override("tenant=acme").unwrap(),
+            ("tenant".to_string(), "acme".to_string())
+        );
+        assert_eq!(
+            parse_override("url=postgres://a/b?x=y").unwrap(),
+            ("url".to_string(), "postgres://a/b?x=y".to_string())
+        );
+        assert!(parse_override("tenant").is_err());
+        assert!(parse_override("=acme").is_err());
+    }
+}
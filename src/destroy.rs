@@ -0,0 +1,370 @@
+use crate::apply::{drop_user_sql, privilege_statements};
+use crate::config::{ident, Config, Dialect, Grantee, LoadOptions};
+use crate::connection::{DbConnection, Group, User};
+use crate::prompt::confirm;
+use crate::state::ClusterState;
+use ansi_term::Colour::{Green, Purple, Red};
+use anyhow::{anyhow, Context, Result};
+use log::{error, info, warn};
+use std::collections::BTreeMap;
+use std::fmt;
+use std::path::Path;
+
+/// Options of `grant destroy`
+#[derive(Debug, Clone, Default)]
+pub struct DestroyOptions {
+    /// Only print the statements
+    pub dryrun: bool,
+    /// Also drop the users of the config
+    pub drop_users: bool,
+    /// Do not ask for confirmation
+    pub yes: bool,
+    /// The `--var` overrides of the variables of the config, see
+    /// [crate::config::vars]
+    pub vars: Vec<(String, String)>,
+}
+
+/// What a statement of `destroy` does
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Kind {
+    Revoke,
+    RemoveMember,
+    DropUser,
+}
+
+/// A statement of `destroy`, on the database of the connection or on
+/// another database of the cluster for the roles with a `database`
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Statement {
+    pub kind: Kind,
+    /// The database named as in the catalog, None for the one of the
+    /// connection
+    pub database: Option<String>,
+    pub sql: String,
+}
+
+impl fmt::Display for Statement {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match &self.database {
+            Some(database) => write!(f, "{} -- on database {}", self.sql, database),
+            None => write!(f, "{}", self.sql),
+        }
+    }
+}
+
+/// Revoke every privilege granted by a config, e.g. to decommission a team:
+/// the grants of the roles of its users, groups and `PUBLIC`, and the
+/// members of its groups. With [DestroyOptions::drop_users], its users are
+/// dropped too. The groups and the objects are kept.
+///
+/// The statements are printed and executed once confirmed, the ones of each
+/// other database in its own transaction, then the ones of the database of
+/// the connection in a single transaction.
+pub fn destroy(file: &Path, opts: &DestroyOptions) -> Result<()> {
+    let load = LoadOptions {
+        vars: opts.vars.clone(),
+        ..Default::default()
+    };
+    let config = Config::new_with(file, &load)?;
+    let mut conn = DbConnection::new(&config)?;
+    let dialect = conn.dialect();
+    config.validate_dialect(dialect)?;
+
+    let users = conn.get_users()?;
+    let groups = match config.groups.is_empty() {
+        true => vec![],
+        false => conn.get_groups()?,
+    };
+    // Redshift has no DROP OWNED, see `drop_user_sql`
+    let state = match opts.drop_users && dialect.is_redshift() {
+        true => Some(conn.get_cluster_state()),
+        false => None,
+    };
+    let current_user = conn.get_current_user().map(str::to_string);
+    let statements = destroy_statements(
+        &config,
+        &users,
+        &groups,
+        current_user.as_deref(),
+        opts,
+        state.as_ref(),
+        dialect,
+    );
+
+    if statements.is_empty() {
+        info!(
+            "Nothing to destroy, no privilege of {} is granted",
+            file.display()
+        );
+        return Ok(());
+    }
+    if opts.dryrun {
+        for statement in &statements {
+            info!("{}: {}", Purple.paint("Dry-run"), statement);
+        }
+        info!("Would destroy (dryrun): {}", counts(&statements));
+        return Ok(());
+    }
+
+    if !opts.yes {
+        println!("Destroy plan of {}:", file.display());
+        for statement in &statements {
+            println!("{}", statement);
+        }
+        if !confirm(&format!("Destroy {}?", counts(&statements)), "--yes")? {
+            return Err(anyhow!("destroy cancelled, nothing is changed"));
+        }
+    }
+
+    // The users are dropped once their privileges on the other databases
+    // are revoked
+    let current = conn.get_current_database().map(str::to_string);
+    let mut others: BTreeMap<&str, Vec<&Statement>> = BTreeMap::new();
+    let mut main = vec![];
+    for statement in &statements {
+        match statement.database.as_deref() {
+            Some(database) if Some(database) != current.as_deref() => {
+                others.entry(database).or_default().push(statement)
+            }
+            _ => main.push(statement),
+        }
+    }
+    for (database, statements) in others {
+        let mut conn = conn.pooled(database)?;
+        execute(&mut conn, &statements).with_context(|| format!("database {}", database))?;
+    }
+    execute(&mut conn, &main)?;
+
+    info!("Destroyed: {}", counts(&statements));
+
+    Ok(())
+}
+
+/// The statements destroying a config on a cluster with `users` and
+/// `groups`: the revokes of the roles of each user and group of the config
+/// which exists, then the removal of the members of its groups, then the
+/// users dropped with [DestroyOptions::drop_users]. `PUBLIC`, the
+/// superusers and the user of the connection are never dropped.
+pub fn destroy_statements(
+    config: &Config,
+    users: &[User],
+    groups: &[Group],
+    current_user: Option<&str>,
+    opts: &DestroyOptions,
+    state: Option<&ClusterState>,
+    dialect: Dialect,
+) -> Vec<Statement> {
+    let has_user = |name: &str| users.iter().any(|u| u.name == ident::fold(name));
+    let group_of = |name: &str| groups.iter().find(|g| g.name == ident::fold(name));
+    let mut statements = vec![];
+
+    for (grantee, _, role, revoked, _) in privilege_statements(config) {
+        // Nothing is granted by a revoked role
+        if revoked {
+            continue;
+        }
+        let exists = match grantee {
+            Grantee::User(name) => has_user(name),
+            Grantee::Group(name) => group_of(name).is_some(),
+            Grantee::Public => true,
+        };
+        if !exists {
+            continue;
+        }
+
+        let grants = role.get_grants().join(", ");
+        for (_, object) in role.objects().into_iter().filter(|(revoked, _)| !revoked) {
+            statements.push(Statement {
+                kind: Kind::Revoke,
+                database: role.database().map(ident::fold),
+                sql: format!("REVOKE {} ON {} FROM {};", grants, object, grantee.to_sql()),
+            });
+        }
+    }
+
+    for group in &config.groups {
+        let Some(current) = group_of(&group.name) else {
+            continue;
+        };
+        for member in group
+            .folded_members()
+            .iter()
+            .filter(|m| current.members.contains(m))
+        {
+            statements.push(Statement {
+                kind: Kind::RemoveMember,
+                database: None,
+                sql: group.to_sql_remove(member, dialect),
+            });
+        }
+    }
+
+    if opts.drop_users {
+        for user in config.users.iter().filter(|u| !u.is_public()) {
+            let name = ident::fold(&user.name);
+            let Some(user) = users.iter().find(|u| u.name == name) else {
+                continue;
+            };
+            if user.user_super {
+                warn!("{} is a superuser, it is not dropped", user.name);
+            } else if Some(user.name.as_str()) == current_user {
+                warn!(
+                    "{} is the user of the connection, it is not dropped",
+                    user.name
+                );
+            } else {
+                statements.push(Statement {
+                    kind: Kind::DropUser,
+                    database: None,
+                    sql: drop_user_sql(&user.name, state),
+                });
+            }
+        }
+    }
+
+    statements
+}
+
+/// e.g. `3 revoke(s), 1 member(s) removed, 1 user(s) dropped`
+fn counts(statements: &[Statement]) -> String {
+    let count = |kind| statements.iter().filter(|s| s.kind == kind).count();
+    format!(
+        "{} revoke(s), {} member(s) removed, {} user(s) dropped",
+        count(Kind::Revoke),
+        count(Kind::RemoveMember),
+        count(Kind::DropUser)
+    )
+}
+
+/// Execute the statements in a transaction, rolled back at the first error
+fn execute(conn: &mut DbConnection, statements: &[&Statement]) -> Result<()> {
+    conn.begin()?;
+    for statement in statements {
+        if let Err(e) = conn.execute(&statement.sql, &[]) {
+            error!("{}: {}", Red.paint("Error"), statement);
+            if let Err(e) = conn.rollback() {
+                warn!("{:#}", e);
+            }
+            warn!("Rolled back the transaction, no change is applied");
+            return Err(e);
+        }
+        info!(
+            "{}: {}",
+            Green.paint("Success"),
+            Purple.paint(&statement.sql)
+        );
+    }
+
+    conn.commit()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::config::UserAttributes;
+    use indoc::indoc;
+    use std::str::FromStr;
+
+    fn user(name: &str, user_super: bool) -> User {
+        User {
+            name: name.to_string(),
+            user_createdb: false,
+            user_super,
+            password: String::new(),
+            attributes: UserAttributes::default(),
+            settings: Default::default(),
+        }
+    }
+
+    #[test]
+    fn test_destroy_statements() {
+        let config = Config::from_str(indoc! {"
+            connection:
+              type: postgres
+              url: postgres://localhost:5432/postgres
+            roles:
+              - name: role_read
+                type: table
+                grants: [SELECT]
+                schemas: [sales]
+                tables: [ALL, -secrets]
+              - name: role_marts
+                type: schema
+                database: analytics
+                grants: [ALL]
+                schemas: [marts]
+            users:
+              - name: Duyet
+                roles: [role_read]
+              - name: gone
+                roles: [role_read]
+              - name: grant_bot
+                roles: []
+              - name: admin
+                roles: []
+            groups:
+              - name: analysts
+                members: [duyet, gone]
+                roles: [role_marts]
+        "})
+        .unwrap();
+        let users = [
+            user("duyet", false),
+            user("grant_bot", false),
+            user("admin", true),
+        ];
+        let groups = [Group {
+            name: "analysts".to_string(),
+            members: vec!["duyet".to_string()],
+        }];
+        let statements = |opts: &DestroyOptions| {
+            destroy_statements(
+                &config,
+                &users,
+                &groups,
+                Some("grant_bot"),
+                opts,
+                None,
+                Dialect::Postgres,
+            )
+            .iter()
+            .map(|s| s.to_string())
+            .collect::<Vec<_>>()
+        };
+
+        assert_eq!(
+            statements(&DestroyOptions::default()),
+            vec![
+                "REVOKE SELECT ON ALL TABLES IN SCHEMA sales FROM Duyet;",
+                "REVOKE ALL ON SCHEMA marts FROM GROUP analysts; -- on database analytics",
+                "REVOKE analysts FROM duyet;",
+            ]
+        );
+
+        let opts = DestroyOptions {
+            drop_users: true,
+            ..Default::default()
+        };
+        assert_eq!(
+            statements(&opts)[3..],
+            ["REASSIGN OWNED BY duyet TO CURRENT_USER;\nDROP OWNED BY duyet;\nDROP USER duyet;"]
+        );
+    }
+
+    #[test]
+    fn test_counts() {
+        let statement = |kind| Statement {
+            kind,
+            database: None,
+            sql: String::new(),
+        };
+        assert_eq!(
+            counts(&[statement(Kind::Revoke), statement(Kind::Revoke)]),
+            "2 revoke(s), 0 member(s) removed, 0 user(s) dropped"
+        );
+        assert_eq!(
+            counts(&[statement(Kind::RemoveMember), statement(Kind::DropUser)]),
+            "0 revoke(s), 1 member(s) removed, 1 user(s) dropped"
+        );
+    }
+}
@@ -0,0 +1,437 @@
+//! `grant doctor`: a checklist of what is needed to apply a config, from
+//! the config itself to the clock of the server.
+//!
+//! The checks run in order, a check which can't run because an earlier one
+//! failed is skipped:
+//!
+//! ```text
+//! ✓ config: ./cluster/config.yaml, 3 roles, 2 users, 0 groups
+//! ✓ environment variables: the connection url is fully expanded
+//! ✓ dns: localhost resolves to 127.0.0.1
+//! ✓ port: 127.0.0.1:5432 is reachable
+//! - tls: plain text, sslmode is prefer
+//! ✓ authentication: connected as postgres (superuser)
+//! ✓ server: postgres, PostgreSQL 16.2
+//! ✓ catalog permissions: users, groups, database privileges, ... are readable
+//! ✓ clock skew: 0.1s
+//! ```
+
+use crate::catalog::Query;
+use crate::config::{Config, Dialect, LoadOptions};
+use crate::connection::{ssl_mode, DbConnection};
+use ansi_term::Colour::{Green, Red, Yellow};
+use anyhow::{anyhow, Result};
+use std::fmt;
+use std::net::{TcpStream, ToSocketAddrs};
+use std::path::Path;
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+use tokio_postgres::config::Host;
+use tokio_postgres::Config as ConnConfig;
+
+/// Timeout of the TCP connection of the `port` check
+const CONNECT_TIMEOUT: Duration = Duration::from_secs(5);
+
+/// Clock skew with the server above which the timestamps of the logs and
+/// of the audit logs of the database are hard to match
+const MAX_CLOCK_SKEW: f64 = 5.0;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Status {
+    Pass,
+    Warn,
+    Fail,
+    Skip,
+}
+
+/// Result of a check
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Check {
+    pub name: &'static str,
+    pub status: Status,
+    pub detail: String,
+}
+
+impl Check {
+    fn new(name: &'static str, status: Status, detail: impl Into<String>) -> Self {
+        Check {
+            name,
+            status,
+            detail: detail.into(),
+        }
+    }
+}
+
+impl fmt::Display for Check {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let mark = match self.status {
+            Status::Pass => Green.paint("✓"),
+            Status::Warn => Yellow.paint("!"),
+            Status::Fail => Red.paint("✗"),
+            Status::Skip => Yellow.paint("-"),
+        };
+
+        write!(f, "{} {}: {}", mark, self.name, self.detail)
+    }
+}
+
+/// Run the checks and print the checklist, fails if a check failed
+pub fn doctor(file: &Path, load: &LoadOptions) -> Result<()> {
+    let checks = checks(file, load);
+    for check in &checks {
+        println!("{}", check);
+    }
+
+    let count = |status| checks.iter().filter(|c| c.status == status).count();
+    println!(
+        "{} passed, {} warning(s), {} failed, {} skipped",
+        count(Status::Pass),
+        count(Status::Warn),
+        count(Status::Fail),
+        count(Status::Skip)
+    );
+    match count(Status::Fail) {
+        0 => Ok(()),
+        failed => Err(anyhow!("{} check(s) failed", failed)),
+    }
+}
+
+/// Names of the checks, in order
+const CHECKS: &[&str] = &[
+    "config",
+    "environment variables",
+    "dns",
+    "port",
+    "tls",
+    "authentication",
+    "server",
+    "catalog permissions",
+    "clock skew",
+];
+
+/// Run the checks, the ones after a failure which they depend on are skipped
+pub fn checks(file: &Path, load: &LoadOptions) -> Vec<Check> {
+    let mut checks = vec![];
+    run_checks(file, load, &mut checks);
+
+    for name in &CHECKS[checks.len()..] {
+        checks.push(Check::new(name, Status::Skip, "skipped"));
+    }
+
+    checks
+}
+
+/// Push the checks until one fails which the next ones depend on
+fn run_checks(file: &Path, load: &LoadOptions, checks: &mut Vec<Check>) {
+    let config = match Config::new_with(file, load) {
+        Ok(config) => {
+            checks.push(Check::new(
+                "config",
+                Status::Pass,
+                format!(
+                    "{}, {} roles, {} users, {} groups",
+                    file.display(),
+                    config.roles.len(),
+                    config.users.len(),
+                    config.groups.len()
+                ),
+            ));
+            config
+        }
+        Err(e) => {
+            checks.push(Check::new("config", Status::Fail, format!("{:#}", e)));
+            return;
+        }
+    };
+
+    let url = &config.connection.url;
+    if let Some(start) = url.find("${") {
+        let end = url[start..].find('}').map_or(url.len(), |i| start + i + 1);
+        checks.push(Check::new(
+            "environment variables",
+            Status::Fail,
+            format!("{} is not set in the connection url", &url[start..end]),
+        ));
+        return;
+    }
+    checks.push(Check::new(
+        "environment variables",
+        Status::Pass,
+        "the connection url is fully expanded",
+    ));
+
+    let conn_config = match url.parse::<ConnConfig>() {
+        Ok(conn_config) => conn_config,
+        Err(e) => {
+            checks.push(Check::new(
+                "dns",
+                Status::Fail,
+                format!("invalid connection url: {}", e),
+            ));
+            return;
+        }
+    };
+    let port = conn_config.get_ports().first().copied().unwrap_or(5432);
+    let addrs = match conn_config.get_hosts().first() {
+        Some(Host::Tcp(host)) => match (host.as_str(), port).to_socket_addrs() {
+            Ok(addrs) => {
+                let addrs = addrs.collect::<Vec<_>>();
+                checks.push(Check::new(
+                    "dns",
+                    Status::Pass,
+                    format!(
+                        "{} resolves to {}",
+                        host,
+                        join(addrs.iter().map(|a| a.ip()))
+                    ),
+                ));
+                addrs
+            }
+            Err(e) => {
+                checks.push(Check::new(
+                    "dns",
+                    Status::Fail,
+                    format!("cannot resolve {}: {}", host, e),
+                ));
+                return;
+            }
+        },
+        Some(Host::Unix(path)) => {
+            checks.push(Check::new(
+                "dns",
+                Status::Skip,
+                format!("unix socket {}", path.display()),
+            ));
+            vec![]
+        }
+        None => {
+            checks.push(Check::new("dns", Status::Fail, "no host in the url"));
+            return;
+        }
+    };
+
+    if !addrs.is_empty() {
+        let reachable = addrs
+            .iter()
+            .find(|addr| TcpStream::connect_timeout(addr, CONNECT_TIMEOUT).is_ok());
+        match reachable {
+            Some(addr) => checks.push(Check::new(
+                "port",
+                Status::Pass,
+                format!("{} is reachable", addr),
+            )),
+            None => {
+                checks.push(Check::new(
+                    "port",
+                    Status::Fail,
+                    format!(
+                        "cannot connect to {} within {}s",
+                        join(&addrs),
+                        CONNECT_TIMEOUT.as_secs()
+                    ),
+                ));
+                return;
+            }
+        }
+    } else {
+        checks.push(Check::new("port", Status::Skip, "unix socket"));
+    }
+
+    // TLS and authentication are both checked by connecting, the error
+    // tells which one failed
+    let sslmode = ssl_mode(&config.connection, &conn_config);
+    let tls = |status, detail: String| match sslmode.is_tls() {
+        true => Check::new("tls", status, detail),
+        false => Check::new(
+            "tls",
+            Status::Skip,
+            format!("plain text, sslmode is {}", sslmode),
+        ),
+    };
+    let mut conn = match DbConnection::new(&config) {
+        Ok(conn) => {
+            checks.push(tls(
+                Status::Pass,
+                format!("handshake with sslmode {}", sslmode),
+            ));
+            conn
+        }
+        Err(e) => {
+            let message = format!("{:#}", e);
+            if message.contains("TLS") || message.contains("certificate") {
+                checks.push(tls(Status::Fail, message));
+            } else {
+                checks.push(tls(
+                    Status::Pass,
+                    format!("handshake with sslmode {}", sslmode),
+                ));
+                checks.push(Check::new("authentication", Status::Fail, message));
+            }
+            return;
+        }
+    };
+
+    let dialect = conn.dialect();
+    checks.push(authentication(&mut conn));
+    checks.push(server(&mut conn, dialect));
+    checks.push(catalog_permissions(&mut conn));
+    checks.push(clock_skew(&mut conn, dialect));
+}
+
+/// The connected user, a warning if it is not a superuser
+fn authentication(conn: &mut DbConnection) -> Check {
+    let rows = conn.query(
+        "SELECT usename::text, usesuper FROM pg_catalog.pg_user WHERE usename = current_user",
+        &[],
+    );
+    match rows.as_deref() {
+        Ok([row, ..]) => {
+            let name: String = row.get(0);
+            if row.get::<_, bool>(1) {
+                Check::new(
+                    "authentication",
+                    Status::Pass,
+                    format!("connected as {} (superuser)", name),
+                )
+            } else {
+                Check::new(
+                    "authentication",
+                    Status::Warn,
+                    format!(
+                        "connected as {}, not a superuser: it must own the objects or \
+                         hold the privileges with grant option",
+                        name
+                    ),
+                )
+            }
+        }
+        _ => Check::new("authentication", Status::Pass, "connected"),
+    }
+}
+
+fn server(conn: &mut DbConnection, dialect: Dialect) -> Check {
+    match conn.query("SELECT version()", &[]) {
+        Ok(rows) => {
+            let version: String = rows.first().map(|r| r.get(0)).unwrap_or_default();
+            Check::new(
+                "server",
+                Status::Pass,
+                format!("{}, {}", dialect, short_version(&version, dialect)),
+            )
+        }
+        Err(e) => Check::new("server", Status::Fail, format!("{:#}", e)),
+    }
+}
+
+/// The product and version of `version()`, e.g. `PostgreSQL 16.2` or
+/// `Redshift 1.0.12103`
+fn short_version(version: &str, dialect: Dialect) -> &str {
+    if dialect.is_redshift() {
+        if let Some(redshift) = version.rsplit(", ").find(|p| p.starts_with("Redshift")) {
+            return redshift;
+        }
+    }
+
+    version
+        .split(" on ")
+        .next()
+        .unwrap_or(version)
+        .split(',')
+        .next()
+        .unwrap_or(version)
+        .trim()
+}
+
+/// The catalog queries of the plan and apply, which need their permissions
+fn catalog_permissions(conn: &mut DbConnection) -> Check {
+    let state = conn.get_cluster_state();
+    let queries = [
+        Query::Users,
+        Query::Groups,
+        Query::DatabasePrivileges,
+        Query::SchemaPrivileges,
+        Query::TablePrivileges,
+        Query::Owners,
+    ];
+
+    if state.unavailable.is_empty() {
+        Check::new(
+            "catalog permissions",
+            Status::Pass,
+            format!("{} are readable", join(queries)),
+        )
+    } else {
+        Check::new(
+            "catalog permissions",
+            Status::Fail,
+            format!("cannot read {}", join(&state.unavailable)),
+        )
+    }
+}
+
+/// Difference between the clocks of the server and of this machine, the
+/// middle of the query is compared with the server time
+fn clock_skew(conn: &mut DbConnection, dialect: Dialect) -> Check {
+    let sql = if dialect.is_redshift() {
+        "SELECT extract(epoch FROM getdate())::float8"
+    } else {
+        "SELECT extract(epoch FROM clock_timestamp())::float8"
+    };
+
+    let before = epoch();
+    let server: f64 = match conn.query(sql, &[]) {
+        Ok(rows) => rows.first().map(|r| r.get(0)).unwrap_or_default(),
+        Err(e) => return Check::new("clock skew", Status::Warn, format!("{:#}", e)),
+    };
+    let local = (before + epoch()) / 2.0;
+
+    let skew = server - local;
+    let detail = match skew {
+        s if s.abs() < 0.1 => "0.0s".to_string(),
+        s if s > 0.0 => format!("{:.1}s, the server clock is ahead", s),
+        s => format!("{:.1}s, the server clock is behind", -s),
+    };
+    if skew.abs() > MAX_CLOCK_SKEW {
+        Check::new("clock skew", Status::Warn, detail)
+    } else {
+        Check::new("clock skew", Status::Pass, detail)
+    }
+}
+
+fn epoch() -> f64 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map(|d| d.as_secs_f64())
+        .unwrap_or_default()
+}
+
+fn join(items: impl IntoIterator<Item = impl fmt::Display>) -> String {
+    items
+        .into_iter()
+        .map(|item| item.to_string())
+        .collect::<Vec<_>>()
+        .join(", ")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_short_version() {
+        assert_eq!(
+            short_version(
+                "PostgreSQL 16.2 (Debian 16.2-1.pgdg120+2) on x86_64-pc-linux-gnu, compiled by gcc",
+                Dialect::Postgres
+            ),
+            "PostgreSQL 16.2 (Debian 16.2-1.pgdg120+2)"
+        );
+        assert_eq!(
+            short_version(
+                "PostgreSQL 8.0.2 on i686-pc-linux-gnu, compiled by GCC gcc (GCC) 3.4.2 20041017 \
+                 (Red Hat 3.4.2-6.fc3), Redshift 1.0.12103",
+                Dialect::Redshift
+            ),
+            "Redshift 1.0.12103"
+        );
+    }
+}
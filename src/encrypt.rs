@@ -0,0 +1,264 @@
+//! Encryption at rest of the passwords of a config file.
+//!
+//! `grant encrypt` replaces each plaintext `password:` with an envelope:
+//!
+//! ```yaml
+//! users:
+//!   - name: duyet
+//!     password: !enc AES256:mUkO1lBVt0d7Z2ykcWy4vbjXxEv8y3hB2CjUa1H+jF3p
+//! ```
+//!
+//! The envelope is the base64 of the 12 bytes nonce followed by the
+//! AES-256-GCM ciphertext, the key is the SHA-256 of the key file content.
+//! The passwords are decrypted by `apply` only for the executed statements,
+//! the logs and dry runs keep the envelopes.
+
+use crate::config::edit::ConfigEditor;
+use crate::config::{Config, User};
+use crate::secrets;
+use aes_gcm::aead::{Aead, KeyInit};
+use aes_gcm::{Aes256Gcm, Nonce};
+use anyhow::{anyhow, Context, Result};
+use base64::{engine::general_purpose::STANDARD, Engine};
+use log::info;
+use rand::RngCore;
+use sha2::{Digest, Sha256};
+use std::borrow::Cow;
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::str::FromStr;
+
+/// YAML tag marking an encrypted value in the config file
+pub const TAG: &str = "!enc";
+
+/// Prefix of the envelope, naming the cipher
+pub const PREFIX: &str = "AES256:";
+
+/// Environment variable with the path of the key file, for `apply`
+pub const KEY_FILE_ENV: &str = "GRANT_KEY_FILE";
+
+const NONCE_LEN: usize = 12;
+
+/// Encryption key derived from a key file
+pub struct Key(Aes256Gcm);
+
+impl Key {
+    /// Read the key file, any non empty content such as a long random
+    /// passphrase, e.g. from `openssl rand -base64 48`
+    pub fn load(file: &Path) -> Result<Self> {
+        let content = fs::read(file)
+            .with_context(|| format!("failed to read key file {}", file.display()))?;
+        let content = content.trim_ascii();
+        if content.is_empty() {
+            return Err(anyhow!("key file {} is empty", file.display()));
+        }
+
+        Ok(Self::derive(content))
+    }
+
+    fn derive(secret: &[u8]) -> Self {
+        let digest = Sha256::digest(secret);
+
+        Key(Aes256Gcm::new(&digest))
+    }
+
+    /// Encrypt a password into an envelope, `AES256:<base64>`
+    pub fn encrypt(&self, plaintext: &str) -> Result<String> {
+        let mut nonce = [0u8; NONCE_LEN];
+        rand::thread_rng().fill_bytes(&mut nonce);
+
+        let ciphertext = self
+            .0
+            .encrypt(Nonce::from_slice(&nonce), plaintext.as_bytes())
+            .map_err(|_| anyhow!("failed to encrypt"))?;
+
+        let mut envelope = nonce.to_vec();
+        envelope.extend(ciphertext);
+
+        Ok(format!("{}{}", PREFIX, STANDARD.encode(envelope)))
+    }
+
+    /// Decrypt an envelope made by [Key::encrypt]
+    pub fn decrypt(&self, envelope: &str) -> Result<String> {
+        let encoded = envelope
+            .strip_prefix(PREFIX)
+            .ok_or_else(|| anyhow!("not an {} envelope", PREFIX))?;
+        let decoded = STANDARD
+            .decode(encoded.trim())
+            .context("invalid envelope")?;
+        if decoded.len() <= NONCE_LEN {
+            return Err(anyhow!("invalid envelope: too short"));
+        }
+
+        let (nonce, ciphertext) = decoded.split_at(NONCE_LEN);
+        let plaintext = self
+            .0
+            .decrypt(Nonce::from_slice(nonce), ciphertext)
+            .map_err(|_| anyhow!("failed to decrypt, wrong key file?"))?;
+
+        String::from_utf8(plaintext).context("decrypted password is not valid UTF-8")
+    }
+}
+
+/// Whether the password is an envelope. The `!enc` tag is dropped by the
+/// YAML parser, the envelope is recognized by its prefix.
+pub fn is_encrypted(password: &str) -> bool {
+    password.starts_with(PREFIX)
+}
+
+/// Encrypt the plaintext passwords of a config file in place, comments and
+/// formatting are kept. Returns the number of encrypted passwords.
+pub fn encrypt_file(file: &Path, key_file: &Path) -> Result<usize> {
+    let key = Key::load(key_file)?;
+    let mut editor = ConfigEditor::open(file)?;
+
+    let mut encrypted = 0;
+    for name in editor.names("users") {
+        let mut error = None;
+        let changed = editor.map_scalar("users", &name, "password", |password| {
+            // Already encrypted, with or without the tag
+            if is_encrypted(password.trim_start_matches(TAG).trim_start()) {
+                return None;
+            }
+
+            match key.encrypt(password) {
+                Ok(envelope) => Some(format!("{} {}", TAG, envelope)),
+                Err(e) => {
+                    error = Some(e);
+                    None
+                }
+            }
+        })?;
+        if let Some(e) = error {
+            return Err(e.context(format!("user {}", name)));
+        }
+        if changed {
+            encrypted += 1;
+        }
+    }
+
+    // The file must still be a valid config
+    Config::from_str(&editor.to_string())
+        .with_context(|| format!("failed to encrypt {}", file.display()))?;
+    editor.save(file)?;
+
+    info!("Encrypted {} password(s) in {}", encrypted, file.display());
+
+    Ok(encrypted)
+}
+
+/// The key to decrypt the passwords of the config, from `key_file` or the
+/// file in [KEY_FILE_ENV]. None if no password is encrypted.
+pub fn key_for(config: &Config, key_file: Option<&Path>) -> Result<Option<Key>> {
+    let encrypted = config
+        .users
+        .iter()
+        .find(|u| u.password.as_deref().is_some_and(is_encrypted));
+    let user = match encrypted {
+        Some(user) => user,
+        None => return Ok(None),
+    };
+
+    let key_file = match key_file {
+        Some(key_file) => key_file.to_path_buf(),
+        None => std::env::var_os(KEY_FILE_ENV)
+            .map(PathBuf::from)
+            .ok_or_else(|| {
+                anyhow!(
+                    "the password of user {} is encrypted, pass --key-file or set {}",
+                    user.name,
+                    KEY_FILE_ENV
+                )
+            })?,
+    };
+
+    Key::load(&key_file).map(Some)
+}
+
+/// The user with its password decrypted, or read from its secrets backend,
+/// see [secrets]. As is if it is neither.
+pub fn decrypt_user<'a>(user: &'a User, key: Option<&Key>) -> Result<Cow<'a, User>> {
+    let envelope = match user.password.as_deref() {
+        Some(password) if secrets::is_reference(password) => {
+            let password =
+                secrets::resolve(password).with_context(|| format!("user {}", user.name))?;
+            return Ok(Cow::Owned(User {
+                password: Some(password),
+                ..user.clone()
+            }));
+        }
+        Some(password) if is_encrypted(password) => password,
+        _ => return Ok(Cow::Borrowed(user)),
+    };
+    let key = key.ok_or_else(|| anyhow!("no key to decrypt the password of {}", user.name))?;
+
+    let password = key
+        .decrypt(envelope)
+        .with_context(|| format!("user {}", user.name))?;
+
+    Ok(Cow::Owned(User {
+        password: Some(password),
+        ..user.clone()
+    }))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use indoc::indoc;
+
+    #[test]
+    fn test_encrypt_decrypt() {
+        let key = Key::derive(b"secret");
+        let envelope = key.encrypt("p@ssw0rd").unwrap();
+
+        assert!(is_encrypted(&envelope));
+        assert_ne!(envelope, key.encrypt("p@ssw0rd").unwrap());
+        assert_eq!(key.decrypt(&envelope).unwrap(), "p@ssw0rd");
+
+        let err = Key::derive(b"other").decrypt(&envelope).unwrap_err();
+        assert_eq!(err.to_string(), "failed to decrypt, wrong key file?");
+        assert!(key.decrypt("AES256:AAAA").is_err());
+    }
+
+    #[test]
+    fn test_encrypt_file() {
+        let dir = tempfile::tempdir().unwrap();
+        let key_file = dir.path().join("key");
+        fs::write(&key_file, "secret\n").unwrap();
+        let file = dir.path().join("config.yaml");
+        fs::write(
+            &file,
+            indoc! {"
+                connection:
+                  type: postgres
+                  url: postgres://localhost:5432/postgres
+                roles: []
+                users:
+                  - name: duyet
+                    password: 1234567890 # plaintext
+                    roles: []
+                  - name: duyet2
+                    roles: []
+            "},
+        )
+        .unwrap();
+
+        assert_eq!(encrypt_file(&file, &key_file).unwrap(), 1);
+        let content = fs::read_to_string(&file).unwrap();
+        assert!(content.contains("    password: !enc AES256:"));
+        assert!(content.contains(" # plaintext\n"));
+
+        // Encrypted passwords are left as is
+        assert_eq!(encrypt_file(&file, &key_file).unwrap(), 0);
+        assert_eq!(fs::read_to_string(&file).unwrap(), content);
+
+        let config = Config::new(&file).unwrap();
+        let key = key_for(&config, Some(&key_file)).unwrap();
+        let user = decrypt_user(&config.users[0], key.as_ref()).unwrap();
+        assert_eq!(user.password.as_deref(), Some("1234567890"));
+
+        let user = decrypt_user(&config.users[1], None).unwrap();
+        assert!(matches!(user, Cow::Borrowed(_)));
+    }
+}
@@ -1,10 +1,12 @@
-use crate::config::Config;
+use crate::config::role::{RoleDatabaseLevel, RoleSchemaLevel, RoleTableLevel};
+use crate::config::{Config, Role, User};
 use ansi_term::Colour::Green;
+use anyhow::{Context, Result};
 use log::info;
 use md5::compute;
 use rand::Rng;
 use std::fs;
-use std::path::{Path, PathBuf};
+use std::path::Path;
 
 /// Generate project template to given target
 pub fn gen(target: &Path) {
@@ -29,6 +31,83 @@ pub fn gen(target: &Path) {
     info!("Generated: {:?}", config_path);
 }
 
+/// Generate a synthetic config of `users` users, each with every one of the
+/// `roles` roles, the table roles listing `tables` tables.
+///
+/// Roles cycle through the database, schema and table levels, each table role
+/// on its own schema with every 10th table revoked. Used as fixture for the
+/// benchmarks, see `grant bench-config`.
+pub fn gen_bench_config(users: usize, roles: usize, tables: usize) -> Config {
+    let roles = (0..roles)
+        .map(|i| match i % 3 {
+            0 => Role::Database(RoleDatabaseLevel {
+                name: format!("role_database_{}", i),
+                grants: vec!["CREATE".to_string(), "TEMP".to_string()],
+                databases: vec!["postgres".to_string()],
+            }),
+            1 => Role::Schema(RoleSchemaLevel {
+                name: format!("role_schema_{}", i),
+                grants: vec!["USAGE".to_string()],
+                schemas: vec![format!("schema_{}", i)],
+                database: None,
+            }),
+            _ => Role::Table(RoleTableLevel {
+                name: format!("role_table_{}", i),
+                grants: vec!["SELECT".to_string(), "INSERT".to_string()],
+                schemas: vec![format!("schema_{}", i)],
+                tables: (0..tables)
+                    .map(|t| {
+                        let sign = if t % 10 == 9 { "-" } else { "+" };
+                        format!("{}table_{}", sign, t)
+                    })
+                    .collect(),
+                database: None,
+            }),
+        })
+        .collect::<Vec<_>>();
+
+    let role_names = roles.iter().map(|r| r.get_name()).collect::<Vec<_>>();
+    let users = (0..users)
+        .map(|i| User {
+            name: format!("user_{}", i),
+            password: None,
+            password_policy: None,
+            attributes: Default::default(),
+            settings: Default::default(),
+            roles: role_names.clone(),
+            revokes: vec![],
+        })
+        .collect();
+
+    Config {
+        roles,
+        users,
+        ..Config::default()
+    }
+}
+
+/// Write a synthetic config to `file`, or print it
+pub fn bench_config(users: usize, roles: usize, tables: usize, file: Option<&Path>) -> Result<()> {
+    let config = gen_bench_config(users, roles, tables);
+    let yaml = serde_yaml::to_string(&config)?;
+
+    match file {
+        Some(file) => {
+            fs::write(file, yaml).with_context(|| format!("failed to write {}", file.display()))?;
+            info!(
+                "Generated {} user(s) x {} role(s) x {} table(s): {}",
+                users,
+                roles,
+                tables,
+                file.display()
+            );
+        }
+        None => print!("{}", yaml),
+    }
+
+    Ok(())
+}
+
 /// Generating password with given length
 pub fn gen_password(
     length: u8,
@@ -37,29 +116,7 @@ pub fn gen_password(
     password: Option<String>,
 ) {
     // If not password is given, generate random password
-    let password = match password {
-        Some(p) => p,
-        None => {
-            let chars: &[u8] = if no_special {
-                b"ABCDEFGHIJKLMNOPQRSTUVWXYZ\
-                  abcdefghijklmnopqrstuvwxyz\
-                  0123456789"
-            } else {
-                b"ABCDEFGHIJKLMNOPQRSTUVWXYZ\
-                  abcdefghijklmnopqrstuvwxyz\
-                  0123456789)(*&^%#!~"
-            };
-            let mut rng = rand::thread_rng();
-            let password: String = (0..length)
-                .map(|_| {
-                    let idx = rng.gen_range(0..chars.len());
-                    chars[idx] as char
-                })
-                .collect();
-
-            password
-        }
-    };
+    let password = password.unwrap_or_else(|| random_password(length, no_special));
 
     println!("Generated password: {}", Green.paint(password.clone()));
 
@@ -76,12 +133,34 @@ pub fn gen_password(
     }
 }
 
+/// A random password of `length` characters, only letters and digits with
+/// `no_special`
+pub fn random_password(length: u8, no_special: bool) -> String {
+    let chars: &[u8] = if no_special {
+        b"ABCDEFGHIJKLMNOPQRSTUVWXYZ\
+          abcdefghijklmnopqrstuvwxyz\
+          0123456789"
+    } else {
+        b"ABCDEFGHIJKLMNOPQRSTUVWXYZ\
+          abcdefghijklmnopqrstuvwxyz\
+          0123456789)(*&^%#!~"
+    };
+    let mut rng = rand::thread_rng();
+    (0..length)
+        .map(|_| {
+            let idx = rng.gen_range(0..chars.len());
+            chars[idx] as char
+        })
+        .collect()
+}
+
 /// Generate md5 password hash from username and password
 /// 1. Concatenate the password and username
 /// 2. Hash the concatenated string
 /// 3. Concatenate 'md5' in front of the MD5 hash string
+///
 /// https://docs.aws.amazon.com/redshift/latest/dg/r_CREATE_USER.html
-fn gen_md5_password(password: &str, username: &str) -> String {
+pub fn gen_md5_password(password: &str, username: &str) -> String {
     format!(
         "md5{:x}",
         compute(format!("{}{}", password, username).as_bytes())
@@ -108,6 +187,18 @@ mod tests {
         );
     }
 
+    #[test]
+    fn test_gen_bench_config() {
+        let config = gen_bench_config(5, 4, 20);
+
+        assert_eq!(config.users.len(), 5);
+        assert_eq!(config.roles.len(), 4);
+        assert_eq!(config.users[0].roles.len(), 4);
+        assert_eq!(config.roles[2].get_tables().len(), 20);
+        assert_eq!(config.roles[2].revoke_count(), 4);
+        assert!(config.validate().is_ok());
+    }
+
     // Test gen_md5_password
     #[test]
     fn test_gen_md5_password() {
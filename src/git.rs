@@ -0,0 +1,78 @@
+use anyhow::{anyhow, Context, Result};
+use std::path::{Path, PathBuf};
+use std::process::Command;
+
+/// List config files (`.yaml` or `.yml`) under `dir` that were changed since
+/// the given git ref, using `git diff --name-only`.
+///
+/// Deleted files are not returned, untracked files are not seen by `git diff`.
+pub fn changed_config_files(dir: &Path, git_ref: &str) -> Result<Vec<PathBuf>> {
+    let output = Command::new("git")
+        .arg("-C")
+        .arg(dir)
+        .args(["diff", "--name-only", "--relative", git_ref, "--", "."])
+        .output()
+        .context("failed to run git")?;
+
+    if !output.status.success() {
+        return Err(anyhow!(
+            "git diff --name-only {} failed: {}",
+            git_ref,
+            String::from_utf8_lossy(&output.stderr).trim()
+        ));
+    }
+
+    let mut files = String::from_utf8_lossy(&output.stdout)
+        .lines()
+        .filter(|line| line.ends_with(".yaml") || line.ends_with(".yml"))
+        .map(|line| dir.join(line))
+        .filter(|path| path.is_file())
+        .collect::<Vec<_>>();
+    files.sort();
+
+    Ok(files)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::fs;
+
+    fn git(dir: &Path, args: &[&str]) {
+        let status = Command::new("git")
+            .arg("-C")
+            .arg(dir)
+            .args(["-c", "user.name=grant", "-c", "user.email=grant@localhost"])
+            .args(args)
+            .status()
+            .unwrap();
+        assert!(status.success());
+    }
+
+    #[test]
+    fn test_changed_config_files() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path();
+
+        git(path, &["init", "-q"]);
+        fs::write(path.join("a.yaml"), "a").unwrap();
+        fs::write(path.join("b.yml"), "b").unwrap();
+        fs::write(path.join("README.md"), "readme").unwrap();
+        git(path, &["add", "."]);
+        git(path, &["commit", "-q", "-m", "init"]);
+
+        fs::write(path.join("b.yml"), "b2").unwrap();
+        fs::write(path.join("README.md"), "readme2").unwrap();
+
+        let files = changed_config_files(path, "HEAD").unwrap();
+        assert_eq!(files, vec![path.join("b.yml")]);
+    }
+
+    #[test]
+    fn test_changed_config_files_invalid_ref() {
+        let dir = tempfile::tempdir().unwrap();
+        git(dir.path(), &["init", "-q"]);
+
+        assert!(changed_config_files(dir.path(), "not-a-ref").is_err());
+    }
+}
@@ -0,0 +1,460 @@
+use crate::config::role::{RoleDatabaseLevel, RoleSchemaLevel, RoleTableLevel};
+use crate::config::{
+    ident, Config, Group, LoadOptions, Role, SyslogAccess, User, UserAttributes, UserSettings,
+};
+use crate::connection::{DbConnection, Object};
+use crate::inspect;
+use crate::state::ClusterState;
+use anyhow::{Context, Result};
+use jiff::Timestamp;
+use log::{info, warn};
+use serde_yaml::Value;
+use std::collections::BTreeMap;
+use std::fs;
+use std::path::Path;
+
+/// Print the current users, group memberships and privileges of the cluster
+/// of a config as a config, e.g. to start managing an existing cluster.
+///
+/// The connection and the scope are the ones of the config file, the
+/// connection as written so that its secrets are not expanded. The users
+/// have no password, the ones of the cluster are kept.
+pub fn import(file: &Path, load: &LoadOptions) -> Result<()> {
+    let config = Config::new_with(file, load)?;
+    let mut conn = DbConnection::new(&config)?;
+    let state = conn.get_cluster_state();
+    inspect::require_all(&state, "the config")?;
+
+    let imported = imported(&config, &state);
+    imported
+        .validate()
+        .context("the config of the cluster is invalid")?;
+
+    let content =
+        fs::read_to_string(file).with_context(|| format!("failed to read {}", file.display()))?;
+    let written: Value = serde_yaml::from_str(&content)?;
+    let mut value = serde_yaml::to_value(&imported)?;
+    if let (Value::Mapping(fields), Some(connection)) = (&mut value, written.get("connection")) {
+        fields.insert("connection".into(), connection.clone());
+    }
+
+    print!("{}", serde_yaml::to_string(&value)?);
+    info!(
+        "Imported {} user(s), {} role(s) and {} group(s) of {}",
+        imported.users.len(),
+        imported.roles.len(),
+        imported.groups.len(),
+        file.display()
+    );
+
+    Ok(())
+}
+
+/// The config granting the current privileges of the users of the scope,
+/// on the databases and on the schemas and tables of the scope, with the
+/// groups of the cluster whose members are all in the scope.
+///
+/// The objects on which a user has the same privileges share a role, and
+/// the users with the same privileges share their roles. A table role has
+/// the tables of a single schema, `ALL` when they are all of them. The
+/// privileges are the ones of the catalog, including the ones of the groups
+/// and of `PUBLIC`. The superusers and the objects owned by a user are
+/// skipped, they have every privilege.
+pub fn imported(config: &Config, state: &ClusterState) -> Config {
+    let scope = &config.scope;
+    let mut roles = Roles::default();
+    let mut users = vec![];
+
+    for user in state
+        .users()
+        .iter()
+        .filter(|u| scope.has_user(&u.name) && !u.user_super)
+    {
+        let name = user.name.as_str();
+        let held = |object: Object, privileges: Vec<&'static str>| {
+            (!privileges.is_empty() && state.owner_of(&object) != Some(name)).then_some(privileges)
+        };
+        let mut user_roles = vec![];
+
+        let mut databases: BTreeMap<Vec<&str>, Vec<String>> = BTreeMap::new();
+        for p in state.database_privileges_of(name) {
+            let object = Object::Database(p.database_name.to_string());
+            if let Some(privileges) = held(object, p.privileges()) {
+                let database = ident::quote_folded(&p.database_name);
+                databases.entry(privileges).or_default().push(database);
+            }
+        }
+        for (privileges, databases) in databases {
+            user_roles.push(roles.add(
+                role_name(&["database"], &privileges),
+                Role::Database(RoleDatabaseLevel {
+                    name: String::new(),
+                    grants: strings(&privileges),
+                    databases,
+                }),
+            ));
+        }
+
+        let mut schemas: BTreeMap<Vec<&str>, Vec<String>> = BTreeMap::new();
+        for p in state
+            .schema_privileges_of(name)
+            .iter()
+            .filter(|p| scope.has_schema(&p.schema_name))
+        {
+            let object = Object::Schema(p.schema_name.to_string());
+            if let Some(privileges) = held(object, p.privileges()) {
+                let schema = ident::quote_folded(&p.schema_name);
+                schemas.entry(privileges).or_default().push(schema);
+            }
+        }
+        for (privileges, schemas) in schemas {
+            user_roles.push(roles.add(
+                role_name(&["schema"], &privileges),
+                Role::Schema(RoleSchemaLevel {
+                    name: String::new(),
+                    grants: strings(&privileges),
+                    schemas,
+                    database: None,
+                }),
+            ));
+        }
+
+        let mut tables: BTreeMap<(&str, Vec<&str>), Vec<&str>> = BTreeMap::new();
+        for p in state
+            .table_privileges_of(name)
+            .iter()
+            .filter(|p| scope.has_schema(&p.schema_name))
+        {
+            let object = Object::Table(p.schema_name.to_string(), p.table_name.to_string());
+            if let Some(privileges) = held(object, p.privileges()) {
+                tables
+                    .entry((&p.schema_name, privileges))
+                    .or_default()
+                    .push(&p.table_name);
+            }
+        }
+        for ((schema, privileges), tables) in tables {
+            let tables = if tables.len() == state.tables_of(schema).len() {
+                vec!["ALL".to_string()]
+            } else {
+                tables.iter().map(|t| ident::quote_folded(t)).collect()
+            };
+            user_roles.push(roles.add(
+                role_name(&["table", schema], &privileges),
+                Role::Table(RoleTableLevel {
+                    name: String::new(),
+                    grants: strings(&privileges),
+                    schemas: vec![ident::quote_folded(schema)],
+                    tables,
+                    database: None,
+                }),
+            ));
+        }
+
+        users.push(User {
+            name: ident::quote_folded(name),
+            password: None,
+            password_policy: None,
+            attributes: attributes(&user.attributes),
+            settings: UserSettings(
+                user.settings
+                    .iter()
+                    .map(|(name, value)| (name.clone(), Value::String(value.clone())))
+                    .collect(),
+            ),
+            roles: user_roles,
+            revokes: vec![],
+        });
+    }
+
+    // Every member is kept, the members of a group of the config are
+    // replaced by its members at each apply
+    let groups = state
+        .groups()
+        .iter()
+        .filter(|g| match g.members.iter().find(|m| !scope.has_user(m)) {
+            Some(member) => {
+                warn!(
+                    "Group {} skipped, its member {} is outside of the scope",
+                    g.name, member
+                );
+                false
+            }
+            None => true,
+        })
+        .map(|g| Group {
+            name: ident::quote_folded(&g.name),
+            members: g.members.iter().map(|m| ident::quote_folded(m)).collect(),
+            roles: vec![],
+        })
+        .collect();
+
+    Config {
+        connection: config.connection.clone(),
+        roles: roles.into_roles(),
+        users,
+        groups,
+        scope: config.scope.clone(),
+        ..Default::default()
+    }
+}
+
+/// The roles of the imported config, each with its name
+#[derive(Default)]
+struct Roles {
+    roles: Vec<(String, Role)>,
+}
+
+impl Roles {
+    /// The name of the role with the same privileges, or of the role added
+    /// with the first name from `name` which is free, e.g. `table_public_select_2`
+    fn add(&mut self, name: String, role: Role) -> String {
+        if let Some((name, _)) = self.roles.iter().find(|(_, r)| *r == role) {
+            return name.clone();
+        }
+
+        let name = (1..)
+            .map(|i| match i {
+                1 => name.clone(),
+                i => format!("{}_{}", name, i),
+            })
+            .find(|n| self.roles.iter().all(|(other, _)| other != n))
+            .unwrap_or(name);
+        self.roles.push((name.clone(), role));
+
+        name
+    }
+
+    fn into_roles(self) -> Vec<Role> {
+        self.roles
+            .into_iter()
+            .map(|(name, mut role)| {
+                match &mut role {
+                    Role::Database(role) => role.name = name,
+                    Role::Schema(role) => role.name = name,
+                    Role::Table(role) => role.name = name,
+                    Role::Function(role) => role.name = name,
+                    Role::Sequence(role) => role.name = name,
+                    Role::Warehouse(role) => role.name = name,
+                }
+                role
+            })
+            .collect()
+    }
+}
+
+/// A role name from its level, the schema of a table role, and its
+/// privileges, e.g. `table_public_select_insert`, with only lowercase
+/// letters, digits and `_`
+fn role_name(parts: &[&str], privileges: &[&str]) -> String {
+    parts
+        .iter()
+        .chain(privileges)
+        .map(|part| {
+            part.to_lowercase()
+                .chars()
+                .map(|c| if c.is_ascii_alphanumeric() { c } else { '_' })
+                .collect::<String>()
+        })
+        .collect::<Vec<_>>()
+        .join("_")
+}
+
+fn strings(privileges: &[&str]) -> Vec<String> {
+    privileges.iter().map(|p| p.to_string()).collect()
+}
+
+/// The attributes of a user of the cluster which differ from the defaults
+/// of a new user
+fn attributes(attributes: &UserAttributes) -> UserAttributes {
+    UserAttributes {
+        createdb: attributes.createdb.filter(|createdb| *createdb),
+        superuser: None,
+        connection_limit: attributes.connection_limit.filter(|limit| *limit != -1),
+        valid_until: attributes
+            .valid_until
+            .as_deref()
+            .and_then(|v| v.parse::<Timestamp>().ok())
+            .map(|v| v.to_string()),
+        syslog_access: attributes
+            .syslog_access
+            .filter(|access| *access == SyslogAccess::Unrestricted),
+        session_timeout: attributes.session_timeout,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::connection::{self, UserDatabaseRole, UserSchemaRole, UserTableRole};
+    use indoc::indoc;
+    use std::str::FromStr;
+
+    fn user(name: &str, user_super: bool) -> connection::User {
+        connection::User {
+            name: name.to_string(),
+            user_createdb: false,
+            user_super,
+            password: "********".to_string(),
+            attributes: UserAttributes {
+                createdb: Some(false),
+                connection_limit: Some(if name == "app" { 10 } else { -1 }),
+                valid_until: Some("infinity".to_string()),
+                ..Default::default()
+            },
+            settings: match name {
+                "app" => BTreeMap::from([("search_path".to_string(), "sales".to_string())]),
+                _ => BTreeMap::new(),
+            },
+        }
+    }
+
+    fn table(user: &str, table: &str, has_insert: bool) -> UserTableRole {
+        UserTableRole {
+            name: user.into(),
+            schema_name: "sales".into(),
+            table_name: table.into(),
+            has_select: true,
+            has_insert,
+            has_update: false,
+            has_delete: false,
+            has_references: false,
+        }
+    }
+
+    #[test]
+    fn test_imported() {
+        let mut state = ClusterState::new(
+            vec![
+                user("postgres", true),
+                user("Duyet", false),
+                user("app", false),
+            ],
+            vec![connection::Group {
+                name: "analysts".to_string(),
+                members: vec!["Duyet".to_string()],
+            }],
+            ["Duyet", "app"]
+                .map(|name| UserDatabaseRole {
+                    name: name.into(),
+                    database_name: "postgres".into(),
+                    has_create: false,
+                    has_temp: true,
+                })
+                .into(),
+            vec![UserSchemaRole {
+                name: "Duyet".into(),
+                schema_name: "sales".into(),
+                has_create: false,
+                has_usage: true,
+            }],
+            vec![
+                table("Duyet", "orders", false),
+                table("Duyet", "secrets", false),
+                table("app", "orders", true),
+                table("app", "secrets", false),
+                table("postgres", "orders", true),
+            ],
+        );
+        state.set_owners(vec![connection::ObjectOwner {
+            object: Object::Table("sales".to_string(), "secrets".to_string()),
+            owner: "app".to_string(),
+        }]);
+
+        let config = Config::from_str(indoc! {"
+            connection:
+              type: postgres
+              url: postgres://localhost:5432/postgres
+            roles: []
+            users: []
+        "})
+        .unwrap();
+        let imported = imported(&config, &state);
+        imported.validate().unwrap();
+
+        assert_eq!(
+            serde_yaml::to_string(&imported).unwrap(),
+            indoc! {r#"
+                connection:
+                  type: postgres
+                  url: postgres://localhost:5432/postgres
+                roles:
+                - type: database
+                  name: database_temp
+                  grants:
+                  - TEMP
+                  databases:
+                  - postgres
+                - type: schema
+                  name: schema_usage
+                  grants:
+                  - USAGE
+                  schemas:
+                  - sales
+                - type: table
+                  name: table_sales_select
+                  grants:
+                  - SELECT
+                  schemas:
+                  - sales
+                  tables:
+                  - ALL
+                - type: table
+                  name: table_sales_select_insert
+                  grants:
+                  - SELECT
+                  - INSERT
+                  schemas:
+                  - sales
+                  tables:
+                  - orders
+                users:
+                - name: '"Duyet"'
+                  roles:
+                  - database_temp
+                  - schema_usage
+                  - table_sales_select
+                - name: app
+                  connection_limit: 10
+                  settings:
+                    search_path: sales
+                  roles:
+                  - database_temp
+                  - table_sales_select_insert
+                groups:
+                - name: analysts
+                  members:
+                  - '"Duyet"'
+            "#}
+        );
+    }
+
+    #[test]
+    fn test_role_name() {
+        assert_eq!(
+            role_name(&["table", "Sales Data"], &["SELECT", "INSERT"]),
+            "table_sales_data_select_insert"
+        );
+
+        let mut roles = Roles::default();
+        let role = |databases: &[&str]| {
+            Role::Database(RoleDatabaseLevel {
+                name: String::new(),
+                grants: vec!["TEMP".to_string()],
+                databases: databases.iter().map(|d| d.to_string()).collect(),
+            })
+        };
+        assert_eq!(
+            roles.add("database_temp".to_string(), role(&["a"])),
+            "database_temp"
+        );
+        assert_eq!(
+            roles.add("database_temp".to_string(), role(&["b"])),
+            "database_temp_2"
+        );
+        assert_eq!(
+            roles.add("database_temp".to_string(), role(&["a"])),
+            "database_temp"
+        );
+    }
+}
@@ -1,68 +1,152 @@
-use crate::config::Config;
-use crate::connection::{DbConnection, UserDatabaseRole, UserSchemaRole, UserTableRole};
-use anyhow::Result;
-use ascii_table::AsciiTable;
+use crate::catalog::Query;
+use crate::config::effective::Source;
+use crate::config::{ident, Config, Dialect, Group, Role, Scope};
+use crate::connection::{DbConnection, Object};
+use crate::patterns;
+use crate::plan::{self, Action};
+use crate::render::{CsvRenderer, OutputFormat, Renderer};
+use crate::report::{Drift, GrantRow, Report, UserPrivileges};
+use crate::state::ClusterState;
+use crate::telemetry;
+use anyhow::{anyhow, Result};
 use indoc::indoc;
 use log::info;
+use serde::Serialize;
+use std::collections::BTreeSet;
+use std::fmt;
 
-pub fn inspect(config: &Config) -> Result<()> {
-    let mut conn = DbConnection::new(config);
+/// What `grant inspect` prints
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum InspectFormat {
+    /// The privileges of each user, rendered with the [OutputFormat]
+    #[default]
+    Table,
+    /// The statements granting the current privileges again, see
+    /// [regrant_script]
+    Sql,
+    /// The privileges of each user as an [Inspection], for dashboards and
+    /// CI checks
+    Json,
+    Yaml,
+    /// A row per group membership and privileged object of each user, see
+    /// [GrantRow]
+    Csv,
+}
 
-    let users_in_db = conn.get_users()?;
-    let user_database_privileges = conn
-        .get_user_database_privileges()
-        .unwrap()
-        .into_iter()
-        .filter(|p| p.database_name == conn.get_current_database().unwrap())
-        .collect::<Vec<_>>();
-    let user_schema_privileges = conn.get_user_schema_privileges()?;
-    let user_table_privileges = conn.get_user_table_privileges()?;
+impl InspectFormat {
+    pub const VARIANTS: &'static [&'static str] = &["table", "sql", "json", "yaml", "csv"];
+}
+
+impl fmt::Display for InspectFormat {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            InspectFormat::Table => write!(f, "table"),
+            InspectFormat::Sql => write!(f, "sql"),
+            InspectFormat::Json => write!(f, "json"),
+            InspectFormat::Yaml => write!(f, "yaml"),
+            InspectFormat::Csv => write!(f, "csv"),
+        }
+    }
+}
+
+impl std::str::FromStr for InspectFormat {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.to_lowercase().as_str() {
+            "table" => Ok(InspectFormat::Table),
+            "sql" => Ok(InspectFormat::Sql),
+            "json" => Ok(InspectFormat::Json),
+            "yaml" => Ok(InspectFormat::Yaml),
+            "csv" => Ok(InspectFormat::Csv),
+            _ => Err(anyhow!("unknown inspect format: {}", s)),
+        }
+    }
+}
+
+pub fn inspect(config: &Config, output: OutputFormat, format: InspectFormat) -> Result<()> {
+    let _span = telemetry::span("inspect", vec![]);
+    let mut conn = DbConnection::new(config)?;
+    let state = conn.get_cluster_state();
+    // Only the schemas and users managed by the config are shown
+    let scope = &config.scope;
+
+    match format {
+        InspectFormat::Table => {}
+        InspectFormat::Sql => {
+            require_all(&state, "the SQL script")?;
+            println!("{}", regrant_script(&state, scope, conn.dialect()));
+            return Ok(());
+        }
+        InspectFormat::Json => {
+            println!(
+                "{}",
+                serde_json::to_string_pretty(&inspection(&state, scope))?
+            );
+            return Ok(());
+        }
+        InspectFormat::Yaml => {
+            print!("{}", serde_yaml::to_string(&inspection(&state, scope))?);
+            return Ok(());
+        }
+        InspectFormat::Csv => {
+            // A missing section would be missing rows, not a null
+            require_all(&state, "the CSV")?;
+            let report = grant_rows(&inspection(&state, scope));
+            println!("{}", CsvRenderer.render(&report.to_table()));
+            return Ok(());
+        }
+    }
 
-    let mut users = users_in_db
+    let mut report = Report::new();
+    report.rows = state
+        .users()
         .iter()
+        .filter(|u| scope.has_user(&u.name))
         .map(|u| {
-            vec![
-                u.name.clone(),
-                u.user_super.to_string(),
-                get_user_database_privileges(&user_database_privileges, &u.name).unwrap(),
-                get_user_schema_privileges(&user_schema_privileges, &u.name).unwrap(),
-                get_user_table_privileges(&user_table_privileges, &u.name).unwrap(),
-            ]
+            // Memberships added by hand are shown as the managed ones
+            let groups = state.groups_of(&u.name).into_iter().map(String::from);
+            let databases = state
+                .database_privileges_of(&u.name)
+                .iter()
+                .filter(|p| Some(&*p.database_name) == state.current_database.as_deref())
+                .filter(|p| p.has_create || p.has_temp) // has at least create or temp
+                .map(|p| p.perm_to_string(true));
+            let schemas = state
+                .schema_privileges_of(&u.name)
+                .iter()
+                .filter(|p| scope.has_schema(&p.schema_name))
+                .filter(|p| p.has_create || p.has_usage)
+                .map(|p| p.perm_to_string(true));
+            let tables = state
+                .table_privileges_of(&u.name)
+                .iter()
+                .filter(|p| scope.has_schema(&p.schema_name))
+                .filter(|p| {
+                    p.has_select || p.has_insert || p.has_update || p.has_delete || p.has_references
+                }) // has at least one privilege
+                .map(|p| p.perm_to_string(true));
+
+            UserPrivileges {
+                user: u.name.clone(),
+                superuser: u.user_super,
+                groups: if_available(&state, Query::Groups, groups),
+                databases: if_available(&state, Query::DatabasePrivileges, databases),
+                schemas: if_available(&state, Query::SchemaPrivileges, schemas),
+                tables: if_available(&state, Query::TablePrivileges, tables),
+            }
         })
         .collect::<Vec<_>>();
 
-    users.insert(
-        0,
-        vec![
-            "User".to_string(),
-            "Super".to_string(),
-            "Current Database".to_string(),
-            "Schemas".to_string(),
-            "Tables".to_string(),
-        ],
-    );
-    users.insert(
-        1,
-        vec![
-            "---".to_string(),
-            "---".to_string(),
-            "---".to_string(),
-            "---".to_string(),
-        ],
+    output.output(
+        &format!("Current users in {}", config.connection.url),
+        &report.to_table(),
     );
 
-    // Get the terminal with
-    let term_width = term_size::dimensions().map(|(w, _)| w).unwrap_or(120) - 5;
-
-    // Print the table in max size
-    let mut table = AsciiTable::default();
-    table.set_max_width(term_width);
-
-    info!(
-        "Current users in {}:\n{}",
-        config.connection.url,
-        table.format(users)
-    );
+    // The legend is only useful for the terminal table
+    if output != OutputFormat::Table {
+        return Ok(());
+    }
 
     info!(indoc! { r#"
         == Legend ==
@@ -89,43 +173,602 @@ pub fn inspect(config: &Config) -> Result<()> {
     Ok(())
 }
 
-/// Get current user database privileges
-fn get_user_database_privileges(privileges: &[UserDatabaseRole], user: &str) -> Result<String> {
-    let privileges = privileges
-        .iter()
-        .filter(|p| p.name == *user) // is current user
-        .filter(|p| p.has_create || p.has_temp) // has at least create or temp
-        .map(|p| p.perm_to_string(true))
-        .collect::<Vec<_>>()
-        .join(", ");
+/// Compare the cluster with its config and print the drift, see [drift].
+/// Fails if there is any, e.g. to alert from a nightly CI job.
+pub fn diff(config: &Config, output: OutputFormat) -> Result<()> {
+    let _span = telemetry::span("inspect", vec![]);
+    let mut conn = DbConnection::new(config)?;
+    let config = &*patterns::expand(config, &mut conn)?;
+    let state = conn.get_cluster_state();
+    require_all(&state, "the drift report")?;
+
+    let report = drift(config, &state);
+    output.output(
+        &format!("Drift of {} from the config", config.connection.url),
+        &report.to_table(),
+    );
+    if report.is_empty() {
+        info!("No drift, the cluster is as in the config");
+        return Ok(());
+    }
 
-    Ok(privileges)
+    Err(anyhow!(
+        "{} drift(s) between the cluster and the config",
+        report.rows.len()
+    ))
 }
 
-/// Get current user schema privileges
-fn get_user_schema_privileges(privileges: &[UserSchemaRole], user: &str) -> Result<String> {
-    let privileges = privileges
+/// The differences between the cluster and its config: the users of the
+/// config which are missing, the privileges their roles grant which are
+/// missing or revoke which are held, the privileges held on the objects of
+/// the roles which no role grants, the group memberships, and the users of
+/// the scope which are not in the config. The superusers have every
+/// privilege, they are never unknown.
+///
+/// The privileges are the ones of the catalog, including the ones of the
+/// groups and of `PUBLIC`. As for `grant plan`, the privileges on functions,
+/// sequences and the other databases are not read, they are not compared.
+pub fn drift(config: &Config, state: &ClusterState) -> Report<Drift> {
+    let mut report = Report::new();
+    let row = |user: &str, kind: &str, object: &str, privileges: Vec<String>| Drift {
+        user: user.to_string(),
+        kind: kind.to_string(),
+        object: object.to_string(),
+        privileges,
+    };
+
+    // The privileges of each user granted by the config or already
+    // reported, and the objects of the roles
+    let mut known = BTreeSet::new();
+    let mut managed = BTreeSet::new();
+    let mut users = vec![];
+    for user in config.users.iter().filter(|u| !u.is_public()) {
+        let name = ident::fold(&user.name);
+        if state.user(&name).is_none() {
+            report.push(row(&name, "missing user", "", vec![]));
+            continue;
+        }
+
+        let effective = config.effective_privileges(&user.name);
+        let mut seen = BTreeSet::new();
+        let roles = effective
+            .privileges
+            .iter()
+            .map(|p| (&p.role, p.source == Source::Revoked))
+            .filter(|role| seen.insert(*role))
+            .filter_map(|(role, revoked)| {
+                Some((
+                    config.roles.iter().find(|r| r.get_name() == *role)?,
+                    revoked,
+                ))
+            })
+            .filter(|(r, _)| matches!(r, Role::Database(_) | Role::Schema(_) | Role::Table(_)))
+            .filter(|(r, _)| {
+                r.database()
+                    .is_none_or(|d| Some(ident::fold(d)) == state.current_database)
+            });
+        for (role, revoked) in roles {
+            let changes = match revoked {
+                true => plan::revoke_changes(&name, role, state),
+                false => plan::role_changes(&name, role, state),
+            };
+            for change in changes {
+                managed.insert(change.object.clone());
+                match change.action {
+                    Action::Grant => report.push(row(
+                        &name,
+                        "missing grant",
+                        &change.object,
+                        change.privileges.clone(),
+                    )),
+                    Action::Revoke => report.push(row(
+                        &name,
+                        "extra grant",
+                        &change.object,
+                        change.privileges.clone(),
+                    )),
+                    _ if change.revoke => continue,
+                    _ => {}
+                }
+                for privilege in change.privileges {
+                    known.insert((name.clone(), change.object.clone(), privilege));
+                }
+            }
+        }
+        users.push(name);
+    }
+
+    for user in &users {
+        let mut extra = |object: String, held: Vec<&str>, owner: Object| {
+            if !managed.contains(&object) || state.owner_of(&owner) == Some(user) {
+                return;
+            }
+            let privileges = held
+                .into_iter()
+                .map(String::from)
+                .filter(|p| !known.contains(&(user.clone(), object.clone(), p.clone())))
+                .collect::<Vec<_>>();
+            if !privileges.is_empty() {
+                report.push(row(user, "extra grant", &object, privileges));
+            }
+        };
+
+        for p in state.database_privileges_of(user) {
+            let database = p.database_name.to_string();
+            extra(
+                format!("DATABASE {}", ident::quote_folded(&database)),
+                p.privileges(),
+                Object::Database(database),
+            );
+        }
+        for p in state.schema_privileges_of(user) {
+            let schema = p.schema_name.to_string();
+            extra(
+                format!("SCHEMA {}", ident::quote_folded(&schema)),
+                p.privileges(),
+                Object::Schema(schema),
+            );
+        }
+        for p in state.table_privileges_of(user) {
+            let (schema, table) = (p.schema_name.to_string(), p.table_name.to_string());
+            extra(
+                format!(
+                    "TABLE {}.{}",
+                    ident::quote_folded(&schema),
+                    ident::quote_folded(&table)
+                ),
+                p.privileges(),
+                Object::Table(schema, table),
+            );
+        }
+    }
+
+    for change in plan::group_changes(config, state.groups()) {
+        let kind = match change.action {
+            Action::CreateGroup => "missing group",
+            Action::AddMember => "missing member",
+            _ => "extra member",
+        };
+        report.push(row(&change.user, kind, &change.object, vec![]));
+    }
+
+    for user in state
+        .users()
         .iter()
-        .filter(|p| p.name == *user)
-        .filter(|p| p.has_create || p.has_usage)
-        .map(|p| p.perm_to_string(true))
-        .collect::<Vec<_>>()
-        .join(", ");
+        .filter(|u| config.scope.has_user(&u.name) && !u.user_super)
+    {
+        if !config
+            .users
+            .iter()
+            .any(|u| ident::fold(&u.name) == user.name)
+        {
+            report.push(row(&user.name, "unknown user", "", vec![]));
+        }
+    }
+
+    report
+}
+
+/// Fail unless every section of the cluster was read, `what` is written
+/// from them
+pub fn require_all(state: &ClusterState, what: &str) -> Result<()> {
+    for query in [
+        Query::Users,
+        Query::Groups,
+        Query::DatabasePrivileges,
+        Query::SchemaPrivileges,
+        Query::TablePrivileges,
+    ] {
+        if !state.is_available(query) {
+            return Err(anyhow!(
+                "cannot write {} without the {} of the cluster",
+                what,
+                query
+            ));
+        }
+    }
 
-    Ok(privileges)
+    Ok(())
+}
+
+/// The current privileges of the users of the scope, as printed by `inspect
+/// --format json` and `yaml`:
+///
+/// ```json
+/// {
+///   "database": "postgres",
+///   "users": [
+///     {
+///       "name": "duyet",
+///       "superuser": false,
+///       "groups": ["analysts"],
+///       "databases": [{ "name": "postgres", "privileges": ["CREATE", "TEMP"] }],
+///       "schemas": [{ "name": "public", "privileges": ["USAGE"] }],
+///       "tables": [{ "name": "public.orders", "privileges": ["SELECT"] }]
+///     }
+///   ]
+/// }
+/// ```
+#[derive(Debug, Clone, Serialize, PartialEq, Eq)]
+pub struct Inspection {
+    /// The database of the connection
+    pub database: Option<String>,
+    pub users: Vec<InspectedUser>,
+}
+
+/// A user of the cluster with its privileges, a section which could not be
+/// read is None
+#[derive(Debug, Clone, Serialize, PartialEq, Eq)]
+pub struct InspectedUser {
+    pub name: String,
+    pub superuser: bool,
+    pub groups: Option<Vec<String>>,
+    pub databases: Option<Vec<ObjectPrivileges>>,
+    pub schemas: Option<Vec<ObjectPrivileges>>,
+    pub tables: Option<Vec<ObjectPrivileges>>,
 }
 
-/// Get current user schema.table privileges
-fn get_user_table_privileges(privileges: &[UserTableRole], user: &str) -> Result<String> {
-    let privileges = privileges
+/// The privileges held on a database, schema or table, named as in the
+/// catalog, e.g. `public.orders` for a table
+#[derive(Debug, Clone, Serialize, PartialEq, Eq)]
+pub struct ObjectPrivileges {
+    pub name: String,
+    pub privileges: Vec<&'static str>,
+}
+
+/// The privileges of the users of the scope on every database, and on the
+/// schemas and tables of the scope
+pub fn inspection(state: &ClusterState, scope: &Scope) -> Inspection {
+    let users = state
+        .users()
         .iter()
-        .filter(|p| p.name == *user) // is current user
-        .filter(|p| {
-            p.has_select || p.has_insert || p.has_update || p.has_delete || p.has_references
-        }) // has at least create or select
-        .map(|p| p.perm_to_string(true))
-        .collect::<Vec<_>>()
-        .join(", ");
-
-    Ok(privileges)
+        .filter(|u| scope.has_user(&u.name))
+        .map(|u| {
+            let groups = state.groups_of(&u.name).into_iter().map(String::from);
+            let databases = state
+                .database_privileges_of(&u.name)
+                .iter()
+                .map(|p| object_privileges(p.database_name.to_string(), p.privileges()));
+            let schemas = state
+                .schema_privileges_of(&u.name)
+                .iter()
+                .filter(|p| scope.has_schema(&p.schema_name))
+                .map(|p| object_privileges(p.schema_name.to_string(), p.privileges()));
+            let tables = state
+                .table_privileges_of(&u.name)
+                .iter()
+                .filter(|p| scope.has_schema(&p.schema_name))
+                .map(|p| {
+                    let name = format!("{}.{}", p.schema_name, p.table_name);
+                    object_privileges(name, p.privileges())
+                });
+
+            InspectedUser {
+                name: u.name.clone(),
+                superuser: u.user_super,
+                groups: if_available(state, Query::Groups, groups),
+                databases: if_available(state, Query::DatabasePrivileges, databases.flatten()),
+                schemas: if_available(state, Query::SchemaPrivileges, schemas.flatten()),
+                tables: if_available(state, Query::TablePrivileges, tables.flatten()),
+            }
+        })
+        .collect();
+
+    Inspection {
+        database: state.current_database.clone(),
+        users,
+    }
+}
+
+/// The privileges on an object, None if none is held
+fn object_privileges(name: String, privileges: Vec<&'static str>) -> Option<ObjectPrivileges> {
+    (!privileges.is_empty()).then_some(ObjectPrivileges { name, privileges })
+}
+
+/// The rows of `inspect --format csv`: a row per group membership and per
+/// privileged object of each user, a single row without an object for a
+/// user without any
+pub fn grant_rows(inspection: &Inspection) -> Report<GrantRow> {
+    let mut report = Report::new();
+    for user in &inspection.users {
+        let row = |kind: &str, object: &str, privileges: &[&str]| GrantRow {
+            user: user.name.clone(),
+            superuser: user.superuser,
+            kind: kind.to_string(),
+            object: object.to_string(),
+            privileges: privileges.iter().map(|p| p.to_string()).collect(),
+        };
+
+        let count = report.rows.len();
+        for group in user.groups.iter().flatten() {
+            report.push(row("group", group, &[]));
+        }
+        for (kind, objects) in [
+            ("database", &user.databases),
+            ("schema", &user.schemas),
+            ("table", &user.tables),
+        ] {
+            for object in objects.iter().flatten() {
+                report.push(row(kind, &object.name, &object.privileges));
+            }
+        }
+        if report.rows.len() == count {
+            report.push(row("", "", &[]));
+        }
+    }
+
+    report
+}
+
+/// The statements granting the current privileges of the users of the scope
+/// again, e.g. to restore them or to compare two clusters: the group
+/// memberships, then the privileges on the databases, schemas and tables of
+/// each user. The users and groups are not created, and the superusers are
+/// skipped as they have every privilege.
+pub fn regrant_script(state: &ClusterState, scope: &Scope, dialect: Dialect) -> String {
+    let mut lines = vec![format!(
+        "-- Privileges of {}, generated by grant inspect --format sql",
+        state.current_database.as_deref().unwrap_or("the cluster")
+    )];
+
+    for user in state.users().iter().filter(|u| scope.has_user(&u.name)) {
+        let name = ident::quote_folded(&user.name);
+        lines.push(String::new());
+        if user.user_super {
+            lines.push(format!("-- {} is a superuser, skipped", name));
+            continue;
+        }
+        lines.push(format!("-- {}", name));
+
+        for group in state.groups_of(&user.name) {
+            let group = Group {
+                name: ident::quote_folded(group),
+                members: vec![],
+                roles: vec![],
+            };
+            lines.push(group.to_sql_add(&user.name, dialect));
+        }
+
+        for p in state.database_privileges_of(&user.name) {
+            lines.extend(grant(
+                &p.privileges(),
+                &format!("DATABASE {}", ident::quote_folded(&p.database_name)),
+                &name,
+            ));
+        }
+
+        for p in state
+            .schema_privileges_of(&user.name)
+            .iter()
+            .filter(|p| scope.has_schema(&p.schema_name))
+        {
+            lines.extend(grant(
+                &p.privileges(),
+                &format!("SCHEMA {}", ident::quote_folded(&p.schema_name)),
+                &name,
+            ));
+        }
+
+        for p in state
+            .table_privileges_of(&user.name)
+            .iter()
+            .filter(|p| scope.has_schema(&p.schema_name))
+        {
+            let table = format!(
+                "TABLE {}.{}",
+                ident::quote_folded(&p.schema_name),
+                ident::quote_folded(&p.table_name)
+            );
+            lines.extend(grant(&p.privileges(), &table, &name));
+        }
+    }
+
+    lines.join("\n")
+}
+
+/// The GRANT of the privileges, None if there is none
+fn grant(privileges: &[&str], object: &str, user: &str) -> Option<String> {
+    if privileges.is_empty() {
+        return None;
+    }
+
+    Some(format!(
+        "GRANT {} ON {} TO {};",
+        privileges.join(", "),
+        object,
+        user
+    ))
+}
+
+/// The privileges of an available section, None otherwise
+fn if_available<T>(
+    state: &ClusterState,
+    query: Query,
+    privileges: impl Iterator<Item = T>,
+) -> Option<Vec<T>> {
+    state.is_available(query).then(|| privileges.collect())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::connection::{Group, User, UserDatabaseRole, UserSchemaRole, UserTableRole};
+    use std::str::FromStr;
+
+    fn user(name: &str, user_super: bool) -> User {
+        User {
+            name: name.to_string(),
+            user_createdb: false,
+            user_super,
+            password: "********".to_string(),
+            attributes: Default::default(),
+            settings: Default::default(),
+        }
+    }
+
+    fn state() -> ClusterState {
+        let mut state = ClusterState::new(
+            vec![
+                user("postgres", true),
+                user("Duyet", false),
+                user("other", false),
+            ],
+            vec![Group {
+                name: "analysts".to_string(),
+                members: vec!["Duyet".to_string()],
+            }],
+            vec![UserDatabaseRole {
+                name: "Duyet".into(),
+                database_name: "postgres".into(),
+                has_create: false,
+                has_temp: true,
+            }],
+            vec![UserSchemaRole {
+                name: "Duyet".into(),
+                schema_name: "sales".into(),
+                has_create: false,
+                has_usage: true,
+            }],
+            vec![
+                UserTableRole {
+                    name: "Duyet".into(),
+                    schema_name: "sales".into(),
+                    table_name: "orders".into(),
+                    has_select: true,
+                    has_insert: true,
+                    has_update: false,
+                    has_delete: false,
+                    has_references: false,
+                },
+                UserTableRole {
+                    name: "Duyet".into(),
+                    schema_name: "sales".into(),
+                    table_name: "secrets".into(),
+                    has_select: false,
+                    has_insert: false,
+                    has_update: false,
+                    has_delete: false,
+                    has_references: false,
+                },
+            ],
+        );
+        state.current_database = Some("postgres".to_string());
+
+        state
+    }
+
+    fn scope() -> Scope {
+        Scope {
+            schemas: None,
+            users: Some(vec!["postgres".to_string(), "\"Duyet\"".to_string()]),
+        }
+    }
+
+    #[test]
+    fn test_regrant_script() {
+        let (state, scope) = (state(), scope());
+
+        assert_eq!(
+            regrant_script(&state, &scope, Dialect::Postgres),
+            indoc! {r#"
+                -- Privileges of postgres, generated by grant inspect --format sql
+
+                -- postgres is a superuser, skipped
+
+                -- "Duyet"
+                GRANT analysts TO "Duyet";
+                GRANT TEMP ON DATABASE postgres TO "Duyet";
+                GRANT USAGE ON SCHEMA sales TO "Duyet";
+                GRANT SELECT, INSERT ON TABLE sales.orders TO "Duyet";"#}
+        );
+        assert!(regrant_script(&state, &scope, Dialect::Redshift)
+            .contains("ALTER GROUP analysts ADD USER \"Duyet\";"));
+    }
+
+    #[test]
+    fn test_drift() {
+        let config = Config::from_str(indoc! {r#"
+            connection:
+              type: postgres
+              url: postgres://localhost:5432/postgres
+            roles:
+              - name: role_sales
+                type: table
+                grants: [SELECT]
+                schemas: [sales]
+                tables: [orders, secrets]
+              - name: role_other_database
+                type: schema
+                database: analytics
+                grants: [USAGE]
+                schemas: [marts]
+            users:
+              - name: '"Duyet"'
+                roles: [role_sales, role_other_database]
+              - name: missing
+                roles: [role_sales]
+            groups:
+              - name: analysts
+                members: []
+                roles: []
+        "#})
+        .unwrap();
+
+        assert_eq!(
+            CsvRenderer.render(&drift(&config, &state()).to_table()),
+            indoc! {"
+                User,Drift,Object,Privileges
+                Duyet,missing grant,TABLE sales.secrets,SELECT
+                missing,missing user,,
+                Duyet,extra grant,TABLE sales.orders,INSERT
+                Duyet,extra member,analysts,
+                other,unknown user,,"}
+        );
+    }
+
+    #[test]
+    fn test_inspection() {
+        let inspection = inspection(&state(), &scope());
+
+        assert_eq!(
+            serde_yaml::to_string(&inspection).unwrap(),
+            indoc! {"
+                database: postgres
+                users:
+                - name: postgres
+                  superuser: true
+                  groups: []
+                  databases: []
+                  schemas: []
+                  tables: []
+                - name: Duyet
+                  superuser: false
+                  groups:
+                  - analysts
+                  databases:
+                  - name: postgres
+                    privileges:
+                    - TEMP
+                  schemas:
+                  - name: sales
+                    privileges:
+                    - USAGE
+                  tables:
+                  - name: sales.orders
+                    privileges:
+                    - SELECT
+                    - INSERT
+            "}
+        );
+        assert_eq!(
+            CsvRenderer.render(&grant_rows(&inspection).to_table()),
+            indoc! {"
+                User,Super,Type,Object,Privileges
+                postgres,true,,,
+                Duyet,false,group,analysts,
+                Duyet,false,database,postgres,TEMP
+                Duyet,false,schema,sales,USAGE
+                Duyet,false,table,sales.orders,SELECT INSERT"}
+        );
+    }
 }
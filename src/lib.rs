@@ -201,13 +201,45 @@
 //!
 //! MIT
 
+pub mod adapter;
 pub mod apply;
+pub mod audit;
+pub mod bootstrap;
+pub mod catalog;
 pub mod cli;
+pub mod clone;
 pub mod config;
 pub mod connection;
+pub mod destroy;
+pub mod doctor;
+pub mod encrypt;
 pub mod gen;
+pub mod git;
+pub mod import;
 pub mod inspect;
+pub mod notify;
+pub mod objects;
+pub mod owners;
+pub mod patterns;
+pub mod plan;
+pub mod prompt;
+pub mod refactor;
+pub mod render;
+pub mod report;
+pub mod retry;
+pub mod roles;
+pub mod secrets;
+pub mod shutdown;
+pub mod slack;
+pub mod state;
+pub mod telemetry;
+pub mod timestamp;
+pub mod trigger;
+pub mod update;
+pub mod users;
 pub mod validate;
+pub mod verify;
+pub mod version;
 
 pub use cli::Cli;
 pub use config::Config;
@@ -1,22 +1,69 @@
-mod apply;
-mod cli;
-mod config;
-mod connection;
-mod gen;
-mod inspect;
-mod validate;
-
-use crate::config::Config;
-use anyhow::Result;
-use cli::Command;
+use anyhow::{anyhow, Result};
 use env_logger::Env;
+use grant::apply::{self, ApplyOptions, Outcome};
+use grant::bootstrap::{self, BootstrapOptions};
+use grant::cli::{self, Command, EventTriggerCommand, RefactorCommand, RolesCommand, UsersCommand};
+use grant::config::LoadOptions;
+use grant::destroy::{self, DestroyOptions};
+use grant::inspect::InspectFormat;
+use grant::roles::{self, NewRole};
+use grant::shutdown::{self, Interrupted};
+use grant::{
+    clone, doctor, encrypt, gen, import, inspect, plan, refactor, telemetry, timestamp, trigger,
+    update, users, validate, verify, version, Config,
+};
+use log::{error, info};
+use std::io::Write;
 
 fn main() -> Result<()> {
-    // Logger config, for debugger export RUST_LOG=debug
+    let cli = cli::parse();
+    timestamp::set_timezone(cli.timezone);
+    let load = LoadOptions {
+        vars: cli.vars,
+        ..Default::default()
+    };
+
+    // Logger config, for debugger export RUST_LOG=debug. The default format
+    // with the timestamps of `--timezone`
     let env = Env::new().default_filter_or("info");
-    env_logger::init_from_env(env);
+    env_logger::Builder::from_env(env)
+        .format(|buf, record| {
+            let style = buf.default_level_style(record.level());
+            writeln!(
+                buf,
+                "[{} {style}{:<5}{style:#} {}] {}",
+                timestamp::now(),
+                record.level(),
+                record.target(),
+                record.args()
+            )
+        })
+        .init();
+
+    // Traces of plan, apply and inspect, if an OTLP endpoint is set
+    let telemetry = telemetry::init();
+    let result = run(cli.cmd, &load);
+    telemetry.shutdown();
+
+    // Interrupted by SIGINT or SIGTERM, the summary and checkpoint are written already
+    if let Err(e) = &result {
+        if e.is::<Interrupted>() {
+            error!("{}", e);
+            std::process::exit(shutdown::EXIT_CODE);
+        }
+    }
+
+    match result? {
+        0 => Ok(()),
+        code => std::process::exit(code),
+    }
+}
 
-    match cli::parse().cmd {
+/// Run the command, returning the exit code of a successful run. The config
+/// files are read with the `--var` overrides of `load`, but by the commands
+/// editing them.
+fn run(cmd: Command, load: &LoadOptions) -> Result<i32> {
+    match cmd {
         Command::Gen { target } => {
             gen::gen(&target);
         }
@@ -30,29 +77,277 @@ fn main() -> Result<()> {
             gen::gen_password(length, no_special, username, password);
         }
 
-        Command::Validate { file } => {
+        Command::Validate {
+            file,
+            overlay,
+            owners,
+        } => {
             let target = if let Some(file) = file {
                 file
             } else {
                 std::env::current_dir()?
             };
 
-            validate::validate_target(&target)?;
+            let load = LoadOptions {
+                overlays: overlay,
+                vars: load.vars.clone(),
+            };
+            validate::validate_target(&target, &load, owners.as_deref())?;
+        }
+
+        Command::Inspect {
+            file,
+            output,
+            format,
+            diff,
+        } => {
+            let value = Config::new_with(&file, load)?;
+            if diff {
+                if format != InspectFormat::Table {
+                    return Err(anyhow!("--diff cannot be used with --format {}", format));
+                }
+                inspect::diff(&value, output)?;
+            } else {
+                inspect::inspect(&value, output, format)?;
+            }
+        }
+
+        Command::Import { file } => {
+            import::import(&file, load)?;
         }
 
-        Command::Inspect { file } => {
-            let value = Config::new(&file)?;
-            inspect::inspect(&value)?;
+        Command::Bootstrap {
+            url,
+            maintenance_user,
+            password,
+            schemas,
+            dryrun,
+        } => {
+            let opts = BootstrapOptions {
+                user: maintenance_user,
+                password,
+                schemas,
+                dryrun,
+            };
+            bootstrap::bootstrap(&url, &opts)?;
         }
 
-        Command::Apply { file, dryrun, all } => {
-            if all {
-                apply::apply_all(&file, dryrun)?;
+        Command::Destroy {
+            file,
+            dryrun,
+            drop_users,
+            yes,
+        } => {
+            let opts = DestroyOptions {
+                dryrun,
+                drop_users,
+                yes,
+                vars: load.vars.clone(),
+            };
+            destroy::destroy(&file, &opts)?;
+        }
+
+        Command::Apply {
+            file,
+            overlay,
+            dryrun,
+            all,
+            incremental,
+            changed_since,
+            env_config,
+            resume,
+            rate_limit,
+            parallel,
+            allow_mass_changes,
+            allow_self_modification,
+            auto_approve,
+            max_revokes,
+            max_dropped_users,
+            output,
+            key_file,
+            notify,
+            context,
+            prune_users,
+            offline,
+            no_transaction,
+            plan,
+            detailed_exitcode,
+            verify_objects,
+        } => {
+            let opts = ApplyOptions {
+                dryrun,
+                overlays: overlay,
+                vars: load.vars.clone(),
+                incremental,
+                resume,
+                rate_limit,
+                allow_mass_changes,
+                allow_self_modification,
+                max_revokes,
+                max_dropped_users,
+                output,
+                key_file,
+                notify,
+                context,
+                prune_users,
+                offline,
+                no_transaction,
+                plan,
+                parallel,
+                auto_approve,
+                verify_objects: verify_objects.map(Option::unwrap_or_default),
+            };
+
+            shutdown::install()?;
+
+            let outcome = if env_config {
+                apply::apply_env(&opts)?
             } else {
-                apply::apply(&file, dryrun)?;
+                let file = file.ok_or_else(|| anyhow!("--file is required"))?;
+
+                if let Some(git_ref) = changed_since {
+                    apply::apply_changed_since(&file, &git_ref, &opts)?
+                } else if all {
+                    apply::apply_all(&file, &opts)?
+                } else {
+                    apply::apply(&file, &opts)?
+                }
+            };
+            if detailed_exitcode {
+                return Ok(outcome.exit_code());
             }
         }
+
+        Command::Plan {
+            file,
+            out,
+            output,
+            detailed_exitcode,
+        } => {
+            let config = Config::new_with(&file, load)?;
+            let plan = plan::plan(&config, output)?;
+            if let Some(out) = out {
+                plan::SavedPlan::new(&file, &config, &plan)?.save(&out)?;
+                info!("Saved the plan to {}", out.display());
+            }
+            if detailed_exitcode && plan.has_changes() {
+                return Ok(Outcome::Changed.exit_code());
+            }
+        }
+
+        Command::CloneGrants {
+            from,
+            to,
+            file,
+            apply,
+        } => {
+            let config = Config::new_with(&file, load)?;
+            clone::clone_grants(&config, &from, &to, apply)?;
+        }
+
+        Command::Encrypt { file, key_file } => {
+            encrypt::encrypt_file(&file, &key_file)?;
+        }
+
+        Command::BenchConfig {
+            users,
+            roles,
+            tables,
+            file,
+        } => {
+            gen::bench_config(users, roles, tables, file.as_deref())?;
+        }
+
+        Command::Users(cmd) => match cmd {
+            UsersCommand::List { file, output } => users::list(&file, output)?,
+            UsersCommand::Show { name, file } => users::show(&file, &name)?,
+            UsersCommand::Add {
+                name,
+                roles,
+                password,
+                file,
+            } => users::add(&file, &name, &roles, password)?,
+            UsersCommand::Import {
+                csv,
+                roles,
+                dryrun,
+                file,
+            } => users::import(&file, &csv, &roles, dryrun)?,
+            UsersCommand::Remove { name, file } => users::remove(&file, &name)?,
+        },
+
+        Command::Roles(cmd) => match cmd {
+            RolesCommand::List { file, output } => roles::list(&file, output)?,
+            RolesCommand::Show { name, file } => roles::show(&file, &name)?,
+            RolesCommand::Add {
+                name,
+                type_,
+                grants,
+                databases,
+                schemas,
+                tables,
+                functions,
+                procedures,
+                sequences,
+                warehouses,
+                file,
+            } => {
+                let role = NewRole {
+                    name,
+                    grants,
+                    databases,
+                    schemas,
+                    tables,
+                    functions,
+                    procedures,
+                    sequences,
+                    warehouses,
+                }
+                .build(type_)?;
+                roles::add(&file, &role)?
+            }
+            RolesCommand::Remove { name, file } => roles::remove(&file, &name)?,
+        },
+
+        Command::Refactor(cmd) => match cmd {
+            RefactorCommand::RenameSchema {
+                old,
+                new,
+                file,
+                plan,
+                dryrun,
+            } => refactor::rename_schema(&file, &old, &new, plan, dryrun)?,
+        },
+
+        Command::EventTrigger(cmd) => match cmd {
+            EventTriggerCommand::Install { file, apply } => {
+                trigger::install(&Config::new_with(&file, load)?, apply)?
+            }
+            EventTriggerCommand::Remove { file, apply } => {
+                trigger::remove(&Config::new_with(&file, load)?, apply)?
+            }
+        },
+
+        Command::SelfUpdate { yes } => {
+            update::self_update(yes)?;
+        }
+
+        Command::Doctor { file } => {
+            doctor::doctor(&file, load)?;
+        }
+
+        Command::VerifyPasswords {
+            file,
+            key_file,
+            output,
+        } => {
+            verify::verify_passwords(&file, load, key_file.as_deref(), output)?;
+        }
+
+        Command::Version { json } => {
+            version::version(json);
+        }
     }
 
-    Ok(())
+    Ok(0)
 }
@@ -0,0 +1,312 @@
+//! Change records of the applies in an ITSM tool, e.g. a ServiceNow change
+//! request or a Jira issue.
+//!
+//! The notifier file, passed with `grant apply --notify`, holds the REST
+//! requests: `open` is sent with the plan before the apply, `update` with
+//! the result after it.
+//!
+//! ```yaml
+//! open:
+//!   method: POST
+//!   url: https://acme.service-now.com/api/now/table/change_request
+//!   headers:
+//!     Authorization: Basic ${SERVICENOW_AUTH}
+//!   body: |
+//!     {"short_description": "grant apply {{file}}", "description": "{{plan}}"}
+//!   # JSON pointer of the ticket id in the response, for `{{id}}`
+//!   id: /result/sys_id
+//! update:
+//!   method: PATCH
+//!   url: https://acme.service-now.com/api/now/table/change_request/{{id}}
+//!   body: |
+//!     {"close_code": "{{status}}", "close_notes": "{{result}}"}
+//! ```
+//!
+//! Environment variables are expanded, then the placeholders `{{file}}`,
+//! `{{plan}}`, `{{status}}` (`success` or `failure`), `{{result}}` and
+//! `{{id}}` are replaced. The values are JSON escaped in the body.
+
+use anyhow::{anyhow, Context, Result};
+use envmnt::{ExpandOptions, ExpansionType};
+use log::info;
+use reqwest::blocking::Client;
+use reqwest::Method;
+use serde::Deserialize;
+use serde_json::Value;
+use std::collections::BTreeMap;
+use std::fs;
+use std::path::Path;
+use std::str::FromStr;
+
+/// REST requests of the change records
+#[derive(Debug, Clone, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct Notifier {
+    /// Opens the change record with the plan, before the apply
+    pub open: Request,
+    /// Updates the change record with the result, after the apply
+    pub update: Option<Request>,
+}
+
+/// A request template
+#[derive(Debug, Clone, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct Request {
+    #[serde(default = "default_method")]
+    pub method: String,
+    pub url: String,
+    #[serde(default)]
+    pub headers: BTreeMap<String, String>,
+    #[serde(default)]
+    pub body: String,
+    /// JSON pointer of the ticket id in the response, e.g. `/key` for Jira
+    pub id: Option<String>,
+}
+
+fn default_method() -> String {
+    "POST".to_string()
+}
+
+/// Values of the placeholders of the templates
+#[derive(Debug, Clone, Default)]
+pub struct Record {
+    /// Config file applied, `GRANT_CONFIG` for `--env-config`
+    pub file: String,
+    pub plan: String,
+    /// `success` or `failure`, set after the apply
+    pub status: String,
+    pub result: String,
+    /// Ticket id read from the response of `open`
+    pub id: Option<String>,
+}
+
+impl Notifier {
+    pub fn load(file: &Path) -> Result<Self> {
+        let content = fs::read_to_string(file)
+            .with_context(|| format!("failed to read notifier file {}", file.display()))?;
+
+        Self::from_str(&content).with_context(|| format!("invalid {}", file.display()))
+    }
+
+    /// Open the change record, returns the ticket id if `open.id` is set
+    pub fn open(&self, record: &Record) -> Result<Option<String>> {
+        let response = self
+            .open
+            .send(record)
+            .context("failed to open the change record")?;
+
+        let id = match &self.open.id {
+            Some(pointer) => Some(ticket_id(&response, pointer)?),
+            None => None,
+        };
+        match &id {
+            Some(id) => info!("Opened change record {}", id),
+            None => info!("Opened change record"),
+        }
+
+        Ok(id)
+    }
+
+    /// Update the change record with the result, if `update` is set
+    pub fn update(&self, record: &Record) -> Result<()> {
+        let update = match &self.update {
+            Some(update) => update,
+            None => return Ok(()),
+        };
+
+        update
+            .send(record)
+            .context("failed to update the change record")?;
+        info!(
+            "Updated change record {}",
+            record.id.as_deref().unwrap_or_default()
+        );
+
+        Ok(())
+    }
+}
+
+impl FromStr for Notifier {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> Result<Self> {
+        let notifier: Notifier = serde_yaml::from_str(s)?;
+
+        for request in std::iter::once(&notifier.open).chain(&notifier.update) {
+            Method::from_str(&request.method.to_uppercase())
+                .map_err(|_| anyhow!("invalid method: {}", request.method))?;
+        }
+        let uses_id = |r: &Request| r.url.contains("{{id}}") || r.body.contains("{{id}}");
+        if notifier.open.id.is_none() && notifier.update.as_ref().is_some_and(uses_id) {
+            return Err(anyhow!("update uses {{{{id}}}} but open.id is not set"));
+        }
+
+        Ok(notifier)
+    }
+}
+
+impl Request {
+    /// The method, url, headers and body with the environment variables and
+    /// the placeholders replaced
+    fn render(&self, record: &Record) -> (Method, String, Vec<(String, String)>, String) {
+        let method = Method::from_str(&self.method.to_uppercase()).unwrap_or(Method::POST);
+        let url = fill(&expand(&self.url), record, |v| v.to_string());
+        let headers = self
+            .headers
+            .iter()
+            .map(|(name, value)| {
+                (
+                    name.clone(),
+                    fill(&expand(value), record, |v| v.to_string()),
+                )
+            })
+            .collect();
+        let body = fill(&expand(&self.body), record, json_escape);
+
+        (method, url, headers, body)
+    }
+
+    fn send(&self, record: &Record) -> Result<Value> {
+        let (method, url, headers, body) = self.render(record);
+
+        let mut request = Client::new()
+            .request(method, &url)
+            .header("Content-Type", "application/json")
+            .header("Accept", "application/json");
+        for (name, value) in headers {
+            request = request.header(name, value);
+        }
+
+        let response = request.body(body).send()?;
+        let status = response.status();
+        let text = response.text()?;
+        if !status.is_success() {
+            return Err(anyhow!("{} {}: {}", status, url, text.trim()));
+        }
+
+        // An empty or non JSON response is fine, unless the id is read from it
+        Ok(serde_json::from_str(&text).unwrap_or(Value::Null))
+    }
+}
+
+/// The environment variables of a template expanded, `${NAME}` or
+/// `${NAME:default}`
+pub fn expand(template: &str) -> String {
+    let options = ExpandOptions {
+        expansion_type: Some(ExpansionType::UnixBracketsWithDefaults),
+        default_to_empty: false,
+    };
+
+    envmnt::expand(template, Some(options))
+}
+
+/// Replace the placeholders, each value formatted with `format`
+fn fill(template: &str, record: &Record, format: impl Fn(&str) -> String) -> String {
+    [
+        ("{{file}}", record.file.as_str()),
+        ("{{plan}}", record.plan.as_str()),
+        ("{{status}}", record.status.as_str()),
+        ("{{result}}", record.result.as_str()),
+        ("{{id}}", record.id.as_deref().unwrap_or_default()),
+    ]
+    .iter()
+    .fold(template.to_string(), |s, (placeholder, value)| {
+        s.replace(placeholder, &format(value))
+    })
+}
+
+/// The value escaped for a JSON string, without the quotes
+fn json_escape(value: &str) -> String {
+    let quoted = Value::String(value.to_string()).to_string();
+
+    quoted[1..quoted.len() - 1].to_string()
+}
+
+fn ticket_id(response: &Value, pointer: &str) -> Result<String> {
+    match response.pointer(pointer) {
+        Some(Value::String(id)) => Ok(id.clone()),
+        Some(Value::Number(id)) => Ok(id.to_string()),
+        _ => Err(anyhow!("no ticket id at {} in the response", pointer)),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use indoc::indoc;
+    use serde_json::json;
+
+    const NOTIFIER: &str = indoc! {r#"
+        open:
+          url: https://acme.atlassian.net/rest/api/2/issue
+          headers:
+            Authorization: Bearer ${GRANT_TEST_JIRA_TOKEN}
+          body: '{"fields": {"summary": "grant apply {{file}}", "description": "{{plan}}"}}'
+          id: /key
+        update:
+          url: https://acme.atlassian.net/rest/api/2/issue/{{id}}/comment
+          body: '{"body": "{{status}}: {{result}}"}'
+    "#};
+
+    #[test]
+    fn test_render() {
+        envmnt::set("GRANT_TEST_JIRA_TOKEN", "t0ken");
+        let notifier = Notifier::from_str(NOTIFIER).unwrap();
+        let record = Record {
+            file: "prod/config.yaml".to_string(),
+            plan: "GRANT SELECT ON TABLE \"Public\".t TO duyet;\n".to_string(),
+            status: "success".to_string(),
+            result: "applied".to_string(),
+            id: Some("OPS-42".to_string()),
+        };
+
+        let (method, url, headers, body) = notifier.open.render(&record);
+        assert_eq!(method, Method::POST);
+        assert_eq!(url, "https://acme.atlassian.net/rest/api/2/issue");
+        assert_eq!(
+            headers,
+            vec![("Authorization".to_string(), "Bearer t0ken".to_string())]
+        );
+        let body: Value = serde_json::from_str(&body).unwrap();
+        assert_eq!(
+            body,
+            json!({"fields": {
+                "summary": "grant apply prod/config.yaml",
+                "description": "GRANT SELECT ON TABLE \"Public\".t TO duyet;\n",
+            }})
+        );
+
+        let (_, url, _, body) = notifier.update.as_ref().unwrap().render(&record);
+        assert_eq!(
+            url,
+            "https://acme.atlassian.net/rest/api/2/issue/OPS-42/comment"
+        );
+        assert_eq!(body, r#"{"body": "success: applied"}"#);
+
+        envmnt::remove("GRANT_TEST_JIRA_TOKEN");
+    }
+
+    #[test]
+    fn test_from_str_invalid() {
+        let err = Notifier::from_str("open:\n  method: 'GET POST'\n  url: http://x").unwrap_err();
+        assert_eq!(err.to_string(), "invalid method: GET POST");
+
+        let err = Notifier::from_str(indoc! {"
+            open:
+              url: http://x
+            update:
+              url: http://x/{{id}}
+        "})
+        .unwrap_err();
+        assert_eq!(err.to_string(), "update uses {{id}} but open.id is not set");
+    }
+
+    #[test]
+    fn test_ticket_id() {
+        let response = json!({"result": {"sys_id": "abc", "number": 42}});
+
+        assert_eq!(ticket_id(&response, "/result/sys_id").unwrap(), "abc");
+        assert_eq!(ticket_id(&response, "/result/number").unwrap(), "42");
+        assert!(ticket_id(&response, "/key").is_err());
+    }
+}
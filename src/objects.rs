@@ -0,0 +1,182 @@
+//! `grant apply --verify-objects`: whether the schemas and tables of the
+//! roles exist in their database, checked before any statement runs.
+//!
+//! A role granting on a schema or a table which does not exist fails the
+//! apply in the middle of its statements, e.g. after a typo or a dropped
+//! table. The schemas of the schema, table, function and sequence roles and
+//! the listed tables of the table roles, the excluded ones too, are looked
+//! up in the catalog of the database of each role, the tables with the
+//! views and foreign tables:
+//!
+//! ```text
+//! role role_read: table marts.orderz does not exist
+//! role role_etl: schema stagging does not exist in database analytics
+//! ```
+//!
+//! With `--verify-objects warn` the missing objects are warnings and the
+//! config is still applied, with `--verify-objects` or `--verify-objects
+//! error` they fail the apply.
+
+use crate::config::{ident, Config, Role};
+use crate::connection::DbConnection;
+use anyhow::{anyhow, Result};
+use log::warn;
+use std::collections::{BTreeMap, BTreeSet};
+use std::fmt;
+
+/// What a missing object of the roles is, selected by `--verify-objects`
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum VerifyObjects {
+    Warn,
+    #[default]
+    Error,
+}
+
+impl VerifyObjects {
+    pub const VARIANTS: &'static [&'static str] = &["warn", "error"];
+}
+
+impl fmt::Display for VerifyObjects {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            VerifyObjects::Warn => write!(f, "warn"),
+            VerifyObjects::Error => write!(f, "error"),
+        }
+    }
+}
+
+impl std::str::FromStr for VerifyObjects {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.to_lowercase().as_str() {
+            "warn" => Ok(VerifyObjects::Warn),
+            "error" => Ok(VerifyObjects::Error),
+            _ => Err(anyhow!("unknown verify mode: {}", s)),
+        }
+    }
+}
+
+/// The schemas of a database, with their tables
+type Relations = BTreeMap<String, BTreeSet<String>>;
+
+/// Log the missing objects of the roles as warnings, or fail with them
+pub fn verify(config: &Config, conn: &mut DbConnection, mode: VerifyObjects) -> Result<()> {
+    let missing = missing_objects(config, conn)?;
+    if missing.is_empty() {
+        return Ok(());
+    }
+
+    match mode {
+        VerifyObjects::Warn => {
+            for object in &missing {
+                warn!("{}", object);
+            }
+            Ok(())
+        }
+        VerifyObjects::Error => Err(anyhow!(
+            "the roles reference {} missing object(s):\n  {}",
+            missing.len(),
+            missing.join("\n  ")
+        )),
+    }
+}
+
+/// The schemas and tables of the roles which do not exist in their
+/// database, e.g. `role role_read: table marts.orderz does not exist`
+pub fn missing_objects(config: &Config, conn: &mut DbConnection) -> Result<Vec<String>> {
+    let mut databases: BTreeMap<Option<String>, Relations> = BTreeMap::new();
+    let mut missing = vec![];
+    for role in &config.roles {
+        let schemas = role
+            .get_schemas()
+            .iter()
+            .map(|s| ident::fold(s))
+            .collect::<Vec<_>>();
+        let tables = match role {
+            Role::Table(role) => role.table_names(),
+            _ => vec![],
+        };
+        if schemas.is_empty() && tables.is_empty() {
+            continue;
+        }
+
+        let database = role.database().map(str::to_string);
+        if !databases.contains_key(&database) {
+            let relations = conn.on_database(database.as_deref(), |conn| conn.get_relations())?;
+            databases.insert(database.clone(), relations_by_schema(relations));
+        }
+        let relations = &databases[&database];
+        let in_database = match &database {
+            Some(database) => format!(" in database {}", database),
+            None => String::new(),
+        };
+
+        let name = role.get_name();
+        for schema in &schemas {
+            if !relations.contains_key(schema) {
+                missing.push(format!(
+                    "role {}: schema {} does not exist{}",
+                    name, schema, in_database
+                ));
+            }
+        }
+        for (schema, table) in tables {
+            let exists = relations
+                .get(&schema)
+                .is_some_and(|tables| tables.contains(&table));
+            // A missing schema of the role is already reported
+            if !exists && (relations.contains_key(&schema) || !schemas.contains(&schema)) {
+                missing.push(format!(
+                    "role {}: table {}.{} does not exist{}",
+                    name, schema, table, in_database
+                ));
+            }
+        }
+    }
+
+    Ok(missing)
+}
+
+fn relations_by_schema(relations: Vec<(String, Option<String>)>) -> Relations {
+    let mut by_schema = Relations::new();
+    for (schema, table) in relations {
+        let tables = by_schema.entry(schema).or_default();
+        tables.extend(table);
+    }
+
+    by_schema
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_relations_by_schema() {
+        let relations = relations_by_schema(vec![
+            ("empty".to_string(), None),
+            ("marts".to_string(), Some("orders".to_string())),
+            ("marts".to_string(), Some("users".to_string())),
+        ]);
+
+        assert!(relations["empty"].is_empty());
+        assert_eq!(
+            relations["marts"].iter().collect::<Vec<_>>(),
+            vec!["orders", "users"]
+        );
+    }
+
+    #[test]
+    fn test_verify_objects_from_str() {
+        assert_eq!(
+            "warn".parse::<VerifyObjects>().unwrap(),
+            VerifyObjects::Warn
+        );
+        assert_eq!(
+            "ERROR".parse::<VerifyObjects>().unwrap(),
+            VerifyObjects::Error
+        );
+        assert!("fail".parse::<VerifyObjects>().is_err());
+    }
+}
@@ -0,0 +1,258 @@
+//! Per-team ownership of the config files in a monorepo.
+//!
+//! The owners file maps the paths of the config files to the prefixes of the
+//! schemas and users each team may declare, in the spirit of `CODEOWNERS`:
+//!
+//! ```text
+//! # path               allowed prefixes
+//! teams/analytics/     schema:analytics_ user:analytics_ user:svc_analytics_
+//! teams/marketing/     schema:mkt_ user:mkt_
+//! ```
+//!
+//! Paths are relative to the directory of the owners file, the last matching
+//! line wins and `*` matches every file. A file without matching line, or a
+//! kind (`schema` or `user`) without prefix on its line, is not restricted.
+
+use crate::config::{ident, Config};
+use anyhow::{anyhow, Context, Result};
+use std::fs;
+use std::path::{Path, PathBuf};
+
+/// Name of the owners file, looked up in the validated directory and its parents
+pub const OWNERS_FILE: &str = ".grant-owners";
+
+#[derive(Debug)]
+pub struct Owners {
+    root: PathBuf,
+    rules: Vec<Rule>,
+}
+
+#[derive(Debug, PartialEq, Eq)]
+struct Rule {
+    path: String,
+    schemas: Vec<String>,
+    users: Vec<String>,
+}
+
+impl Owners {
+    /// Read an owners file, its paths are relative to its directory
+    pub fn load(file: &Path) -> Result<Self> {
+        let content = fs::read_to_string(file)
+            .with_context(|| format!("failed to read owners file {}", file.display()))?;
+        let root = file
+            .canonicalize()?
+            .parent()
+            .map(Path::to_path_buf)
+            .unwrap_or_default();
+
+        Self::parse(root, &content).with_context(|| format!("invalid {}", file.display()))
+    }
+
+    /// The owners file of the target: in its directory or the closest parent
+    pub fn find(target: &Path) -> Option<PathBuf> {
+        let target = target.canonicalize().ok()?;
+        let dir = if target.is_dir() {
+            target.as_path()
+        } else {
+            target.parent()?
+        };
+
+        dir.ancestors()
+            .map(|dir| dir.join(OWNERS_FILE))
+            .find(|file| file.is_file())
+    }
+
+    fn parse(root: PathBuf, content: &str) -> Result<Self> {
+        let mut rules = vec![];
+
+        for (i, line) in content.lines().enumerate() {
+            let line = line.split('#').next().unwrap_or_default().trim();
+            let mut fields = line.split_whitespace();
+            let path = match fields.next() {
+                Some(path) => path.trim_start_matches('/').to_string(),
+                None => continue,
+            };
+
+            let mut rule = Rule {
+                path,
+                schemas: vec![],
+                users: vec![],
+            };
+            for field in fields {
+                match field.split_once(':') {
+                    Some(("schema", prefix)) => rule.schemas.push(ident::fold(prefix)),
+                    Some(("user", prefix)) => rule.users.push(ident::fold(prefix)),
+                    _ => {
+                        return Err(anyhow!(
+                            "line {}: expected schema:<prefix> or user:<prefix>, got {}",
+                            i + 1,
+                            field
+                        ))
+                    }
+                }
+            }
+            rules.push(rule);
+        }
+
+        Ok(Owners { root, rules })
+    }
+
+    /// Check the schemas and users declared by the config file are allowed
+    /// for the team owning the file
+    pub fn check(&self, file: &Path, config: &Config) -> Result<()> {
+        let file = file.canonicalize()?;
+        let relative = match file.strip_prefix(&self.root) {
+            Ok(relative) => relative,
+            Err(_) => return Ok(()),
+        };
+
+        let rule = match self.rules.iter().rev().find(|r| r.matches(relative)) {
+            Some(rule) => rule,
+            None => return Ok(()),
+        };
+
+        for user in config.users.iter().filter(|u| !u.is_public()) {
+            if !allowed(&rule.users, &user.name) {
+                return Err(anyhow!(
+                    "user {} is not allowed for {} (user prefixes: {})",
+                    user.name,
+                    rule.path,
+                    rule.users.join(", ")
+                ));
+            }
+        }
+
+        for role in &config.roles {
+            for schema in role.referenced_schemas() {
+                if !allowed(&rule.schemas, &schema) {
+                    return Err(anyhow!(
+                        "role {}: schema {} is not allowed for {} (schema prefixes: {})",
+                        role.get_name(),
+                        schema,
+                        rule.path,
+                        rule.schemas.join(", ")
+                    ));
+                }
+            }
+        }
+
+        Ok(())
+    }
+}
+
+impl Rule {
+    fn matches(&self, relative: &Path) -> bool {
+        self.path == "*" || relative.starts_with(&self.path)
+    }
+}
+
+/// No prefix allows every name
+fn allowed(prefixes: &[String], name: &str) -> bool {
+    let name = ident::fold(name);
+
+    prefixes.is_empty() || prefixes.iter().any(|p| name.starts_with(p.as_str()))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::str::FromStr;
+
+    const OWNERS: &str = "
+        # path               allowed prefixes
+        *                    schema:shared_
+        teams/analytics/     schema:analytics_ user:analytics_ # the data team
+    ";
+
+    fn config(schema: &str, user: &str) -> Config {
+        Config::from_str(&format!(
+            "
+            connection:
+              type: postgres
+              url: postgres://localhost:5432/postgres
+            roles:
+              - name: role_schema
+                type: schema
+                grants: [USAGE]
+                schemas: [{}]
+            users:
+              - name: {}
+                roles: [role_schema]
+            ",
+            schema, user
+        ))
+        .unwrap()
+    }
+
+    fn owners(dir: &Path) -> Owners {
+        let file = dir.join(OWNERS_FILE);
+        fs::write(&file, OWNERS).unwrap();
+
+        Owners::load(&file).unwrap()
+    }
+
+    #[test]
+    fn test_parse() {
+        let owners = Owners::parse(PathBuf::new(), OWNERS).unwrap();
+
+        assert_eq!(
+            owners.rules[1],
+            Rule {
+                path: "teams/analytics/".to_string(),
+                schemas: vec!["analytics_".to_string()],
+                users: vec!["analytics_".to_string()],
+            }
+        );
+
+        let err = Owners::parse(PathBuf::new(), "teams/ team:a").unwrap_err();
+        assert_eq!(
+            err.to_string(),
+            "line 1: expected schema:<prefix> or user:<prefix>, got team:a"
+        );
+    }
+
+    #[test]
+    fn test_check() {
+        let dir = tempfile::tempdir().unwrap();
+        let owners = owners(dir.path());
+        let file = dir.path().join("teams/analytics/config.yaml");
+        fs::create_dir_all(file.parent().unwrap()).unwrap();
+        fs::write(&file, "").unwrap();
+
+        assert!(owners
+            .check(&file, &config("Analytics_Events", "analytics_bot"))
+            .is_ok());
+
+        let err = owners
+            .check(&file, &config("mkt_campaigns", "analytics_bot"))
+            .unwrap_err();
+        assert_eq!(
+            err.to_string(),
+            "role role_schema: schema mkt_campaigns is not allowed for teams/analytics/ (schema prefixes: analytics_)"
+        );
+
+        let err = owners
+            .check(&file, &config("analytics_events", "mkt_bot"))
+            .unwrap_err();
+        assert!(err.to_string().starts_with("user mkt_bot is not allowed"));
+
+        // Only the schemas are restricted outside of the team directories
+        let other = dir.path().join("config.yaml");
+        fs::write(&other, "").unwrap();
+        assert!(owners.check(&other, &config("shared_x", "mkt_bot")).is_ok());
+        assert!(owners.check(&other, &config("mkt_x", "mkt_bot")).is_err());
+    }
+
+    #[test]
+    fn test_find() {
+        let dir = tempfile::tempdir().unwrap();
+        owners(dir.path());
+        let nested = dir.path().join("teams/analytics");
+        fs::create_dir_all(&nested).unwrap();
+
+        assert_eq!(
+            Owners::find(&nested),
+            Some(dir.path().canonicalize().unwrap().join(OWNERS_FILE))
+        );
+    }
+}
@@ -0,0 +1,386 @@
+//! Schema and table patterns and `ALL` databases of the roles, expanded when
+//! the config is applied.
+//!
+//! A schema of a schema, table, function or sequence role can be a pattern
+//! matching the schemas of the cluster, so that the new schemas of a tenant
+//! get the privileges of the role without editing the config:
+//!
+//! - a glob, with `*` for any characters and `?` for one, e.g. `analytics_*`
+//! - a POSIX regular expression after `~`, as the operator of Postgres, e.g.
+//!   `~^tenant_[0-9]+$`
+//!
+//! ```yaml
+//! roles:
+//!   - name: role_tenants
+//!     type: table
+//!     grants: [SELECT]
+//!     schemas: [common, "analytics_*", "~^tenant_[0-9]+$"]
+//!     tables: [ALL]
+//! ```
+//!
+//! The patterns are matched against `pg_namespace` of the database of the
+//! role by `apply`, `plan` and `inspect --diff`, then the role is the one
+//! with the matched schemas, in order, after its other schemas. The schemas
+//! outside of the [Scope] of the config are not matched. A pattern matching
+//! no schema is a warning, a role left without any schema an error.
+//!
+//! The excluded tables of a table role can be patterns too, matched against
+//! `pg_tables`: `-tmp_*` in the schemas of the role, `-staging.*_raw` in
+//! `staging`, the schema can be a pattern as well. Each matched table is
+//! excluded, a pattern without match excludes nothing:
+//!
+//! ```yaml
+//! roles:
+//!   - name: role_read
+//!     type: table
+//!     grants: [SELECT]
+//!     schemas: [marts, staging]
+//!     tables: [ALL, "-tmp_*", "-staging.*_raw"]
+//! ```
+//!
+//! `ALL` in the databases of a database role is every database of the
+//! cluster but the templates, listed from `pg_database`, without the ones
+//! excluded with `-`:
+//!
+//! ```yaml
+//! roles:
+//!   - name: role_temp
+//!     type: database
+//!     grants: [TEMP]
+//!     databases: [ALL, -scratch]
+//! ```
+//!
+//! [Scope]: crate::config::Scope
+
+use crate::config::role::{RoleDatabaseLevel, RoleTableLevel};
+use crate::config::{ident, Config, Role};
+use crate::connection::DbConnection;
+use anyhow::{anyhow, Context, Result};
+use log::{info, warn};
+use std::borrow::Cow;
+use std::collections::BTreeMap;
+
+/// Whether a schema or table of a role is a pattern: a glob with `*` or
+/// `?`, or a regular expression after `~`
+pub fn is_pattern(name: &str) -> bool {
+    name.starts_with('~') || name.contains(['*', '?'])
+}
+
+/// The POSIX regular expression of a pattern, as matched by Postgres and
+/// Redshift
+pub fn to_regex(pattern: &str) -> String {
+    if let Some(regex) = pattern.strip_prefix('~') {
+        return regex.to_string();
+    }
+
+    let mut regex = String::from("^");
+    for c in pattern.chars() {
+        match c {
+            '*' => regex.push_str(".*"),
+            '?' => regex.push('.'),
+            c => regex.push_str(&escape(&c.to_string())),
+        }
+    }
+    regex.push('$');
+
+    regex
+}
+
+/// The regular expression matching only the given names
+fn exactly(names: &[String]) -> String {
+    let names = names.iter().map(|name| escape(name)).collect::<Vec<_>>();
+
+    format!("^({})$", names.join("|"))
+}
+
+/// A name with the special characters of a regular expression escaped
+fn escape(name: &str) -> String {
+    name.chars()
+        .map(|c| match c.is_alphanumeric() || c == '_' {
+            true => c.to_string(),
+            false => format!("\\{}", c),
+        })
+        .collect()
+}
+
+/// Whether a role of the config has a schema or excluded table pattern, or
+/// `ALL` databases
+pub fn has_patterns(config: &Config) -> bool {
+    config.roles.iter().any(|role| {
+        role.get_schemas().iter().any(|s| is_pattern(s))
+            || excluded_patterns(role).next().is_some()
+            || role.get_databases().iter().any(|d| d == "ALL")
+    })
+}
+
+/// The excluded tables of a table role which are patterns, without their `-`
+fn excluded_patterns(role: &Role) -> impl Iterator<Item = &str> {
+    let tables = match role {
+        Role::Table(role) => role.tables.as_slice(),
+        _ => &[],
+    };
+
+    tables
+        .iter()
+        .filter_map(|t| t.strip_prefix('-'))
+        .filter(|t| is_pattern(t))
+}
+
+/// The config with the schema and table patterns and the `ALL` databases of
+/// its roles replaced by the schemas, tables and databases they match in the
+/// cluster, the config itself without patterns
+pub fn expand<'a>(config: &'a Config, conn: &mut DbConnection) -> Result<Cow<'a, Config>> {
+    if !has_patterns(config) {
+        return Ok(Cow::Borrowed(config));
+    }
+
+    let current = conn.get_current_database().map(str::to_string);
+    let mut schemas = Schemas::default();
+    let mut databases = None;
+    let mut expanded = config.clone();
+    for role in expanded.roles.iter_mut() {
+        if let Role::Database(role) = role {
+            expand_all_databases(role, conn, &mut databases)?;
+            continue;
+        }
+
+        let database = role
+            .database()
+            .map(str::to_string)
+            .filter(|database| Some(database) != current.as_ref());
+        expand_schemas(role, config, database.as_deref(), conn, &mut schemas)?;
+        if let Role::Table(role) = role {
+            expand_excluded_tables(role, database.as_deref(), conn)?;
+        }
+    }
+    expanded.validate_revokes()?;
+
+    Ok(Cow::Owned(expanded))
+}
+
+/// The schemas matched by each pattern, by database
+type Schemas = BTreeMap<(Option<String>, String), Vec<String>>;
+
+/// Replace the schema patterns of a role by the schemas they match
+fn expand_schemas(
+    role: &mut Role,
+    config: &Config,
+    database: Option<&str>,
+    conn: &mut DbConnection,
+    matched: &mut Schemas,
+) -> Result<()> {
+    let name = role.get_name();
+    let Some(schemas) = role.schemas_mut() else {
+        return Ok(());
+    };
+    if !schemas.iter().any(|s| is_pattern(s)) {
+        return Ok(());
+    }
+
+    let mut resolved = schemas
+        .iter()
+        .filter(|s| !is_pattern(s))
+        .cloned()
+        .collect::<Vec<_>>();
+    for pattern in schemas.iter().filter(|s| is_pattern(s)) {
+        let key = (database.map(str::to_string), pattern.clone());
+        if !matched.contains_key(&key) {
+            let regex = to_regex(pattern);
+            let found = conn
+                .on_database(database, |conn| conn.get_schemas_matching(&regex))
+                .with_context(|| format!("role {}: invalid schema pattern {}", name, pattern))?;
+            matched.insert(key.clone(), found);
+        }
+
+        let found = matched[&key]
+            .iter()
+            .filter(|schema| config.scope.has_schema(schema))
+            .collect::<Vec<_>>();
+        match found.is_empty() {
+            true => warn!(
+                "role {}: the schema pattern {} matches no schema",
+                name, pattern
+            ),
+            false => info!(
+                "role {}: the schema pattern {} matches {}",
+                name,
+                pattern,
+                found
+                    .iter()
+                    .map(|s| s.as_str())
+                    .collect::<Vec<_>>()
+                    .join(", ")
+            ),
+        }
+        for schema in found {
+            let schema = ident::quote_folded(schema);
+            if !resolved.contains(&schema) {
+                resolved.push(schema);
+            }
+        }
+    }
+
+    if resolved.is_empty() {
+        return Err(anyhow!(
+            "role {}: no schema matches {}",
+            name,
+            schemas.join(", ")
+        ));
+    }
+    *schemas = resolved;
+
+    Ok(())
+}
+
+/// Replace `ALL` in the databases of a database role by the databases of the
+/// cluster, without the excluded ones. `databases` has the databases of the
+/// cluster once listed.
+fn expand_all_databases(
+    role: &mut RoleDatabaseLevel,
+    conn: &mut DbConnection,
+    databases: &mut Option<Vec<String>>,
+) -> Result<()> {
+    if !role.databases.iter().any(|d| d == "ALL") {
+        return Ok(());
+    }
+
+    if databases.is_none() {
+        *databases = Some(conn.get_databases()?);
+    }
+    let excluded = role
+        .databases
+        .iter()
+        .filter_map(|d| d.strip_prefix('-'))
+        .map(ident::fold)
+        .collect::<Vec<_>>();
+    let mut resolved = vec![];
+    for database in &role.databases {
+        let matched = match database.as_str() {
+            "ALL" => databases
+                .iter()
+                .flatten()
+                .filter(|d| !excluded.contains(d))
+                .map(|d| ident::quote_folded(d))
+                .collect(),
+            d if d.starts_with('-') => continue,
+            d if excluded.contains(&ident::fold(d)) => continue,
+            d => vec![d.to_string()],
+        };
+        for database in matched {
+            if !resolved.contains(&database) {
+                resolved.push(database);
+            }
+        }
+    }
+
+    if resolved.is_empty() {
+        return Err(anyhow!(
+            "role {}: no database left in {}",
+            role.name,
+            role.databases.join(", ")
+        ));
+    }
+    info!(
+        "role {}: ALL databases are {}",
+        role.name,
+        resolved.join(", ")
+    );
+    role.databases = resolved;
+
+    Ok(())
+}
+
+/// Replace the excluded table patterns of a table role by the tables they
+/// match, in the schemas of the role or in the schema of the pattern
+fn expand_excluded_tables(
+    role: &mut RoleTableLevel,
+    database: Option<&str>,
+    conn: &mut DbConnection,
+) -> Result<()> {
+    if !role
+        .tables
+        .iter()
+        .any(|t| t.strip_prefix('-').is_some_and(is_pattern))
+    {
+        return Ok(());
+    }
+
+    let role_schemas = role
+        .schemas
+        .iter()
+        .map(|s| ident::fold(s))
+        .collect::<Vec<_>>();
+    let mut tables = vec![];
+    for table in &role.tables {
+        let pattern = match table.strip_prefix('-') {
+            Some(pattern) if is_pattern(pattern) => pattern,
+            _ => {
+                tables.push(table.clone());
+                continue;
+            }
+        };
+
+        let (schema_regex, table_regex) = match ident::split_qualified(pattern)[..] {
+            [schema, table] if is_pattern(schema) => (to_regex(schema), to_regex(table)),
+            [schema, table] => (exactly(&[ident::fold(schema)]), to_regex(table)),
+            _ => (exactly(&role_schemas), to_regex(pattern)),
+        };
+        let found = conn
+            .on_database(database, |conn| {
+                conn.get_tables_matching(&schema_regex, &table_regex)
+            })
+            .with_context(|| format!("role {}: invalid table pattern {}", role.name, pattern))?;
+        match found.is_empty() {
+            true => info!(
+                "role {}: the excluded table pattern {} matches no table",
+                role.name, pattern
+            ),
+            false => info!(
+                "role {}: the excluded table pattern {} matches {} table(s)",
+                role.name,
+                pattern,
+                found.len()
+            ),
+        }
+
+        for (schema, table) in found {
+            let table = format!(
+                "-{}.{}",
+                ident::quote_folded(&schema),
+                ident::quote_folded(&table)
+            );
+            if !tables.contains(&table) {
+                tables.push(table);
+            }
+        }
+    }
+    role.tables = tables;
+
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_is_pattern() {
+        assert!(is_pattern("analytics_*"));
+        assert!(is_pattern("tenant_?"));
+        assert!(is_pattern("~^tenant_[0-9]+$"));
+        assert!(!is_pattern("analytics"));
+        assert!(!is_pattern("\"Analytics\""));
+    }
+
+    #[test]
+    fn test_to_regex() {
+        assert_eq!(to_regex("analytics_*"), "^analytics_.*$");
+        assert_eq!(to_regex("tenant_?"), "^tenant_.$");
+        assert_eq!(to_regex("a.b-*"), "^a\\.b\\-.*$");
+        assert_eq!(to_regex("~^tenant_[0-9]+$"), "^tenant_[0-9]+$");
+        assert_eq!(
+            exactly(&["marts".to_string(), "a.b".to_string()]),
+            "^(marts|a\\.b)$"
+        );
+    }
+}
@@ -0,0 +1,1695 @@
+//! Diff of a config against the live cluster, for `grant plan`.
+//!
+//! The privileges the config grants or revokes are compared with the
+//! privileges read from the catalog, see [ClusterState], and each one is
+//! either a change or a no-op. Nothing is executed by `grant plan`, `apply`
+//! executes the changes of each role, see [role_changes].
+//!
+//! `grant plan --out plan.json` saves the plan as a [SavedPlan], e.g. as the
+//! reviewed artifact of a pull request. `grant apply --plan plan.json` then
+//! only applies the config if it still plans exactly the same changes: the
+//! config file, its cluster and the privileges of the cluster are unchanged.
+
+use crate::apply::without_password;
+use crate::catalog::Query;
+use crate::config::{ident, Config, Role};
+use crate::connection::{DbConnection, Group, NativeRole, Object};
+use crate::encrypt;
+use crate::patterns;
+use crate::render::OutputFormat;
+use crate::report::{Report, Row};
+use crate::state::{hash_file, ClusterState};
+use crate::telemetry;
+use ansi_term::Colour::{Green, Red, Yellow};
+use anyhow::{anyhow, Context, Result};
+use jiff::Timestamp;
+use log::info;
+use serde::{Deserialize, Serialize};
+use std::collections::{BTreeMap, BTreeSet};
+use std::fmt;
+use std::fs;
+use std::path::Path;
+
+/// Privileges read from the catalog, by object type. The other ones (e.g.
+/// `TRUNCATE` on a table) are always planned, their state is unknown.
+const DATABASE_PRIVILEGES: &[&str] = &["CREATE", "TEMP"];
+const SCHEMA_PRIVILEGES: &[&str] = &["CREATE", "USAGE"];
+const TABLE_PRIVILEGES: &[&str] = &["SELECT", "INSERT", "UPDATE", "DELETE", "REFERENCES"];
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum Action {
+    CreateUser,
+    UpdatePassword,
+    /// Attributes of the user which differ from the cluster, see
+    /// [crate::config::UserAttributes]
+    UpdateAttributes,
+    /// Session defaults of the user which differ from the cluster, see
+    /// [crate::config::UserSettings]
+    UpdateSettings,
+    Grant,
+    Revoke,
+    NoOp,
+    /// Privileges on an object owned by the user, which has them implicitly:
+    /// they are neither granted nor revoked
+    Owner,
+    CreateGroup,
+    AddMember,
+    RemoveMember,
+    /// Membership of a user of the config in a group which is not in the
+    /// config, e.g. added by hand: it is flagged, not removed
+    UnmanagedMember,
+    /// Native role of Redshift, see [crate::config::RedshiftRole]
+    CreateRole,
+    GrantRole,
+    RevokeRole,
+    GrantSystemPrivilege,
+    RevokeSystemPrivilege,
+}
+
+impl fmt::Display for Action {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let action = match self {
+            Action::CreateUser => "create user",
+            Action::UpdatePassword => "update password",
+            Action::UpdateAttributes => "update attributes",
+            Action::UpdateSettings => "update settings",
+            Action::Grant => "grant",
+            Action::Revoke => "revoke",
+            Action::NoOp => "no change",
+            Action::Owner => "owner (implicit ALL)",
+            Action::CreateGroup => "create group",
+            Action::AddMember => "add member",
+            Action::RemoveMember => "remove member",
+            Action::UnmanagedMember => "unmanaged member",
+            Action::CreateRole => "create role",
+            Action::GrantRole => "grant role",
+            Action::RevokeRole => "revoke role",
+            Action::GrantSystemPrivilege => "grant system privilege",
+            Action::RevokeSystemPrivilege => "revoke system privilege",
+        };
+
+        write!(f, "{}", action)
+    }
+}
+
+/// A change of the plan, on a user, on the privileges of a user on an object
+/// or on the members of a group or a native role
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Change {
+    pub action: Action,
+    /// Name of the user, as in the catalog, empty for the group and role
+    /// creations and the system privileges
+    pub user: String,
+    /// Privileges of the change, the options of an attribute update, the
+    /// settings of a settings update, the system privilege of a role, empty
+    /// for the other user and group changes
+    pub privileges: Vec<String>,
+    /// Object of the privileges, e.g. `TABLE public.orders`, or name of the
+    /// group or role, as in the catalog
+    pub object: String,
+    /// Whether the config revokes the privileges, also for a no-op
+    pub revoke: bool,
+}
+
+#[derive(Debug, Default, PartialEq, Eq)]
+pub struct Plan {
+    pub changes: Vec<Change>,
+}
+
+impl Plan {
+    pub fn count(&self, action: Action) -> usize {
+        self.changes.iter().filter(|c| c.action == action).count()
+    }
+
+    /// Number of changes removing access: the revokes of privileges, the
+    /// members removed from groups and the roles and system privileges
+    /// revoked, as limited by `apply --max-revokes`
+    pub fn revokes(&self) -> usize {
+        self.changes
+            .iter()
+            .filter(|c| {
+                matches!(
+                    c.action,
+                    Action::Revoke
+                        | Action::RemoveMember
+                        | Action::RevokeRole
+                        | Action::RevokeSystemPrivilege
+                )
+            })
+            .count()
+    }
+
+    /// Whether applying the config would change the cluster
+    pub fn has_changes(&self) -> bool {
+        self.changes.iter().any(|c| {
+            !matches!(
+                c.action,
+                Action::NoOp | Action::Owner | Action::UnmanagedMember
+            )
+        })
+    }
+
+    /// One line summary, e.g. `Plan: 1 user(s) to create, 0 password(s) to
+    /// update, 2 grant(s), 0 revoke(s), 3 unchanged.`, followed by the group
+    /// changes and the owned objects if there are any
+    pub fn summary(&self) -> String {
+        let mut summary = format!(
+            "Plan: {} user(s) to create, {} password(s) to update, {} grant(s), {} revoke(s), {} unchanged.",
+            self.count(Action::CreateUser),
+            self.count(Action::UpdatePassword),
+            self.count(Action::Grant),
+            self.count(Action::Revoke),
+            self.count(Action::NoOp),
+        );
+
+        let groups = [Action::CreateGroup, Action::AddMember, Action::RemoveMember];
+        if groups.iter().any(|&action| self.count(action) > 0) {
+            summary += &format!(
+                " Groups: {} to create, {} member(s) to add, {} member(s) to remove.",
+                self.count(Action::CreateGroup),
+                self.count(Action::AddMember),
+                self.count(Action::RemoveMember),
+            );
+        }
+
+        let roles = [
+            Action::CreateRole,
+            Action::GrantSystemPrivilege,
+            Action::RevokeSystemPrivilege,
+            Action::GrantRole,
+            Action::RevokeRole,
+        ];
+        if roles.iter().any(|&action| self.count(action) > 0) {
+            summary += &format!(
+                " Redshift roles: {} to create, {} privilege(s) to grant, {} privilege(s) to revoke, {} member(s) to add, {} member(s) to remove.",
+                self.count(Action::CreateRole),
+                self.count(Action::GrantSystemPrivilege),
+                self.count(Action::RevokeSystemPrivilege),
+                self.count(Action::GrantRole),
+                self.count(Action::RevokeRole),
+            );
+        }
+
+        let attributes = self.count(Action::UpdateAttributes);
+        if attributes > 0 {
+            summary += &format!(" {} user(s) with attributes to update.", attributes);
+        }
+
+        let settings = self.count(Action::UpdateSettings);
+        if settings > 0 {
+            summary += &format!(" {} user(s) with settings to update.", settings);
+        }
+
+        let owned = self.count(Action::Owner);
+        if owned > 0 {
+            summary += &format!(" {} owned by the user (implicit ALL).", owned);
+        }
+
+        let unmanaged = self.count(Action::UnmanagedMember);
+        if unmanaged > 0 {
+            summary += &format!(" {} membership(s) of groups not in the config.", unmanaged);
+        }
+
+        summary
+    }
+}
+
+impl Change {
+    /// The GRANT or REVOKE of a privilege change, None for the others
+    pub fn to_sql(&self) -> Option<String> {
+        let user = ident::quote_folded(&self.user);
+        let privileges = self.privileges.join(", ");
+
+        match self.action {
+            Action::Grant => Some(format!(
+                "GRANT {} ON {} TO {};",
+                privileges, self.object, user
+            )),
+            Action::Revoke => Some(format!(
+                "REVOKE {} ON {} FROM {};",
+                privileges, self.object, user
+            )),
+            _ => None,
+        }
+    }
+}
+
+impl Row for Change {
+    const HEADERS: &'static [&'static str] = &["Action", "User", "Privileges", "Object"];
+
+    fn cells(&self) -> Vec<String> {
+        vec![
+            self.action.to_string(),
+            self.user.clone(),
+            self.privileges.join(", "),
+            self.object.clone(),
+        ]
+    }
+}
+
+impl fmt::Display for Change {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let user = ident::quote_folded(&self.user);
+        let privileges = self.privileges.join(", ");
+
+        match self.action {
+            Action::CreateUser => write!(f, "{} create user {}", Green.paint("+"), user),
+            Action::UpdatePassword => {
+                write!(f, "{} update password of user {}", Yellow.paint("~"), user)
+            }
+            Action::UpdateAttributes => write!(
+                f,
+                "{} update attributes of user {}: {}",
+                Yellow.paint("~"),
+                user,
+                privileges
+            ),
+            Action::UpdateSettings => write!(
+                f,
+                "{} update settings of user {}: {}",
+                Yellow.paint("~"),
+                user,
+                privileges
+            ),
+            Action::Grant => write!(
+                f,
+                "{} grant {} on {} to {}",
+                Green.paint("+"),
+                privileges,
+                self.object,
+                user
+            ),
+            Action::Revoke => write!(
+                f,
+                "{} revoke {} on {} from {}",
+                Red.paint("-"),
+                privileges,
+                self.object,
+                user
+            ),
+            Action::NoOp => write!(
+                f,
+                "  {} on {} for {} (no change)",
+                privileges, self.object, user
+            ),
+            Action::Owner => write!(
+                f,
+                "{} {} on {} for {}: owner (implicit ALL)",
+                Yellow.paint("!"),
+                privileges,
+                self.object,
+                user
+            ),
+            Action::CreateGroup => write!(
+                f,
+                "{} create group {}",
+                Green.paint("+"),
+                ident::quote_folded(&self.object)
+            ),
+            Action::AddMember => write!(
+                f,
+                "{} add {} to group {}",
+                Green.paint("+"),
+                user,
+                ident::quote_folded(&self.object)
+            ),
+            Action::RemoveMember => write!(
+                f,
+                "{} remove {} from group {}",
+                Red.paint("-"),
+                user,
+                ident::quote_folded(&self.object)
+            ),
+            Action::UnmanagedMember => write!(
+                f,
+                "{} {} is a member of group {}, which is not in the config",
+                Yellow.paint("!"),
+                user,
+                ident::quote_folded(&self.object)
+            ),
+            Action::CreateRole => write!(
+                f,
+                "{} create role {}",
+                Green.paint("+"),
+                ident::quote_folded(&self.object)
+            ),
+            Action::GrantRole => write!(
+                f,
+                "{} grant role {} to {}",
+                Green.paint("+"),
+                ident::quote_folded(&self.object),
+                user
+            ),
+            Action::RevokeRole => write!(
+                f,
+                "{} revoke role {} from {}",
+                Red.paint("-"),
+                ident::quote_folded(&self.object),
+                user
+            ),
+            Action::GrantSystemPrivilege => write!(
+                f,
+                "{} grant {} to role {}",
+                Green.paint("+"),
+                privileges,
+                ident::quote_folded(&self.object)
+            ),
+            Action::RevokeSystemPrivilege => write!(
+                f,
+                "{} revoke {} from role {}",
+                Red.paint("-"),
+                privileges,
+                ident::quote_folded(&self.object)
+            ),
+        }
+    }
+}
+
+/// Connect to the cluster of the config and print the diff, one line per
+/// change in the terminal or a table in the other `output` formats. In
+/// markdown, e.g. for a PR comment, the summary follows the table.
+pub fn plan(config: &Config, output: OutputFormat) -> Result<Plan> {
+    let _span = telemetry::span("plan", vec![]);
+    let mut conn = DbConnection::new(config)?;
+    config.validate_dialect(conn.dialect())?;
+    let config = &*patterns::expand(config, &mut conn)?;
+
+    let state = conn.get_cluster_state();
+    check_state(config, &state)?;
+
+    let plan = diff(config, &state);
+    match output {
+        OutputFormat::Table => {
+            for change in &plan.changes {
+                println!("{}", change);
+            }
+            if !plan.changes.is_empty() {
+                println!();
+            }
+            println!("{}", plan.summary());
+        }
+        output => {
+            let report = Report {
+                rows: plan.changes.clone(),
+            };
+            output.output_section("Plan", &report.to_table());
+            match output {
+                OutputFormat::Markdown => println!("{}", plan.summary()),
+                _ => info!("{}", plan.summary()),
+            }
+        }
+    }
+
+    Ok(plan)
+}
+
+/// Version of the [SavedPlan] files, a file of another version is refused
+pub const SAVED_PLAN_VERSION: u32 = 1;
+
+/// A plan saved by `grant plan --out`, to be applied by `grant apply
+/// --plan` only if the config still plans the same changes
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct SavedPlan {
+    pub version: u32,
+    pub created_at: String,
+    /// The config file, as given to `grant plan`
+    pub file: String,
+    /// The MD5 of the config file, see [hash_file]
+    pub config_hash: String,
+    /// The url of the connection, without its password
+    pub connection: String,
+    pub summary: String,
+    pub changes: Vec<Change>,
+}
+
+impl SavedPlan {
+    pub fn new(file: &Path, config: &Config, plan: &Plan) -> Result<Self> {
+        Ok(SavedPlan {
+            version: SAVED_PLAN_VERSION,
+            created_at: Timestamp::now().to_string(),
+            file: file.display().to_string(),
+            config_hash: hash_file(file)?,
+            connection: without_password(&config.connection.url),
+            summary: plan.summary(),
+            changes: plan.changes.clone(),
+        })
+    }
+
+    pub fn load(path: &Path) -> Result<Self> {
+        let content = fs::read_to_string(path)
+            .with_context(|| format!("failed to read the plan {}", path.display()))?;
+        let saved: SavedPlan = serde_json::from_str(&content)
+            .with_context(|| format!("invalid plan {}", path.display()))?;
+        if saved.version != SAVED_PLAN_VERSION {
+            return Err(anyhow!(
+                "plan {} has version {}, expected {}: plan again with this version of grant",
+                path.display(),
+                saved.version,
+                SAVED_PLAN_VERSION
+            ));
+        }
+
+        Ok(saved)
+    }
+
+    pub fn save(&self, path: &Path) -> Result<()> {
+        fs::write(path, serde_json::to_string_pretty(self)? + "\n")
+            .with_context(|| format!("failed to write the plan {}", path.display()))
+    }
+
+    /// Fail unless the config of `file` still plans exactly the saved
+    /// changes on the cluster in `state`
+    pub fn check(&self, file: &Path, config: &Config, state: &ClusterState) -> Result<()> {
+        if hash_file(file)? != self.config_hash {
+            return Err(anyhow!(
+                "{} changed since the plan was computed at {}, plan again",
+                file.display(),
+                self.created_at
+            ));
+        }
+        let connection = without_password(&config.connection.url);
+        if connection != self.connection {
+            return Err(anyhow!(
+                "the plan is for {}, not {}",
+                self.connection,
+                connection
+            ));
+        }
+
+        // As in the file, without the colors of the printed plan
+        let json = |c: &Change| serde_json::to_string(c).unwrap_or_default();
+        let changes = diff(config, state).changes;
+        let stale = changes
+            .iter()
+            .filter(|c| !self.changes.contains(c))
+            .map(|c| format!("new: {}", json(c)))
+            .chain(
+                self.changes
+                    .iter()
+                    .filter(|c| !changes.contains(c))
+                    .map(|c| format!("gone: {}", json(c))),
+            )
+            .collect::<Vec<_>>();
+        if !stale.is_empty() {
+            return Err(anyhow!(
+                "the cluster changed since the plan was computed at {}, plan again:\n{}",
+                self.created_at,
+                stale.join("\n")
+            ));
+        }
+
+        Ok(())
+    }
+}
+
+/// Fail if a section of the cluster needed to plan the config could not be
+/// read
+pub fn check_state(config: &Config, state: &ClusterState) -> Result<()> {
+    for query in [
+        Query::Users,
+        Query::Groups,
+        Query::DatabasePrivileges,
+        Query::SchemaPrivileges,
+        Query::TablePrivileges,
+    ] {
+        // The groups are only needed for the groups of the config, the
+        // unmanaged memberships are flagged when they are available
+        if query == Query::Groups && config.groups.is_empty() {
+            continue;
+        }
+        if !state.is_available(query) {
+            return Err(anyhow!("cannot plan without the {} of the cluster", query));
+        }
+    }
+
+    Ok(())
+}
+
+/// Compare the users and privileges of the config with the cluster, in the
+/// config order
+pub fn diff(config: &Config, state: &ClusterState) -> Plan {
+    let mut plan = Plan::default();
+
+    for user in &config.users {
+        let name = ident::fold(&user.name);
+        let change = |action, privileges| Change {
+            action,
+            user: name.clone(),
+            privileges,
+            object: String::new(),
+            revoke: false,
+        };
+        let settings = match state.user(&name) {
+            _ if user.is_public() => vec![],
+            Some(current) => user.settings.diff(&current.settings),
+            None => user.settings.diff(&BTreeMap::new()),
+        };
+        match state.user(&name) {
+            _ if user.is_public() => {}
+            None => plan.changes.push(change(Action::CreateUser, vec![])),
+            Some(current) => {
+                if user.updates_password() {
+                    plan.changes.push(change(Action::UpdatePassword, vec![]));
+                }
+                let attributes = user.attributes.diff(&current.attributes);
+                if !attributes.is_empty() {
+                    plan.changes.push(change(
+                        Action::UpdateAttributes,
+                        vec![attributes.to_string()],
+                    ));
+                }
+            }
+        }
+        if !settings.is_empty() {
+            plan.changes.push(change(
+                Action::UpdateSettings,
+                settings.iter().map(|s| s.to_string()).collect(),
+            ));
+        }
+
+        let granted = user.roles.iter().map(|r| (r, false));
+        let revoked = user.revokes.iter().map(|r| (r, true));
+        for (role, revoked) in granted.chain(revoked).filter_map(|(r, revoked)| {
+            Some((config.roles.iter().find(|role| role.find(r))?, revoked))
+        }) {
+            let start = plan.changes.len();
+            diff_role(&mut plan, &name, role, state, revoked);
+
+            // The privileges of PUBLIC and the ones on another database are
+            // not read from the catalog, they are always planned
+            let other_database = role
+                .database()
+                .is_some_and(|d| Some(ident::fold(d)) != state.current_database);
+            if user.is_public() || other_database {
+                for change in plan.changes[start..]
+                    .iter_mut()
+                    .filter(|c| c.action == Action::NoOp)
+                {
+                    change.action = match change.revoke {
+                        true => Action::Revoke,
+                        false => Action::Grant,
+                    };
+                }
+            }
+        }
+    }
+    plan.changes.extend(group_changes(config, state.groups()));
+    plan.changes
+        .extend(unmanaged_memberships(config, state.groups()));
+
+    plan
+}
+
+/// The plan of a config file, for the conflicts between the files applied
+/// to the same cluster
+pub struct FilePlan {
+    pub file: String,
+    pub config: Config,
+    pub plan: Plan,
+}
+
+/// The conflicts between config files applied to the same cluster, which
+/// would undo each other at every apply: a user with two passwords which
+/// are both set, a group with two lists of members, or a privilege granted
+/// by a file and revoked by another. Encrypted passwords are not compared,
+/// a dry run has no key.
+pub fn conflicts(plans: &[FilePlan]) -> Vec<String> {
+    let mut conflicts = vec![];
+
+    for (i, a) in plans.iter().enumerate() {
+        for b in &plans[i + 1..] {
+            for user in &a.config.users {
+                let other = b
+                    .config
+                    .users
+                    .iter()
+                    .find(|u| ident::fold(&u.name) == ident::fold(&user.name));
+                let (Some(other), Some(password), Some(other_password)) = (
+                    other,
+                    &user.password,
+                    other.and_then(|u| u.password.as_ref()),
+                ) else {
+                    continue;
+                };
+                if password != other_password
+                    && !encrypt::is_encrypted(password)
+                    && !encrypt::is_encrypted(other_password)
+                    && (user.updates_password() || other.updates_password())
+                {
+                    conflicts.push(format!(
+                        "user {}: different passwords in {} and {}",
+                        user.name, a.file, b.file
+                    ));
+                }
+            }
+
+            for group in &a.config.groups {
+                let other = b
+                    .config
+                    .groups
+                    .iter()
+                    .find(|g| ident::fold(&g.name) == ident::fold(&group.name));
+                if let Some(other) = other {
+                    let members = group.folded_members().into_iter().collect::<BTreeSet<_>>();
+                    if members != other.folded_members().into_iter().collect() {
+                        conflicts.push(format!(
+                            "group {}: different members in {} and {}, each apply removes the members of the other",
+                            group.name, a.file, b.file
+                        ));
+                    }
+                }
+            }
+        }
+    }
+
+    // The privileges of each user on each object, with the first file
+    // granting or revoking them
+    let mut seen: BTreeMap<(&str, &str, &str), (bool, &str)> = BTreeMap::new();
+    let mut reported = BTreeSet::new();
+    for file in plans {
+        for change in file
+            .plan
+            .changes
+            .iter()
+            .filter(|c| matches!(c.action, Action::Grant | Action::Revoke | Action::NoOp))
+        {
+            for privilege in &change.privileges {
+                let key = (
+                    change.user.as_str(),
+                    change.object.as_str(),
+                    privilege.as_str(),
+                );
+                match seen.get(&key) {
+                    None => {
+                        seen.insert(key, (change.revoke, &file.file));
+                    }
+                    Some(&(revoke, other)) if revoke != change.revoke && other != file.file => {
+                        let (granted, revoked) = match revoke {
+                            true => (file.file.as_str(), other),
+                            false => (other, file.file.as_str()),
+                        };
+                        if reported.insert((key, granted, revoked)) {
+                            conflicts.push(format!(
+                                "{} on {} for {}: granted by {} and revoked by {}",
+                                privilege,
+                                change.object,
+                                ident::quote_folded(&change.user),
+                                granted,
+                                revoked
+                            ));
+                        }
+                    }
+                    Some(_) => {}
+                }
+            }
+        }
+    }
+
+    conflicts
+}
+
+/// Compare the groups of the config and their members with the groups of
+/// the cluster, the members not in the config are removed
+pub fn group_changes(config: &Config, groups: &[Group]) -> Vec<Change> {
+    let mut changes = vec![];
+    let change = |action, user: &str, group: &str| Change {
+        action,
+        user: user.to_string(),
+        privileges: vec![],
+        object: group.to_string(),
+        revoke: false,
+    };
+
+    for group in &config.groups {
+        let name = ident::fold(&group.name);
+        let members = group.folded_members();
+        let current = match groups.iter().find(|g| g.name == name) {
+            Some(group) => group.members.as_slice(),
+            None => {
+                changes.push(change(Action::CreateGroup, "", &name));
+                &[]
+            }
+        };
+
+        for member in members.iter().filter(|m| !current.contains(m)) {
+            changes.push(change(Action::AddMember, member, &name));
+        }
+        for member in current.iter().filter(|m| !members.contains(m)) {
+            changes.push(change(Action::RemoveMember, member, &name));
+        }
+    }
+
+    changes
+}
+
+/// Compare the native roles of Redshift of the config, their system
+/// privileges and members with the ones of the cluster, the privileges and
+/// members not in the config are revoked
+pub fn redshift_role_changes(config: &Config, roles: &[NativeRole]) -> Vec<Change> {
+    let mut changes = vec![];
+    let change = |action, user: &str, privilege: Option<&String>, role: &str| Change {
+        action,
+        user: user.to_string(),
+        privileges: privilege.into_iter().cloned().collect(),
+        object: role.to_string(),
+        revoke: matches!(action, Action::RevokeRole | Action::RevokeSystemPrivilege),
+    };
+
+    for role in &config.redshift_roles {
+        let name = ident::fold(&role.name);
+        let privileges = role.normalized_privileges();
+        let members = role.folded_members();
+        let (current_privileges, current_members) = match roles.iter().find(|r| r.name == name) {
+            Some(role) => (role.privileges.as_slice(), role.members.as_slice()),
+            None => {
+                changes.push(change(Action::CreateRole, "", None, &name));
+                (&[][..], &[][..])
+            }
+        };
+
+        for privilege in privileges
+            .iter()
+            .filter(|p| !current_privileges.contains(p))
+        {
+            changes.push(change(
+                Action::GrantSystemPrivilege,
+                "",
+                Some(privilege),
+                &name,
+            ));
+        }
+        for privilege in current_privileges
+            .iter()
+            .filter(|p| !privileges.contains(p))
+        {
+            changes.push(change(
+                Action::RevokeSystemPrivilege,
+                "",
+                Some(privilege),
+                &name,
+            ));
+        }
+        for member in members.iter().filter(|m| !current_members.contains(m)) {
+            changes.push(change(Action::GrantRole, member, None, &name));
+        }
+        for member in current_members.iter().filter(|m| !members.contains(m)) {
+            changes.push(change(Action::RevokeRole, member, None, &name));
+        }
+    }
+
+    changes
+}
+
+/// The memberships of the users of the config in the groups of the cluster
+/// which are not in the config, e.g. added from the console. The groups of
+/// the config converge with [group_changes].
+pub fn unmanaged_memberships(config: &Config, groups: &[Group]) -> Vec<Change> {
+    let users = config
+        .users
+        .iter()
+        .filter(|u| !u.is_public())
+        .map(|u| ident::fold(&u.name))
+        .collect::<Vec<_>>();
+    let managed = config
+        .groups
+        .iter()
+        .map(|g| ident::fold(&g.name))
+        .collect::<Vec<_>>();
+
+    groups
+        .iter()
+        .filter(|g| !managed.contains(&g.name))
+        .flat_map(|group| {
+            group
+                .members
+                .iter()
+                .filter(|m| users.contains(m))
+                .map(move |member| Change {
+                    action: Action::UnmanagedMember,
+                    user: member.clone(),
+                    privileges: vec![],
+                    object: group.name.clone(),
+                    revoke: false,
+                })
+        })
+        .collect()
+}
+
+/// Compare the privileges of a role of the user with the cluster, the user
+/// named as in the catalog
+pub fn role_changes(user: &str, role: &Role, state: &ClusterState) -> Vec<Change> {
+    let mut plan = Plan::default();
+    diff_role(&mut plan, user, role, state, false);
+
+    plan.changes
+}
+
+/// Compare the privileges of a role revoked from the user with the cluster,
+/// see [Role::to_revoke_sql_for]
+pub fn revoke_changes(user: &str, role: &Role, state: &ClusterState) -> Vec<Change> {
+    let mut plan = Plan::default();
+    diff_role(&mut plan, user, role, state, true);
+
+    plan.changes
+}
+
+/// Compare the privileges of a role with the cluster, every one of them
+/// revoked if `revoked`
+fn diff_role(plan: &mut Plan, user: &str, role: &Role, state: &ClusterState, revoked: bool) {
+    match role {
+        Role::Database(role) => {
+            let privileges = expand(&role.grants, DATABASE_PRIVILEGES);
+            for database in role.databases.iter().map(|d| ident::fold(d)) {
+                compare(
+                    plan,
+                    user,
+                    format!("DATABASE {}", ident::quote_folded(&database)),
+                    &privileges,
+                    revoked,
+                    state.owner_of(&Object::Database(database.clone())) == Some(user),
+                    |p| state.has_database_priv(user, &database, p),
+                );
+            }
+        }
+        Role::Schema(role) => {
+            let privileges = expand(&role.grants, SCHEMA_PRIVILEGES);
+            for schema in role.schemas.iter().map(|s| ident::fold(s)) {
+                compare(
+                    plan,
+                    user,
+                    format!("SCHEMA {}", ident::quote_folded(&schema)),
+                    &privileges,
+                    revoked,
+                    state.owner_of(&Object::Schema(schema.clone())) == Some(user),
+                    |p| state.has_schema_priv(user, &schema, p),
+                );
+            }
+        }
+        Role::Table(role) => {
+            let privileges = expand(&role.grants, TABLE_PRIVILEGES);
+            let schemas = role
+                .schemas
+                .iter()
+                .map(|s| ident::fold(s))
+                .collect::<Vec<_>>();
+            let qualified = |table: &str| -> Vec<(String, String)> {
+                match ident::split_qualified(table).as_slice() {
+                    [schema, table] => vec![(ident::fold(schema), ident::fold(table))],
+                    _ => schemas
+                        .iter()
+                        .map(|s| (s.clone(), ident::fold(table)))
+                        .collect(),
+                }
+            };
+
+            // As applied: `ALL` or the `+` tables are granted, then the `-`
+            // tables are revoked, the last statement on a table wins
+            let entries = role.tables.iter().map(|t| signed(t)).collect::<Vec<_>>();
+            let mut tables: Vec<((String, String), bool)> = vec![];
+            let mut push = |table: (String, String), revoke: bool| match tables
+                .iter_mut()
+                .find(|(t, _)| *t == table)
+            {
+                Some(entry) => entry.1 = revoke,
+                None => tables.push((table, revoke)),
+            };
+
+            match entries.iter().find(|(_, name)| *name == "ALL") {
+                Some((revoke, _)) => {
+                    for schema in &schemas {
+                        for table in state.tables_of(schema) {
+                            push((schema.clone(), table.to_string()), *revoke);
+                        }
+                    }
+                }
+                None => {
+                    for (_, name) in entries.iter().filter(|(revoke, _)| !revoke) {
+                        for table in qualified(name) {
+                            push(table, false);
+                        }
+                    }
+                }
+            }
+            for (_, name) in entries
+                .iter()
+                .filter(|(revoke, name)| *revoke && *name != "ALL")
+            {
+                for table in qualified(name) {
+                    push(table, true);
+                }
+            }
+
+            for ((schema, table), revoke) in tables {
+                compare(
+                    plan,
+                    user,
+                    format!(
+                        "TABLE {}.{}",
+                        ident::quote_folded(&schema),
+                        ident::quote_folded(&table)
+                    ),
+                    &privileges,
+                    revoke || revoked,
+                    state.owner_of(&Object::Table(schema.clone(), table.clone())) == Some(user),
+                    |p| state.has_table_priv(user, &schema, &table, p),
+                );
+            }
+        }
+        // The EXECUTE privileges are not read from the catalog, they are
+        // always planned
+        Role::Function(role) => {
+            let privileges = expand(&role.grants, &["EXECUTE"]);
+            for (revoke, object) in role.objects() {
+                let revoke = revoke || revoked;
+                compare(plan, user, object, &privileges, revoke, false, |_| false);
+            }
+        }
+        // Neither are the privileges on sequences, `has` is the opposite of
+        // the change so that they are always planned
+        Role::Sequence(role) => {
+            let privileges = expand(&role.grants, &["USAGE", "SELECT", "UPDATE"]);
+            for (revoke, object) in role.objects() {
+                let revoke = revoke || revoked;
+                compare(plan, user, object, &privileges, revoke, false, |_| revoke);
+            }
+        }
+        // Only Snowflake has warehouses, the config is rejected by
+        // Config::validate_dialect before planning
+        Role::Warehouse(_) => {}
+    }
+}
+
+/// Split the privileges to grant or revoke on an object into a change for
+/// the ones to update and a no-op for the others. The owner of the object
+/// has every privilege whatever is granted, its entry is only reported.
+fn compare(
+    plan: &mut Plan,
+    user: &str,
+    object: String,
+    privileges: &[String],
+    revoke: bool,
+    owned: bool,
+    has: impl Fn(&str) -> bool,
+) {
+    if owned {
+        plan.changes.push(Change {
+            action: Action::Owner,
+            user: user.to_string(),
+            privileges: privileges.to_vec(),
+            object,
+            revoke,
+        });
+        return;
+    }
+
+    let (unchanged, changed): (Vec<String>, Vec<String>) =
+        privileges.iter().cloned().partition(|p| {
+            let known = [DATABASE_PRIVILEGES, SCHEMA_PRIVILEGES, TABLE_PRIVILEGES]
+                .iter()
+                .any(|known| known.contains(&p.as_str()));
+
+            known && has(p) != revoke
+        });
+
+    let action = if revoke {
+        Action::Revoke
+    } else {
+        Action::Grant
+    };
+    for (action, privileges) in [(action, changed), (Action::NoOp, unchanged)] {
+        if !privileges.is_empty() {
+            plan.changes.push(Change {
+                action,
+                user: user.to_string(),
+                privileges,
+                object: object.clone(),
+                revoke,
+            });
+        }
+    }
+}
+
+/// The privileges of the grants, `ALL` (or no grant) being every privilege
+/// read from the catalog
+fn expand(grants: &[String], all: &[&str]) -> Vec<String> {
+    if grants.is_empty() || grants.iter().any(|g| g == "ALL") {
+        return all.iter().map(|p| p.to_string()).collect();
+    }
+
+    grants
+        .iter()
+        .map(|g| match g.as_str() {
+            "TEMPORARY" => "TEMP".to_string(),
+            g => g.to_string(),
+        })
+        .collect()
+}
+
+/// A table entry of a table role split into its sign and name, only the
+/// first character is the sign
+fn signed(entry: &str) -> (bool, &str) {
+    match entry.strip_prefix('-') {
+        Some(name) => (true, name),
+        None => (false, entry.strip_prefix('+').unwrap_or(entry)),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::connection::{ObjectOwner, User, UserDatabaseRole, UserSchemaRole, UserTableRole};
+    use std::io::Write;
+    use std::str::FromStr;
+
+    fn table(schema_name: &str, table_name: &str, has_select: bool) -> UserTableRole {
+        UserTableRole {
+            name: "duyet".into(),
+            schema_name: schema_name.into(),
+            table_name: table_name.into(),
+            has_select,
+            has_insert: false,
+            has_update: false,
+            has_delete: false,
+            has_references: false,
+        }
+    }
+
+    fn state() -> ClusterState {
+        state_with_tables(vec![
+            table("public", "orders", true),
+            table("public", "secrets", true),
+            table("public", "events", false),
+        ])
+    }
+
+    fn state_with_tables(tables: Vec<UserTableRole>) -> ClusterState {
+        ClusterState::new(
+            vec![User {
+                name: "duyet".to_string(),
+                user_createdb: false,
+                user_super: false,
+                password: "********".to_string(),
+                attributes: Default::default(),
+                settings: Default::default(),
+            }],
+            vec![],
+            vec![UserDatabaseRole {
+                name: "duyet".into(),
+                database_name: "postgres".into(),
+                has_create: true,
+                has_temp: false,
+            }],
+            vec![UserSchemaRole {
+                name: "duyet".into(),
+                schema_name: "public".into(),
+                has_create: false,
+                has_usage: true,
+            }],
+            tables,
+        )
+    }
+
+    fn config() -> Config {
+        Config::from_str(
+            "
+            connection:
+              type: postgres
+              url: postgres://localhost:5432/postgres
+            roles:
+              - name: role_database
+                type: database
+                grants: [CREATE, TEMP]
+                databases: [Postgres]
+              - name: role_schema
+                type: schema
+                grants: [USAGE]
+                schemas: [public]
+              - name: role_table
+                type: table
+                grants: [SELECT, TRUNCATE]
+                schemas: [public]
+                tables: [ALL, -secrets]
+            users:
+              - name: Duyet
+                password_policy: always
+                roles: [role_database, role_schema, role_table]
+              - name: new_user
+                roles: [role_schema]
+            ",
+        )
+        .unwrap()
+    }
+
+    fn change(action: Action, user: &str, privileges: &[&str], object: &str) -> Change {
+        Change {
+            action,
+            user: user.to_string(),
+            privileges: privileges.iter().map(|p| p.to_string()).collect(),
+            object: object.to_string(),
+            revoke: action == Action::Revoke,
+        }
+    }
+
+    #[test]
+    fn test_diff() {
+        let plan = diff(&config(), &state());
+
+        assert_eq!(
+            plan.changes,
+            vec![
+                change(Action::UpdatePassword, "duyet", &[], ""),
+                change(Action::Grant, "duyet", &["TEMP"], "DATABASE postgres"),
+                change(Action::NoOp, "duyet", &["CREATE"], "DATABASE postgres"),
+                change(Action::NoOp, "duyet", &["USAGE"], "SCHEMA public"),
+                change(
+                    Action::Grant,
+                    "duyet",
+                    &["SELECT", "TRUNCATE"],
+                    "TABLE public.events"
+                ),
+                change(Action::Grant, "duyet", &["TRUNCATE"], "TABLE public.orders"),
+                change(Action::NoOp, "duyet", &["SELECT"], "TABLE public.orders"),
+                change(
+                    Action::Revoke,
+                    "duyet",
+                    &["SELECT", "TRUNCATE"],
+                    "TABLE public.secrets"
+                ),
+                change(Action::CreateUser, "new_user", &[], ""),
+                change(Action::Grant, "new_user", &["USAGE"], "SCHEMA public"),
+            ]
+        );
+        assert!(plan.has_changes());
+        assert_eq!(
+            plan.summary(),
+            "Plan: 1 user(s) to create, 1 password(s) to update, 4 grant(s), 1 revoke(s), 3 unchanged."
+        );
+    }
+
+    #[test]
+    fn test_diff_owner() {
+        let mut state = state();
+        state.set_owners(vec![ObjectOwner {
+            object: Object::Table("public".to_string(), "secrets".to_string()),
+            owner: "duyet".to_string(),
+        }]);
+        let plan = diff(&config(), &state);
+
+        // The revoke on the owned table is only reported
+        let secrets = plan
+            .changes
+            .iter()
+            .find(|c| c.object == "TABLE public.secrets")
+            .unwrap();
+        assert_eq!(
+            secrets,
+            &Change {
+                revoke: true,
+                ..change(
+                    Action::Owner,
+                    "duyet",
+                    &["SELECT", "TRUNCATE"],
+                    "TABLE public.secrets"
+                )
+            }
+        );
+        assert_eq!(secrets.to_sql(), None);
+        assert!(secrets
+            .to_string()
+            .ends_with("SELECT, TRUNCATE on TABLE public.secrets for duyet: owner (implicit ALL)"));
+        assert_eq!(
+            plan.summary(),
+            "Plan: 1 user(s) to create, 1 password(s) to update, 4 grant(s), 0 revoke(s), 3 unchanged. \
+             1 owned by the user (implicit ALL)."
+        );
+    }
+
+    #[test]
+    fn test_diff_explicit_tables() {
+        let config = Config::from_str(
+            "
+            connection:
+              type: postgres
+              url: postgres://localhost:5432/postgres
+            roles:
+              - name: role_table
+                type: table
+                grants: [ALL]
+                schemas: [public]
+                tables: [orders, +sales.Orders, -events, -orders]
+            users:
+              - name: duyet
+                roles: [role_table]
+            ",
+        )
+        .unwrap();
+        let plan = diff(&config, &state());
+
+        let objects = plan
+            .changes
+            .iter()
+            .map(|c| (c.action, c.object.as_str()))
+            .collect::<Vec<_>>();
+        assert_eq!(
+            objects,
+            vec![
+                (Action::Revoke, "TABLE public.orders"),
+                (Action::NoOp, "TABLE public.orders"),
+                (Action::Grant, "TABLE sales.orders"),
+                (Action::NoOp, "TABLE public.events"),
+            ]
+        );
+        assert!(plan.changes[0]
+            .to_string()
+            .ends_with("revoke SELECT on TABLE public.orders from duyet"));
+    }
+
+    #[test]
+    fn test_group_changes() {
+        let config = Config::from_str(
+            "
+            connection:
+              type: postgres
+              url: postgres://localhost:5432/postgres
+            roles: []
+            users: []
+            groups:
+              - name: Analysts
+                members: [duyet, new_user]
+              - name: engineers
+                members: [duyet]
+            ",
+        )
+        .unwrap();
+        let groups = vec![Group {
+            name: "analysts".to_string(),
+            members: vec!["duyet".to_string(), "old_user".to_string()],
+        }];
+
+        let plan = Plan {
+            changes: group_changes(&config, &groups),
+        };
+        assert_eq!(
+            plan.changes,
+            vec![
+                change(Action::AddMember, "new_user", &[], "analysts"),
+                change(Action::RemoveMember, "old_user", &[], "analysts"),
+                change(Action::CreateGroup, "", &[], "engineers"),
+                change(Action::AddMember, "duyet", &[], "engineers"),
+            ]
+        );
+        assert!(plan.changes[1]
+            .to_string()
+            .ends_with("remove old_user from group analysts"));
+        assert_eq!(
+            plan.summary(),
+            "Plan: 0 user(s) to create, 0 password(s) to update, 0 grant(s), 0 revoke(s), 0 unchanged. \
+             Groups: 1 to create, 2 member(s) to add, 1 member(s) to remove."
+        );
+    }
+
+    #[test]
+    fn test_redshift_role_changes() {
+        let config = Config::from_str(
+            "
+            connection:
+              type: redshift
+              url: postgres://localhost:5439/dev
+            roles: []
+            users: []
+            redshift_roles:
+              - name: Data_Admin
+                privileges: [create user, ALTER USER]
+                members: [duyet, new_user]
+              - name: auditor
+                privileges: [ACCESS SYSTEM TABLE]
+                members: [duyet]
+            ",
+        )
+        .unwrap();
+        let roles = vec![NativeRole {
+            name: "data_admin".to_string(),
+            members: vec!["duyet".to_string(), "old_user".to_string()],
+            privileges: vec!["CREATE USER".to_string(), "DROP USER".to_string()],
+        }];
+
+        let plan = Plan {
+            changes: redshift_role_changes(&config, &roles),
+        };
+        let revoke = |mut change: Change| {
+            change.revoke = true;
+            change
+        };
+        assert_eq!(
+            plan.changes,
+            vec![
+                change(
+                    Action::GrantSystemPrivilege,
+                    "",
+                    &["ALTER USER"],
+                    "data_admin"
+                ),
+                revoke(change(
+                    Action::RevokeSystemPrivilege,
+                    "",
+                    &["DROP USER"],
+                    "data_admin"
+                )),
+                change(Action::GrantRole, "new_user", &[], "data_admin"),
+                revoke(change(Action::RevokeRole, "old_user", &[], "data_admin")),
+                change(Action::CreateRole, "", &[], "auditor"),
+                change(
+                    Action::GrantSystemPrivilege,
+                    "",
+                    &["ACCESS SYSTEM TABLE"],
+                    "auditor"
+                ),
+                change(Action::GrantRole, "duyet", &[], "auditor"),
+            ]
+        );
+        assert!(plan.changes[1]
+            .to_string()
+            .ends_with("revoke DROP USER from role data_admin"));
+        assert_eq!(
+            plan.summary(),
+            "Plan: 0 user(s) to create, 0 password(s) to update, 0 grant(s), 0 revoke(s), 0 unchanged. \
+             Redshift roles: 1 to create, 2 privilege(s) to grant, 1 privilege(s) to revoke, 2 member(s) to add, 1 member(s) to remove."
+        );
+    }
+
+    #[test]
+    fn test_unmanaged_memberships() {
+        let config = Config::from_str(
+            "
+            connection:
+              type: postgres
+              url: postgres://localhost:5432/postgres
+            roles: []
+            users:
+              - name: Duyet
+                roles: []
+            groups:
+              - name: analysts
+                members: [duyet]
+            ",
+        )
+        .unwrap();
+        let group = |name: &str, members: &[&str]| Group {
+            name: name.to_string(),
+            members: members.iter().map(|m| m.to_string()).collect(),
+        };
+        let groups = vec![
+            group("analysts", &["duyet", "other"]),
+            group("console_admins", &["duyet", "other"]),
+            group("empty", &[]),
+        ];
+
+        let plan = Plan {
+            changes: unmanaged_memberships(&config, &groups),
+        };
+        assert_eq!(
+            plan.changes,
+            vec![change(
+                Action::UnmanagedMember,
+                "duyet",
+                &[],
+                "console_admins"
+            )]
+        );
+        assert!(plan.changes[0]
+            .to_string()
+            .ends_with("duyet is a member of group console_admins, which is not in the config"));
+        assert!(!plan.has_changes());
+        assert!(plan
+            .summary()
+            .ends_with(" 1 membership(s) of groups not in the config."));
+    }
+
+    #[test]
+    fn test_role_changes_to_sql() {
+        let config = config();
+        let sqls = role_changes("duyet", &config.roles[2], &state())
+            .iter()
+            .filter_map(|c| c.to_sql())
+            .collect::<Vec<_>>();
+
+        assert_eq!(
+            sqls,
+            vec![
+                "GRANT SELECT, TRUNCATE ON TABLE public.events TO duyet;",
+                "GRANT TRUNCATE ON TABLE public.orders TO duyet;",
+                "REVOKE SELECT, TRUNCATE ON TABLE public.secrets FROM duyet;",
+            ]
+        );
+        assert!(role_changes("duyet", &config.roles[1], &state())
+            .iter()
+            .all(|c| c.to_sql().is_none()));
+    }
+
+    #[test]
+    fn test_revoke_changes_to_sql() {
+        let config = config();
+        let sqls = revoke_changes("duyet", &config.roles[2], &state())
+            .iter()
+            .filter_map(|c| c.to_sql())
+            .collect::<Vec<_>>();
+
+        assert_eq!(
+            sqls,
+            vec![
+                "REVOKE TRUNCATE ON TABLE public.events FROM duyet;",
+                "REVOKE SELECT, TRUNCATE ON TABLE public.orders FROM duyet;",
+                "REVOKE SELECT, TRUNCATE ON TABLE public.secrets FROM duyet;",
+            ]
+        );
+    }
+
+    #[test]
+    fn test_conflicts() {
+        let file = |file: &str, yaml: &str| {
+            let config = Config::from_str(yaml).unwrap();
+            let plan = diff(&config, &state());
+            FilePlan {
+                file: file.to_string(),
+                config,
+                plan,
+            }
+        };
+        let a = file(
+            "a.yaml",
+            "
+            connection:
+              type: postgres
+              url: postgres://localhost:5432/postgres
+            roles:
+              - name: role_table
+                type: table
+                grants: [SELECT]
+                schemas: [public]
+                tables: [orders]
+            groups:
+              - name: analysts
+                members: [duyet]
+            users:
+              - name: duyet
+                password: secret
+                password_policy: always
+                roles: [role_table]
+            ",
+        );
+        let b = file(
+            "b.yaml",
+            "
+            connection:
+              type: postgres
+              url: postgres://localhost:5432/postgres
+            roles:
+              - name: role_table
+                type: table
+                grants: [SELECT, INSERT]
+                schemas: [public]
+                tables: [-orders]
+            groups:
+              - name: Analysts
+                members: [duyet, other]
+            users:
+              - name: Duyet
+                password: other
+                roles: [role_table]
+            ",
+        );
+
+        assert_eq!(
+            conflicts(&[a, b]),
+            vec![
+                "user duyet: different passwords in a.yaml and b.yaml",
+                "group analysts: different members in a.yaml and b.yaml, \
+                 each apply removes the members of the other",
+                "SELECT on TABLE public.orders for duyet: granted by a.yaml and revoked by b.yaml",
+            ]
+        );
+    }
+
+    #[test]
+    fn test_diff_public() {
+        let config = Config::from_str(
+            "
+            connection:
+              type: postgres
+              url: postgres://localhost:5432/postgres
+            roles:
+              - name: role_schema
+                type: schema
+                grants: [USAGE]
+                schemas: [public]
+              - name: role_table
+                type: table
+                grants: [SELECT]
+                schemas: [public]
+                tables: [-secrets]
+            users:
+              - name: PUBLIC
+                roles: [role_schema, role_table]
+            ",
+        )
+        .unwrap();
+
+        // Neither created nor compared with the catalog
+        assert_eq!(
+            diff(&config, &state()).changes,
+            vec![
+                change(Action::Grant, "public", &["USAGE"], "SCHEMA public"),
+                change(
+                    Action::Revoke,
+                    "public",
+                    &["SELECT"],
+                    "TABLE public.secrets"
+                ),
+            ]
+        );
+    }
+
+    #[test]
+    fn test_diff_other_database() {
+        let config = Config::from_str(
+            "
+            connection:
+              type: postgres
+              url: postgres://localhost:5432/postgres
+            roles:
+              - name: role_schema
+                type: schema
+                grants: [USAGE]
+                schemas: [public]
+              - name: role_analytics
+                type: schema
+                database: analytics
+                grants: [USAGE]
+                schemas: [public]
+            users:
+              - name: duyet
+                roles: [role_schema, role_analytics]
+            ",
+        )
+        .unwrap();
+        let mut state = state();
+        state.current_database = Some("postgres".to_string());
+
+        // The catalog is the one of the database of the connection
+        assert_eq!(
+            diff(&config, &state).changes,
+            vec![
+                change(Action::NoOp, "duyet", &["USAGE"], "SCHEMA public"),
+                change(Action::Grant, "duyet", &["USAGE"], "SCHEMA public"),
+            ]
+        );
+    }
+
+    #[test]
+    fn test_diff_user_attributes() {
+        let config = Config::from_str(
+            "
+            connection:
+              type: postgres
+              url: postgres://localhost:5432/postgres
+            roles: []
+            users:
+              - name: duyet
+                syslog_access: unrestricted
+                session_timeout: 3600
+                roles: []
+            ",
+        )
+        .unwrap();
+
+        let plan = diff(&config, &state());
+        assert_eq!(
+            plan.changes,
+            vec![change(
+                Action::UpdateAttributes,
+                "duyet",
+                &["syslog_access: unrestricted, session_timeout: 3600"],
+                ""
+            )]
+        );
+        assert!(plan
+            .summary()
+            .ends_with(" 1 user(s) with attributes to update."));
+    }
+
+    #[test]
+    fn test_saved_plan() {
+        let mut file = tempfile::NamedTempFile::new().unwrap();
+        write!(file, "config").unwrap();
+        let config = config();
+        let saved = SavedPlan::new(file.path(), &config, &diff(&config, &state())).unwrap();
+        assert_eq!(saved.connection, "postgres://localhost:5432/postgres");
+        assert!(saved.check(file.path(), &config, &state()).is_ok());
+
+        // A privilege granted since the plan
+        let changed = state_with_tables(vec![
+            table("public", "orders", true),
+            table("public", "secrets", true),
+            table("public", "events", true),
+        ]);
+        let err = saved
+            .check(file.path(), &config, &changed)
+            .unwrap_err()
+            .to_string();
+        assert!(err.starts_with("the cluster changed since the plan was computed at"));
+        assert!(err.contains(
+            r#"new: {"action":"no_op","user":"duyet","privileges":["SELECT"],"object":"TABLE public.events","revoke":false}"#
+        ));
+        assert!(err.contains(
+            r#"gone: {"action":"grant","user":"duyet","privileges":["SELECT","TRUNCATE"]"#
+        ));
+
+        write!(file, " changed").unwrap();
+        assert!(saved
+            .check(file.path(), &config, &state())
+            .unwrap_err()
+            .to_string()
+            .contains("changed since the plan was computed"));
+    }
+
+    #[test]
+    fn test_saved_plan_load() {
+        let config = config();
+        let file = tempfile::NamedTempFile::new().unwrap();
+        let saved = SavedPlan::new(file.path(), &config, &diff(&config, &state())).unwrap();
+        saved.save(file.path()).unwrap();
+        assert_eq!(SavedPlan::load(file.path()).unwrap(), saved);
+
+        SavedPlan {
+            version: 0,
+            ..saved
+        }
+        .save(file.path())
+        .unwrap();
+        assert!(SavedPlan::load(file.path())
+            .unwrap_err()
+            .to_string()
+            .ends_with("has version 0, expected 1: plan again with this version of grant"));
+    }
+}
@@ -0,0 +1,20 @@
+use anyhow::{anyhow, Result};
+use std::io::{self, BufRead, IsTerminal, Write};
+
+/// Ask to type `yes` on the terminal. Without a terminal, e.g. in CI, there
+/// is nobody to confirm: the command must be run with `flag`, e.g. `--yes`.
+pub fn confirm(question: &str, flag: &str) -> Result<bool> {
+    if !io::stdin().is_terminal() {
+        return Err(anyhow!(
+            "cannot ask for confirmation, stdin is not a terminal: run with {} to proceed anyway",
+            flag
+        ));
+    }
+
+    print!("{} Only 'yes' will be accepted: ", question);
+    io::stdout().flush()?;
+    let mut answer = String::new();
+    io::stdin().lock().read_line(&mut answer)?;
+
+    Ok(answer.trim() == "yes")
+}
@@ -0,0 +1,279 @@
+use crate::config::edit::ConfigEditor;
+use crate::config::ident;
+use crate::config::{Config, Role};
+use anyhow::Result;
+use log::{info, warn};
+use std::path::{Path, PathBuf};
+use walkdir::WalkDir;
+
+/// Rename the schema `old` to `new` in every role of the config files in
+/// `target` (a file or a directory tree).
+///
+/// Only the changed roles are rewritten, the rest of each file is kept as is.
+/// With `plan`, the SQL to move the privileges of the affected users from the
+/// old schema to the new one on the live cluster is printed.
+pub fn rename_schema(target: &Path, old: &str, new: &str, plan: bool, dryrun: bool) -> Result<()> {
+    let mut changed = 0;
+
+    for file in config_files(target) {
+        let config = match Config::new(&file) {
+            Ok(config) => config,
+            Err(e) => {
+                warn!("Skipping {}: {}", file.display(), e);
+                continue;
+            }
+        };
+
+        // Only the schema, table, function and sequence entries are rewritten, so that comments
+        // and keys unknown to grant are kept
+        let mut editor = ConfigEditor::open(&file)?;
+        let mut renamed = vec![];
+        for role in &config.roles {
+            if let Some(new_role) = rename_schema_in_role(role, old, new) {
+                let name = role.get_name();
+                editor.map_list("roles", &name, "schemas", |s| {
+                    rename_schema_name(s, old, new)
+                })?;
+                for field in ["tables", "functions", "procedures", "sequences"] {
+                    editor.map_list("roles", &name, field, |t| rename_table(t, old, new))?;
+                }
+                renamed.push((role, new_role));
+            }
+        }
+
+        if renamed.is_empty() {
+            continue;
+        }
+
+        for (role, _) in &renamed {
+            info!(
+                "{}: role {} {} schema {} to {}",
+                file.display(),
+                role.get_name(),
+                if dryrun { "would rename" } else { "renamed" },
+                old,
+                new
+            );
+        }
+        if !dryrun {
+            editor.save(&file)?;
+        }
+
+        if plan {
+            for (role, new_role) in &renamed {
+                for user in config
+                    .users
+                    .iter()
+                    .filter(|u| u.roles.contains(&role.get_name()))
+                {
+                    println!("-- {}: {}", user.name, role.get_name());
+                    println!("{}", revoke_schema_sql(role, old, &user.name));
+                    println!("{}", new_role.to_sql(&user.name));
+                }
+            }
+        }
+
+        changed += renamed.len();
+    }
+
+    info!("{} role(s) referencing schema {}", changed, old);
+
+    Ok(())
+}
+
+/// Config files in the target, `.yaml` or `.yml` files in a directory tree
+fn config_files(target: &Path) -> Vec<PathBuf> {
+    if target.is_file() {
+        return vec![target.to_path_buf()];
+    }
+
+    let mut files = WalkDir::new(target)
+        .into_iter()
+        .filter_map(|entry| entry.ok())
+        .map(|entry| entry.into_path())
+        .filter(|path| {
+            let ext = path.extension().and_then(|e| e.to_str());
+            path.is_file() && (ext == Some("yaml") || ext == Some("yml"))
+        })
+        .collect::<Vec<_>>();
+    files.sort();
+
+    files
+}
+
+/// The role with the schema renamed, None if it does not reference it.
+fn rename_schema_in_role(role: &Role, old: &str, new: &str) -> Option<Role> {
+    let mut renamed = role.clone();
+    match &mut renamed {
+        Role::Database(_) | Role::Warehouse(_) => return None,
+        Role::Schema(role) => {
+            role.schemas = role
+                .schemas
+                .iter()
+                .map(|s| rename_schema_name(s, old, new))
+                .collect();
+        }
+        Role::Table(role) => {
+            role.schemas = role
+                .schemas
+                .iter()
+                .map(|s| rename_schema_name(s, old, new))
+                .collect();
+            role.tables = role
+                .tables
+                .iter()
+                .map(|t| rename_table(t, old, new))
+                .collect();
+        }
+        Role::Function(role) => {
+            role.schemas = role
+                .schemas
+                .iter()
+                .map(|s| rename_schema_name(s, old, new))
+                .collect();
+            for entries in [&mut role.functions, &mut role.procedures] {
+                *entries = entries.iter().map(|f| rename_table(f, old, new)).collect();
+            }
+        }
+        Role::Sequence(role) => {
+            role.schemas = role
+                .schemas
+                .iter()
+                .map(|s| rename_schema_name(s, old, new))
+                .collect();
+            role.sequences = role
+                .sequences
+                .iter()
+                .map(|s| rename_table(s, old, new))
+                .collect();
+        }
+    }
+
+    if renamed == *role {
+        None
+    } else {
+        Some(renamed)
+    }
+}
+
+fn rename_schema_name(schema: &str, old: &str, new: &str) -> String {
+    if ident::fold(schema) == ident::fold(old) {
+        new.to_string()
+    } else {
+        schema.to_string()
+    }
+}
+
+/// Rename schema qualified tables: `old.table`, `+old.table` or `-old.table`,
+/// functions, `old.function(argtypes)`, or sequences
+fn rename_table(table: &str, old: &str, new: &str) -> String {
+    let (sign, name) = match table.chars().next() {
+        Some(c @ ('+' | '-')) => table.split_at(c.len_utf8()),
+        _ => ("", table),
+    };
+
+    match ident::split_qualified(name)[..] {
+        [schema, table] if ident::fold(schema) == ident::fold(old) => {
+            format!("{}{}.{}", sign, new, table)
+        }
+        _ => table.to_string(),
+    }
+}
+
+/// SQL revoking the privileges of the role on the old schema from the user
+fn revoke_schema_sql(role: &Role, schema: &str, user: &str) -> String {
+    let grants = role.get_grants();
+    let grants = if grants.is_empty() || grants.contains(&"ALL".to_string()) {
+        "ALL PRIVILEGES".to_string()
+    } else {
+        grants.join(", ")
+    };
+
+    match role {
+        Role::Table(_) => format!(
+            "REVOKE {} ON ALL TABLES IN SCHEMA {} FROM {};",
+            grants,
+            ident::quote(schema),
+            ident::quote(user)
+        ),
+        Role::Sequence(_) => format!(
+            "REVOKE {} ON ALL SEQUENCES IN SCHEMA {} FROM {};",
+            grants,
+            ident::quote(schema),
+            ident::quote(user)
+        ),
+        Role::Function(role) => {
+            let mut kinds = vec![];
+            if !role.functions.is_empty() {
+                kinds.push("FUNCTIONS");
+            }
+            if !role.procedures.is_empty() {
+                kinds.push("PROCEDURES");
+            }
+            kinds
+                .iter()
+                .map(|kind| {
+                    format!(
+                        "REVOKE {} ON ALL {} IN SCHEMA {} FROM {};",
+                        grants,
+                        kind,
+                        ident::quote(schema),
+                        ident::quote(user)
+                    )
+                })
+                .collect::<Vec<_>>()
+                .join(" ")
+        }
+        _ => format!(
+            "REVOKE {} ON SCHEMA {} FROM {};",
+            grants,
+            ident::quote(schema),
+            ident::quote(user)
+        ),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::config::role::{RoleDatabaseLevel, RoleTableLevel};
+
+    fn table_role(schemas: &[&str], tables: &[&str]) -> Role {
+        Role::Table(RoleTableLevel {
+            name: "role_table".to_string(),
+            grants: vec!["SELECT".to_string()],
+            schemas: schemas.iter().map(|s| s.to_string()).collect(),
+            tables: tables.iter().map(|s| s.to_string()).collect(),
+            database: None,
+        })
+    }
+
+    #[test]
+    fn test_rename_schema_in_role() {
+        let role = table_role(&["v1", "public"], &["ALL", "-v1.secret", "+public.t"]);
+        let renamed = rename_schema_in_role(&role, "v1", "v2").unwrap();
+
+        assert_eq!(
+            renamed,
+            table_role(&["v2", "public"], &["ALL", "-v2.secret", "+public.t"])
+        );
+        assert!(rename_schema_in_role(&role, "other", "v2").is_none());
+
+        let role = Role::Database(RoleDatabaseLevel {
+            name: "role_database".to_string(),
+            grants: vec!["CREATE".to_string()],
+            databases: vec!["v1".to_string()],
+        });
+        assert!(rename_schema_in_role(&role, "v1", "v2").is_none());
+    }
+
+    #[test]
+    fn test_revoke_schema_sql() {
+        let role = table_role(&["v1"], &["ALL"]);
+
+        assert_eq!(
+            revoke_schema_sql(&role, "v1", "duyet"),
+            "REVOKE SELECT ON ALL TABLES IN SCHEMA v1 FROM duyet;"
+        );
+    }
+}
@@ -0,0 +1,331 @@
+//! Output of the tables of grant: the summaries of `apply`, the users of
+//! `inspect`, `users list` and `roles list`.
+//!
+//! Each [OutputFormat] has a [Renderer], a new format only needs a new
+//! implementation of the trait.
+
+use ascii_table::AsciiTable;
+use log::info;
+use serde_json::{Map, Value};
+use std::fmt;
+
+/// A table with a header row
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct Table {
+    pub headers: Vec<String>,
+    pub rows: Vec<Vec<String>>,
+}
+
+impl Table {
+    pub fn new(headers: &[&str]) -> Self {
+        Table {
+            headers: headers.iter().map(|h| h.to_string()).collect(),
+            rows: vec![],
+        }
+    }
+
+    pub fn push(&mut self, row: Vec<String>) {
+        self.rows.push(row);
+    }
+
+    /// The cells without the terminal colors, for the formats other than
+    /// the terminal table
+    fn plain_rows(&self) -> Vec<Vec<String>> {
+        self.rows
+            .iter()
+            .map(|row| row.iter().map(|cell| strip_ansi(cell)).collect())
+            .collect()
+    }
+}
+
+/// Render a [Table] to a string
+pub trait Renderer {
+    fn render(&self, table: &Table) -> String;
+}
+
+/// Table for the terminal, the default
+#[derive(Debug, Default)]
+pub struct AsciiRenderer {
+    pub max_width: Option<usize>,
+}
+
+impl Renderer for AsciiRenderer {
+    fn render(&self, table: &Table) -> String {
+        let mut ascii_table = AsciiTable::default();
+        if let Some(max_width) = self.max_width {
+            ascii_table.set_max_width(max_width);
+        }
+
+        let mut rows = vec![table.headers.clone()];
+        rows.push(vec!["---".to_string(); table.headers.len()]);
+        rows.extend(table.rows.iter().cloned());
+
+        ascii_table.format(rows)
+    }
+}
+
+/// JSON array of objects keyed by the headers
+#[derive(Debug, Default)]
+pub struct JsonRenderer;
+
+impl Renderer for JsonRenderer {
+    fn render(&self, table: &Table) -> String {
+        let rows = table
+            .plain_rows()
+            .into_iter()
+            .map(|row| {
+                let object = table
+                    .headers
+                    .iter()
+                    .cloned()
+                    .zip(row.into_iter().map(Value::String))
+                    .collect::<Map<_, _>>();
+                Value::Object(object)
+            })
+            .collect::<Vec<_>>();
+
+        serde_json::to_string_pretty(&rows).unwrap_or_default()
+    }
+}
+
+/// GitHub flavored Markdown table, e.g. for PR comments
+#[derive(Debug, Default)]
+pub struct MarkdownRenderer;
+
+impl Renderer for MarkdownRenderer {
+    fn render(&self, table: &Table) -> String {
+        let line = |cells: &[String]| {
+            let cells = cells
+                .iter()
+                .map(|c| c.replace('|', "\\|"))
+                .collect::<Vec<_>>();
+            format!("| {} |", cells.join(" | "))
+        };
+
+        let mut lines = vec![line(&table.headers)];
+        lines.push(line(&vec!["---".to_string(); table.headers.len()]));
+        lines.extend(table.plain_rows().iter().map(|row| line(row)));
+
+        lines.join("\n")
+    }
+}
+
+/// HTML `<table>`
+#[derive(Debug, Default)]
+pub struct HtmlRenderer;
+
+impl Renderer for HtmlRenderer {
+    fn render(&self, table: &Table) -> String {
+        let line = |tag: &str, cells: &[String]| {
+            let cells = cells
+                .iter()
+                .map(|c| format!("<{}>{}</{}>", tag, escape_html(c), tag))
+                .collect::<String>();
+            format!("  <tr>{}</tr>", cells)
+        };
+
+        let mut lines = vec!["<table>".to_string(), line("th", &table.headers)];
+        lines.extend(table.plain_rows().iter().map(|row| line("td", row)));
+        lines.push("</table>".to_string());
+
+        lines.join("\n")
+    }
+}
+
+/// CSV with a header row, the fields with a comma, a quote or a line break
+/// are double quoted
+#[derive(Debug, Default)]
+pub struct CsvRenderer;
+
+impl Renderer for CsvRenderer {
+    fn render(&self, table: &Table) -> String {
+        let line = |cells: &[String]| {
+            cells
+                .iter()
+                .map(|c| match c.contains([',', '"', '\n', '\r']) {
+                    true => format!("\"{}\"", c.replace('"', "\"\"")),
+                    false => c.clone(),
+                })
+                .collect::<Vec<_>>()
+                .join(",")
+        };
+
+        let mut lines = vec![line(&table.headers)];
+        lines.extend(table.plain_rows().iter().map(|row| line(row)));
+
+        lines.join("\n")
+    }
+}
+
+/// Format of the output tables, selected by `--output`
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum OutputFormat {
+    #[default]
+    Table,
+    Json,
+    Markdown,
+    Html,
+}
+
+impl OutputFormat {
+    pub const VARIANTS: &'static [&'static str] = &["table", "json", "markdown", "html"];
+
+    pub fn renderer(&self) -> Box<dyn Renderer> {
+        match self {
+            OutputFormat::Table => Box::new(AsciiRenderer {
+                max_width: term_size::dimensions().map(|(w, _)| w.saturating_sub(5)),
+            }),
+            OutputFormat::Json => Box::new(JsonRenderer),
+            OutputFormat::Markdown => Box::new(MarkdownRenderer),
+            OutputFormat::Html => Box::new(HtmlRenderer),
+        }
+    }
+
+    /// Output the table: the terminal table is logged with the title, like
+    /// the rest of the logs, the other formats are printed alone to stdout so
+    /// they can be piped. A markdown table ends with a blank line, the next
+    /// one would be read as its rows.
+    pub fn output(&self, title: &str, table: &Table) {
+        let rendered = self.renderer().render(table);
+
+        match self {
+            OutputFormat::Table => info!("{}:\n{}", title, rendered),
+            OutputFormat::Markdown => println!("{}\n", rendered),
+            _ => println!("{}", rendered),
+        }
+    }
+
+    /// Output a table of a document with several ones, e.g. the summaries
+    /// of an apply posted as a PR comment: in markdown it is under a
+    /// `### title` heading, the other formats are as [Self::output]
+    pub fn output_section(&self, title: &str, table: &Table) {
+        match self {
+            OutputFormat::Markdown => {
+                println!("### {}\n\n{}\n", title, self.renderer().render(table))
+            }
+            _ => self.output(title, table),
+        }
+    }
+}
+
+impl fmt::Display for OutputFormat {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            OutputFormat::Table => write!(f, "table"),
+            OutputFormat::Json => write!(f, "json"),
+            OutputFormat::Markdown => write!(f, "markdown"),
+            OutputFormat::Html => write!(f, "html"),
+        }
+    }
+}
+
+impl std::str::FromStr for OutputFormat {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.to_lowercase().as_str() {
+            "table" => Ok(OutputFormat::Table),
+            "json" => Ok(OutputFormat::Json),
+            "markdown" => Ok(OutputFormat::Markdown),
+            "html" => Ok(OutputFormat::Html),
+            _ => Err(anyhow::anyhow!("unknown output format: {}", s)),
+        }
+    }
+}
+
+/// Remove the ANSI escape sequences (colors) of the cell
+fn strip_ansi(s: &str) -> String {
+    let mut out = String::with_capacity(s.len());
+    let mut chars = s.chars();
+
+    while let Some(c) = chars.next() {
+        if c == '\x1b' {
+            // Skip `ESC [ ... <letter>`
+            for c in chars.by_ref() {
+                if c.is_ascii_alphabetic() {
+                    break;
+                }
+            }
+        } else {
+            out.push(c);
+        }
+    }
+
+    out
+}
+
+fn escape_html(s: &str) -> String {
+    s.replace('&', "&amp;")
+        .replace('<', "&lt;")
+        .replace('>', "&gt;")
+        .replace('"', "&quot;")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use ansi_term::Colour::Green;
+    use std::str::FromStr;
+
+    fn table() -> Table {
+        let mut table = Table::new(&["User", "Action"]);
+        table.push(vec![
+            "duyet".to_string(),
+            Green.paint("created <a|b>").to_string(),
+        ]);
+
+        table
+    }
+
+    #[test]
+    fn test_ascii_renderer() {
+        let rendered = AsciiRenderer::default().render(&table());
+
+        assert!(rendered.contains("│ User  │ Action"));
+        assert!(rendered.contains("│ ---   │ ---"));
+    }
+
+    #[test]
+    fn test_json_renderer() {
+        assert_eq!(
+            JsonRenderer.render(&table()),
+            "[\n  {\n    \"User\": \"duyet\",\n    \"Action\": \"created <a|b>\"\n  }\n]"
+        );
+    }
+
+    #[test]
+    fn test_markdown_renderer() {
+        assert_eq!(
+            MarkdownRenderer.render(&table()),
+            "| User | Action |\n| --- | --- |\n| duyet | created <a\\|b> |"
+        );
+    }
+
+    #[test]
+    fn test_html_renderer() {
+        assert_eq!(
+            HtmlRenderer.render(&table()),
+            "<table>\n  <tr><th>User</th><th>Action</th></tr>\n  <tr><td>duyet</td><td>created &lt;a|b&gt;</td></tr>\n</table>"
+        );
+    }
+
+    #[test]
+    fn test_csv_renderer() {
+        let mut table = table();
+        table.push(vec!["a,b".to_string(), "say \"hi\"".to_string()]);
+
+        assert_eq!(
+            CsvRenderer.render(&table),
+            "User,Action\nduyet,created <a|b>\n\"a,b\",\"say \"\"hi\"\"\""
+        );
+    }
+
+    #[test]
+    fn test_output_format_from_str() {
+        for variant in OutputFormat::VARIANTS {
+            let format = OutputFormat::from_str(variant).unwrap();
+            assert_eq!(format.to_string(), *variant);
+        }
+        assert!(OutputFormat::from_str("xml").is_err());
+    }
+}
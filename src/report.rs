@@ -0,0 +1,421 @@
+//! Typed reports of grant: the summaries of `apply`, the users of `inspect`,
+//! the drift of `inspect --diff`, the lists of `users` and `roles`, the
+//! passwords of `verify-passwords`.
+//!
+//! A command pushes typed rows into a [Report], which can be sorted by a
+//! column and counted by the values of a column, then converted into a
+//! [Table] rendered in any [OutputFormat](crate::render::OutputFormat). The
+//! headers of a report are the ones of its [Row] type, so every command
+//! prints the same columns for the same rows.
+
+use crate::render::Table;
+use anyhow::{anyhow, Result};
+use serde::Serialize;
+use std::collections::BTreeMap;
+
+/// A row of a report, its cells in the order of the headers
+pub trait Row {
+    const HEADERS: &'static [&'static str];
+
+    fn cells(&self) -> Vec<String>;
+
+    /// The row alone in a table, e.g. the totals of a run
+    fn to_table(&self) -> Table
+    where
+        Self: Sized,
+    {
+        let mut table = Table::new(Self::HEADERS);
+        table.push(self.cells());
+
+        table
+    }
+}
+
+/// The rows of a report, in the order they were pushed until sorted
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Report<R: Row> {
+    pub rows: Vec<R>,
+}
+
+impl<R: Row> Default for Report<R> {
+    fn default() -> Self {
+        Report { rows: vec![] }
+    }
+}
+
+impl<R: Row> Report<R> {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn push(&mut self, row: R) {
+        self.rows.push(row);
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.rows.is_empty()
+    }
+
+    /// Sort the rows by the cells of a column, named by its header in any
+    /// case. The sort is stable, the rows with the same cell keep their
+    /// order.
+    pub fn sort_by(&mut self, column: &str) -> Result<()> {
+        let index = column_index::<R>(column)?;
+        self.rows
+            .sort_by_cached_key(|row| row.cells().swap_remove(index));
+
+        Ok(())
+    }
+
+    /// Number of rows by cell of a column, e.g. the statuses of the
+    /// privileges summary
+    pub fn totals(&self, column: &str) -> Result<BTreeMap<String, usize>> {
+        let index = column_index::<R>(column)?;
+        let mut totals = BTreeMap::new();
+        for row in &self.rows {
+            *totals.entry(row.cells().swap_remove(index)).or_default() += 1;
+        }
+
+        Ok(totals)
+    }
+
+    pub fn to_table(&self) -> Table {
+        let mut table = Table::new(R::HEADERS);
+        table.rows = self.rows.iter().map(Row::cells).collect();
+
+        table
+    }
+}
+
+fn column_index<R: Row>(column: &str) -> Result<usize> {
+    R::HEADERS
+        .iter()
+        .position(|h| h.eq_ignore_ascii_case(column))
+        .ok_or_else(|| {
+            anyhow!(
+                "unknown column {}, expected one of: {}",
+                column,
+                R::HEADERS.join(", ")
+            )
+        })
+}
+
+/// What `apply` did to a user of the config or of the cluster, e.g.
+/// `created CREATE USER duyet;` or `no action (not in config)`
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct UserAction {
+    pub user: String,
+    pub action: String,
+}
+
+impl UserAction {
+    pub fn new(user: &str, action: impl Into<String>) -> Self {
+        UserAction {
+            user: user.to_string(),
+            action: action.into(),
+        }
+    }
+
+    /// Whether `apply` left the user as it was
+    pub fn is_unchanged(&self) -> bool {
+        self.action.starts_with("no action")
+    }
+}
+
+impl Row for UserAction {
+    const HEADERS: &'static [&'static str] = &["User", "Action"];
+
+    fn cells(&self) -> Vec<String> {
+        vec![self.user.clone(), self.action.clone()]
+    }
+}
+
+/// What `apply` did to a member of a group
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct MemberAction {
+    pub group: String,
+    pub member: String,
+    pub action: String,
+}
+
+impl Row for MemberAction {
+    const HEADERS: &'static [&'static str] = &["Group", "Member", "Action"];
+
+    fn cells(&self) -> Vec<String> {
+        vec![self.group.clone(), self.member.clone(), self.action.clone()]
+    }
+}
+
+/// What `apply` did to a system privilege or a member of a native role of
+/// Redshift
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct RoleAction {
+    pub role: String,
+    /// The member or the system privilege, empty for the creation of the role
+    pub target: String,
+    pub action: String,
+}
+
+impl Row for RoleAction {
+    const HEADERS: &'static [&'static str] = &["Role", "Member / Privilege", "Action"];
+
+    fn cells(&self) -> Vec<String> {
+        vec![self.role.clone(), self.target.clone(), self.action.clone()]
+    }
+}
+
+/// The status of a role of a grantee after `apply`, e.g. `updated`,
+/// `unchanged` or `rolled back`
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct PrivilegeStatus {
+    /// The user, `GROUP name` or `PUBLIC`
+    pub grantee: String,
+    pub role: String,
+    /// The objects of the role, e.g. `table["ALL", "-secrets"]`
+    pub detail: String,
+    pub status: String,
+    /// The statements of the privileges which differ from the cluster,
+    /// executed unless the status tells otherwise. Only in JSON, the
+    /// tables would be too wide.
+    pub sql: String,
+}
+
+impl Row for PrivilegeStatus {
+    const HEADERS: &'static [&'static str] = &["User", "Role Name", "Detail", "Status"];
+
+    fn cells(&self) -> Vec<String> {
+        vec![
+            self.grantee.clone(),
+            self.role.clone(),
+            self.detail.clone(),
+            self.status.clone(),
+        ]
+    }
+}
+
+/// The current privileges of a user of the cluster, as shown by `inspect`.
+/// Each list is None when the cluster could not be read for it.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct UserPrivileges {
+    pub user: String,
+    pub superuser: bool,
+    pub groups: Option<Vec<String>>,
+    /// The privileges on the current database, e.g. `postgres(CT)`
+    pub databases: Option<Vec<String>>,
+    pub schemas: Option<Vec<String>>,
+    pub tables: Option<Vec<String>>,
+}
+
+/// The cell of a list which could not be read
+pub const UNAVAILABLE: &str = "(unavailable)";
+
+fn list_cell(list: &Option<Vec<String>>) -> String {
+    match list {
+        Some(list) => list.join(", "),
+        None => UNAVAILABLE.to_string(),
+    }
+}
+
+impl Row for UserPrivileges {
+    const HEADERS: &'static [&'static str] = &[
+        "User",
+        "Super",
+        "Groups",
+        "Current Database",
+        "Schemas",
+        "Tables",
+    ];
+
+    fn cells(&self) -> Vec<String> {
+        vec![
+            self.user.clone(),
+            self.superuser.to_string(),
+            list_cell(&self.groups),
+            list_cell(&self.databases),
+            list_cell(&self.schemas),
+            list_cell(&self.tables),
+        ]
+    }
+}
+
+/// A group membership or the privileges on an object of a user of the
+/// cluster, as printed by `inspect --format csv`
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct GrantRow {
+    pub user: String,
+    pub superuser: bool,
+    /// `group`, `database`, `schema` or `table`, empty for a user without
+    /// any
+    pub kind: String,
+    /// The group, or the object named as in the catalog
+    pub object: String,
+    pub privileges: Vec<String>,
+}
+
+impl Row for GrantRow {
+    const HEADERS: &'static [&'static str] = &["User", "Super", "Type", "Object", "Privileges"];
+
+    fn cells(&self) -> Vec<String> {
+        vec![
+            self.user.clone(),
+            self.superuser.to_string(),
+            self.kind.clone(),
+            self.object.clone(),
+            self.privileges.join(" "),
+        ]
+    }
+}
+
+/// A difference between the cluster and its config, as reported by
+/// `inspect --diff`
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Drift {
+    /// The user, empty for a missing group
+    pub user: String,
+    /// e.g. `missing grant`, `extra grant` or `unknown user`
+    pub kind: String,
+    /// The object of the privileges or the group, as in the catalog
+    pub object: String,
+    pub privileges: Vec<String>,
+}
+
+impl Row for Drift {
+    const HEADERS: &'static [&'static str] = &["User", "Drift", "Object", "Privileges"];
+
+    fn cells(&self) -> Vec<String> {
+        vec![
+            self.user.clone(),
+            self.kind.clone(),
+            self.object.clone(),
+            self.privileges.join(", "),
+        ]
+    }
+}
+
+/// A user of the config with its roles, as listed by `users list`
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct UserRoles {
+    pub user: String,
+    pub roles: Vec<String>,
+}
+
+impl Row for UserRoles {
+    const HEADERS: &'static [&'static str] = &["User", "Roles"];
+
+    fn cells(&self) -> Vec<String> {
+        vec![self.user.clone(), self.roles.join(", ")]
+    }
+}
+
+/// A role of the config, as listed by `roles list`
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct RoleGrants {
+    pub role: String,
+    /// The level of the role, e.g. `table`
+    pub level: String,
+    pub grants: Vec<String>,
+    /// The objects of the role, e.g. `public.orders, -public.secrets`
+    pub objects: String,
+}
+
+impl Row for RoleGrants {
+    const HEADERS: &'static [&'static str] = &["Role", "Type", "Grants", "Objects"];
+
+    fn cells(&self) -> Vec<String> {
+        vec![
+            self.role.clone(),
+            self.level.clone(),
+            self.grants.join(", "),
+            self.objects.clone(),
+        ]
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn report() -> Report<PrivilegeStatus> {
+        let mut report = Report::new();
+        for (grantee, status) in [
+            ("duyet", "updated"),
+            ("app", "unchanged"),
+            ("bi", "updated"),
+        ] {
+            report.push(PrivilegeStatus {
+                grantee: grantee.to_string(),
+                role: "role_read".to_string(),
+                detail: "table[\"ALL\"]".to_string(),
+                status: status.to_string(),
+                sql: String::new(),
+            });
+        }
+
+        report
+    }
+
+    #[test]
+    fn test_sort_by() {
+        let mut report = report();
+        report.sort_by("user").unwrap();
+        assert_eq!(
+            report
+                .rows
+                .iter()
+                .map(|r| &r.grantee[..])
+                .collect::<Vec<_>>(),
+            ["app", "bi", "duyet"]
+        );
+
+        // Stable
+        report.sort_by("Status").unwrap();
+        assert_eq!(
+            report
+                .rows
+                .iter()
+                .map(|r| &r.grantee[..])
+                .collect::<Vec<_>>(),
+            ["app", "bi", "duyet"]
+        );
+
+        assert_eq!(
+            report.sort_by("Object").unwrap_err().to_string(),
+            "unknown column Object, expected one of: User, Role Name, Detail, Status"
+        );
+    }
+
+    #[test]
+    fn test_totals() {
+        assert_eq!(
+            report().totals("Status").unwrap(),
+            BTreeMap::from([("unchanged".to_string(), 1), ("updated".to_string(), 2)])
+        );
+    }
+
+    #[test]
+    fn test_to_table() {
+        let mut report = Report::new();
+        report.push(UserPrivileges {
+            user: "duyet".to_string(),
+            superuser: false,
+            groups: Some(vec!["analysts".to_string(), "bi".to_string()]),
+            databases: Some(vec![]),
+            schemas: None,
+            tables: Some(vec!["public.orders(S)".to_string()]),
+        });
+
+        let table = report.to_table();
+        assert_eq!(table.headers, UserPrivileges::HEADERS);
+        assert_eq!(
+            table.rows,
+            vec![vec![
+                "duyet",
+                "false",
+                "analysts, bi",
+                "",
+                UNAVAILABLE,
+                "public.orders(S)"
+            ]]
+        );
+    }
+}
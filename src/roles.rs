@@ -0,0 +1,345 @@
+use crate::config::edit::ConfigEditor;
+use crate::config::ident;
+use crate::config::role::{
+    RoleDatabaseLevel, RoleFunctionLevel, RoleSchemaLevel, RoleSequenceLevel, RoleTableLevel,
+    RoleWarehouseLevel,
+};
+use crate::config::{Config, Role, RoleLevelType};
+use crate::render::OutputFormat;
+use crate::report::{Report, RoleGrants};
+use anyhow::{anyhow, Result};
+use log::info;
+use std::path::Path;
+
+/// Placeholder for the user in the SQL printed by [show]
+const USER_PLACEHOLDER: &str = "<user>";
+
+/// Arguments of `grant roles add`
+#[derive(Debug, Clone, Default)]
+pub struct NewRole {
+    pub name: String,
+    pub grants: Vec<String>,
+    pub databases: Vec<String>,
+    pub schemas: Vec<String>,
+    pub tables: Vec<String>,
+    pub functions: Vec<String>,
+    pub procedures: Vec<String>,
+    pub sequences: Vec<String>,
+    pub warehouses: Vec<String>,
+}
+
+impl NewRole {
+    /// Build the role of the given type. Arguments the type does not use are
+    /// rejected, rather than silently dropped.
+    pub fn build(self, level: RoleLevelType) -> Result<Role> {
+        let unused = match level {
+            RoleLevelType::Database => vec![
+                ("--schemas", &self.schemas),
+                ("--tables", &self.tables),
+                ("--functions", &self.functions),
+                ("--procedures", &self.procedures),
+                ("--sequences", &self.sequences),
+                ("--warehouses", &self.warehouses),
+            ],
+            RoleLevelType::Schema => vec![
+                ("--databases", &self.databases),
+                ("--tables", &self.tables),
+                ("--functions", &self.functions),
+                ("--procedures", &self.procedures),
+                ("--sequences", &self.sequences),
+                ("--warehouses", &self.warehouses),
+            ],
+            RoleLevelType::Table => vec![
+                ("--databases", &self.databases),
+                ("--functions", &self.functions),
+                ("--procedures", &self.procedures),
+                ("--sequences", &self.sequences),
+                ("--warehouses", &self.warehouses),
+            ],
+            RoleLevelType::Function => vec![
+                ("--databases", &self.databases),
+                ("--tables", &self.tables),
+                ("--sequences", &self.sequences),
+                ("--warehouses", &self.warehouses),
+            ],
+            RoleLevelType::Sequence => vec![
+                ("--databases", &self.databases),
+                ("--tables", &self.tables),
+                ("--functions", &self.functions),
+                ("--procedures", &self.procedures),
+                ("--warehouses", &self.warehouses),
+            ],
+            RoleLevelType::Warehouse => vec![
+                ("--databases", &self.databases),
+                ("--schemas", &self.schemas),
+                ("--tables", &self.tables),
+                ("--functions", &self.functions),
+                ("--procedures", &self.procedures),
+                ("--sequences", &self.sequences),
+            ],
+        };
+        if let Some((arg, _)) = unused.iter().find(|(_, values)| !values.is_empty()) {
+            return Err(anyhow!("{} is not used by {} roles", arg, level));
+        }
+
+        let grants = self.grants.iter().map(|g| g.to_uppercase()).collect();
+        let role = match level {
+            RoleLevelType::Database => Role::Database(RoleDatabaseLevel {
+                name: self.name,
+                grants,
+                databases: self.databases,
+            }),
+            RoleLevelType::Schema => Role::Schema(RoleSchemaLevel {
+                name: self.name,
+                grants,
+                schemas: self.schemas,
+                database: None,
+            }),
+            RoleLevelType::Table => Role::Table(RoleTableLevel {
+                name: self.name,
+                grants,
+                schemas: self.schemas,
+                tables: self.tables,
+                database: None,
+            }),
+            RoleLevelType::Function => Role::Function(RoleFunctionLevel {
+                name: self.name,
+                grants,
+                schemas: self.schemas,
+                functions: self.functions,
+                procedures: self.procedures,
+                database: None,
+            }),
+            RoleLevelType::Sequence => Role::Sequence(RoleSequenceLevel {
+                name: self.name,
+                grants,
+                schemas: self.schemas,
+                sequences: self.sequences,
+                database: None,
+            }),
+            RoleLevelType::Warehouse => Role::Warehouse(RoleWarehouseLevel {
+                name: self.name,
+                grants,
+                warehouses: self.warehouses,
+            }),
+        };
+        role.validate()?;
+
+        Ok(role)
+    }
+}
+
+/// Print the roles of the config file
+pub fn list(file: &Path, output: OutputFormat) -> Result<()> {
+    let config = Config::new(file)?;
+
+    let mut report = Report::new();
+    for role in &config.roles {
+        report.push(RoleGrants {
+            role: role.get_name(),
+            level: role.get_level().to_string(),
+            grants: role.get_grants(),
+            objects: objects(role),
+        });
+    }
+
+    println!("{}", output.renderer().render(&report.to_table()));
+
+    Ok(())
+}
+
+/// Print the role as in the config file, its users and the SQL it renders to
+pub fn show(file: &Path, name: &str) -> Result<()> {
+    let config = Config::new(file)?;
+    let editor = ConfigEditor::open(file)?;
+
+    let role = config
+        .roles
+        .iter()
+        .find(|r| r.get_name() == name)
+        .ok_or_else(|| anyhow!("role {} not found in {}", name, file.display()))?;
+    let source = editor.get("roles", name).unwrap_or_default();
+
+    let users = config
+        .users
+        .iter()
+        .filter(|u| u.roles.iter().any(|r| r == name))
+        .map(|u| u.name.as_str())
+        .collect::<Vec<_>>();
+
+    println!("{}\n", source);
+    println!("-- users: {}", users.join(", "));
+    println!("{}", sql_for_any_user(role));
+
+    Ok(())
+}
+
+/// Add a role to the config file
+pub fn add(file: &Path, role: &Role) -> Result<()> {
+    let config = Config::new(file)?;
+    let name = role.get_name();
+    if config.roles.iter().any(|r| r.get_name() == name) {
+        return Err(anyhow!(
+            "role {} already exists in {}",
+            name,
+            file.display()
+        ));
+    }
+
+    let mut editor = ConfigEditor::open(file)?;
+    editor.add("roles", &role.to_yaml()?)?;
+    editor.save(file)?;
+
+    info!("Added role {} to {}", name, file.display());
+    info!("{}", sql_for_any_user(role));
+
+    Ok(())
+}
+
+/// Remove a role from the config file, it must not be used by any user
+pub fn remove(file: &Path, name: &str) -> Result<()> {
+    let config = Config::new(file)?;
+    if !config.roles.iter().any(|r| r.get_name() == name) {
+        return Err(anyhow!("role {} not found in {}", name, file.display()));
+    }
+
+    let users = users_of(&config, name);
+    if !users.is_empty() {
+        return Err(anyhow!(
+            "role {} is used by users: {}",
+            name,
+            users.join(", ")
+        ));
+    }
+
+    let mut editor = ConfigEditor::open(file)?;
+    editor.remove("roles", name)?;
+    editor.save(file)?;
+
+    info!("Removed role {} from {}", name, file.display());
+
+    Ok(())
+}
+
+/// SQL of the role with the user placeholder, which is not a valid name
+/// and would be quoted otherwise
+fn sql_for_any_user(role: &Role) -> String {
+    role.to_sql(USER_PLACEHOLDER)
+        .replace(&ident::quote(USER_PLACEHOLDER), USER_PLACEHOLDER)
+}
+
+/// Users having the role, including the ones excluding it with `-role`
+fn users_of(config: &Config, role_name: &str) -> Vec<String> {
+    config
+        .users
+        .iter()
+        .filter(|u| {
+            u.roles
+                .iter()
+                .any(|r| r.strip_prefix('-').unwrap_or(r) == role_name)
+        })
+        .map(|u| u.name.clone())
+        .collect()
+}
+
+/// Objects of the role, e.g. `public.ALL`
+fn objects(role: &Role) -> String {
+    match role {
+        Role::Database(role) => role.databases.join(", "),
+        Role::Warehouse(role) => role.warehouses.join(", "),
+        Role::Schema(role) => role.schemas.join(", "),
+        Role::Table(role) => role
+            .schemas
+            .iter()
+            .flat_map(|s| role.tables.iter().map(move |t| format!("{}.{}", s, t)))
+            .collect::<Vec<_>>()
+            .join(", "),
+        Role::Sequence(role) => role
+            .schemas
+            .iter()
+            .flat_map(|s| role.sequences.iter().map(move |q| format!("{}.{}", s, q)))
+            .collect::<Vec<_>>()
+            .join(", "),
+        Role::Function(role) => role
+            .objects()
+            .into_iter()
+            .map(|(revoke, object)| {
+                if revoke {
+                    format!("-{}", object)
+                } else {
+                    object
+                }
+            })
+            .collect::<Vec<_>>()
+            .join(", "),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_build_role() {
+        let role = NewRole {
+            name: "read_marts".to_string(),
+            grants: vec!["select".to_string()],
+            schemas: vec!["marts".to_string()],
+            tables: vec!["ALL".to_string()],
+            ..Default::default()
+        }
+        .build(RoleLevelType::Table)
+        .unwrap();
+
+        assert_eq!(
+            role.to_sql("duyet"),
+            "GRANT SELECT ON ALL TABLES IN SCHEMA marts TO duyet;"
+        );
+    }
+
+    #[test]
+    fn test_build_function_role() {
+        let role = NewRole {
+            name: "run_etl".to_string(),
+            grants: vec!["execute".to_string()],
+            schemas: vec!["etl".to_string()],
+            procedures: vec!["load(date, text)".to_string()],
+            ..Default::default()
+        }
+        .build(RoleLevelType::Function)
+        .unwrap();
+
+        assert_eq!(
+            role.to_sql("duyet"),
+            "GRANT EXECUTE ON PROCEDURE etl.load(date, text) TO duyet;"
+        );
+        assert_eq!(objects(&role), "PROCEDURE etl.load(date, text)");
+    }
+
+    #[test]
+    fn test_build_role_unused_argument() {
+        let err = NewRole {
+            name: "read_db".to_string(),
+            grants: vec!["CREATE".to_string()],
+            databases: vec!["postgres".to_string()],
+            tables: vec!["ALL".to_string()],
+            ..Default::default()
+        }
+        .build(RoleLevelType::Database)
+        .unwrap_err();
+
+        assert_eq!(err.to_string(), "--tables is not used by database roles");
+    }
+
+    #[test]
+    fn test_build_role_invalid() {
+        let err = NewRole {
+            name: "read_marts".to_string(),
+            grants: vec!["SELECT".to_string()],
+            ..Default::default()
+        }
+        .build(RoleLevelType::Table);
+
+        assert!(err.is_err());
+    }
+}
@@ -0,0 +1,445 @@
+//! AWS Secrets Manager, `aws-sm:<secret-id>[#<key>]`, and SSM Parameter
+//! Store, `ssm:<name>`.
+//!
+//! The secret id is its name or its ARN. The key is a key of a JSON secret,
+//! e.g. the `password` of the secrets managed by RDS and Redshift; the whole
+//! secret string is the value without it. The parameters are decrypted, the
+//! `SecureString` ones too.
+//!
+//! The requests are signed (Signature Version 4) with the credentials of
+//! `AWS_ACCESS_KEY_ID`, `AWS_SECRET_ACCESS_KEY` and `AWS_SESSION_TOKEN`, or of
+//! the profile `AWS_PROFILE` (`default`) of `~/.aws/credentials`, in the
+//! region of the ARN, or else `AWS_REGION` or `AWS_DEFAULT_REGION`.
+//! `AWS_ENDPOINT_URL_SECRETS_MANAGER`, `AWS_ENDPOINT_URL_SSM` and
+//! `AWS_ENDPOINT_URL` override the endpoints, e.g. for a VPC endpoint.
+//!
+//! These are the only credentials: the instance profiles, the task roles of
+//! ECS, the web identity tokens, SSO and `credential_process` are not
+//! supported. The responses are neither logged nor put in the errors, only
+//! the type and message of an AWS error.
+
+use super::var;
+use anyhow::{anyhow, Context, Result};
+use jiff::Timestamp;
+use log::debug;
+use reqwest::blocking::Client;
+use reqwest::{StatusCode, Url};
+use ring::{digest, hmac};
+use serde_json::{json, Value};
+use std::collections::BTreeMap;
+use std::env;
+use std::fmt;
+use std::fs;
+use std::path::PathBuf;
+
+/// Content type of the JSON protocol of the AWS APIs
+const CONTENT_TYPE: &str = "application/x-amz-json-1.1";
+
+/// An AWS API holding secrets
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Service {
+    SecretsManager,
+    Ssm,
+}
+
+impl Service {
+    /// The name of the service in the endpoints and the signatures
+    fn name(&self) -> &'static str {
+        match self {
+            Service::SecretsManager => "secretsmanager",
+            Service::Ssm => "ssm",
+        }
+    }
+
+    fn endpoint_var(&self) -> &'static str {
+        match self {
+            Service::SecretsManager => "AWS_ENDPOINT_URL_SECRETS_MANAGER",
+            Service::Ssm => "AWS_ENDPOINT_URL_SSM",
+        }
+    }
+}
+
+impl fmt::Display for Service {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Service::SecretsManager => write!(f, "AWS Secrets Manager"),
+            Service::Ssm => write!(f, "SSM Parameter Store"),
+        }
+    }
+}
+
+/// The credentials signing the requests
+#[derive(Debug, PartialEq, Eq)]
+struct Credentials {
+    access_key_id: String,
+    secret_access_key: String,
+    session_token: Option<String>,
+}
+
+impl Credentials {
+    /// The credentials of the environment, or else of the profile of the
+    /// shared credentials file
+    fn load() -> Result<Self> {
+        if let (Some(access_key_id), Some(secret_access_key)) =
+            (var("AWS_ACCESS_KEY_ID"), var("AWS_SECRET_ACCESS_KEY"))
+        {
+            return Ok(Credentials {
+                access_key_id,
+                secret_access_key,
+                session_token: var("AWS_SESSION_TOKEN"),
+            });
+        }
+
+        let profile = var("AWS_PROFILE").unwrap_or_else(|| "default".to_string());
+        let file = var("AWS_SHARED_CREDENTIALS_FILE")
+            .map(PathBuf::from)
+            .or_else(|| {
+                env::var_os("HOME").map(|home| PathBuf::from(home).join(".aws/credentials"))
+            });
+        file.and_then(|file| fs::read_to_string(file).ok())
+            .and_then(|content| profile_credentials(&content, &profile))
+            .ok_or_else(|| {
+                anyhow!(
+                    "no AWS credentials: set AWS_ACCESS_KEY_ID and AWS_SECRET_ACCESS_KEY, or the profile {} of ~/.aws/credentials (instance profiles, web identity, SSO and credential_process are not supported)",
+                    profile
+                )
+            })
+    }
+}
+
+/// The credentials of a profile of a shared credentials file
+fn profile_credentials(content: &str, profile: &str) -> Option<Credentials> {
+    let mut section = None;
+    let mut values = BTreeMap::new();
+    for line in content.lines().map(str::trim) {
+        if line.is_empty() || line.starts_with('#') || line.starts_with(';') {
+            continue;
+        }
+        if let Some(name) = line.strip_prefix('[').and_then(|l| l.strip_suffix(']')) {
+            section = Some(name.trim());
+            continue;
+        }
+        if section == Some(profile) {
+            if let Some((key, value)) = line.split_once('=') {
+                values.insert(key.trim(), value.trim().to_string());
+            }
+        }
+    }
+
+    Some(Credentials {
+        access_key_id: values.remove("aws_access_key_id")?,
+        secret_access_key: values.remove("aws_secret_access_key")?,
+        session_token: values.remove("aws_session_token"),
+    })
+}
+
+/// The `SecretString` of a secret of Secrets Manager
+pub fn get_secret_value(id: &str) -> Result<String> {
+    let body = call(
+        Service::SecretsManager,
+        arn_region(id),
+        json!({ "SecretId": id }),
+    )?;
+
+    body["SecretString"]
+        .as_str()
+        .map(str::to_string)
+        .ok_or_else(|| anyhow!("the secret has no SecretString, binary secrets are not supported"))
+}
+
+/// The decrypted value of a parameter of Parameter Store
+pub fn get_parameter(name: &str) -> Result<String> {
+    let body = call(
+        Service::Ssm,
+        arn_region(name),
+        json!({ "Name": name, "WithDecryption": true }),
+    )?;
+
+    body["Parameter"]["Value"]
+        .as_str()
+        .map(str::to_string)
+        .ok_or_else(|| anyhow!("the parameter has no value"))
+}
+
+/// The region of an ARN, `arn:aws:secretsmanager:<region>:...`
+fn arn_region(id: &str) -> Option<&str> {
+    id.strip_prefix("arn:")?
+        .split(':')
+        .nth(2)
+        .filter(|region| !region.is_empty())
+}
+
+/// Call an action of the JSON protocol, `GetSecretValue` of Secrets Manager
+/// or `GetParameter` of SSM
+fn call(service: Service, region: Option<&str>, body: Value) -> Result<Value> {
+    let region = match region {
+        Some(region) => region.to_string(),
+        None => var("AWS_REGION")
+            .or_else(|| var("AWS_DEFAULT_REGION"))
+            .ok_or_else(|| anyhow!("AWS_REGION is not set"))?,
+    };
+    let endpoint = var(service.endpoint_var())
+        .or_else(|| var("AWS_ENDPOINT_URL"))
+        .unwrap_or_else(|| format!("https://{}.{}.amazonaws.com", service.name(), region));
+    let url = Url::parse(&endpoint).with_context(|| format!("invalid endpoint {}", endpoint))?;
+    let host = match url.port() {
+        Some(port) => format!("{}:{}", url.host_str().unwrap_or_default(), port),
+        None => url.host_str().unwrap_or_default().to_string(),
+    };
+    let target = match service {
+        Service::SecretsManager => "secretsmanager.GetSecretValue",
+        Service::Ssm => "AmazonSSM.GetParameter",
+    };
+
+    let credentials = Credentials::load()?;
+    let amz_date = Timestamp::now().strftime("%Y%m%dT%H%M%SZ").to_string();
+    let mut headers = vec![
+        ("content-type", CONTENT_TYPE.to_string()),
+        ("host", host),
+        ("x-amz-date", amz_date),
+    ];
+    if let Some(token) = &credentials.session_token {
+        headers.push(("x-amz-security-token", token.clone()));
+    }
+    headers.push(("x-amz-target", target.to_string()));
+
+    let payload = body.to_string();
+    let authorization = authorization(
+        &credentials,
+        &region,
+        service.name(),
+        url.path(),
+        &headers,
+        &payload,
+    );
+
+    debug!("{}: {} {}", service, target, payload);
+    let mut request = Client::new()
+        .post(url)
+        .header("Authorization", authorization)
+        .body(payload);
+    for (name, value) in headers.iter().filter(|(name, _)| *name != "host") {
+        request = request.header(*name, value);
+    }
+    let response = request
+        .send()
+        .with_context(|| format!("failed to reach {} at {}", service, endpoint))?;
+
+    let status = response.status();
+    let text = response.text().unwrap_or_default();
+    let body = serde_json::from_str::<Value>(&text).unwrap_or(Value::Null);
+    if !status.is_success() {
+        return Err(api_error(service, status, &body));
+    }
+
+    Ok(body)
+}
+
+/// The error of a failed call, from the type and the message of the AWS
+/// error only: the body of a response which is not an AWS error, e.g. of a
+/// proxy, is left out
+fn api_error(service: Service, status: StatusCode, body: &Value) -> anyhow::Error {
+    // e.g. `com.amazonaws.secretsmanager#ResourceNotFoundException`
+    let kind = body["__type"].as_str().unwrap_or_default();
+    let kind = kind.rsplit('#').next().unwrap_or(kind);
+    let message = body["message"]
+        .as_str()
+        .or(body["Message"].as_str())
+        .unwrap_or_default();
+
+    match kind {
+        "ResourceNotFoundException" | "ParameterNotFound" => {
+            anyhow!("not found in {}: {}", service, message)
+        }
+        "" => anyhow!("{} {}", status, service),
+        _ => anyhow!(
+            "{} {}: {}",
+            status,
+            service,
+            format!("{} {}", kind, message).trim()
+        ),
+    }
+}
+
+/// The `Authorization` header of Signature Version 4 of a POST. The headers
+/// are signed, sorted by their lower case names.
+fn authorization(
+    credentials: &Credentials,
+    region: &str,
+    service: &str,
+    path: &str,
+    headers: &[(&str, String)],
+    payload: &str,
+) -> String {
+    let amz_date = headers
+        .iter()
+        .find(|(name, _)| *name == "x-amz-date")
+        .map(|(_, value)| value.as_str())
+        .unwrap_or_default();
+    let date = amz_date.get(..8).unwrap_or_default();
+
+    let canonical_headers = headers
+        .iter()
+        .map(|(name, value)| format!("{}:{}\n", name, value.trim()))
+        .collect::<String>();
+    let signed_headers = headers
+        .iter()
+        .map(|(name, _)| *name)
+        .collect::<Vec<_>>()
+        .join(";");
+    let canonical_request = format!(
+        "POST\n{}\n\n{}\n{}\n{}",
+        if path.is_empty() { "/" } else { path },
+        canonical_headers,
+        signed_headers,
+        hex(digest::digest(&digest::SHA256, payload.as_bytes()).as_ref())
+    );
+
+    let scope = format!("{}/{}/{}/aws4_request", date, region, service);
+    let string_to_sign = format!(
+        "AWS4-HMAC-SHA256\n{}\n{}\n{}",
+        amz_date,
+        scope,
+        hex(digest::digest(&digest::SHA256, canonical_request.as_bytes()).as_ref())
+    );
+    let key = hmac::Key::new(
+        hmac::HMAC_SHA256,
+        &signing_key(&credentials.secret_access_key, date, region, service),
+    );
+    let signature = hex(hmac::sign(&key, string_to_sign.as_bytes()).as_ref());
+
+    format!(
+        "AWS4-HMAC-SHA256 Credential={}/{}, SignedHeaders={}, Signature={}",
+        credentials.access_key_id, scope, signed_headers, signature
+    )
+}
+
+/// The key of the signatures of a day, region and service
+fn signing_key(secret_access_key: &str, date: &str, region: &str, service: &str) -> Vec<u8> {
+    let sign = |key: &[u8], data: &str| {
+        hmac::sign(&hmac::Key::new(hmac::HMAC_SHA256, key), data.as_bytes())
+    };
+
+    let key = sign(format!("AWS4{}", secret_access_key).as_bytes(), date);
+    let key = sign(key.as_ref(), region);
+    let key = sign(key.as_ref(), service);
+    let key = sign(key.as_ref(), "aws4_request");
+
+    key.as_ref().to_vec()
+}
+
+fn hex(bytes: &[u8]) -> String {
+    bytes.iter().map(|b| format!("{:02x}", b)).collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_signing_key() {
+        // The example of the AWS documentation of Signature Version 4
+        let key = signing_key(
+            "wJalrXUtnFEMI/K7MDENG+bPxRfiCYEXAMPLEKEY",
+            "20120215",
+            "us-east-1",
+            "iam",
+        );
+
+        assert_eq!(
+            hex(&key),
+            "f4780e2d9f65fa895f9c67b32ce1baf0b0d8a43505a000a1a9e090d414db404d"
+        );
+    }
+
+    #[test]
+    fn test_authorization() {
+        let credentials = Credentials {
+            access_key_id: "AKIDEXAMPLE".to_string(),
+            secret_access_key: "wJalrXUtnFEMI/K7MDENG+bPxRfiCYEXAMPLEKEY".to_string(),
+            session_token: None,
+        };
+        let headers = vec![
+            ("content-type", CONTENT_TYPE.to_string()),
+            ("host", "ssm.eu-west-1.amazonaws.com".to_string()),
+            ("x-amz-date", "20240102T030405Z".to_string()),
+            ("x-amz-target", "AmazonSSM.GetParameter".to_string()),
+        ];
+        let authorization = authorization(
+            &credentials,
+            "eu-west-1",
+            "ssm",
+            "/",
+            &headers,
+            r#"{"Name":"/db"}"#,
+        );
+
+        assert!(authorization.starts_with(
+            "AWS4-HMAC-SHA256 Credential=AKIDEXAMPLE/20240102/eu-west-1/ssm/aws4_request, \
+             SignedHeaders=content-type;host;x-amz-date;x-amz-target, Signature="
+        ));
+        // The signature depends on the payload
+        assert_ne!(
+            authorization,
+            super::authorization(&credentials, "eu-west-1", "ssm", "/", &headers, "{}")
+        );
+    }
+
+    #[test]
+    fn test_profile_credentials() {
+        let content = "[default]\naws_access_key_id = A\naws_secret_access_key = B\n\n# ci\n[ci]\naws_access_key_id=C\naws_secret_access_key=D\naws_session_token=E\n";
+
+        assert_eq!(
+            profile_credentials(content, "ci"),
+            Some(Credentials {
+                access_key_id: "C".to_string(),
+                secret_access_key: "D".to_string(),
+                session_token: Some("E".to_string()),
+            })
+        );
+        assert_eq!(
+            profile_credentials(content, "default")
+                .unwrap()
+                .access_key_id,
+            "A"
+        );
+        assert!(profile_credentials(content, "prod").is_none());
+    }
+
+    #[test]
+    fn test_api_error() {
+        let error = |status, body: &str| {
+            let body = serde_json::from_str::<Value>(body).unwrap_or(Value::Null);
+            api_error(Service::SecretsManager, status, &body).to_string()
+        };
+
+        assert_eq!(
+            error(
+                StatusCode::BAD_REQUEST,
+                r#"{"__type":"com.amazonaws.secretsmanager#ResourceNotFoundException","message":"Secrets Manager can't find the specified secret."}"#
+            ),
+            "not found in AWS Secrets Manager: Secrets Manager can't find the specified secret."
+        );
+        assert_eq!(
+            error(
+                StatusCode::FORBIDDEN,
+                r#"{"__type":"AccessDeniedException","Message":"denied"}"#
+            ),
+            "403 Forbidden AWS Secrets Manager: AccessDeniedException denied"
+        );
+        // The body of a response which is not an AWS error is left out
+        assert_eq!(
+            error(StatusCode::BAD_GATEWAY, "<html>s3cr3t</html>"),
+            "502 Bad Gateway AWS Secrets Manager"
+        );
+    }
+
+    #[test]
+    fn test_arn_region() {
+        assert_eq!(
+            arn_region("arn:aws:secretsmanager:eu-west-1:123456789012:secret:prod/redshift-AbCdEf"),
+            Some("eu-west-1")
+        );
+        assert_eq!(arn_region("prod/redshift"), None);
+    }
+}
@@ -0,0 +1,274 @@
+//! Secrets backends: the passwords of the users and the connection url read
+//! at runtime, so that no plaintext password lives in the config repository.
+//!
+//! A value can be a reference to a secret:
+//!
+//! - `vault:<path>#<key>`, the key of a secret of HashiCorp Vault, see
+//!   [vault]
+//! - `aws-sm:<secret-id>[#<key>]`, a secret of AWS Secrets Manager or a key
+//!   of its JSON, see [aws]
+//! - `ssm:<name>`, a parameter of AWS SSM Parameter Store, see [aws]
+//!
+//! ```yaml
+//! connection:
+//!   type: postgres
+//!   url: ssm:/prod/redshift/url
+//! users:
+//!   - name: analytics
+//!     password: vault:secret/data/redshift#analytics_password
+//!   - name: etl
+//!     password: aws-sm:prod/redshift/etl#password
+//! ```
+//!
+//! The references are resolved when the config is loaded, so that a missing
+//! secret fails before any statement. A secret is read once per run. The
+//! passwords keep their references in the config: the values are only put
+//! in the executed statements, as the encrypted passwords, see
+//! [crate::encrypt], and the logs and dry runs keep the references.
+
+pub mod aws;
+pub mod vault;
+
+use anyhow::{anyhow, Context, Result};
+use serde_json::Value;
+use std::collections::BTreeMap;
+use std::env;
+use std::fmt;
+use std::str::FromStr;
+use std::sync::Mutex;
+use vault::Vault;
+
+/// Prefix of a reference to a secret of Vault
+pub const VAULT_PREFIX: &str = "vault:";
+
+/// Prefix of a reference to a secret of AWS Secrets Manager
+pub const SECRETS_MANAGER_PREFIX: &str = "aws-sm:";
+
+/// Prefix of a reference to a parameter of AWS SSM Parameter Store
+pub const SSM_PREFIX: &str = "ssm:";
+
+/// The secrets read, by reference without its key
+static SECRETS: Mutex<BTreeMap<String, Value>> = Mutex::new(BTreeMap::new());
+
+/// A reference to a secret, see [resolve]
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Reference {
+    /// `vault:<path>#<key>`
+    Vault { path: String, key: String },
+    /// `aws-sm:<secret-id>[#<key>]`
+    SecretsManager { id: String, key: Option<String> },
+    /// `ssm:<name>`
+    Ssm { name: String },
+}
+
+impl FromStr for Reference {
+    type Err = anyhow::Error;
+
+    fn from_str(value: &str) -> Result<Self> {
+        if let Some(rest) = value.strip_prefix(VAULT_PREFIX) {
+            let (path, key) = rest
+                .split_once('#')
+                .map(|(path, key)| (path.trim_matches('/'), key))
+                .filter(|(path, key)| !path.is_empty() && !key.is_empty())
+                .ok_or_else(|| {
+                    anyhow!(
+                        "invalid secret reference {}, expected vault:<path>#<key>",
+                        value
+                    )
+                })?;
+
+            return Ok(Reference::Vault {
+                path: path.to_string(),
+                key: key.to_string(),
+            });
+        }
+
+        if let Some(rest) = value.strip_prefix(SECRETS_MANAGER_PREFIX) {
+            let (id, key) = match rest.split_once('#') {
+                Some((id, key)) => (id, Some(key)),
+                None => (rest, None),
+            };
+            if id.is_empty() || key == Some("") {
+                return Err(anyhow!(
+                    "invalid secret reference {}, expected aws-sm:<secret-id>[#<key>]",
+                    value
+                ));
+            }
+
+            return Ok(Reference::SecretsManager {
+                id: id.to_string(),
+                key: key.map(str::to_string),
+            });
+        }
+
+        if let Some(name) = value.strip_prefix(SSM_PREFIX) {
+            if name.is_empty() {
+                return Err(anyhow!(
+                    "invalid secret reference {}, expected ssm:<name>",
+                    value
+                ));
+            }
+
+            return Ok(Reference::Ssm {
+                name: name.to_string(),
+            });
+        }
+
+        Err(anyhow!("not a secret reference: {}", value))
+    }
+}
+
+impl fmt::Display for Reference {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Reference::Vault { path, key } => write!(f, "{}{}#{}", VAULT_PREFIX, path, key),
+            Reference::SecretsManager { id, key: None } => {
+                write!(f, "{}{}", SECRETS_MANAGER_PREFIX, id)
+            }
+            Reference::SecretsManager { id, key: Some(key) } => {
+                write!(f, "{}{}#{}", SECRETS_MANAGER_PREFIX, id, key)
+            }
+            Reference::Ssm { name } => write!(f, "{}{}", SSM_PREFIX, name),
+        }
+    }
+}
+
+/// Whether a value is a reference to a secret
+pub fn is_reference(value: &str) -> bool {
+    [VAULT_PREFIX, SECRETS_MANAGER_PREFIX, SSM_PREFIX]
+        .iter()
+        .any(|prefix| value.starts_with(prefix))
+}
+
+/// The value, read from its secrets backend if it is a reference
+pub fn resolve(value: &str) -> Result<String> {
+    if !is_reference(value) {
+        return Ok(value.to_string());
+    }
+
+    let reference = Reference::from_str(value)?;
+    match &reference {
+        Reference::Vault { path, key } => {
+            let secret = cached(&format!("{}{}", VAULT_PREFIX, path), || {
+                Vault::from_env()?.read(path)
+            });
+            secret.and_then(|secret| field(&secret, key))
+        }
+        Reference::SecretsManager { id, key } => {
+            let secret = cached(&format!("{}{}", SECRETS_MANAGER_PREFIX, id), || {
+                aws::get_secret_value(id).map(Value::String)
+            });
+            secret.and_then(|secret| match key {
+                Some(key) => serde_json::from_str::<Value>(secret.as_str().unwrap_or_default())
+                    .context("the secret is not JSON")
+                    .and_then(|secret| string(secret.get(key), key)),
+                None => string(Some(&secret), "the secret"),
+            })
+        }
+        Reference::Ssm { name } => {
+            let parameter = cached(value, || aws::get_parameter(name).map(Value::String));
+            parameter.and_then(|parameter| string(Some(&parameter), "the parameter"))
+        }
+    }
+    .with_context(|| format!("failed to read {}", reference))
+}
+
+/// The secret of the cache, read on the first use. The lock is held while
+/// reading, so that concurrent users read it once.
+fn cached(id: &str, read: impl FnOnce() -> Result<Value>) -> Result<Value> {
+    let mut secrets = SECRETS.lock().unwrap_or_else(|e| e.into_inner());
+    if let Some(secret) = secrets.get(id) {
+        return Ok(secret.clone());
+    }
+
+    let secret = read()?;
+    secrets.insert(id.to_string(), secret.clone());
+
+    Ok(secret)
+}
+
+/// The value of a key of the `data` of a secret of Vault: its `data` for a
+/// KV version 2 engine
+fn field(data: &Value, key: &str) -> Result<String> {
+    let value = data
+        .get("data")
+        .filter(|data| data.is_object())
+        .and_then(|data| data.get(key))
+        .or_else(|| data.get(key));
+
+    string(value, key)
+}
+
+/// A scalar value as a string, `name` names it in the errors
+fn string(value: Option<&Value>, name: &str) -> Result<String> {
+    match value {
+        Some(Value::String(value)) => Ok(value.clone()),
+        Some(value @ (Value::Number(_) | Value::Bool(_))) => Ok(value.to_string()),
+        Some(_) => Err(anyhow!("the value of {} is not a string", name)),
+        None => Err(anyhow!("no key {}", name)),
+    }
+}
+
+/// A non empty environment variable
+fn var(name: &str) -> Option<String> {
+    env::var(name).ok().filter(|value| !value.is_empty())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    #[test]
+    fn test_reference() {
+        assert_eq!(
+            Reference::from_str("vault:secret/data/redshift#analytics_password").unwrap(),
+            Reference::Vault {
+                path: "secret/data/redshift".to_string(),
+                key: "analytics_password".to_string(),
+            }
+        );
+        assert_eq!(
+            Reference::from_str("vault:/secret/pg/#url")
+                .unwrap()
+                .to_string(),
+            "vault:secret/pg#url"
+        );
+        assert!(Reference::from_str("vault:secret/data/redshift").is_err());
+        assert!(Reference::from_str("vault:#key").is_err());
+
+        assert_eq!(
+            Reference::from_str("aws-sm:prod/redshift#password").unwrap(),
+            Reference::SecretsManager {
+                id: "prod/redshift".to_string(),
+                key: Some("password".to_string()),
+            }
+        );
+        let arn = "aws-sm:arn:aws:secretsmanager:eu-west-1:123456789012:secret:prod-AbCdEf";
+        assert_eq!(Reference::from_str(arn).unwrap().to_string(), arn);
+        assert!(Reference::from_str("aws-sm:prod/redshift#").is_err());
+        assert_eq!(
+            Reference::from_str("ssm:/prod/redshift/url").unwrap(),
+            Reference::Ssm {
+                name: "/prod/redshift/url".to_string()
+            }
+        );
+        assert!(Reference::from_str("ssm:").is_err());
+
+        assert!(is_reference("vault:secret/pg#url"));
+        assert!(is_reference("ssm:/prod/redshift/url"));
+        assert!(!is_reference("postgres://localhost:5432/postgres"));
+        assert_eq!(resolve("plain").unwrap(), "plain");
+    }
+
+    #[test]
+    fn test_field() {
+        let v2 = json!({"data": {"password": "s3cret", "port": 5439}, "metadata": {}});
+        assert_eq!(field(&v2, "password").unwrap(), "s3cret");
+        assert_eq!(field(&v2, "port").unwrap(), "5439");
+
+        let v1 = json!({"password": "s3cret"});
+        assert_eq!(field(&v1, "password").unwrap(), "s3cret");
+        assert!(field(&v1, "missing").is_err());
+    }
+}
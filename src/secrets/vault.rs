@@ -0,0 +1,170 @@
+//! HashiCorp Vault, `vault:<path>#<key>`.
+//!
+//! The path is the one of the HTTP API: `secret/data/...` for a KV version 2
+//! engine, `secret/...` for a version 1 one. Vault is reached at
+//! `VAULT_ADDR` with the first token of:
+//!
+//! - `VAULT_TOKEN`
+//! - an AppRole login with `VAULT_ROLE_ID` and `VAULT_SECRET_ID`, at
+//!   `VAULT_APPROLE_PATH` if AppRole is not mounted at `approle`
+//! - the token of `vault login`, in `~/.vault-token`
+//!
+//! `VAULT_NAMESPACE` is sent to Vault Enterprise.
+//!
+//! The responses are neither logged nor put in the errors, only the
+//! `errors` returned by Vault.
+
+use super::var;
+use anyhow::{anyhow, Context, Result};
+use log::debug;
+use reqwest::blocking::{Client, RequestBuilder};
+use reqwest::StatusCode;
+use serde_json::{json, Value};
+use std::env;
+use std::fs;
+use std::path::PathBuf;
+
+/// The HTTP API of Vault
+pub struct Vault {
+    client: Client,
+    addr: String,
+    namespace: Option<String>,
+    token: String,
+}
+
+impl Vault {
+    /// The Vault of `VAULT_ADDR`, logged in, see the module documentation
+    pub fn from_env() -> Result<Self> {
+        let addr = var("VAULT_ADDR").ok_or_else(|| anyhow!("VAULT_ADDR is not set"))?;
+        let mut vault = Vault {
+            client: Client::new(),
+            addr: addr.trim_end_matches('/').to_string(),
+            namespace: var("VAULT_NAMESPACE"),
+            token: String::new(),
+        };
+        vault.token = vault.login()?;
+
+        Ok(vault)
+    }
+
+    fn login(&self) -> Result<String> {
+        if let Some(token) = var("VAULT_TOKEN") {
+            return Ok(token);
+        }
+
+        if let (Some(role_id), Some(secret_id)) = (var("VAULT_ROLE_ID"), var("VAULT_SECRET_ID")) {
+            let mount = var("VAULT_APPROLE_PATH").unwrap_or_else(|| "approle".to_string());
+            debug!("vault: logging in with approle {}", mount);
+            let body = self.send(
+                self.client
+                    .post(self.url(&format!("auth/{}/login", mount.trim_matches('/'))))
+                    .header("Content-Type", "application/json")
+                    .body(json!({"role_id": role_id, "secret_id": secret_id}).to_string()),
+                "log in with approle",
+            )?;
+
+            return body["auth"]["client_token"]
+                .as_str()
+                .map(str::to_string)
+                .ok_or_else(|| anyhow!("no client_token in the approle login"));
+        }
+
+        let token_file = env::var_os("HOME").map(|home| PathBuf::from(home).join(".vault-token"));
+        if let Some(token) = token_file.and_then(|file| fs::read_to_string(file).ok()) {
+            if !token.trim().is_empty() {
+                return Ok(token.trim().to_string());
+            }
+        }
+
+        Err(anyhow!(
+            "no vault token: set VAULT_TOKEN, or VAULT_ROLE_ID and VAULT_SECRET_ID to log in with approle"
+        ))
+    }
+
+    /// The `data` of the secret at a path
+    pub fn read(&self, path: &str) -> Result<Value> {
+        debug!("vault: reading {}", path);
+        let mut body = self.send(
+            self.client
+                .get(self.url(path))
+                .header("X-Vault-Token", &self.token),
+            "read the secret",
+        )?;
+
+        match body.get_mut("data") {
+            Some(data) => Ok(data.take()),
+            None => Err(anyhow!("no data in the secret")),
+        }
+    }
+
+    fn url(&self, path: &str) -> String {
+        format!("{}/v1/{}", self.addr, path)
+    }
+
+    /// Send a request, the errors of Vault are in the body of the response
+    fn send(&self, request: RequestBuilder, what: &str) -> Result<Value> {
+        let request = match &self.namespace {
+            Some(namespace) => request.header("X-Vault-Namespace", namespace),
+            None => request,
+        };
+        let response = request
+            .send()
+            .with_context(|| format!("failed to reach vault at {}", self.addr))?;
+
+        let status = response.status();
+        let text = response.text().unwrap_or_default();
+        let body = serde_json::from_str::<Value>(&text).unwrap_or(Value::Null);
+        if !status.is_success() {
+            return Err(api_error(what, status, &body));
+        }
+
+        Ok(body)
+    }
+}
+
+/// The error of a failed request, from the `errors` of Vault only: the body
+/// of a response which is not a Vault error, e.g. of a proxy, is left out
+fn api_error(what: &str, status: StatusCode, body: &Value) -> anyhow::Error {
+    let errors = body["errors"]
+        .as_array()
+        .map(|errors| {
+            errors
+                .iter()
+                .filter_map(Value::as_str)
+                .collect::<Vec<_>>()
+                .join(", ")
+        })
+        .unwrap_or_default();
+
+    match errors.is_empty() {
+        true => anyhow!("failed to {}: {}", what, status),
+        false => anyhow!("failed to {}: {} {}", what, status, errors),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_api_error() {
+        let error = |status, body: &str| {
+            let body = serde_json::from_str::<Value>(body).unwrap_or(Value::Null);
+            api_error("read the secret", status, &body).to_string()
+        };
+
+        assert_eq!(
+            error(StatusCode::FORBIDDEN, r#"{"errors":["permission denied"]}"#),
+            "failed to read the secret: 403 Forbidden permission denied"
+        );
+        assert_eq!(
+            error(StatusCode::NOT_FOUND, r#"{"errors":[]}"#),
+            "failed to read the secret: 404 Not Found"
+        );
+        // The body of a response which is not a Vault error is left out
+        assert_eq!(
+            error(StatusCode::BAD_GATEWAY, "<html>s3cr3t</html>"),
+            "failed to read the secret: 502 Bad Gateway"
+        );
+    }
+}
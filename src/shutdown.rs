@@ -0,0 +1,101 @@
+use anyhow::Result;
+use log::warn;
+use std::collections::BTreeMap;
+use std::fmt;
+use std::sync::atomic::{AtomicBool, AtomicU64, Ordering};
+use std::sync::{Mutex, MutexGuard};
+
+/// Exit code of an interrupted run, as a shell would report for SIGINT
+pub const EXIT_CODE: i32 = 130;
+
+static INTERRUPTED: AtomicBool = AtomicBool::new(false);
+
+type Cancel = Box<dyn Fn() + Send>;
+
+/// The cancellations run on a force quit, by id of their [CancelGuard]
+static CANCELS: Mutex<BTreeMap<u64, Cancel>> = Mutex::new(BTreeMap::new());
+
+static NEXT_CANCEL: AtomicU64 = AtomicU64::new(0);
+
+/// Trap SIGINT and SIGTERM (Ctrl-C and Ctrl-Break on Windows).
+///
+/// The first signal only marks the run as interrupted: the statement in
+/// flight finishes and [is_interrupted] stops the next one. A second signal
+/// cancels the statements in flight, see [on_force_quit], and exits.
+pub fn install() -> Result<()> {
+    ctrlc::set_handler(|| {
+        if INTERRUPTED.swap(true, Ordering::SeqCst) {
+            force_quit();
+        }
+
+        warn!("Interrupted, stopping after the current statement (again to cancel it and quit)");
+    })?;
+
+    Ok(())
+}
+
+/// Run `cancel` if the run is force quit, until the guard is dropped, e.g.
+/// to cancel the statement in flight of a connection on the server instead
+/// of leaving it running there
+pub fn on_force_quit(cancel: impl Fn() + Send + 'static) -> CancelGuard {
+    let id = NEXT_CANCEL.fetch_add(1, Ordering::SeqCst);
+    cancels().insert(id, Box::new(cancel));
+
+    CancelGuard(id)
+}
+
+/// Registration of [on_force_quit], removed on drop
+#[derive(Debug)]
+pub struct CancelGuard(u64);
+
+impl Drop for CancelGuard {
+    fn drop(&mut self) {
+        cancels().remove(&self.0);
+    }
+}
+
+fn cancels() -> MutexGuard<'static, BTreeMap<u64, Cancel>> {
+    CANCELS.lock().unwrap_or_else(|e| e.into_inner())
+}
+
+fn force_quit() -> ! {
+    warn!("Cancelling the statements in flight");
+    for cancel in cancels().values() {
+        cancel();
+    }
+
+    std::process::exit(EXIT_CODE);
+}
+
+/// Returns true once a shutdown signal was received
+pub fn is_interrupted() -> bool {
+    INTERRUPTED.load(Ordering::SeqCst)
+}
+
+/// Error returned when a run stopped because of a shutdown signal,
+/// the binary exits with [EXIT_CODE] for it.
+#[derive(Debug)]
+pub struct Interrupted;
+
+impl fmt::Display for Interrupted {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "interrupted")
+    }
+}
+
+impl std::error::Error for Interrupted {}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_on_force_quit() {
+        let guard = on_force_quit(|| {});
+        assert!(cancels().contains_key(&guard.0));
+
+        let id = guard.0;
+        drop(guard);
+        assert!(!cancels().contains_key(&id));
+    }
+}
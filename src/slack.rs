@@ -0,0 +1,275 @@
+//! Slack notification of `apply`, for the configs with
+//! `notifications.slack`, see [Slack].
+//!
+//! The result of the apply is posted to the incoming webhook as a Block Kit
+//! message: its status and headline numbers, the users it changed, the
+//! error of a failed apply and a link to the CI run, see [ci_run_url]. The
+//! revokes are listed in a red attachment, the privileges taken away are
+//! the ones to review.
+
+use crate::config::notifications::Slack;
+use crate::notify;
+use anyhow::{anyhow, Context, Result};
+use log::info;
+use reqwest::blocking::Client;
+use serde_json::{json, Value};
+
+/// The color of the attachment of the revokes
+pub const REVOKE_COLOR: &str = "#E01E5A";
+
+/// Lines of a list in a message, the others are counted
+const MAX_LINES: usize = 20;
+
+/// Characters of a text block, Slack allows 3000
+const MAX_TEXT: usize = 2900;
+
+/// The result of an apply, as notified
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct Summary {
+    pub file: String,
+    /// `applied`, `rolled back` or `failed`
+    pub status: String,
+    /// The headline numbers of the apply, e.g. `42 users: 12 changed, ...`
+    pub headline: String,
+    pub changed_users: Vec<String>,
+    /// The revokes executed: the statements, the members removed from
+    /// groups and the users dropped
+    pub revokes: Vec<String>,
+    pub error: Option<String>,
+    pub run_url: Option<String>,
+}
+
+/// The url of the CI run applying the config: `GRANT_CI_RUN_URL`, or the
+/// one of GitHub Actions, GitLab CI, Buildkite or Jenkins
+pub fn ci_run_url() -> Option<String> {
+    let var = |name: &str| std::env::var(name).ok().filter(|v| !v.trim().is_empty());
+
+    if let Some(url) = var("GRANT_CI_RUN_URL") {
+        return Some(url);
+    }
+    if let (Some(server), Some(repository), Some(run)) = (
+        var("GITHUB_SERVER_URL"),
+        var("GITHUB_REPOSITORY"),
+        var("GITHUB_RUN_ID"),
+    ) {
+        return Some(format!("{}/{}/actions/runs/{}", server, repository, run));
+    }
+
+    ["CI_JOB_URL", "BUILDKITE_BUILD_URL", "BUILD_URL"]
+        .iter()
+        .find_map(|name| var(name))
+}
+
+/// The Block Kit message of an apply
+pub fn message(slack: &Slack, summary: &Summary) -> Value {
+    let icon = match summary.status.as_str() {
+        "applied" => ":white_check_mark:",
+        "rolled back" => ":leftwards_arrow_with_hook:",
+        _ => ":x:",
+    };
+    let title = format!("grant apply {}: {}", summary.file, summary.status);
+
+    let mut blocks = vec![
+        json!({
+            "type": "header",
+            "text": {"type": "plain_text", "text": truncate(&title, 150)},
+        }),
+        section(&format!("{} {}", icon, escape(&summary.headline))),
+    ];
+    if !summary.changed_users.is_empty() {
+        blocks.push(section(&format!(
+            "*Changed users*\n{}",
+            escape(&lines(&summary.changed_users).join(", "))
+        )));
+    }
+    if let Some(error) = &summary.error {
+        blocks.push(section(&format!("*Error*\n```{}```", escape(error))));
+    }
+    if let Some(url) = &summary.run_url {
+        blocks.push(json!({
+            "type": "context",
+            "elements": [{"type": "mrkdwn", "text": format!("<{}|CI run>", url)}],
+        }));
+    }
+
+    let mut message = json!({"text": title, "blocks": blocks});
+    if !summary.revokes.is_empty() {
+        message["attachments"] = json!([{
+            "color": REVOKE_COLOR,
+            "blocks": [section(&format!(
+                "*{} revoke(s)*\n```{}```",
+                summary.revokes.len(),
+                escape(&lines(&summary.revokes).join("\n"))
+            ))],
+        }]);
+    }
+    if let Some(channel) = &slack.channel {
+        message["channel"] = json!(channel);
+    }
+
+    message
+}
+
+/// Post the message of an apply to the webhook
+pub fn send(slack: &Slack, summary: &Summary) -> Result<()> {
+    // The url is the secret of the webhook, it is not in the errors
+    let url = notify::expand(&slack.webhook_url);
+    let response = Client::new()
+        .post(&url)
+        .header("Content-Type", "application/json")
+        .body(message(slack, summary).to_string())
+        .send()
+        .context("failed to notify Slack")?;
+
+    let status = response.status();
+    if !status.is_success() {
+        let text = response.text().unwrap_or_default();
+        return Err(anyhow!(
+            "failed to notify Slack: {} {}",
+            status,
+            text.trim()
+        ));
+    }
+    info!("Notified Slack");
+
+    Ok(())
+}
+
+fn section(text: &str) -> Value {
+    json!({
+        "type": "section",
+        "text": {"type": "mrkdwn", "text": truncate(text, MAX_TEXT)},
+    })
+}
+
+/// The first [MAX_LINES] of a list, then how many are left out
+fn lines(list: &[String]) -> Vec<String> {
+    let mut lines = list.iter().take(MAX_LINES).cloned().collect::<Vec<_>>();
+    if list.len() > MAX_LINES {
+        lines.push(format!("... and {} more", list.len() - MAX_LINES));
+    }
+
+    lines
+}
+
+/// At most `max` characters, the last one an ellipsis if truncated
+fn truncate(text: &str, max: usize) -> String {
+    match text.char_indices().nth(max - 1) {
+        Some((i, _)) if text.chars().count() > max => format!("{}…", &text[..i]),
+        _ => text.to_string(),
+    }
+}
+
+/// The control characters of Slack mrkdwn
+fn escape(text: &str) -> String {
+    text.replace('&', "&amp;")
+        .replace('<', "&lt;")
+        .replace('>', "&gt;")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn slack() -> Slack {
+        Slack {
+            webhook_url: "https://hooks.slack.com/services/T0/B0/x".to_string(),
+            channel: Some("#data-access".to_string()),
+            only_changes: false,
+        }
+    }
+
+    #[test]
+    fn test_message() {
+        let summary = Summary {
+            file: "prod.yaml".to_string(),
+            status: "applied".to_string(),
+            headline: "2 users: 1 changed, 1 unchanged".to_string(),
+            changed_users: vec!["duyet".to_string()],
+            revokes: vec!["REVOKE SELECT ON TABLE public.a FROM duyet;".to_string()],
+            error: None,
+            run_url: Some("https://github.com/acme/grants/actions/runs/42".to_string()),
+        };
+
+        assert_eq!(
+            message(&slack(), &summary),
+            json!({
+                "text": "grant apply prod.yaml: applied",
+                "blocks": [
+                    {
+                        "type": "header",
+                        "text": {"type": "plain_text", "text": "grant apply prod.yaml: applied"},
+                    },
+                    {
+                        "type": "section",
+                        "text": {
+                            "type": "mrkdwn",
+                            "text": ":white_check_mark: 2 users: 1 changed, 1 unchanged",
+                        },
+                    },
+                    {
+                        "type": "section",
+                        "text": {"type": "mrkdwn", "text": "*Changed users*\nduyet"},
+                    },
+                    {
+                        "type": "context",
+                        "elements": [{
+                            "type": "mrkdwn",
+                            "text": "<https://github.com/acme/grants/actions/runs/42|CI run>",
+                        }],
+                    },
+                ],
+                "attachments": [{
+                    "color": REVOKE_COLOR,
+                    "blocks": [{
+                        "type": "section",
+                        "text": {
+                            "type": "mrkdwn",
+                            "text": "*1 revoke(s)*\n```REVOKE SELECT ON TABLE public.a FROM duyet;```",
+                        },
+                    }],
+                }],
+                "channel": "#data-access",
+            })
+        );
+    }
+
+    #[test]
+    fn test_message_failed() {
+        let summary = Summary {
+            file: "prod.yaml".to_string(),
+            status: "failed".to_string(),
+            error: Some("permission denied for <schema>".to_string()),
+            ..Default::default()
+        };
+        let message = message(
+            &Slack {
+                channel: None,
+                ..slack()
+            },
+            &summary,
+        );
+
+        assert_eq!(
+            message["blocks"][2]["text"]["text"],
+            "*Error*\n```permission denied for &lt;schema&gt;```"
+        );
+        assert!(message["blocks"][1]["text"]["text"]
+            .as_str()
+            .unwrap()
+            .starts_with(":x:"));
+        assert!(message.get("attachments").is_none());
+        assert!(message.get("channel").is_none());
+    }
+
+    #[test]
+    fn test_lines() {
+        let list = (0..25).map(|i| i.to_string()).collect::<Vec<_>>();
+        let lines = lines(&list);
+        assert_eq!(lines.len(), MAX_LINES + 1);
+        assert_eq!(lines[MAX_LINES], "... and 5 more");
+
+        assert_eq!(truncate("abcdef", 4), "abc…");
+        assert_eq!(truncate("abcd", 4), "abcd");
+    }
+}
@@ -0,0 +1,196 @@
+use anyhow::{Context, Result};
+use md5::compute;
+use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
+use std::collections::BTreeMap;
+use std::fs;
+use std::path::{Path, PathBuf};
+
+mod cluster;
+
+pub use cluster::ClusterState;
+
+/// Local state of previous runs, stored as YAML in the configuration directory.
+///
+/// For example:
+///
+/// ```yaml
+/// files:
+///   team_a.yaml: 5d41402abc4b2a76b9719d911017c592
+///   team_b.yaml: 7d793037a0760186574b0282f2f435e7
+/// ```
+///
+/// `files` maps a config file name to the MD5 hash of its content at the
+/// last successful apply.
+///
+/// `checkpoint` is the progress of an unfinished apply, see [Checkpoint].
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq, Eq, Default)]
+pub struct State {
+    #[serde(default)]
+    pub files: BTreeMap<String, String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub checkpoint: Option<Checkpoint>,
+}
+
+/// Progress of an apply, written after each statement and removed once the
+/// apply is finished. An interrupted apply can continue from it with
+/// `grant apply --resume`.
+///
+/// ```yaml
+/// checkpoint:
+///   file: team_a.yaml
+///   hash: 5d41402abc4b2a76b9719d911017c592
+///   statement: 1200
+///   statement_hash: 7d793037a0760186574b0282f2f435e7
+/// ```
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq, Eq)]
+pub struct Checkpoint {
+    /// Config file name
+    pub file: String,
+    /// MD5 hash of the config file content
+    pub hash: String,
+    /// Number of statements already applied
+    pub statement: usize,
+    /// MD5 hash of the last applied statement
+    pub statement_hash: String,
+}
+
+impl State {
+    /// Name of the state file, without extension so that it is never picked
+    /// up as a configuration file.
+    pub const FILE_NAME: &'static str = ".grant-state";
+
+    /// Path to the state file in the given directory
+    pub fn path(dir: &Path) -> PathBuf {
+        dir.join(Self::FILE_NAME)
+    }
+
+    /// Load the state from the given directory, empty state if it does not exist yet
+    pub fn load(dir: &Path) -> Result<Self> {
+        let path = Self::path(dir);
+        if !path.exists() {
+            return Ok(Self::default());
+        }
+
+        let content = fs::read_to_string(&path)
+            .with_context(|| format!("failed to read state file {}", path.display()))?;
+        let state = serde_yaml::from_str(&content)
+            .with_context(|| format!("failed to parse state file {}", path.display()))?;
+
+        Ok(state)
+    }
+
+    /// Write the state into the given directory, an empty state removes the file
+    pub fn save(&self, dir: &Path) -> Result<()> {
+        let path = Self::path(dir);
+        if *self == Self::default() {
+            if path.exists() {
+                fs::remove_file(&path)
+                    .with_context(|| format!("failed to remove state file {}", path.display()))?;
+            }
+            return Ok(());
+        }
+
+        let content = serde_yaml::to_string(self)?;
+        fs::write(&path, content)
+            .with_context(|| format!("failed to write state file {}", path.display()))?;
+
+        Ok(())
+    }
+
+    /// Returns true if the file content hash is the same as the last successful apply
+    pub fn is_unchanged(&self, name: &str, hash: &str) -> bool {
+        self.files.get(name).map(|h| h == hash).unwrap_or(false)
+    }
+
+    /// Record the file content hash after a successful apply
+    pub fn record(&mut self, name: &str, hash: &str) {
+        self.files.insert(name.to_string(), hash.to_string());
+    }
+}
+
+/// MD5 hash of the file content, in hex
+pub fn hash_file(path: &Path) -> Result<String> {
+    let content = fs::read(path).with_context(|| format!("failed to read {}", path.display()))?;
+
+    Ok(hash_str(&content))
+}
+
+/// MD5 hash of a string or bytes, in hex
+pub fn hash_str<T: AsRef<[u8]>>(content: T) -> String {
+    format!("{:x}", compute(content))
+}
+
+/// The first 12 hex digits of the SHA-256 of a content, as printed by
+/// `sha256sum`, e.g. to name a version of a config file in the SQL comments
+pub fn short_sha<T: AsRef<[u8]>>(content: T) -> String {
+    let digest = Sha256::digest(content);
+
+    digest[..6].iter().map(|b| format!("{:02x}", b)).collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_state_load_not_exists() {
+        let dir = tempfile::tempdir().unwrap();
+        let state = State::load(dir.path()).unwrap();
+
+        assert_eq!(state, State::default());
+    }
+
+    #[test]
+    fn test_state_save_and_load() {
+        let dir = tempfile::tempdir().unwrap();
+
+        let mut state = State::default();
+        state.record("config.yaml", "abc");
+        state.save(dir.path()).unwrap();
+
+        let state = State::load(dir.path()).unwrap();
+        assert!(state.is_unchanged("config.yaml", "abc"));
+        assert!(!state.is_unchanged("config.yaml", "def"));
+        assert!(!state.is_unchanged("other.yaml", "abc"));
+    }
+
+    #[test]
+    fn test_state_checkpoint() {
+        let dir = tempfile::tempdir().unwrap();
+
+        let mut state = State {
+            checkpoint: Some(Checkpoint {
+                file: "config.yaml".to_string(),
+                hash: "abc".to_string(),
+                statement: 10,
+                statement_hash: hash_str("GRANT SELECT ON t TO u;"),
+            }),
+            ..Default::default()
+        };
+        state.save(dir.path()).unwrap();
+        assert_eq!(State::load(dir.path()).unwrap(), state);
+
+        // Clearing the checkpoint of an otherwise empty state removes the file
+        state.checkpoint = None;
+        state.save(dir.path()).unwrap();
+        assert!(!State::path(dir.path()).exists());
+    }
+
+    #[test]
+    fn test_short_sha() {
+        assert_eq!(short_sha("test"), "9f86d081884c");
+    }
+
+    #[test]
+    fn test_hash_file() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("config.yaml");
+        fs::write(&path, "test").unwrap();
+
+        assert_eq!(
+            hash_file(&path).unwrap(),
+            "098f6bcd4621d373cade4e832627b4f6"
+        );
+    }
+}
@@ -0,0 +1,286 @@
+use crate::catalog::Query;
+use crate::connection::{
+    Group, Object, ObjectOwner, User, UserDatabaseRole, UserSchemaRole, UserTableRole,
+};
+use std::collections::{BTreeMap, BTreeSet};
+use std::sync::Arc;
+
+/// Current state of a cluster: the users, groups and privileges read from
+/// the catalog, with the privileges indexed by user name.
+///
+/// It is read once by [crate::connection::DbConnection::get_cluster_state],
+/// then the commands comparing the config with the cluster only use the
+/// lookup methods. Names are as in the catalog, see [crate::config::ident::fold].
+#[derive(Debug, Default)]
+pub struct ClusterState {
+    /// Database of the connection
+    pub current_database: Option<String>,
+    /// Catalog queries which failed, their sections are empty
+    pub unavailable: Vec<Query>,
+    users: Vec<User>,
+    groups: Vec<Group>,
+    database_privileges: BTreeMap<Arc<str>, Vec<UserDatabaseRole>>,
+    schema_privileges: BTreeMap<Arc<str>, Vec<UserSchemaRole>>,
+    table_privileges: BTreeMap<Arc<str>, Vec<UserTableRole>>,
+    tables: BTreeSet<(Arc<str>, Arc<str>)>,
+    owners: BTreeMap<Object, String>,
+}
+
+impl ClusterState {
+    pub fn new(
+        users: Vec<User>,
+        groups: Vec<Group>,
+        database_privileges: Vec<UserDatabaseRole>,
+        schema_privileges: Vec<UserSchemaRole>,
+        table_privileges: Vec<UserTableRole>,
+    ) -> Self {
+        let tables = table_privileges
+            .iter()
+            .map(|p| (p.schema_name.clone(), p.table_name.clone()))
+            .collect();
+
+        ClusterState {
+            current_database: None,
+            unavailable: vec![],
+            users,
+            groups,
+            database_privileges: by_user(database_privileges, |p| &p.name),
+            schema_privileges: by_user(schema_privileges, |p| &p.name),
+            table_privileges: by_user(table_privileges, |p| &p.name),
+            tables,
+            owners: BTreeMap::new(),
+        }
+    }
+
+    /// Set the owners of the objects, see [ClusterState::owner_of]
+    pub fn set_owners(&mut self, owners: Vec<ObjectOwner>) {
+        self.owners = owners.into_iter().map(|o| (o.object, o.owner)).collect();
+    }
+
+    /// Name of the owner of the object, None if it is unknown
+    pub fn owner_of(&self, object: &Object) -> Option<&str> {
+        self.owners.get(object).map(String::as_str)
+    }
+
+    /// Whether the catalog query of a section succeeded
+    pub fn is_available(&self, query: Query) -> bool {
+        !self.unavailable.contains(&query)
+    }
+
+    pub fn users(&self) -> &[User] {
+        &self.users
+    }
+
+    pub fn user(&self, name: &str) -> Option<&User> {
+        self.users.iter().find(|u| u.name == name)
+    }
+
+    pub fn groups(&self) -> &[Group] {
+        &self.groups
+    }
+
+    /// Names of the groups the user is a member of
+    pub fn groups_of(&self, user: &str) -> Vec<&str> {
+        self.groups
+            .iter()
+            .filter(|g| g.members.iter().any(|m| m == user))
+            .map(|g| g.name.as_str())
+            .collect()
+    }
+
+    pub fn database_privileges_of(&self, user: &str) -> &[UserDatabaseRole] {
+        self.database_privileges
+            .get(user)
+            .map(Vec::as_slice)
+            .unwrap_or_default()
+    }
+
+    pub fn schema_privileges_of(&self, user: &str) -> &[UserSchemaRole] {
+        self.schema_privileges
+            .get(user)
+            .map(Vec::as_slice)
+            .unwrap_or_default()
+    }
+
+    pub fn table_privileges_of(&self, user: &str) -> &[UserTableRole] {
+        self.table_privileges
+            .get(user)
+            .map(Vec::as_slice)
+            .unwrap_or_default()
+    }
+
+    /// Whether the schema has at least one table
+    pub fn has_schema(&self, schema: &str) -> bool {
+        self.tables.iter().any(|(s, _)| &**s == schema)
+    }
+
+    pub fn has_table(&self, schema: &str, table: &str) -> bool {
+        self.tables
+            .iter()
+            .any(|(s, t)| &**s == schema && &**t == table)
+    }
+
+    /// Names of the tables of the schema, sorted
+    pub fn tables_of(&self, schema: &str) -> Vec<&str> {
+        self.tables
+            .iter()
+            .filter(|(s, _)| &**s == schema)
+            .map(|(_, t)| &**t)
+            .collect()
+    }
+
+    /// Whether the user has the privilege (`CREATE`, `TEMP` or `ALL`) on the database
+    pub fn has_database_priv(&self, user: &str, database: &str, privilege: &str) -> bool {
+        self.database_privileges_of(user)
+            .iter()
+            .find(|p| &*p.database_name == database)
+            .is_some_and(|p| {
+                has_privilege(privilege, &[("CREATE", p.has_create), ("TEMP", p.has_temp)])
+            })
+    }
+
+    /// Whether the user has the privilege (`CREATE`, `USAGE` or `ALL`) on the schema
+    pub fn has_schema_priv(&self, user: &str, schema: &str, privilege: &str) -> bool {
+        self.schema_privileges_of(user)
+            .iter()
+            .find(|p| &*p.schema_name == schema)
+            .is_some_and(|p| {
+                has_privilege(
+                    privilege,
+                    &[("CREATE", p.has_create), ("USAGE", p.has_usage)],
+                )
+            })
+    }
+
+    /// Whether the user has the privilege (`SELECT`, `INSERT`, `UPDATE`,
+    /// `DELETE`, `REFERENCES` or `ALL`) on the table
+    pub fn has_table_priv(&self, user: &str, schema: &str, table: &str, privilege: &str) -> bool {
+        self.table_privileges_of(user)
+            .iter()
+            .find(|p| &*p.schema_name == schema && &*p.table_name == table)
+            .is_some_and(|p| {
+                has_privilege(
+                    privilege,
+                    &[
+                        ("SELECT", p.has_select),
+                        ("INSERT", p.has_insert),
+                        ("UPDATE", p.has_update),
+                        ("DELETE", p.has_delete),
+                        ("REFERENCES", p.has_references),
+                    ],
+                )
+            })
+    }
+}
+
+/// Group the privileges by user, keeping their order
+fn by_user<T>(privileges: Vec<T>, name: impl Fn(&T) -> &Arc<str>) -> BTreeMap<Arc<str>, Vec<T>> {
+    let mut map: BTreeMap<Arc<str>, Vec<T>> = BTreeMap::new();
+    for p in privileges {
+        map.entry(name(&p).clone()).or_default().push(p);
+    }
+
+    map
+}
+
+/// Look up a privilege by name, `ALL` requires every privilege
+fn has_privilege(privilege: &str, privileges: &[(&str, bool)]) -> bool {
+    let privilege = privilege.to_uppercase();
+    if privilege == "ALL" {
+        return privileges.iter().all(|(_, granted)| *granted);
+    }
+
+    privileges
+        .iter()
+        .any(|(name, granted)| *name == privilege && *granted)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn user(name: &str) -> User {
+        User {
+            name: name.to_string(),
+            user_createdb: false,
+            user_super: false,
+            password: "********".to_string(),
+            attributes: Default::default(),
+            settings: Default::default(),
+        }
+    }
+
+    fn state() -> ClusterState {
+        let mut state = ClusterState::new(
+            vec![user("duyet"), user("postgres")],
+            vec![Group {
+                name: "analysts".to_string(),
+                members: vec!["duyet".to_string()],
+            }],
+            vec![UserDatabaseRole {
+                name: "duyet".into(),
+                database_name: "postgres".into(),
+                has_create: true,
+                has_temp: true,
+            }],
+            vec![UserSchemaRole {
+                name: "duyet".into(),
+                schema_name: "public".into(),
+                has_create: false,
+                has_usage: true,
+            }],
+            vec![UserTableRole {
+                name: "duyet".into(),
+                schema_name: "public".into(),
+                table_name: "orders".into(),
+                has_select: true,
+                has_insert: false,
+                has_update: false,
+                has_delete: false,
+                has_references: false,
+            }],
+        );
+        state.set_owners(vec![ObjectOwner {
+            object: Object::Table("public".to_string(), "orders".to_string()),
+            owner: "duyet".to_string(),
+        }]);
+
+        state
+    }
+
+    #[test]
+    fn test_cluster_state_lookup() {
+        let state = state();
+
+        assert!(state.user("duyet").is_some());
+        assert!(state.user("Duyet").is_none());
+        assert_eq!(state.groups_of("duyet"), vec!["analysts"]);
+        assert!(state.groups_of("postgres").is_empty());
+
+        assert_eq!(state.table_privileges_of("duyet").len(), 1);
+        assert!(state.table_privileges_of("postgres").is_empty());
+        assert!(state.has_schema("public"));
+        assert!(state.has_table("public", "orders"));
+        assert!(!state.has_table("public", "events"));
+        assert_eq!(state.tables_of("public"), vec!["orders"]);
+        assert!(state.tables_of("sales").is_empty());
+
+        let orders = Object::Table("public".to_string(), "orders".to_string());
+        assert_eq!(state.owner_of(&orders), Some("duyet"));
+        assert_eq!(state.owner_of(&Object::Schema("public".to_string())), None);
+    }
+
+    #[test]
+    fn test_cluster_state_has_priv() {
+        let state = state();
+
+        assert!(state.has_database_priv("duyet", "postgres", "ALL"));
+        assert!(state.has_schema_priv("duyet", "public", "usage"));
+        assert!(!state.has_schema_priv("duyet", "public", "CREATE"));
+        assert!(state.has_table_priv("duyet", "public", "orders", "SELECT"));
+        assert!(!state.has_table_priv("duyet", "public", "orders", "INSERT"));
+        assert!(!state.has_table_priv("duyet", "public", "orders", "ALL"));
+        assert!(!state.has_table_priv("postgres", "public", "orders", "SELECT"));
+        assert!(!state.has_table_priv("duyet", "public", "orders", "TRUNCATE"));
+    }
+}
@@ -0,0 +1,260 @@
+//! OpenTelemetry traces of `plan`, `apply` and `inspect`.
+//!
+//! The spans are exported with OTLP/HTTP when `OTEL_EXPORTER_OTLP_ENDPOINT`
+//! or `OTEL_EXPORTER_OTLP_TRACES_ENDPOINT` is set, e.g. to Tempo or Jaeger,
+//! with the other standard `OTEL_*` variables (headers, service name,
+//! resource attributes, ...). Otherwise they are no-ops.
+//!
+//! An apply has a span per phase, per user and per statement. Below them,
+//! each statement and catalog query sent on a connection has a span with the
+//! attributes of the database conventions, see [db_span]. The executed
+//! statements carry the `traceparent` of their span in a trailing comment,
+//! sqlcommenter style, to find the trace of a statement in the slow query log.
+
+use crate::audit::mask_passwords;
+use crate::config::Dialect;
+use log::warn;
+use opentelemetry::trace::{Status, TraceContextExt, Tracer};
+use opentelemetry::{global, Context, ContextGuard, KeyValue};
+use opentelemetry_otlp::SpanExporter;
+use opentelemetry_sdk::trace::SdkTracerProvider;
+use opentelemetry_sdk::Resource;
+use std::fmt::Display;
+
+/// Environment variables enabling the export, see the module doc
+pub const ENDPOINT_ENV: &[&str] = &[
+    "OTEL_EXPORTER_OTLP_ENDPOINT",
+    "OTEL_EXPORTER_OTLP_TRACES_ENDPOINT",
+];
+
+/// Installed exporter, the remaining spans are flushed by [Telemetry::shutdown]
+pub struct Telemetry(Option<SdkTracerProvider>);
+
+/// Install the OTLP exporter if one of [ENDPOINT_ENV] is set. Tracing
+/// never fails a command, an invalid exporter config is only logged.
+pub fn init() -> Telemetry {
+    if !ENDPOINT_ENV
+        .iter()
+        .any(|var| std::env::var_os(var).is_some())
+    {
+        return Telemetry(None);
+    }
+
+    let exporter = match SpanExporter::builder().with_http().build() {
+        Ok(exporter) => exporter,
+        Err(e) => {
+            warn!("Failed to set up the OTLP exporter: {}", e);
+            return Telemetry(None);
+        }
+    };
+    let mut resource = Resource::builder();
+    if std::env::var_os("OTEL_SERVICE_NAME").is_none() {
+        resource = resource.with_service_name("grant");
+    }
+
+    let provider = SdkTracerProvider::builder()
+        .with_batch_exporter(exporter)
+        .with_resource(resource.build())
+        .build();
+    global::set_tracer_provider(provider.clone());
+
+    Telemetry(Some(provider))
+}
+
+impl Telemetry {
+    /// Export the remaining spans
+    pub fn shutdown(self) {
+        if let Some(provider) = self.0 {
+            if let Err(e) = provider.shutdown() {
+                warn!("Failed to export the traces: {}", e);
+            }
+        }
+    }
+}
+
+/// A span, current until dropped: the spans started meanwhile are its children
+pub struct SpanGuard {
+    cx: Context,
+    /// None for a detached span, see [detached_span]
+    _guard: Option<ContextGuard>,
+}
+
+/// Start a span as child of the current one
+pub fn span(name: &'static str, attributes: Vec<KeyValue>) -> SpanGuard {
+    let tracer = global::tracer("grant");
+    let span = tracer
+        .span_builder(name)
+        .with_attributes(attributes)
+        .start(&tracer);
+
+    let cx = Context::current_with_span(span);
+    let guard = cx.clone().attach();
+
+    SpanGuard {
+        cx,
+        _guard: Some(guard),
+    }
+}
+
+/// Start a span as child of the current one, without making it current.
+///
+/// The futures polled together on a thread, e.g. the catalog queries of
+/// [DbConnection::get_cluster_state](crate::connection::DbConnection::get_cluster_state),
+/// would otherwise be children of each other's spans.
+pub fn detached_span(name: &'static str, attributes: Vec<KeyValue>) -> SpanGuard {
+    let tracer = global::tracer("grant");
+    let span = tracer
+        .span_builder(name)
+        .with_attributes(attributes)
+        .start(&tracer);
+
+    SpanGuard {
+        cx: Context::current_with_span(span),
+        _guard: None,
+    }
+}
+
+/// Start the detached span of a query sent to the database, see
+/// [db_attributes] and [SpanGuard::with_traceparent]
+pub fn db_span(
+    name: &'static str,
+    dialect: Dialect,
+    database: Option<&str>,
+    query: &str,
+) -> SpanGuard {
+    detached_span(name, db_attributes(dialect, database, query))
+}
+
+/// The attributes of a query of the OpenTelemetry database conventions:
+/// `db.system.name`, `db.namespace` and `db.query.text`, its passwords
+/// masked
+pub fn db_attributes(dialect: Dialect, database: Option<&str>, query: &str) -> Vec<KeyValue> {
+    let system = match dialect.is_redshift() {
+        true => "aws.redshift",
+        false => "postgresql",
+    };
+    let mut attributes = vec![
+        KeyValue::new("db.system.name", system),
+        KeyValue::new("db.query.text", mask_passwords(query)),
+    ];
+    if let Some(database) = database {
+        attributes.push(KeyValue::new("db.namespace", database.to_string()));
+    }
+
+    attributes
+}
+
+impl SpanGuard {
+    pub fn set_attribute(&self, attribute: KeyValue) {
+        self.cx.span().set_attribute(attribute);
+    }
+
+    /// Mark the span as failed
+    pub fn error(&self, error: impl Display) {
+        self.cx
+            .span()
+            .set_status(Status::error(format!("{:#}", error)));
+    }
+
+    /// Mark the span as failed if the result is an error
+    pub fn record<T, E: Display>(&self, result: Result<T, E>) -> Result<T, E> {
+        if let Err(e) = &result {
+            self.error(e);
+        }
+
+        result
+    }
+
+    /// The statement with the `traceparent` of the span, see
+    /// [with_traceparent]
+    pub fn with_traceparent(&self, sql: &str) -> String {
+        traceparent_of(&self.cx, sql)
+    }
+}
+
+impl Drop for SpanGuard {
+    fn drop(&mut self) {
+        self.cx.span().end();
+    }
+}
+
+/// `traceparent` of the current span, None if it is not recorded
+pub fn traceparent() -> Option<String> {
+    traceparent_in(&Context::current())
+}
+
+fn traceparent_in(cx: &Context) -> Option<String> {
+    let span = cx.span();
+    let span_context = span.span_context();
+    if !span_context.is_valid() {
+        return None;
+    }
+
+    Some(format!(
+        "00-{}-{}-{:02x}",
+        span_context.trace_id(),
+        span_context.span_id(),
+        span_context.trace_flags().to_u8()
+    ))
+}
+
+/// The statement with the `traceparent` of the current span in a trailing
+/// comment, as is if there is no recorded span
+pub fn with_traceparent(sql: &str) -> String {
+    traceparent_of(&Context::current(), sql)
+}
+
+fn traceparent_of(cx: &Context, sql: &str) -> String {
+    match traceparent_in(cx) {
+        Some(traceparent) => format!("{} /*traceparent='{}'*/", sql, traceparent),
+        None => sql.to_string(),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use opentelemetry::trace::{SpanContext, SpanId, TraceFlags, TraceId, TraceState};
+
+    #[test]
+    fn test_db_attributes() {
+        assert_eq!(
+            db_attributes(
+                Dialect::Redshift,
+                Some("dev"),
+                "ALTER USER duyet PASSWORD 's3cret';"
+            ),
+            vec![
+                KeyValue::new("db.system.name", "aws.redshift"),
+                KeyValue::new("db.query.text", "ALTER USER duyet PASSWORD '********';"),
+                KeyValue::new("db.namespace", "dev"),
+            ]
+        );
+        assert_eq!(
+            db_attributes(Dialect::Postgres, None, "SELECT 1")[0],
+            KeyValue::new("db.system.name", "postgresql")
+        );
+    }
+
+    #[test]
+    fn test_with_traceparent() {
+        // No exporter, the spans are not recorded
+        let _span = span("test", vec![]);
+        assert_eq!(with_traceparent("GRANT"), "GRANT");
+
+        let span_context = SpanContext::new(
+            TraceId::from_hex("4bf92f3577b34da6a3ce929d0e0e4736").unwrap(),
+            SpanId::from_hex("00f067aa0ba902b7").unwrap(),
+            TraceFlags::SAMPLED,
+            false,
+            TraceState::default(),
+        );
+        let _guard = Context::current()
+            .with_remote_span_context(span_context)
+            .attach();
+        assert_eq!(
+            with_traceparent("GRANT"),
+            "GRANT /*traceparent='00-4bf92f3577b34da6a3ce929d0e0e4736-00f067aa0ba902b7-01'*/"
+        );
+    }
+}
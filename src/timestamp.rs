@@ -0,0 +1,82 @@
+//! Timestamps of the logs and reports.
+//!
+//! They are ISO 8601 in the timezone of `--timezone`, UTC by default, e.g.
+//! `2026-10-16T13:12:28+02:00` with `--timezone Europe/Paris`, so that an
+//! apply can be matched with the audit logs of the database.
+
+use anyhow::{anyhow, Result};
+use jiff::tz::TimeZone;
+use jiff::Timestamp;
+use std::sync::OnceLock;
+
+static TIMEZONE: OnceLock<TimeZone> = OnceLock::new();
+
+/// Parse `--timezone`: `UTC`, `local` for the timezone of the system, or an
+/// IANA name such as `Europe/Paris`
+pub fn parse_timezone(name: &str) -> Result<TimeZone> {
+    match name {
+        "UTC" | "utc" | "Z" => Ok(TimeZone::UTC),
+        "local" => Ok(TimeZone::system()),
+        _ => TimeZone::get(name).map_err(|_| anyhow!("unknown timezone: {}", name)),
+    }
+}
+
+/// Set the timezone of the timestamps, once at startup
+pub fn set_timezone(timezone: TimeZone) {
+    let _ = TIMEZONE.set(timezone);
+}
+
+/// The timezone of the timestamps, UTC if not set
+pub fn timezone() -> TimeZone {
+    TIMEZONE.get().cloned().unwrap_or(TimeZone::UTC)
+}
+
+/// The current time, see [format]
+pub fn now() -> String {
+    format(Timestamp::now())
+}
+
+/// The timestamp in the timezone of `--timezone`, to the second
+pub fn format(timestamp: Timestamp) -> String {
+    format_in(timestamp, &timezone())
+}
+
+/// The timestamp in ISO 8601 to the second, with `Z` for UTC or the offset
+pub fn format_in(timestamp: Timestamp, timezone: &TimeZone) -> String {
+    let zoned = timestamp.to_zoned(timezone.clone());
+    if zoned.offset().seconds() == 0 {
+        zoned.strftime("%Y-%m-%dT%H:%M:%SZ").to_string()
+    } else {
+        zoned.strftime("%Y-%m-%dT%H:%M:%S%:z").to_string()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_format_in() {
+        let timestamp: Timestamp = "2026-10-16T11:12:28.345Z".parse().unwrap();
+
+        assert_eq!(format_in(timestamp, &TimeZone::UTC), "2026-10-16T11:12:28Z");
+        assert_eq!(
+            format_in(timestamp, &TimeZone::fixed(jiff::tz::offset(7))),
+            "2026-10-16T18:12:28+07:00"
+        );
+        assert_eq!(
+            format_in(timestamp, &TimeZone::fixed(jiff::tz::offset(-5))),
+            "2026-10-16T06:12:28-05:00"
+        );
+    }
+
+    #[test]
+    fn test_parse_timezone() {
+        assert_eq!(parse_timezone("UTC").unwrap(), TimeZone::UTC);
+        assert!(parse_timezone("local").is_ok());
+        assert_eq!(
+            parse_timezone("Mars/Olympus").unwrap_err().to_string(),
+            "unknown timezone: Mars/Olympus"
+        );
+    }
+}
@@ -0,0 +1,360 @@
+//! Event trigger granting the table roles of the config on new tables
+//! (Postgres).
+//!
+//! `GRANT ... ON ALL TABLES IN SCHEMA` only covers the tables which exist
+//! when it runs, and `ALTER DEFAULT PRIVILEGES` only the tables created by a
+//! given role. `grant event-trigger install` adds an event trigger running
+//! after each `CREATE TABLE`, which grants the privileges of the table roles
+//! matching the new table, whoever created it:
+//!
+//! ```sql
+//! IF obj.schema_name = 'public' AND tbl NOT IN ('secrets') THEN
+//!   EXECUTE 'GRANT SELECT ON ' || obj.object_identity || ' TO duyet';
+//! END IF;
+//! ```
+//!
+//! The rules are those of the config at install time, install again after
+//! changing it. `grant event-trigger remove` drops the trigger and its
+//! function. A failing grant is a warning, it never fails the `CREATE TABLE`.
+
+use crate::apply::privilege_statements;
+use crate::config::{ident, Config, Dialect, Role};
+use crate::connection::DbConnection;
+use anyhow::{anyhow, Result};
+use log::info;
+
+/// Name of the event trigger and of its function
+pub const NAME: &str = "grant_rs_on_create_table";
+
+/// Install the event trigger, the statements are printed and executed only
+/// with `apply`
+pub fn install(config: &Config, apply: bool) -> Result<()> {
+    run(config, &install_sql(config), apply)?;
+    if apply {
+        info!(
+            "Installed event trigger {} with {} rule(s)",
+            NAME,
+            rules(config).len()
+        );
+    }
+
+    Ok(())
+}
+
+/// Drop the event trigger and its function, the statements are printed and
+/// executed only with `apply`
+pub fn remove(config: &Config, apply: bool) -> Result<()> {
+    run(config, &remove_sql(), apply)?;
+    if apply {
+        info!("Removed event trigger {}", NAME);
+    }
+
+    Ok(())
+}
+
+fn run(config: &Config, statements: &[String], apply: bool) -> Result<()> {
+    if !apply {
+        for sql in statements {
+            println!("{};", sql);
+        }
+        info!("Run with --apply to execute the statements");
+        return Ok(());
+    }
+
+    let mut conn = DbConnection::new(config)?;
+    let dialect = conn.dialect();
+    if dialect.is_redshift() || dialect == Dialect::Greenplum {
+        return Err(anyhow!("event triggers are not supported by {}", dialect));
+    }
+
+    // The function body has semicolons, each statement is sent as a whole
+    for sql in statements {
+        conn.execute_batch(sql)?;
+    }
+
+    Ok(())
+}
+
+/// The statements installing the event trigger: its function, with an `IF`
+/// per rule, then the trigger
+pub fn install_sql(config: &Config) -> Vec<String> {
+    let rules = rules(config);
+    let mut body = vec![];
+    for rule in &rules {
+        body.push(format!("    -- {}", rule.comment));
+        body.push(format!("    IF {} THEN", rule.condition));
+        body.push("      BEGIN".to_string());
+        body.push(format!(
+            "        EXECUTE {} || obj.object_identity || {};",
+            literal(&format!("GRANT {} ON ", rule.grants)),
+            literal(&format!(" TO {}", rule.grantee)),
+        ));
+        body.push("      EXCEPTION WHEN OTHERS THEN".to_string());
+        body.push(format!(
+            "        RAISE WARNING '{}: %', SQLERRM;",
+            rule.comment.replace('\'', "''")
+        ));
+        body.push("      END;".to_string());
+        body.push("    END IF;".to_string());
+    }
+
+    let function = format!(
+        "CREATE OR REPLACE FUNCTION {name}() RETURNS event_trigger LANGUAGE plpgsql AS $grant_rs$
+DECLARE
+  obj record;
+  tbl text;
+BEGIN
+  FOR obj IN SELECT * FROM pg_event_trigger_ddl_commands() WHERE object_type = 'table' LOOP
+    SELECT c.relname INTO tbl FROM pg_catalog.pg_class c WHERE c.oid = obj.objid;
+{body}
+  END LOOP;
+END;
+$grant_rs$",
+        name = NAME,
+        body = body.join("\n"),
+    );
+
+    vec![
+        function,
+        format!("DROP EVENT TRIGGER IF EXISTS {}", NAME),
+        format!(
+            "CREATE EVENT TRIGGER {name} ON ddl_command_end \
+             WHEN TAG IN ('CREATE TABLE', 'CREATE TABLE AS', 'SELECT INTO') \
+             EXECUTE PROCEDURE {name}()",
+            name = NAME
+        ),
+    ]
+}
+
+/// The statements dropping the event trigger and its function
+pub fn remove_sql() -> Vec<String> {
+    vec![
+        format!("DROP EVENT TRIGGER IF EXISTS {}", NAME),
+        format!("DROP FUNCTION IF EXISTS {}()", NAME),
+    ]
+}
+
+/// A grant of a table role on the new tables matching `condition`
+#[derive(Debug, PartialEq, Eq)]
+struct Rule {
+    /// Grantee and role name, e.g. `duyet: role_table`
+    comment: String,
+    /// Condition on `obj.schema_name` and `tbl`, the new table name
+    condition: String,
+    grants: String,
+    grantee: String,
+}
+
+/// The rules of the table roles of the users and groups, in the config
+/// order: a rule per schema of `ALL` (without its `-` tables), or per schema
+/// of the `+` tables. `-ALL` has no rule.
+fn rules(config: &Config) -> Vec<Rule> {
+    let mut rules = vec![];
+
+    for (grantee, role_name, role, revoked, _) in privilege_statements(config) {
+        let role = match role {
+            Role::Table(role) if !revoked => role,
+            _ => continue,
+        };
+        let grants = if role.grants.iter().any(|g| g == "ALL") {
+            "ALL PRIVILEGES".to_string()
+        } else {
+            role.grants.join(", ")
+        };
+        let schemas = role
+            .schemas
+            .iter()
+            .map(|s| ident::fold(s))
+            .collect::<Vec<_>>();
+
+        // The tables of the entries, as (schema, table) in the catalog
+        let qualified = |name: &str| -> Vec<(String, String)> {
+            match ident::split_qualified(name).as_slice() {
+                [schema, table] => vec![(ident::fold(schema), ident::fold(table))],
+                _ => schemas
+                    .iter()
+                    .map(|s| (s.clone(), ident::fold(name)))
+                    .collect(),
+            }
+        };
+        let entries = role
+            .tables
+            .iter()
+            .map(|t| match t.strip_prefix('-') {
+                Some(name) => (true, name),
+                None => (false, t.strip_prefix('+').unwrap_or(t)),
+            })
+            .collect::<Vec<_>>();
+        let revoked = entries
+            .iter()
+            .filter(|(revoke, name)| *revoke && *name != "ALL")
+            .flat_map(|(_, name)| qualified(name))
+            .collect::<Vec<_>>();
+
+        let mut conditions = vec![];
+        match entries.iter().find(|(_, name)| *name == "ALL") {
+            Some((true, _)) => {}
+            Some((false, _)) => {
+                for schema in &schemas {
+                    let excluded = revoked
+                        .iter()
+                        .filter(|(s, _)| s == schema)
+                        .map(|(_, t)| t.as_str())
+                        .collect::<Vec<_>>();
+                    conditions.push(condition(schema, "NOT IN", &excluded));
+                }
+            }
+            None => {
+                let mut granted: Vec<(String, String)> = vec![];
+                for table in entries
+                    .iter()
+                    .filter(|(revoke, _)| !revoke)
+                    .flat_map(|(_, name)| qualified(name))
+                {
+                    if !revoked.contains(&table) && !granted.contains(&table) {
+                        granted.push(table);
+                    }
+                }
+
+                let mut granted_schemas: Vec<&str> = vec![];
+                for (schema, _) in &granted {
+                    if !granted_schemas.contains(&schema.as_str()) {
+                        granted_schemas.push(schema);
+                    }
+                }
+                for schema in granted_schemas {
+                    let tables = granted
+                        .iter()
+                        .filter(|(s, _)| s == schema)
+                        .map(|(_, t)| t.as_str())
+                        .collect::<Vec<_>>();
+                    conditions.push(condition(schema, "IN", &tables));
+                }
+            }
+        }
+
+        rules.extend(conditions.into_iter().map(|condition| Rule {
+            comment: format!("{}: {}", grantee, role_name),
+            condition,
+            grants: grants.clone(),
+            grantee: grantee.to_sql(),
+        }));
+    }
+
+    rules
+}
+
+/// Condition on the schema of the new table and its name, `tbl IN (...)` or
+/// `tbl NOT IN (...)`, the name is not checked for an empty `NOT IN`
+fn condition(schema: &str, op: &str, tables: &[&str]) -> String {
+    let schema = format!("obj.schema_name = {}", literal(schema));
+    if tables.is_empty() {
+        return schema;
+    }
+
+    let tables = tables
+        .iter()
+        .map(|t| literal(t))
+        .collect::<Vec<_>>()
+        .join(", ");
+
+    format!("{} AND tbl {} ({})", schema, op, tables)
+}
+
+/// A SQL string literal
+fn literal(value: &str) -> String {
+    format!("'{}'", value.replace('\'', "''"))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use indoc::indoc;
+    use std::str::FromStr;
+
+    #[test]
+    fn test_rules() {
+        let config = Config::from_str(indoc! {r#"
+            connection:
+              type: postgres
+              url: postgres://localhost:5432/postgres
+            roles:
+              - name: role_all
+                type: table
+                grants: [SELECT]
+                schemas: [public, "Sales Data"]
+                tables: [ALL, -secrets, "-\"Sales Data\".q'1"]
+              - name: role_tables
+                type: table
+                grants: [ALL]
+                schemas: [public]
+                tables: [orders, marts.events, -orders]
+              - name: role_none
+                type: table
+                grants: [SELECT]
+                schemas: [public]
+                tables: [-ALL]
+            users:
+              - name: duyet
+                roles: [role_all, role_tables, role_none]
+            groups:
+              - name: analysts
+                roles: [role_all]
+        "#})
+        .unwrap();
+
+        let rules = rules(&config)
+            .into_iter()
+            .map(|r| (r.comment, r.condition, r.grants, r.grantee))
+            .collect::<Vec<_>>();
+        let rule = |comment: &str, condition: &str, grants: &str, grantee: &str| {
+            (
+                comment.to_string(),
+                condition.to_string(),
+                grants.to_string(),
+                grantee.to_string(),
+            )
+        };
+        assert_eq!(
+            rules,
+            vec![
+                rule(
+                    "duyet: role_all",
+                    "obj.schema_name = 'public' AND tbl NOT IN ('secrets')",
+                    "SELECT",
+                    "duyet"
+                ),
+                rule(
+                    "duyet: role_all",
+                    "obj.schema_name = 'Sales Data' AND tbl NOT IN ('secrets', 'q''1')",
+                    "SELECT",
+                    "duyet"
+                ),
+                rule(
+                    "duyet: role_tables",
+                    "obj.schema_name = 'marts' AND tbl IN ('events')",
+                    "ALL PRIVILEGES",
+                    "duyet"
+                ),
+                rule(
+                    "group analysts: role_all",
+                    "obj.schema_name = 'public' AND tbl NOT IN ('secrets')",
+                    "SELECT",
+                    "GROUP analysts"
+                ),
+                rule(
+                    "group analysts: role_all",
+                    "obj.schema_name = 'Sales Data' AND tbl NOT IN ('secrets', 'q''1')",
+                    "SELECT",
+                    "GROUP analysts"
+                ),
+            ]
+        );
+
+        let sql = install_sql(&config);
+        assert_eq!(sql.len(), 3);
+        assert!(sql[0].contains(
+            "EXECUTE 'GRANT SELECT ON ' || obj.object_identity || ' TO GROUP analysts';"
+        ));
+    }
+}
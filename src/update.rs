@@ -0,0 +1,145 @@
+use crate::prompt::confirm;
+use anyhow::{anyhow, Context, Result};
+use log::info;
+use reqwest::header::{self, HeaderValue};
+use self_update::backends::github::Update;
+use self_update::update::ReleaseAsset;
+use self_update::{cargo_crate_version, self_replace, version, Download, Extract, TempDir};
+use sha2::{Digest, Sha256};
+use std::env::consts::EXE_SUFFIX;
+use std::fs;
+
+const REPO_OWNER: &str = "duyet";
+const REPO_NAME: &str = "grant-rs";
+const BIN_NAME: &str = "grant";
+
+/// Extension of the release archives of the current target
+const ARCHIVE_EXT: &str = if cfg!(windows) { ".zip" } else { ".tar.gz" };
+
+/// Replace the running binary with the latest GitHub release for the
+/// current target (e.g. `x86_64-unknown-linux-musl`).
+///
+/// The release archives are built by `.github/workflows/post-release.yaml`,
+/// named `grant-<version>-<target>.tar.gz` (`.zip` on Windows) with the
+/// binary at the root of the archive, next to the `.sha256` checksum of the
+/// archive. The archive is only extracted if it matches its checksum.
+pub fn self_update(no_confirm: bool) -> Result<()> {
+    let updater = Update::configure()
+        .repo_owner(REPO_OWNER)
+        .repo_name(REPO_NAME)
+        .bin_name(BIN_NAME)
+        .target(self_update::get_target())
+        .current_version(cargo_crate_version!())
+        .build()?;
+    let release = updater
+        .get_latest_release()
+        .context("failed to read the latest GitHub release")?;
+    let current_version = updater.current_version();
+    if !version::bump_is_greater(&current_version, &release.version)? {
+        info!("grant is already up to date ({})", current_version);
+        return Ok(());
+    }
+
+    let target = updater.target();
+    let archive = release
+        .assets
+        .iter()
+        .find(|asset| asset.name.contains(&target) && asset.name.ends_with(ARCHIVE_EXT))
+        .ok_or_else(|| {
+            anyhow!(
+                "the release {} has no archive for {}",
+                release.version,
+                target
+            )
+        })?;
+    let checksum_name = format!("{}.sha256", archive.name);
+    let checksum = release
+        .assets
+        .iter()
+        .find(|asset| asset.name == checksum_name)
+        .ok_or_else(|| anyhow!("the release {} has no {}", release.version, checksum_name))?;
+
+    if !no_confirm
+        && !confirm(
+            &format!(
+                "Replace grant {} with {} ({})?",
+                current_version, release.version, archive.name
+            ),
+            "--yes",
+        )?
+    {
+        return Err(anyhow!("update cancelled, grant is not changed"));
+    }
+
+    let dir = TempDir::new()?;
+    let archive_path = dir.path().join(&archive.name);
+    download(archive, fs::File::create(&archive_path)?)?;
+    let mut expected = vec![];
+    download(checksum, &mut expected)?;
+    verify_checksum(
+        &fs::read(&archive_path)?,
+        &String::from_utf8_lossy(&expected),
+    )
+    .with_context(|| format!("refusing to install {}", archive.name))?;
+
+    let bin = format!("{}{}", BIN_NAME, EXE_SUFFIX);
+    Extract::from_source(&archive_path)
+        .extract_file(dir.path(), &bin)
+        .with_context(|| format!("could not find {} in {}", bin, archive.name))?;
+    self_replace::self_replace(dir.path().join(&bin))?;
+    info!("grant is updated to {}", release.version);
+
+    Ok(())
+}
+
+/// Download an asset of a release through the GitHub API
+fn download(asset: &ReleaseAsset, dest: impl std::io::Write) -> Result<()> {
+    let mut download = Download::from_url(&asset.download_url);
+    download.set_header(
+        header::ACCEPT,
+        HeaderValue::from_static("application/octet-stream"),
+    );
+    download
+        .download_to(dest)
+        .with_context(|| format!("failed to download {}", asset.name))
+}
+
+/// Check the SHA-256 of an archive against its checksum file, the output of
+/// `sha256sum`: the hex digest, then the file name
+fn verify_checksum(archive: &[u8], checksum: &str) -> Result<()> {
+    let expected = checksum
+        .split_whitespace()
+        .next()
+        .ok_or_else(|| anyhow!("the checksum file is empty"))?;
+    let actual = format!("{:x}", Sha256::digest(archive));
+    if !expected.eq_ignore_ascii_case(&actual) {
+        return Err(anyhow!(
+            "checksum mismatch: expected {}, got {}",
+            expected,
+            actual
+        ));
+    }
+
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_verify_checksum() {
+        let checksum = "2cf24dba5fb0a30e26e83b2ac5b9e29e1b161e5c1fa7425e73043362938b9824  grant-0.1.0-x86_64-unknown-linux-musl.tar.gz\n";
+
+        assert!(verify_checksum(b"hello", checksum).is_ok());
+        assert!(verify_checksum(b"hello", &checksum.to_uppercase()).is_ok());
+        assert_eq!(
+            verify_checksum(b"hello!", checksum)
+                .unwrap_err()
+                .to_string(),
+            "checksum mismatch: expected 2cf24dba5fb0a30e26e83b2ac5b9e29e1b161e5c1fa7425e73043362938b9824, \
+             got ce06092fb948d9ffac7d1a376e404b26b7575bcc11ee05a4615fef4fec3a308b"
+        );
+        assert!(verify_checksum(b"hello", "").is_err());
+    }
+}
@@ -0,0 +1,256 @@
+use crate::config::user_mapping::Identity;
+use crate::config::{edit::ConfigEditor, ident, Config, User};
+use crate::render::OutputFormat;
+use crate::report::{Report, UserRoles};
+use anyhow::{anyhow, Context, Result};
+use log::info;
+use std::fs;
+use std::path::Path;
+
+/// Print the users of the config file with their roles
+pub fn list(file: &Path, output: OutputFormat) -> Result<()> {
+    let config = Config::new(file)?;
+
+    let mut report = Report::new();
+    for user in &config.users {
+        report.push(UserRoles {
+            user: user.name.clone(),
+            roles: user.roles.clone(),
+        });
+    }
+
+    println!("{}", output.renderer().render(&report.to_table()));
+
+    Ok(())
+}
+
+/// Print the user as in the config file, the SQL of its roles, and its
+/// effective privileges with the ones of its groups and of `PUBLIC`
+pub fn show(file: &Path, name: &str) -> Result<()> {
+    let config = Config::new(file)?;
+    let editor = ConfigEditor::open(file)?;
+
+    let user = config
+        .users
+        .iter()
+        .find(|u| u.name == name)
+        .ok_or_else(|| anyhow!("user {} not found in {}", name, file.display()))?;
+    let source = editor.get("users", name).unwrap_or_default();
+
+    println!("{}\n", source);
+    for role_name in &user.roles {
+        if let Some(role) = config.roles.iter().find(|r| r.find(role_name)) {
+            println!("-- {}\n{}", role_name, role.to_sql(&user.name));
+        }
+    }
+
+    let effective = config.effective_privileges(&user.name);
+    println!("\n-- Effective privileges");
+    for privilege in &effective.privileges {
+        println!("-- {}", privilege);
+    }
+
+    Ok(())
+}
+
+/// Add a user to the config file, the roles must exist in the file
+pub fn add(file: &Path, name: &str, roles: &[String], password: Option<String>) -> Result<()> {
+    let config = Config::new(file)?;
+    if config.users.iter().any(|u| u.name == name) {
+        return Err(anyhow!(
+            "user {} already exists in {}",
+            name,
+            file.display()
+        ));
+    }
+
+    let user = User {
+        name: name.to_string(),
+        password,
+        password_policy: None,
+        attributes: Default::default(),
+        settings: Default::default(),
+        roles: roles.to_vec(),
+        revokes: vec![],
+    };
+    user.validate()?;
+
+    let mut editor = ConfigEditor::open(file)?;
+    editor.add("users", &user)?;
+    editor.save(file)?;
+
+    info!("Added user {} to {}", name, file.display());
+
+    Ok(())
+}
+
+/// Remove a user from the config file.
+/// The user is not dropped from the database, apply does not drop users.
+pub fn remove(file: &Path, name: &str) -> Result<()> {
+    let config = Config::new(file)?;
+    if !config.users.iter().any(|u| u.name == name) {
+        return Err(anyhow!("user {} not found in {}", name, file.display()));
+    }
+
+    let mut editor = ConfigEditor::open(file)?;
+    editor.remove("users", name)?;
+    editor.save(file)?;
+
+    info!("Removed user {} from {}", name, file.display());
+
+    Ok(())
+}
+
+/// Add the identities of a directory export (CSV with a header, e.g. from
+/// LDAP or Okta) as users with the given roles. Their names are those of the
+/// `user_mapping` of the config, the users already in the config are kept.
+pub fn import(file: &Path, csv: &Path, roles: &[String], dryrun: bool) -> Result<()> {
+    let config = Config::new(file)?;
+    let mapping = config
+        .user_mapping
+        .as_ref()
+        .ok_or_else(|| anyhow!("user_mapping is not set in {}", file.display()))?;
+    for role in roles {
+        if !config.roles.iter().any(|r| r.find(role)) {
+            return Err(anyhow!("role {} is not available", role));
+        }
+    }
+
+    let content =
+        fs::read_to_string(csv).with_context(|| format!("failed to read {}", csv.display()))?;
+    let identities = parse_csv(&content).with_context(|| format!("invalid {}", csv.display()))?;
+    let names = mapping.usernames(&identities)?;
+
+    let mut editor = ConfigEditor::open(file)?;
+    let mut added = 0;
+    for name in names {
+        if config
+            .users
+            .iter()
+            .any(|u| ident::fold(&u.name) == ident::fold(&name))
+        {
+            info!("User {} is already in {}, skipped", name, file.display());
+            continue;
+        }
+
+        let user = User {
+            name,
+            password: None,
+            password_policy: None,
+            attributes: Default::default(),
+            settings: Default::default(),
+            roles: roles.to_vec(),
+            revokes: vec![],
+        };
+        user.validate()?;
+        info!(
+            "{} user {}",
+            if dryrun { "Would add" } else { "Add" },
+            user.name
+        );
+        editor.add("users", &user)?;
+        added += 1;
+    }
+
+    if !dryrun {
+        editor.save(file)?;
+    }
+    info!(
+        "{} {} user(s) of {} to {}",
+        if dryrun { "Would import" } else { "Imported" },
+        added,
+        csv.display(),
+        file.display()
+    );
+
+    Ok(())
+}
+
+/// The rows of a CSV file by the names of its header, lowercased. Fields may
+/// be double quoted, with `""` for a quote.
+fn parse_csv(content: &str) -> Result<Vec<Identity>> {
+    let mut records = vec![];
+    let mut record = vec![];
+    let mut field = String::new();
+    let mut in_quotes = false;
+    let mut chars = content.chars().peekable();
+
+    while let Some(c) = chars.next() {
+        match c {
+            '"' if in_quotes && chars.peek() == Some(&'"') => {
+                field.push('"');
+                chars.next();
+            }
+            '"' => in_quotes = !in_quotes,
+            ',' if !in_quotes => record.push(std::mem::take(&mut field)),
+            '\n' if !in_quotes => {
+                record.push(std::mem::take(&mut field));
+                records.push(std::mem::take(&mut record));
+            }
+            '\r' if !in_quotes => {}
+            c => field.push(c),
+        }
+    }
+    if in_quotes {
+        return Err(anyhow!("unclosed quote"));
+    }
+    if !field.is_empty() || !record.is_empty() {
+        record.push(field);
+        records.push(record);
+    }
+    records.retain(|r| r.iter().any(|f| !f.trim().is_empty()));
+
+    let mut records = records.into_iter();
+    let header = records
+        .next()
+        .ok_or_else(|| anyhow!("no header"))?
+        .iter()
+        .map(|h| h.trim().to_lowercase())
+        .collect::<Vec<_>>();
+
+    records
+        .enumerate()
+        .map(|(i, record)| {
+            if record.len() != header.len() {
+                return Err(anyhow!(
+                    "line {}: {} field(s), expected {}",
+                    i + 2,
+                    record.len(),
+                    header.len()
+                ));
+            }
+
+            Ok(header
+                .iter()
+                .cloned()
+                .zip(record.into_iter().map(|f| f.trim().to_string()))
+                .collect())
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_csv() {
+        let rows = parse_csv(
+            "Email,Team,Name\r\njane@corp.com,data,\"Doe, \"\"Jane\"\"\"\r\n\njohn@corp.com, ops ,John\n",
+        )
+        .unwrap();
+
+        assert_eq!(rows.len(), 2);
+        assert_eq!(rows[0]["email"], "jane@corp.com");
+        assert_eq!(rows[0]["name"], "Doe, \"Jane\"");
+        assert_eq!(rows[1]["team"], "ops");
+
+        assert_eq!(
+            parse_csv("email,team\njane@corp.com\n")
+                .unwrap_err()
+                .to_string(),
+            "line 2: 1 field(s), expected 2"
+        );
+        assert!(parse_csv("email\n\"jane").is_err());
+    }
+}
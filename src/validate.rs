@@ -1,11 +1,16 @@
-use crate::config::Config;
-use ansi_term::Colour::{Green, Red};
+use crate::config::{Config, LoadOptions};
+use crate::owners::Owners;
+use ansi_term::Colour::{Green, Red, Yellow};
 use anyhow::{anyhow, Result};
+use log::info;
 use std::path::{Path, PathBuf};
 use walkdir::WalkDir;
 
-/// Validate the target PathBuf
-pub fn validate_target(target: &Path) -> Result<()> {
+/// Validate the target PathBuf.
+///
+/// The files are also checked against the owners file, `owners` or the
+/// [crate::owners::OWNERS_FILE] found in the target directory or its parents.
+pub fn validate_target(target: &Path, options: &LoadOptions, owners: Option<&Path>) -> Result<()> {
     let target = PathBuf::from(target);
 
     if !target.exists() {
@@ -16,9 +21,24 @@ pub fn validate_target(target: &Path) -> Result<()> {
         ));
     }
 
+    let owners = match owners
+        .map(Path::to_path_buf)
+        .or_else(|| Owners::find(&target))
+    {
+        Some(file) => {
+            info!("Checking ownership with {}", file.display());
+            Some(Owners::load(&file)?)
+        }
+        None => None,
+    };
+
     // Scan all files recursive from current directory
     // that match *.yaml or *.yml and validate them
     if target.is_dir() {
+        if !options.overlays.is_empty() {
+            return Err(anyhow!("--overlay needs a config file, not a directory"));
+        }
+
         let mut files = vec![];
         for entry in WalkDir::new(target) {
             let entry = entry?;
@@ -33,29 +53,50 @@ pub fn validate_target(target: &Path) -> Result<()> {
             }
         }
 
+        // Validate every file before failing
+        let mut invalid = 0;
         for file in files {
-            // Validate but not panic
-            validate_file(&file).unwrap_or_else(|e| {
+            validate_file(&file, options, owners.as_ref()).unwrap_or_else(|e| {
                 println!("{}", e);
+                invalid += 1;
             });
         }
 
+        if invalid > 0 {
+            return Err(anyhow!("{} invalid file(s)", invalid));
+        }
+
         return Ok(());
     }
 
     // Validate single file
-    validate_file(&target)
+    validate_file(&target, options, owners.as_ref())
 }
 
-/// Validate target yaml file
-pub fn validate_file(file: &Path) -> Result<()> {
+/// Validate target yaml file, with its overlays merged over it, see
+/// [crate::config::overlay], and its variables overridden, see
+/// [crate::config::vars]
+pub fn validate_file(file: &Path, options: &LoadOptions, owners: Option<&Owners>) -> Result<()> {
     let file = PathBuf::from(file);
-    let value = Config::new(&file)
-        .map_err(|e| anyhow!("{:?} ... {} - {}", file, Red.paint("invalid"), e))?;
+    // A config per connection, see `connections`
+    let configs = Config::new_per_connection_with(&file, options)
+        .map_err(|e| anyhow!("{:?} ... {} - {:#}", file, Red.paint("invalid"), e))?;
 
-    value
-        .validate()
-        .map_err(|e| anyhow!("{:?} ... {} - {}", file, Red.paint("invalid"), e))?;
+    for (_, value) in configs {
+        value
+            .validate()
+            .map_err(|e| anyhow!("{:?} ... {} - {}", file, Red.paint("invalid"), e))?;
+
+        if let Some(owners) = owners {
+            owners
+                .check(&file, &value)
+                .map_err(|e| anyhow!("{:?} ... {} - {}", file, Red.paint("not allowed"), e))?;
+        }
+
+        for warning in value.lint() {
+            println!("{:?} ... {} - {}", file, Yellow.paint("warning"), warning);
+        }
+    }
 
     // "OK" in green color
     println!("{:?} ... {}", file, Green.paint("ok"));
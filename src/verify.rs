@@ -0,0 +1,233 @@
+//! `grant verify-passwords`: whether the passwords of the users of the config
+//! are still the ones of the cluster, e.g. after someone ran `ALTER USER ...
+//! PASSWORD` by hand.
+//!
+//! The hashes are read from `pg_shadow`, which only a superuser can read
+//! (`pg_user` masks them). A password of the config is compared as a hash:
+//! an MD5 or SCRAM-SHA-256 hash of the config must be the stored one, a
+//! plaintext (or decrypted, see [crate::encrypt]) password is hashed like
+//! the stored one, with its salt for SCRAM. Neither the passwords nor the
+//! hashes are printed:
+//!
+//! ```text
+//! +--------+--------------------------+
+//! | User   | Password                 |
+//! +--------+--------------------------+
+//! | duyet  | ok                       |
+//! | duyet2 | drifted                  |
+//! | duyet3 | not set in the config    |
+//! +--------+--------------------------+
+//! ```
+
+use crate::config::{ident, Config, LoadOptions};
+use crate::connection::DbConnection;
+use crate::encrypt::{decrypt_user, key_for};
+use crate::gen::gen_md5_password;
+use crate::render::OutputFormat;
+use crate::report::{Report, Row};
+use anyhow::{anyhow, Context, Result};
+use base64::{engine::general_purpose::STANDARD, Engine};
+use log::info;
+use ring::{digest, hmac, pbkdf2};
+use std::collections::HashMap;
+use std::fmt;
+use std::num::NonZeroU32;
+use std::path::Path;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Status {
+    Ok,
+    Drifted,
+    /// No password in the config, nothing to compare
+    NotSet,
+    /// The user does not exist, `apply` creates it
+    NoUser,
+    /// The user has no password in the cluster
+    NoPassword,
+    /// A hash which is neither MD5 nor SCRAM-SHA-256, e.g. from Redshift
+    UnknownFormat,
+}
+
+impl fmt::Display for Status {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Status::Ok => write!(f, "ok"),
+            Status::Drifted => write!(f, "drifted"),
+            Status::NotSet => write!(f, "not set in the config"),
+            Status::NoUser => write!(f, "user does not exist"),
+            Status::NoPassword => write!(f, "no password in the cluster"),
+            Status::UnknownFormat => write!(f, "unknown hash format"),
+        }
+    }
+}
+
+/// The password of a user of the config compared with the cluster
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct PasswordCheck {
+    pub user: String,
+    pub status: Status,
+}
+
+impl Row for PasswordCheck {
+    const HEADERS: &'static [&'static str] = &["User", "Password"];
+
+    fn cells(&self) -> Vec<String> {
+        vec![self.user.clone(), self.status.to_string()]
+    }
+}
+
+/// Compare the passwords of the config with the cluster, fails if one has
+/// drifted
+pub fn verify_passwords(
+    file: &Path,
+    load: &LoadOptions,
+    key_file: Option<&Path>,
+    output: OutputFormat,
+) -> Result<()> {
+    let config = Config::new_with(file, load)?;
+    let key = key_for(&config, key_file)?;
+
+    let mut conn = DbConnection::new(&config)?;
+    let rows = conn
+        .query("SELECT usename, passwd FROM pg_shadow", &[])
+        .context("cannot read the password hashes of pg_shadow, it needs a superuser")?;
+    let hashes: HashMap<String, Option<String>> = rows
+        .iter()
+        .map(|row| (row.get("usename"), row.get("passwd")))
+        .collect();
+
+    let mut report = Report::new();
+    for user in config.users.iter().filter(|u| !u.is_public()) {
+        let status = match user.password {
+            None => Status::NotSet,
+            Some(_) => {
+                let user = decrypt_user(user, key.as_ref())?;
+                let password = user.password.as_deref().unwrap_or_default();
+                match hashes.get(&ident::fold(&user.name)) {
+                    None => Status::NoUser,
+                    Some(None) => Status::NoPassword,
+                    Some(Some(stored)) => verify(&ident::fold(&user.name), password, stored),
+                }
+            }
+        };
+        report.push(PasswordCheck {
+            user: user.name.clone(),
+            status,
+        });
+    }
+
+    println!("{}", output.renderer().render(&report.to_table()));
+    let drifted = report
+        .rows
+        .iter()
+        .filter(|c| c.status == Status::Drifted)
+        .count();
+    if drifted > 0 {
+        return Err(anyhow!(
+            "{} password(s) drifted from {}, `password_policy: always` sets them at the next apply",
+            drifted,
+            file.display()
+        ));
+    }
+    info!("The passwords of {} match the cluster", file.display());
+
+    Ok(())
+}
+
+/// Compare the password of the config of a user, named as in the catalog,
+/// with the hash stored by the cluster
+pub fn verify(user: &str, password: &str, stored: &str) -> Status {
+    if is_md5(password) || is_scram(password) {
+        return if password == stored {
+            Status::Ok
+        } else {
+            Status::Drifted
+        };
+    }
+
+    let matches = if is_md5(stored) {
+        Some(gen_md5_password(password, user) == stored)
+    } else if is_scram(stored) {
+        scram_matches(password, stored)
+    } else {
+        None
+    };
+
+    match matches {
+        Some(true) => Status::Ok,
+        Some(false) => Status::Drifted,
+        None => Status::UnknownFormat,
+    }
+}
+
+fn is_md5(password: &str) -> bool {
+    password.len() == 35
+        && password.starts_with("md5")
+        && password[3..].chars().all(|c| c.is_ascii_hexdigit())
+}
+
+fn is_scram(password: &str) -> bool {
+    password.starts_with("SCRAM-SHA-256$")
+}
+
+/// Whether the password matches a `SCRAM-SHA-256$<iterations>:<salt>$<stored
+/// key>:<server key>` hash, None if the hash is malformed. The password is
+/// not normalized with SASLprep, as the server does, which only changes
+/// non-ASCII passwords.
+fn scram_matches(password: &str, stored: &str) -> Option<bool> {
+    let (params, keys) = stored.strip_prefix("SCRAM-SHA-256$")?.split_once('$')?;
+    let (iterations, salt) = params.split_once(':')?;
+    let (stored_key, server_key) = keys.split_once(':')?;
+    let iterations: NonZeroU32 = iterations.parse().ok()?;
+    let salt = STANDARD.decode(salt).ok()?;
+
+    let mut salted = [0u8; 32];
+    pbkdf2::derive(
+        pbkdf2::PBKDF2_HMAC_SHA256,
+        iterations,
+        &salt,
+        password.as_bytes(),
+        &mut salted,
+    );
+    let key = hmac::Key::new(hmac::HMAC_SHA256, &salted);
+    let client_key = hmac::sign(&key, b"Client Key");
+    let server = hmac::sign(&key, b"Server Key");
+
+    Some(
+        STANDARD.encode(digest::digest(&digest::SHA256, client_key.as_ref())) == stored_key
+            && STANDARD.encode(server.as_ref()) == server_key,
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// `SET password_encryption = 'scram-sha-256'; CREATE USER duyet
+    /// PASSWORD 'secret'` in Postgres 15
+    const SCRAM: &str = "SCRAM-SHA-256$4096:xEvSdiHG0p8abMEgZrrYvQ==$\
+        U37Yj13yHn9fIom7iG3RLxIaSs+t7yIiRODfRfMHaCA=:\
+        gk/hAIPyNXWAl6kqPmH6TpIKfNrotXuLQonZznyONPE=";
+
+    #[test]
+    fn test_verify() {
+        let md5 = gen_md5_password("secret", "duyet");
+
+        assert_eq!(verify("duyet", "secret", &md5), Status::Ok);
+        assert_eq!(verify("duyet", "other", &md5), Status::Drifted);
+        assert_eq!(verify("duyet", &md5, &md5), Status::Ok);
+        assert_eq!(verify("duyet2", &md5, SCRAM), Status::Drifted);
+        assert_eq!(verify("duyet", "secret", "sha256|x"), Status::UnknownFormat);
+    }
+
+    #[test]
+    fn test_verify_scram() {
+        assert_eq!(verify("duyet", "secret", SCRAM), Status::Ok);
+        assert_eq!(verify("duyet", "Secret", SCRAM), Status::Drifted);
+        assert_eq!(verify("duyet", SCRAM, SCRAM), Status::Ok);
+        assert_eq!(
+            verify("duyet", "secret", "SCRAM-SHA-256$x:y$z"),
+            Status::UnknownFormat
+        );
+    }
+}
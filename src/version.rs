@@ -0,0 +1,170 @@
+//! `grant version`: build metadata and capabilities of the binary, so that
+//! tooling can check that it supports a config before applying it.
+//!
+//! The names of the capabilities are stable, new ones are only added.
+
+use crate::config::Dialect;
+use crate::timestamp;
+use jiff::Timestamp;
+use serde::Serialize;
+
+/// Capabilities compiled in
+pub const FEATURES: &[&str] = &[
+    // `sslmode`, `sslrootcert`, `sslcert` and `sslkey` of the connection
+    "tls",
+    // Traces exported with OTLP, see `telemetry`
+    "opentelemetry",
+    // Passwords encrypted with `grant encrypt`
+    "encrypted-passwords",
+    // Change records of `apply --notify`
+    "notify",
+    "event-trigger",
+    "self-update",
+];
+
+/// Connection types
+pub const BACKENDS: &[&str] = &["postgres", "redshift", "mysql", "clickhouse", "snowflake"];
+
+/// Top level sections of the config. The config has no `apiVersion`, a
+/// config is supported if all its sections are.
+pub const CONFIG_SECTIONS: &[&str] = &[
+    "connection",
+    "roles",
+    "users",
+    "groups",
+    "redshift_roles",
+    "scope",
+    "managed_users",
+    "user_mapping",
+    "allow_public_write",
+    "audit",
+    "notifications",
+];
+
+#[derive(Debug, Serialize)]
+pub struct Version {
+    pub version: &'static str,
+    /// Short commit of the build, None outside of a git checkout
+    pub git_sha: Option<&'static str>,
+    /// Build time, see [timestamp]
+    pub build_date: String,
+    pub target: &'static str,
+    pub features: Vec<&'static str>,
+    pub backends: Vec<&'static str>,
+    /// Dialects of the `postgres` connections
+    pub dialects: Vec<String>,
+    pub config_sections: Vec<&'static str>,
+}
+
+impl Version {
+    pub fn current() -> Self {
+        let build_time = env!("GRANT_BUILD_TIME").parse().unwrap_or_default();
+
+        Version {
+            version: env!("CARGO_PKG_VERSION"),
+            git_sha: Some(env!("GRANT_GIT_SHA")).filter(|sha| !sha.is_empty()),
+            build_date: timestamp::format(Timestamp::from_second(build_time).unwrap_or_default()),
+            target: self_update::get_target(),
+            features: FEATURES.to_vec(),
+            backends: BACKENDS.to_vec(),
+            dialects: [
+                Dialect::Postgres,
+                Dialect::Redshift,
+                Dialect::RedshiftServerless,
+                Dialect::AuroraPostgres,
+                Dialect::Greenplum,
+            ]
+            .iter()
+            .map(|d| d.to_string())
+            .collect(),
+            config_sections: CONFIG_SECTIONS.to_vec(),
+        }
+    }
+}
+
+/// Print the version, as JSON with `json`
+pub fn version(json: bool) {
+    let version = Version::current();
+    if json {
+        println!("{}", serde_json::to_string_pretty(&version).unwrap());
+        return;
+    }
+
+    match version.git_sha {
+        Some(sha) => println!(
+            "grant {} ({} {}, {})",
+            version.version, sha, version.build_date, version.target
+        ),
+        None => println!(
+            "grant {} ({}, {})",
+            version.version, version.build_date, version.target
+        ),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::config::notifications::Slack;
+    use crate::config::{Audit, Config, Group, Notifications, RedshiftRole, Scope, UserMapping};
+
+    #[test]
+    fn test_version() {
+        let version = Version::current();
+
+        assert_eq!(version.version, env!("CARGO_PKG_VERSION"));
+        assert_eq!(
+            version.backends,
+            vec!["postgres", "redshift", "mysql", "clickhouse", "snowflake"]
+        );
+        assert!(version
+            .dialects
+            .contains(&"redshift_serverless".to_string()));
+        assert!(version.build_date.ends_with('Z'));
+    }
+
+    #[test]
+    fn test_config_sections() {
+        let config = Config {
+            groups: vec![Group {
+                name: "analysts".to_string(),
+                members: vec![],
+                roles: vec![],
+            }],
+            redshift_roles: vec![RedshiftRole {
+                name: "data_admin".to_string(),
+                privileges: vec![],
+                members: vec![],
+            }],
+            scope: Scope {
+                users: Some(vec!["duyet".to_string()]),
+                ..Default::default()
+            },
+            managed_users: vec!["svc_".to_string()],
+            user_mapping: Some(UserMapping {
+                username_template: "{email_local}".to_string(),
+                lowercase: true,
+                max_length: 63,
+            }),
+            allow_public_write: true,
+            audit: Some(Audit::default()),
+            notifications: Notifications {
+                slack: Some(Slack {
+                    webhook_url: "${SLACK_WEBHOOK_URL}".to_string(),
+                    channel: None,
+                    only_changes: false,
+                }),
+            },
+            ..Default::default()
+        };
+
+        let value = serde_yaml::to_value(&config).unwrap();
+        let sections = value
+            .as_mapping()
+            .unwrap()
+            .keys()
+            .map(|k| k.as_str().unwrap())
+            .collect::<Vec<_>>();
+        assert_eq!(sections, CONFIG_SECTIONS);
+    }
+}
@@ -81,3 +81,37 @@ fn gen_pass_with_username_and_password() {
         .stdout(predicate::str::contains("Generated password:"))
         .stdout(predicate::str::contains("Generated MD5 (user: duyet):"));
 }
+
+#[test]
+/// `grant bench-config` generates a valid config
+fn bench_config() {
+    let dir = tempfile::tempdir().unwrap();
+    let file = dir.path().join("bench.yaml");
+
+    Command::cargo_bin("grant")
+        .unwrap()
+        .args([
+            "bench-config",
+            "--users",
+            "20",
+            "--roles",
+            "3",
+            "--tables",
+            "5",
+        ])
+        .arg("--file")
+        .arg(&file)
+        .assert()
+        .success()
+        .stderr(predicate::str::contains(
+            "Generated 20 user(s) x 3 role(s) x 5 table(s)",
+        ));
+
+    Command::cargo_bin("grant")
+        .unwrap()
+        .arg("validate")
+        .arg("--file")
+        .arg(&file)
+        .assert()
+        .success();
+}
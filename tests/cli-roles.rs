@@ -0,0 +1,88 @@
+use assert_cmd::prelude::*; // Add methods on commands
+use predicates::prelude::*; // Used for writing assertions
+use std::path::PathBuf;
+use std::process::Command; // Run programs
+
+fn copy_example(dir: &tempfile::TempDir) -> PathBuf {
+    let path = dir.path().join("config.yaml");
+    std::fs::copy("./examples/example.yaml", &path).unwrap();
+
+    path
+}
+
+/// `grant roles add` appends the role, `grant roles show` prints its SQL
+#[test]
+fn roles_add_and_show() {
+    let dir = tempfile::tempdir().unwrap();
+    let path = copy_example(&dir);
+
+    let mut cmd = Command::cargo_bin("grant").unwrap();
+    cmd.args(["roles", "add", "read_marts", "--type", "table"])
+        .args([
+            "--schemas",
+            "marts",
+            "--grants",
+            "SELECT",
+            "--tables",
+            "ALL",
+        ])
+        .arg("--file")
+        .arg(&path)
+        .assert()
+        .success();
+
+    let content = std::fs::read_to_string(&path).unwrap();
+    let example = std::fs::read_to_string("./examples/example.yaml").unwrap();
+    assert!(content.contains("  - name: read_marts\n    type: table\n"));
+    assert_eq!(content.lines().count(), example.lines().count() + 8);
+
+    let mut cmd = Command::cargo_bin("grant").unwrap();
+    cmd.args(["roles", "show", "read_marts", "--file"])
+        .arg(&path)
+        .assert()
+        .success()
+        .stdout(predicate::str::contains(
+            "GRANT SELECT ON ALL TABLES IN SCHEMA marts TO <user>;",
+        ));
+}
+
+/// `grant roles add` rejects arguments which the role type does not use
+#[test]
+fn roles_add_unused_argument() {
+    let dir = tempfile::tempdir().unwrap();
+    let path = copy_example(&dir);
+
+    let mut cmd = Command::cargo_bin("grant").unwrap();
+    cmd.args(["roles", "add", "read_db", "--type", "database"])
+        .args([
+            "--databases",
+            "postgres",
+            "--grants",
+            "CREATE",
+            "--tables",
+            "ALL",
+        ])
+        .arg("--file")
+        .arg(&path)
+        .assert()
+        .failure()
+        .stderr(predicate::str::contains(
+            "--tables is not used by database roles",
+        ));
+}
+
+/// `grant roles remove` refuses roles which are still used by users
+#[test]
+fn roles_remove_in_use() {
+    let dir = tempfile::tempdir().unwrap();
+    let path = copy_example(&dir);
+
+    let mut cmd = Command::cargo_bin("grant").unwrap();
+    cmd.args(["roles", "remove", "role_table_level", "--file"])
+        .arg(&path)
+        .assert()
+        .failure()
+        .stderr(predicate::str::contains(
+            "role role_table_level is used by users: duyet, duyet2, duyet3",
+        ));
+}
@@ -0,0 +1,42 @@
+use assert_cmd::prelude::*; // Add methods on commands
+use predicates::prelude::*; // Used for writing assertions
+use std::process::Command; // Run programs
+
+#[test]
+/// grant version prints the version of the package
+fn version() {
+    let mut cmd = Command::cargo_bin("grant").unwrap();
+    cmd.arg("version")
+        .assert()
+        .success()
+        .stdout(predicate::str::starts_with(format!(
+            "grant {} (",
+            env!("CARGO_PKG_VERSION")
+        )));
+}
+
+#[test]
+/// grant version --json prints the build metadata and the capabilities
+fn version_json() {
+    let mut cmd = Command::cargo_bin("grant").unwrap();
+    let output = cmd
+        .args(["version", "--json", "--timezone", "Asia/Ho_Chi_Minh"])
+        .assert()
+        .success()
+        .get_output()
+        .stdout
+        .clone();
+
+    let version: serde_json::Value = serde_json::from_slice(&output).unwrap();
+    assert_eq!(version["version"], env!("CARGO_PKG_VERSION"));
+    assert!(version["build_date"].as_str().unwrap().ends_with("+07:00"));
+    assert!(version["features"]
+        .as_array()
+        .unwrap()
+        .contains(&serde_json::json!("tls")));
+    assert_eq!(
+        version["backends"],
+        serde_json::json!(["postgres", "redshift", "mysql", "clickhouse", "snowflake"])
+    );
+    assert_eq!(version["config_sections"][0], "connection");
+}